@@ -0,0 +1,12 @@
+//! Parses arbitrary Lua values as a battery of types, to find inputs that
+//! make the derived `FromLua` implementations panic. Run it from the
+//! `typed_nodes` directory with `cargo fuzz run from_lua`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../tests/battery/mod.rs"]
+mod battery;
+
+fuzz_target!(|data: &[u8]| battery::parse_all(data));
@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+use core::{borrow::Borrow, hash::Hash};
+
+use downcast_rs::{impl_downcast, Downcast};
+
+use crate::{DynKey, HashMap};
+
+/// A type erased map from alias IDs of one type to node keys. The group
+/// bounds decide which other traits it has.
+pub trait AliasMap: Downcast {
+    /// Remove all aliases for `key`.
+    fn remove_key(&mut self, key: DynKey);
+
+    /// Let all aliases for `from` refer to `into` instead.
+    fn replace_key(&mut self, from: DynKey, into: DynKey);
+}
+impl_downcast!(AliasMap);
+
+/// The aliases of type `J`, for one node type. The aliases of each key are
+/// also kept, so they can be removed without going through all of them.
+pub struct Aliases<J> {
+    keys: HashMap<J, DynKey>,
+    ids: HashMap<DynKey, Vec<J>>,
+}
+
+impl<J> Aliases<J>
+where
+    J: Hash + Eq + Clone,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            keys: HashMap::with_hasher(Default::default()),
+            ids: HashMap::with_hasher(Default::default()),
+        }
+    }
+
+    /// Let `id` refer to `key`, and return the key it referred to before.
+    pub(crate) fn insert(&mut self, id: J, key: DynKey) -> Option<DynKey> {
+        let old_key = self.keys.insert(id.clone(), key);
+
+        if old_key == Some(key) {
+            return old_key;
+        }
+
+        if let Some(old_key) = old_key {
+            self.remove_id_of(old_key, &id);
+        }
+
+        self.ids.entry(key).or_default().push(id);
+
+        old_key
+    }
+
+    pub(crate) fn remove<Q>(&mut self, id: &Q) -> Option<DynKey>
+    where
+        Q: ?Sized + Hash + Eq,
+        J: Borrow<Q>,
+    {
+        let key = self.keys.remove(id)?;
+        self.remove_id_of(key, id);
+
+        Some(key)
+    }
+
+    pub(crate) fn get<Q>(&self, id: &Q) -> Option<DynKey>
+    where
+        Q: ?Sized + Hash + Eq,
+        J: Borrow<Q>,
+    {
+        self.keys.get(id).copied()
+    }
+
+    fn remove_id_of<Q>(&mut self, key: DynKey, id: &Q)
+    where
+        Q: ?Sized + Hash + Eq,
+        J: Borrow<Q>,
+    {
+        if let Some(ids) = self.ids.get_mut(&key) {
+            ids.retain(|key_id| key_id.borrow() != id);
+
+            if ids.is_empty() {
+                self.ids.remove(&key);
+            }
+        }
+    }
+}
+
+impl<J> AliasMap for Aliases<J>
+where
+    J: Hash + Eq + Clone + 'static,
+{
+    fn remove_key(&mut self, key: DynKey) {
+        for id in self.ids.remove(&key).unwrap_or_default() {
+            self.keys.remove(&id);
+        }
+    }
+
+    fn replace_key(&mut self, from: DynKey, into: DynKey) {
+        let Some(ids) = self.ids.remove(&from) else {
+            return;
+        };
+
+        for id in &ids {
+            self.keys.insert(id.clone(), into);
+        }
+
+        self.ids.entry(into).or_default().extend(ids);
+    }
+}
@@ -0,0 +1,279 @@
+//! Parse nodes ahead of time, so the program doesn't have to do it.
+//!
+//! This module is only available with the `snapshot` feature. The nodes are
+//! baked into a [snapshot][crate::snapshot], together with the key of a root
+//! node. This is typically done in a build script, where the `lua` feature is
+//! enabled, while the program itself only needs the `snapshot` feature to
+//! load them. That way the program doesn't have to include Lua or the
+//! scripts.
+//!
+//! A build script can use [`bake_lua_file`] and [`write_to_out_dir`]:
+//!
+//! ```no_run
+//! # use typed_nodes::{mlua::FromLua, snapshot::SnapshotRegistry};
+//! # #[derive(FromLua, serde::Serialize, serde::Deserialize)]
+//! # #[typed_nodes(is_node)]
+//! # struct Level { name: String }
+//! let mut registry = SnapshotRegistry::new();
+//! registry.register::<Level>("Level");
+//!
+//! let lua = mlua::Lua::new();
+//! let baked = typed_nodes::bake::bake_lua_file::<Level>(&lua, "levels/intro.lua", &registry).unwrap();
+//! typed_nodes::bake::write_to_out_dir("intro.bin", &baked).unwrap();
+//! ```
+//!
+//! The program can then include and load the result:
+//!
+//! ```ignore
+//! let intro = typed_nodes::bake::load::<Level, _, _>(
+//!     typed_nodes::include_baked!("intro.bin"),
+//!     &registry,
+//! )?;
+//! ```
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bounds::Bounds,
+    snapshot::{serialize_bytes, SnapshotError, SnapshotRegistry},
+    Key, Nodes,
+};
+
+/// Nodes that have been loaded with [`load`], and the key of their root.
+pub struct Baked<T, I = (), B: Bounds = crate::bounds::AnyBounds> {
+    pub nodes: Nodes<I, B>,
+    pub root: Key<T>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct BakedData<'a, T> {
+    root: Key<T>,
+    #[serde(serialize_with = "serialize_bytes")]
+    snapshot: &'a [u8],
+}
+
+/// Bake `nodes` into a snapshot, where `root` is the node the program should
+/// start from.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use typed_nodes::{bake, snapshot::SnapshotRegistry, Key, Nodes};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Level {
+///     name: String,
+///     next: Option<Key<Level>>,
+/// }
+///
+/// let mut registry = SnapshotRegistry::new();
+/// registry.register::<Level>("Level");
+///
+/// let mut nodes = Nodes::<()>::new();
+/// let outro = nodes.insert(Level { name: "outro".into(), next: None });
+/// let intro = nodes.insert(Level { name: "intro".into(), next: Some(outro) });
+///
+/// let bytes = bake::bake(&nodes, intro, &registry).unwrap();
+/// let baked = bake::load::<Level, _, _>(&bytes, &registry).unwrap();
+///
+/// let root = baked.nodes.get(baked.root).unwrap();
+/// assert_eq!(root.name, "intro");
+/// assert!(root.next == Some(outro));
+/// ```
+pub fn bake<T, I, B>(
+    nodes: &Nodes<I, B>,
+    root: Key<T>,
+    registry: &SnapshotRegistry<I, B>,
+) -> Result<Vec<u8>, SnapshotError>
+where
+    I: Hash + Eq + 'static,
+    B: Bounds,
+{
+    let snapshot = nodes.to_snapshot(registry)?;
+
+    Ok(postcard::to_allocvec(&BakedData {
+        root,
+        snapshot: &snapshot,
+    })?)
+}
+
+/// Load nodes that were baked with [`bake`] or [`bake_lua`].
+pub fn load<T, I, B>(
+    bytes: &[u8],
+    registry: &SnapshotRegistry<I, B>,
+) -> Result<Baked<T, I, B>, SnapshotError>
+where
+    I: Hash + Eq + 'static,
+    B: Bounds,
+{
+    let data: BakedData<T> = postcard::from_bytes(bytes)?;
+
+    Ok(Baked {
+        nodes: Nodes::from_snapshot(data.snapshot, registry)?,
+        root: data.root,
+    })
+}
+
+/// Parse a Lua chunk and bake the resulting nodes. The chunk should evaluate
+/// to the root node, and `name` is used as the chunk name and the source in
+/// the nodes' [`Provenance`][crate::Provenance].
+///
+/// Any named references have to be resolved within the chunk.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use typed_nodes::{bake, mlua::{FromLua, TableId}, snapshot::SnapshotRegistry};
+///
+/// #[derive(FromLua, Serialize, Deserialize)]
+/// #[typed_nodes(is_node)]
+/// struct Level {
+///     name: String,
+///     enemies: u32,
+/// }
+///
+/// let mut registry = SnapshotRegistry::<TableId>::new();
+/// registry.register::<Level>("Level");
+///
+/// let lua = mlua::Lua::new();
+/// let source = r#"{name = "intro", enemies = 3}"#;
+/// let bytes = bake::bake_lua::<Level>(&lua, "intro.lua", source, &registry).unwrap();
+///
+/// let baked = bake::load::<Level, _, _>(&bytes, &registry).unwrap();
+/// assert_eq!(baked.nodes.get(baked.root).unwrap().enemies, 3);
+/// ```
+#[cfg(feature = "lua")]
+pub fn bake_lua<T>(
+    lua: &mlua::Lua,
+    name: &str,
+    source: &str,
+    registry: &SnapshotRegistry<crate::mlua::TableId>,
+) -> Result<Vec<u8>, BakeError>
+where
+    T: 'static,
+    for<'lua> Key<T>: crate::mlua::FromLua<'lua, crate::bounds::AnyBounds>,
+{
+    use crate::mlua::{Context, FromLua};
+
+    let mut nodes = Nodes::new();
+    let value = lua
+        .load(source)
+        .set_name(name)
+        .eval()
+        .map_err(|error| crate::mlua::chunk_error(error, name, source.as_bytes()))?;
+
+    let root = {
+        let mut context = Context::new(lua, &mut nodes).with_source(name);
+        let root = Key::<T>::from_lua(value, &mut context)?;
+        context.finish()?;
+        root
+    };
+
+    Ok(bake(&nodes, root, registry)?)
+}
+
+/// Read a Lua file and bake it with [`bake_lua`]. It also tells Cargo to run
+/// the build script again if the file changes.
+#[cfg(feature = "lua")]
+pub fn bake_lua_file<T>(
+    lua: &mlua::Lua,
+    path: impl AsRef<std::path::Path>,
+    registry: &SnapshotRegistry<crate::mlua::TableId>,
+) -> Result<Vec<u8>, BakeError>
+where
+    T: 'static,
+    for<'lua> Key<T>: crate::mlua::FromLua<'lua, crate::bounds::AnyBounds>,
+{
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let source = std::fs::read_to_string(path)?;
+    bake_lua::<T>(lua, &path.to_string_lossy(), &source, registry)
+}
+
+/// Write baked nodes to `file_name` in the build script's output directory,
+/// where [`include_baked`][crate::include_baked] can find it.
+#[cfg(feature = "std")]
+pub fn write_to_out_dir(file_name: &str, bytes: &[u8]) -> std::io::Result<std::path::PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "OUT_DIR is not set, make sure this runs in a build script",
+        )
+    })?;
+
+    let path = std::path::Path::new(&out_dir).join(file_name);
+    std::fs::write(&path, bytes)?;
+
+    Ok(path)
+}
+
+/// Include a file that was written with
+/// [`write_to_out_dir`][crate::bake::write_to_out_dir] as a `&'static [u8]`,
+/// that can be loaded with [`load`][crate::bake::load].
+#[macro_export]
+macro_rules! include_baked {
+    ($file_name:expr) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $file_name)) as &'static [u8]
+    };
+}
+
+/// An error from baking nodes from Lua.
+#[cfg(feature = "lua")]
+#[derive(Debug)]
+pub enum BakeError {
+    /// The Lua file couldn't be read.
+    Io(std::io::Error),
+    /// The Lua code couldn't be evaluated or parsed.
+    Lua(mlua::Error),
+    /// The nodes couldn't be stored in a snapshot.
+    Snapshot(SnapshotError),
+}
+
+#[cfg(feature = "lua")]
+impl core::fmt::Display for BakeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BakeError::Io(error) => error.fmt(f),
+            BakeError::Lua(error) => error.fmt(f),
+            BakeError::Snapshot(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "lua")]
+impl std::error::Error for BakeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BakeError::Io(error) => Some(error),
+            BakeError::Lua(error) => Some(error),
+            BakeError::Snapshot(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(feature = "lua")]
+impl From<std::io::Error> for BakeError {
+    #[inline]
+    fn from(error: std::io::Error) -> Self {
+        BakeError::Io(error)
+    }
+}
+
+#[cfg(feature = "lua")]
+impl From<mlua::Error> for BakeError {
+    #[inline]
+    fn from(error: mlua::Error) -> Self {
+        BakeError::Lua(error)
+    }
+}
+
+#[cfg(feature = "lua")]
+impl From<SnapshotError> for BakeError {
+    #[inline]
+    fn from(error: SnapshotError) -> Self {
+        BakeError::Snapshot(error)
+    }
+}
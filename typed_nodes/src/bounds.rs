@@ -1,6 +1,15 @@
-use std::any::Any;
+use alloc::boxed::Box;
+use core::{
+    any::Any,
+    hash::Hash,
+    panic::{RefUnwindSafe, UnwindSafe},
+};
 
-use crate::node_group::{DynNodeGroup, GroupBounds, NodeGroup};
+use crate::{
+    alias::{AliasMap, Aliases},
+    index::{FieldIndex, NodeIndex},
+    node_group::{DynNodeGroup, GroupBounds, NodeGroup},
+};
 
 /// Makes a new type that represents a set of trait bounds.
 ///
@@ -136,6 +145,49 @@ pub trait GroupBoundedBy<I, G: GroupBounds>: Sized {
         B: Bounds<GroupBounds = G>;
 }
 
+/// Types of values that are stored next to the nodes, such as alias IDs
+/// and index values, with the same restrictions as the node groups.
+///
+/// Without restrictions, any type can be used:
+///
+/// ```
+/// use std::rc::Rc;
+/// use typed_nodes::Nodes;
+///
+/// struct Texture;
+///
+/// let mut nodes = Nodes::<()>::new();
+/// let texture = nodes.insert(Texture);
+/// nodes.alias_id(texture, Rc::<str>::from("grass"));
+///
+/// assert!(nodes.get_key_by_alias::<Texture, Rc<str>, str>("grass") == Some(texture));
+/// ```
+///
+/// The IDs of nodes that have to be [`Send`] and [`Sync`] have to be that as
+/// well:
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+/// use typed_nodes::{bounds::SendSyncBounds, Nodes};
+///
+/// struct Texture;
+///
+/// let mut nodes = Nodes::<(), SendSyncBounds>::new();
+/// let texture = nodes.insert(Texture);
+/// nodes.alias_id(texture, Rc::<str>::from("grass"));
+/// ```
+pub trait SideBoundedBy<G: GroupBounds>: Sized + 'static {
+    fn box_aliases(aliases: Aliases<Self>) -> Box<G::DynAliasMap>
+    where
+        Self: Hash + Eq + Clone;
+    fn downcast_aliases_ref(aliases: &G::DynAliasMap) -> Option<&Aliases<Self>>;
+    fn downcast_aliases_mut(aliases: &mut G::DynAliasMap) -> Option<&mut Aliases<Self>>;
+    fn box_index<T: 'static>(index: FieldIndex<T, Self>) -> Box<G::DynIndex>
+    where
+        Self: Hash + Eq + Clone;
+    fn downcast_index_ref<T: 'static>(index: &G::DynIndex) -> Option<&FieldIndex<T, Self>>;
+}
+
 pub enum AnyBounds {}
 
 impl Bounds for AnyBounds {
@@ -144,7 +196,12 @@ impl Bounds for AnyBounds {
 }
 
 impl GroupBounds for AnyBounds {
-    type BoxedGroup<B> = Box<dyn DynNodeGroup<B> + 'static> where B: Bounds<GroupBounds = Self> + 'static;
+    type BoxedGroup<B>
+        = Box<dyn DynNodeGroup<B> + 'static>
+    where
+        B: Bounds<GroupBounds = Self> + 'static;
+    type DynAliasMap = dyn AliasMap;
+    type DynIndex = dyn NodeIndex;
 }
 
 impl<T> BoundsFor<T> for AnyBounds
@@ -194,6 +251,43 @@ where
     }
 }
 
+impl<V> SideBoundedBy<AnyBounds> for V
+where
+    V: 'static,
+{
+    fn box_aliases(aliases: Aliases<V>) -> Box<<AnyBounds as GroupBounds>::DynAliasMap>
+    where
+        V: Hash + Eq + Clone,
+    {
+        Box::new(aliases)
+    }
+
+    fn downcast_aliases_ref(
+        aliases: &<AnyBounds as GroupBounds>::DynAliasMap,
+    ) -> Option<&Aliases<V>> {
+        aliases.as_any().downcast_ref()
+    }
+
+    fn downcast_aliases_mut(
+        aliases: &mut <AnyBounds as GroupBounds>::DynAliasMap,
+    ) -> Option<&mut Aliases<V>> {
+        aliases.as_any_mut().downcast_mut()
+    }
+
+    fn box_index<T: 'static>(index: FieldIndex<T, V>) -> Box<<AnyBounds as GroupBounds>::DynIndex>
+    where
+        V: Hash + Eq + Clone,
+    {
+        Box::new(index)
+    }
+
+    fn downcast_index_ref<T: 'static>(
+        index: &<AnyBounds as GroupBounds>::DynIndex,
+    ) -> Option<&FieldIndex<T, V>> {
+        index.as_any().downcast_ref()
+    }
+}
+
 pub enum SendSyncBounds {}
 
 impl Bounds for SendSyncBounds {
@@ -202,7 +296,12 @@ impl Bounds for SendSyncBounds {
 }
 
 impl GroupBounds for SendSyncBounds {
-    type BoxedGroup<B> = Box<dyn DynNodeGroup<B> + Send + Sync + 'static> where B: Bounds<GroupBounds = Self>;
+    type BoxedGroup<B>
+        = Box<dyn DynNodeGroup<B> + Send + Sync + 'static>
+    where
+        B: Bounds<GroupBounds = Self>;
+    type DynAliasMap = dyn AliasMap + Send + Sync;
+    type DynIndex = dyn NodeIndex + Send + Sync;
 }
 
 impl<T> BoundsFor<T> for SendSyncBounds
@@ -251,3 +350,189 @@ where
         (&mut **group as &mut dyn DynNodeGroup<B>).downcast_mut()
     }
 }
+
+impl<V> SideBoundedBy<SendSyncBounds> for V
+where
+    V: Send + Sync + 'static,
+{
+    fn box_aliases(aliases: Aliases<V>) -> Box<<SendSyncBounds as GroupBounds>::DynAliasMap>
+    where
+        V: Hash + Eq + Clone,
+    {
+        Box::new(aliases)
+    }
+
+    fn downcast_aliases_ref(
+        aliases: &<SendSyncBounds as GroupBounds>::DynAliasMap,
+    ) -> Option<&Aliases<V>> {
+        aliases.as_any().downcast_ref()
+    }
+
+    fn downcast_aliases_mut(
+        aliases: &mut <SendSyncBounds as GroupBounds>::DynAliasMap,
+    ) -> Option<&mut Aliases<V>> {
+        aliases.as_any_mut().downcast_mut()
+    }
+
+    fn box_index<T: 'static>(
+        index: FieldIndex<T, V>,
+    ) -> Box<<SendSyncBounds as GroupBounds>::DynIndex>
+    where
+        V: Hash + Eq + Clone,
+    {
+        Box::new(index)
+    }
+
+    fn downcast_index_ref<T: 'static>(
+        index: &<SendSyncBounds as GroupBounds>::DynIndex,
+    ) -> Option<&FieldIndex<T, V>> {
+        index.as_any().downcast_ref()
+    }
+}
+
+/// Requires nodes and IDs to be [`Unpin`], [`UnwindSafe`] and
+/// [`RefUnwindSafe`]. A [`Nodes`][crate::Nodes] collection with these bounds
+/// can be borrowed inside [`catch_unwind`][std::panic::catch_unwind], or
+/// passed through FFI code that expects the same.
+///
+/// ```
+/// use typed_nodes::{bounds::UnwindSafeBounds, DynKey, Nodes};
+///
+/// struct Texture {
+///     width: u32,
+/// }
+///
+/// let mut nodes = Nodes::<(), UnwindSafeBounds>::new();
+/// let texture = nodes.insert(Texture { width: 256 });
+///
+/// nodes.alias_id(texture, "grass");
+/// nodes.add_index(|texture: &Texture| texture.width);
+///
+/// let width = std::panic::catch_unwind(|| {
+///     let texture = nodes.get_key_by_alias::<Texture, &str, _>("grass").unwrap();
+///     nodes.get(texture).unwrap().width
+/// });
+/// assert_eq!(width.unwrap(), 256);
+/// ```
+///
+/// Nodes that aren't unwind safe are rejected:
+///
+/// ```compile_fail
+/// use std::cell::Cell;
+/// use typed_nodes::{bounds::UnwindSafeBounds, Nodes};
+///
+/// let mut nodes = Nodes::<(), UnwindSafeBounds>::new();
+/// nodes.insert(Cell::new(1));
+/// ```
+///
+/// It can also restrict the node groups of custom bounds:
+///
+/// ```
+/// use std::panic::{RefUnwindSafe, UnwindSafe};
+/// use typed_nodes::{bounds::UnwindSafeBounds, make_bounds};
+///
+/// trait Asset {}
+///
+/// make_bounds!(AssetBounds<GroupBounds = UnwindSafeBounds>: Asset + Unpin + RefUnwindSafe + UnwindSafe + 'static);
+/// ```
+pub enum UnwindSafeBounds {}
+
+impl Bounds for UnwindSafeBounds {
+    type GroupBounds = Self;
+    type DynSelf = dyn Any + Unpin + RefUnwindSafe + UnwindSafe + 'static;
+}
+
+impl GroupBounds for UnwindSafeBounds {
+    type BoxedGroup<B>
+        = Box<dyn DynNodeGroup<B> + RefUnwindSafe + UnwindSafe + 'static>
+    where
+        B: Bounds<GroupBounds = Self>;
+    type DynAliasMap = dyn AliasMap + RefUnwindSafe + UnwindSafe;
+    type DynIndex = dyn NodeIndex + RefUnwindSafe + UnwindSafe;
+}
+
+impl<T> BoundsFor<T> for UnwindSafeBounds
+where
+    T: Unpin + RefUnwindSafe + UnwindSafe + 'static,
+{
+    fn as_dyn_ref(value: &T) -> &<UnwindSafeBounds as Bounds>::DynSelf {
+        value
+    }
+
+    fn as_dyn_mut(value: &mut T) -> &mut <UnwindSafeBounds as Bounds>::DynSelf {
+        value
+    }
+}
+
+impl<I, T> GroupBoundedBy<I, UnwindSafeBounds> for T
+where
+    I: RefUnwindSafe + UnwindSafe + 'static,
+    T: Unpin + RefUnwindSafe + UnwindSafe + 'static,
+{
+    fn box_group<B>(group: NodeGroup<I, T>) -> <UnwindSafeBounds as GroupBounds>::BoxedGroup<B>
+    where
+        T: BoundedBy<I, B>,
+        B: Bounds<GroupBounds = UnwindSafeBounds>,
+    {
+        Box::new(group)
+    }
+
+    fn downcast_group_ref<B>(
+        group: &<UnwindSafeBounds as GroupBounds>::BoxedGroup<B>,
+    ) -> Option<&NodeGroup<I, T>>
+    where
+        T: BoundedBy<I, B>,
+        B: Bounds<GroupBounds = UnwindSafeBounds>,
+    {
+        (&**group as &dyn DynNodeGroup<B>).downcast_ref()
+    }
+
+    fn downcast_group_mut<B>(
+        group: &mut <UnwindSafeBounds as GroupBounds>::BoxedGroup<B>,
+    ) -> Option<&mut NodeGroup<I, T>>
+    where
+        T: BoundedBy<I, B>,
+        B: Bounds<GroupBounds = UnwindSafeBounds>,
+    {
+        (&mut **group as &mut dyn DynNodeGroup<B>).downcast_mut()
+    }
+}
+
+impl<V> SideBoundedBy<UnwindSafeBounds> for V
+where
+    V: RefUnwindSafe + UnwindSafe + 'static,
+{
+    fn box_aliases(aliases: Aliases<V>) -> Box<<UnwindSafeBounds as GroupBounds>::DynAliasMap>
+    where
+        V: Hash + Eq + Clone,
+    {
+        Box::new(aliases)
+    }
+
+    fn downcast_aliases_ref(
+        aliases: &<UnwindSafeBounds as GroupBounds>::DynAliasMap,
+    ) -> Option<&Aliases<V>> {
+        aliases.as_any().downcast_ref()
+    }
+
+    fn downcast_aliases_mut(
+        aliases: &mut <UnwindSafeBounds as GroupBounds>::DynAliasMap,
+    ) -> Option<&mut Aliases<V>> {
+        aliases.as_any_mut().downcast_mut()
+    }
+
+    fn box_index<T: 'static>(
+        index: FieldIndex<T, V>,
+    ) -> Box<<UnwindSafeBounds as GroupBounds>::DynIndex>
+    where
+        V: Hash + Eq + Clone,
+    {
+        Box::new(index)
+    }
+
+    fn downcast_index_ref<T: 'static>(
+        index: &<UnwindSafeBounds as GroupBounds>::DynIndex,
+    ) -> Option<&FieldIndex<T, V>> {
+        index.as_any().downcast_ref()
+    }
+}
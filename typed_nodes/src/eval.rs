@@ -0,0 +1,472 @@
+//! Evaluation of nodes that depend on the values of other nodes.
+//!
+//! Node types that describe computations, such as expressions, implement
+//! [`Evaluate`] and use an [`Evaluator`] for getting the values of the nodes
+//! they refer to. The evaluator remembers the value of each node, so shared
+//! nodes are only evaluated once, and stops with an error if a node depends
+//! on itself.
+//!
+//! The remembered values are kept in an [`EvalCache`], that can be reused
+//! after the nodes have changed. It keeps track of which nodes each value
+//! depends on, so only the values that depend on changed nodes are evaluated
+//! again. This requires the change ticks to be enabled with
+//! [`Nodes::enable_change_ticks`].
+//!
+//! ```
+//! use std::collections::HashMap;
+//!
+//! use typed_nodes::{
+//!     eval::{EvalError, Evaluate, Evaluator},
+//!     DynKey, Key, Nodes,
+//! };
+//!
+//! enum Expr {
+//!     Constant(i32),
+//!     Variable(&'static str),
+//!     Add(Key<Expr>, Key<Expr>),
+//! }
+//!
+//! impl Evaluate<HashMap<&'static str, i32>> for Expr {
+//!     type Output = i32;
+//!
+//!     fn evaluate(
+//!         &self,
+//!         evaluator: &mut Evaluator<'_, HashMap<&'static str, i32>>,
+//!     ) -> Result<i32, EvalError> {
+//!         match *self {
+//!             Expr::Constant(value) => Ok(value),
+//!             Expr::Variable(name) => Ok(evaluator.context()[name]),
+//!             Expr::Add(left, right) => Ok(evaluator.evaluate(left)? + evaluator.evaluate(right)?),
+//!         }
+//!     }
+//! }
+//!
+//! let mut nodes = Nodes::<()>::new();
+//! let x = nodes.insert(Expr::Variable("x"));
+//! let one = nodes.insert(Expr::Constant(1));
+//! let x_plus_one = nodes.insert(Expr::Add(x, one));
+//! let sum = nodes.insert(Expr::Add(x_plus_one, x_plus_one));
+//!
+//! let mut evaluator = Evaluator::new(&nodes, HashMap::from([("x", 4)]));
+//! assert_eq!(evaluator.evaluate(sum), Ok(10));
+//! assert_eq!(evaluator.cached(x_plus_one), Some(&5));
+//!
+//! // A node that adds itself can't be evaluated.
+//! let reservation = nodes.reserve::<Expr>();
+//! let looped = reservation.key();
+//! nodes.fulfill(reservation, Expr::Add(one, looped)).ok().unwrap();
+//!
+//! let mut evaluator = Evaluator::new(&nodes, HashMap::new());
+//! assert_eq!(
+//!     evaluator.evaluate(looped),
+//!     Err(EvalError::Cycle(vec![DynKey::from(looped)]))
+//! );
+//! ```
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{any::TypeId, fmt::Display};
+
+use downcast_rs::{impl_downcast, Downcast};
+
+use crate::{
+    bounds::{AnyBounds, BoundedBy, Bounds},
+    DynKey, HashMap, HashSet, Key, Nodes, SideTable,
+};
+
+/// Node types that can be evaluated to a value, with a context of type
+/// `Ctx`. The context can be anything that is needed for the evaluation,
+/// such as the values of variables.
+pub trait Evaluate<Ctx = (), I = (), B: Bounds = AnyBounds>: BoundedBy<I, B> {
+    /// The value that the node is evaluated to. It's cloned from the cache
+    /// each time it's needed again.
+    type Output: Clone + 'static;
+
+    /// Evaluate the node. The nodes it refers to should be evaluated with
+    /// [`Evaluator::evaluate`], to have their values cached and cycles
+    /// detected.
+    fn evaluate(&self, evaluator: &mut Evaluator<'_, Ctx, I, B>)
+        -> Result<Self::Output, EvalError>;
+}
+
+/// Evaluates nodes and remembers their values. See the
+/// [module documentation](self) for an example.
+///
+/// The values are stored in an [`EvalCache`], which can be taken with
+/// [`Evaluator::into_cache`] and reused with [`Evaluator::with_cache`]
+/// after the nodes have changed. The values don't depend on the context, so
+/// the cache should be cleared if the context changes.
+pub struct Evaluator<'a, Ctx, I = (), B: Bounds = AnyBounds> {
+    nodes: &'a Nodes<I, B>,
+    context: Ctx,
+    cache: EvalCache,
+    stack: Vec<DynKey>,
+    in_progress: HashSet<DynKey>,
+}
+
+impl<'a, Ctx, I, B> Evaluator<'a, Ctx, I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Create an evaluator for `nodes`, with `context` as the evaluation
+    /// context.
+    #[inline]
+    pub fn new(nodes: &'a Nodes<I, B>, context: Ctx) -> Self {
+        Self::with_cache(nodes, context, EvalCache::new())
+    }
+
+    /// Create an evaluator for `nodes` that continues with the values in
+    /// `cache`. The values of nodes that have changed since they were
+    /// evaluated, and of the nodes that depend on them, are removed first,
+    /// as with [`EvalCache::invalidate_changed`].
+    pub fn with_cache(nodes: &'a Nodes<I, B>, context: Ctx, mut cache: EvalCache) -> Self {
+        cache.invalidate_changed(nodes);
+
+        Self {
+            nodes,
+            context,
+            cache,
+            stack: Vec::new(),
+            in_progress: HashSet::with_hasher(Default::default()),
+        }
+    }
+
+    /// The nodes that are evaluated.
+    #[inline]
+    pub fn nodes(&self) -> &'a Nodes<I, B> {
+        self.nodes
+    }
+
+    /// The evaluation context.
+    #[inline]
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+
+    /// The evaluation context, as mutable.
+    #[inline]
+    pub fn context_mut(&mut self) -> &mut Ctx {
+        &mut self.context
+    }
+
+    /// Take the evaluation context and drop the cached values.
+    #[inline]
+    pub fn into_context(self) -> Ctx {
+        self.context
+    }
+
+    /// Take the cached values, to continue with them later.
+    #[inline]
+    pub fn into_cache(self) -> EvalCache {
+        self.cache
+    }
+
+    /// The cached values.
+    #[inline]
+    pub fn cache(&self) -> &EvalCache {
+        &self.cache
+    }
+
+    /// Evaluate the node with the key `key`, or get its value from the cache
+    /// if it has already been evaluated. Errors are not cached.
+    ///
+    /// If another node is being evaluated, it's recorded as depending on
+    /// this node.
+    pub fn evaluate<T>(&mut self, key: Key<T>) -> Result<T::Output, EvalError>
+    where
+        T: Evaluate<Ctx, I, B>,
+    {
+        let dyn_key = DynKey::from(key);
+
+        let value = if let Some(value) = self.cached(key) {
+            value.clone()
+        } else {
+            if self.in_progress.contains(&dyn_key) {
+                let start = self
+                    .stack
+                    .iter()
+                    .position(|&stack_key| stack_key == dyn_key)
+                    .unwrap_or(0);
+
+                return Err(EvalError::Cycle(self.stack[start..].to_vec()));
+            }
+
+            let node = self.nodes.get(key).ok_or(EvalError::MissingNode(dyn_key))?;
+
+            self.stack.push(dyn_key);
+            self.in_progress.insert(dyn_key);
+            let result = node.evaluate(self);
+            self.in_progress.remove(&dyn_key);
+            self.stack.pop();
+
+            let value = result?;
+            let tick = self.nodes.change_tick(key);
+            self.cache.insert(key, value.clone(), tick);
+
+            value
+        };
+
+        if let Some(&dependent) = self.stack.last() {
+            self.cache.add_dependent(dyn_key, dependent);
+        }
+
+        Ok(value)
+    }
+
+    /// Get the cached value of the node with the key `key`, if it has been
+    /// evaluated.
+    #[inline]
+    pub fn cached<T>(&self, key: Key<T>) -> Option<&T::Output>
+    where
+        T: Evaluate<Ctx, I, B>,
+    {
+        self.cache.get(key)
+    }
+
+    /// Forget the cached value of the node with the key `key`, and of the
+    /// nodes that depend on it, as with [`EvalCache::invalidate`].
+    #[inline]
+    pub fn invalidate(&mut self, key: impl Into<DynKey>) -> usize {
+        self.cache.invalidate(key)
+    }
+
+    /// Forget all cached values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// The keys of the nodes that are currently being evaluated, from the
+    /// first one to the innermost one.
+    #[inline]
+    pub fn stack(&self) -> &[DynKey] {
+        &self.stack
+    }
+}
+
+/// The values of evaluated nodes, from an [`Evaluator`].
+///
+/// Each value is stored with the change tick its node had when it was
+/// evaluated, from [`Nodes::change_tick`], and with the nodes that depend on
+/// it. A value is invalid if its node has changed, or if it depends on an
+/// invalid value. The change ticks have to be enabled with
+/// [`Nodes::enable_change_ticks`] for the values to be reused, or else all
+/// of them are treated as invalid.
+///
+/// ```
+/// use typed_nodes::{
+///     eval::{EvalCache, EvalError, Evaluate, Evaluator},
+///     Key, Nodes,
+/// };
+///
+/// enum Expr {
+///     Constant(i32),
+///     Add(Key<Expr>, Key<Expr>),
+/// }
+///
+/// impl Evaluate for Expr {
+///     type Output = i32;
+///
+///     fn evaluate(&self, evaluator: &mut Evaluator<'_, ()>) -> Result<i32, EvalError> {
+///         match *self {
+///             Expr::Constant(value) => Ok(value),
+///             Expr::Add(left, right) => Ok(evaluator.evaluate(left)? + evaluator.evaluate(right)?),
+///         }
+///     }
+/// }
+///
+/// let mut nodes = Nodes::<()>::new();
+/// nodes.enable_change_ticks();
+/// let one = nodes.insert(Expr::Constant(1));
+/// let two = nodes.insert(Expr::Constant(2));
+/// let three = nodes.insert(Expr::Add(one, two));
+/// let four = nodes.insert(Expr::Add(two, two));
+///
+/// let mut evaluator = Evaluator::new(&nodes, ());
+/// assert_eq!(evaluator.evaluate(three), Ok(3));
+/// assert_eq!(evaluator.evaluate(four), Ok(4));
+/// let cache = evaluator.into_cache();
+/// assert_eq!(cache.len(), 4);
+///
+/// // Only `one` and `three` have to be evaluated again.
+/// nodes.replace(one, Expr::Constant(10));
+///
+/// let mut evaluator = Evaluator::with_cache(&nodes, (), cache);
+/// assert_eq!(evaluator.cache().len(), 2);
+/// assert_eq!(evaluator.cached(four), Some(&4));
+/// assert_eq!(evaluator.evaluate(three), Ok(12));
+/// ```
+#[derive(Default)]
+pub struct EvalCache {
+    /// The values, by node type and output type. A node type can have more
+    /// than one output type if it's evaluated with different contexts.
+    tables: HashMap<(TypeId, TypeId), Box<dyn CacheTable>>,
+    entries: HashMap<DynKey, CacheEntry>,
+}
+
+impl EvalCache {
+    /// Create an empty cache.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if there is a value for `key`.
+    #[inline]
+    pub fn contains(&self, key: impl Into<DynKey>) -> bool {
+        self.entries.contains_key(&key.into())
+    }
+
+    /// The number of nodes that have values in the cache.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the cache is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove the value for `key`, and the values that depend on it.
+    /// Returns the number of removed values.
+    pub fn invalidate(&mut self, key: impl Into<DynKey>) -> usize {
+        let mut pending = vec![key.into()];
+        let mut count = 0;
+
+        while let Some(key) = pending.pop() {
+            let Some(entry) = self.entries.remove(&key) else {
+                continue;
+            };
+
+            for (_, table) in self
+                .tables
+                .iter_mut()
+                .filter(|((node_type, _), _)| *node_type == key.node_type)
+            {
+                table.remove_key(key);
+            }
+
+            pending.extend(entry.dependents);
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Remove the values of the nodes that have been changed or removed
+    /// since they were evaluated, and the values that depend on them.
+    /// Returns the number of removed values.
+    pub fn invalidate_changed<I, B>(&mut self, nodes: &Nodes<I, B>) -> usize
+    where
+        I: 'static,
+        B: Bounds,
+    {
+        let changed: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|&(&key, entry)| entry.tick.is_none() || nodes.change_tick(key) != entry.tick)
+            .map(|(&key, _)| key)
+            .collect();
+
+        changed.into_iter().map(|key| self.invalidate(key)).sum()
+    }
+
+    /// Remove all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.tables.clear();
+        self.entries.clear();
+    }
+
+    fn get<T, V>(&self, key: Key<T>) -> Option<&V>
+    where
+        T: 'static,
+        V: 'static,
+    {
+        self.tables
+            .get(&(TypeId::of::<T>(), TypeId::of::<V>()))?
+            .downcast_ref::<SideTable<T, V>>()?
+            .get(key)
+    }
+
+    fn insert<T, V>(&mut self, key: Key<T>, value: V, tick: Option<u64>)
+    where
+        T: 'static,
+        V: 'static,
+    {
+        self.tables
+            .entry((TypeId::of::<T>(), TypeId::of::<V>()))
+            .or_insert_with(|| Box::new(SideTable::<T, V>::new()))
+            .downcast_mut::<SideTable<T, V>>()
+            .expect("the cache table should have the type of its key")
+            .insert(key, value);
+
+        // The node may already have a value of another output type, and
+        // its dependents should be kept.
+        self.entries
+            .entry(key.into())
+            .and_modify(|entry| entry.tick = tick)
+            .or_insert_with(|| CacheEntry {
+                tick,
+                dependents: Vec::new(),
+            });
+    }
+
+    fn add_dependent(&mut self, key: DynKey, dependent: DynKey) {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            if !entry.dependents.contains(&dependent) {
+                entry.dependents.push(dependent);
+            }
+        }
+    }
+}
+
+struct CacheEntry {
+    /// The change tick of the node, or `None` if the ticks weren't enabled.
+    tick: Option<u64>,
+    dependents: Vec<DynKey>,
+}
+
+/// A type erased [`SideTable`] of cached values.
+trait CacheTable: Downcast {
+    /// Remove the value for `key`.
+    fn remove_key(&mut self, key: DynKey);
+}
+impl_downcast!(CacheTable);
+
+impl<T, V> CacheTable for SideTable<T, V>
+where
+    T: 'static,
+    V: 'static,
+{
+    fn remove_key(&mut self, key: DynKey) {
+        if let Some(key) = key.into_static() {
+            self.remove(key);
+        }
+    }
+}
+
+/// An error from evaluating a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvalError {
+    /// A node depends on itself. It has the keys of the nodes in the cycle,
+    /// starting with the node that was evaluated again.
+    Cycle(Vec<DynKey>),
+    /// A key refers to a node that doesn't exist.
+    MissingNode(DynKey),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EvalError::Cycle(keys) => {
+                write!(f, "found a cycle of {} nodes during evaluation", keys.len())
+            }
+            EvalError::MissingNode(_) => write!(f, "a key refers to a missing node"),
+        }
+    }
+}
+
+impl core::error::Error for EvalError {}
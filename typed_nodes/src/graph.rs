@@ -0,0 +1,1553 @@
+//! Tools for working with the graph that is formed by the keys in the nodes.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    rc::Rc,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+#[cfg(feature = "lua")]
+use crate::mlua::{Lazy, Maybe};
+use crate::{
+    bounds::Bounds, make_bounds, DynKey, HashMap, HashSet, Key, Nodes, SlotState, WeakKey,
+};
+
+pub use typed_nodes_macros::{RemapKeys, VisitKeys};
+
+/// Types that contain keys to other nodes.
+///
+/// The keys should be visited in the same order every time, so algorithms
+/// that walk the graph become deterministic. It can be derived for structs and
+/// enums, where each field is visited in declaration order.
+pub trait VisitKeys {
+    /// Call `visitor` with each key in `self`.
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey));
+}
+
+impl<T: 'static> VisitKeys for Key<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        visitor(DynKey::new(*self))
+    }
+}
+
+/// Weak keys are not visited, since they don't keep their nodes alive.
+impl<T> VisitKeys for WeakKey<T> {
+    #[inline]
+    fn visit_keys(&self, _visitor: &mut dyn FnMut(DynKey)) {}
+}
+
+impl VisitKeys for DynKey {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        visitor(*self)
+    }
+}
+
+#[cfg(feature = "lua")]
+impl<T: 'static> VisitKeys for Lazy<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        visitor(DynKey::new(self.key()))
+    }
+}
+
+impl<T: VisitKeys> VisitKeys for Option<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        if let Some(value) = self {
+            value.visit_keys(visitor);
+        }
+    }
+}
+
+#[cfg(feature = "lua")]
+impl<T: VisitKeys> VisitKeys for Maybe<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        if let Maybe::Value(value) = self {
+            value.visit_keys(visitor);
+        }
+    }
+}
+
+impl<T: VisitKeys> VisitKeys for [T] {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        for value in self {
+            value.visit_keys(visitor);
+        }
+    }
+}
+
+impl<T: VisitKeys, const N: usize> VisitKeys for [T; N] {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        self[..].visit_keys(visitor);
+    }
+}
+
+impl<T: VisitKeys> VisitKeys for Vec<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        self[..].visit_keys(visitor);
+    }
+}
+
+impl<T: VisitKeys> VisitKeys for VecDeque<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        for value in self {
+            value.visit_keys(visitor);
+        }
+    }
+}
+
+impl<T: VisitKeys> VisitKeys for BTreeSet<T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        for value in self {
+            value.visit_keys(visitor);
+        }
+    }
+}
+
+impl<K: VisitKeys, V: VisitKeys> VisitKeys for BTreeMap<K, V> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        for (key, value) in self {
+            key.visit_keys(visitor);
+            value.visit_keys(visitor);
+        }
+    }
+}
+
+impl<T: VisitKeys + ?Sized> VisitKeys for &T {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        (**self).visit_keys(visitor);
+    }
+}
+
+impl<T: VisitKeys + ToOwned + ?Sized> VisitKeys for Cow<'_, T> {
+    #[inline]
+    fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+        (**self).visit_keys(visitor);
+    }
+}
+
+macro_rules! impl_visit_keys_deref {
+    ($($self_ty:ident),+) => {$(
+        impl<T: VisitKeys + ?Sized> VisitKeys for $self_ty<T> {
+            #[inline]
+            fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+                (**self).visit_keys(visitor);
+            }
+        }
+    )+};
+}
+
+impl_visit_keys_deref!(Box, Rc, Arc);
+
+macro_rules! impl_visit_keys_tuples {
+    ($first:ident $(,$ty:ident)* ) => {
+        impl_visit_keys_tuples!($($ty),*);
+
+        impl<$first: VisitKeys $(,$ty: VisitKeys)*> VisitKeys for ($first $(,$ty)*,) {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn visit_keys(&self, visitor: &mut dyn FnMut(DynKey)) {
+                let ($first $(,$ty)*,) = self;
+                $first.visit_keys(visitor);
+                $($ty.visit_keys(visitor);)*
+            }
+        }
+    };
+
+    () => {};
+}
+
+impl_visit_keys_tuples!(A, B, C, D, E, F, G, H);
+
+macro_rules! impl_visit_keys_leaf {
+    ($($self_ty:ty),+) => {$(
+        impl VisitKeys for $self_ty {
+            #[inline]
+            fn visit_keys(&self, _visitor: &mut dyn FnMut(DynKey)) {}
+        }
+    )+};
+}
+
+impl_visit_keys_leaf!(
+    (),
+    bool,
+    char,
+    str,
+    String,
+    f32,
+    f64,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize
+);
+
+impl<T: ?Sized> VisitKeys for PhantomData<T> {
+    #[inline]
+    fn visit_keys(&self, _visitor: &mut dyn FnMut(DynKey)) {}
+}
+
+/// Replacements for keys to nodes that have been removed, such as by
+/// [`Nodes::dedup`].
+#[derive(Default)]
+pub struct KeyRemap {
+    keys: HashMap<DynKey, DynKey>,
+}
+
+impl KeyRemap {
+    /// Get the replacement for `key`, or `key` itself if it isn't replaced.
+    #[inline]
+    pub fn get<T: 'static>(&self, key: Key<T>) -> Key<T> {
+        self.get_dyn(key.into())
+            .into_static()
+            .expect("the replacement should have the same node type")
+    }
+
+    /// Get the replacement for `key`, or `key` itself if it isn't replaced.
+    #[inline]
+    pub fn get_dyn(&self, key: DynKey) -> DynKey {
+        self.keys.get(&key).copied().unwrap_or(key)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[inline]
+    pub(crate) fn insert<T: 'static>(&mut self, from: Key<T>, into: Key<T>) {
+        self.keys.insert(from.into(), into.into());
+    }
+
+    /// Replace `from` with `into`, including where `from` is the replacement
+    /// for another key. `into` should not have a replacement itself.
+    pub(crate) fn redirect<T: 'static>(&mut self, from: Key<T>, into: Key<T>) {
+        let (from, into) = (from.into(), into.into());
+
+        for replacement in self.keys.values_mut() {
+            if *replacement == from {
+                *replacement = into;
+            }
+        }
+
+        self.keys.insert(from, into);
+    }
+}
+
+/// Types that contain keys that can be replaced using a [`KeyRemap`]. It can
+/// be derived for structs and enums, where each field is remapped.
+pub trait RemapKeys {
+    /// Replace each key in `self` that has a replacement in `remap`.
+    fn remap_keys(&mut self, remap: &KeyRemap);
+}
+
+impl<T: 'static> RemapKeys for Key<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        *self = remap.get(*self);
+    }
+}
+
+impl<T: 'static> RemapKeys for WeakKey<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        *self = remap.get(self.key_unchecked()).downgrade();
+    }
+}
+
+impl RemapKeys for DynKey {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        *self = remap.get_dyn(*self);
+    }
+}
+
+#[cfg(feature = "lua")]
+impl<T: 'static> RemapKeys for Lazy<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        self.key.remap_keys(remap);
+    }
+}
+
+impl<T: RemapKeys> RemapKeys for Option<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        if let Some(value) = self {
+            value.remap_keys(remap);
+        }
+    }
+}
+
+#[cfg(feature = "lua")]
+impl<T: RemapKeys> RemapKeys for Maybe<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        if let Maybe::Value(value) = self {
+            value.remap_keys(remap);
+        }
+    }
+}
+
+impl<T: RemapKeys> RemapKeys for [T] {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        for value in self {
+            value.remap_keys(remap);
+        }
+    }
+}
+
+impl<T: RemapKeys, const N: usize> RemapKeys for [T; N] {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        self[..].remap_keys(remap);
+    }
+}
+
+impl<T: RemapKeys> RemapKeys for Vec<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        self[..].remap_keys(remap);
+    }
+}
+
+impl<T: RemapKeys> RemapKeys for VecDeque<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        for value in self {
+            value.remap_keys(remap);
+        }
+    }
+}
+
+impl<T: RemapKeys + ?Sized> RemapKeys for &mut T {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        (**self).remap_keys(remap);
+    }
+}
+
+impl<T: RemapKeys + ?Sized> RemapKeys for Box<T> {
+    #[inline]
+    fn remap_keys(&mut self, remap: &KeyRemap) {
+        (**self).remap_keys(remap);
+    }
+}
+
+macro_rules! impl_remap_keys_tuples {
+    ($first:ident $(,$ty:ident)* ) => {
+        impl_remap_keys_tuples!($($ty),*);
+
+        impl<$first: RemapKeys $(,$ty: RemapKeys)*> RemapKeys for ($first $(,$ty)*,) {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn remap_keys(&mut self, remap: &KeyRemap) {
+                let ($first $(,$ty)*,) = self;
+                $first.remap_keys(remap);
+                $($ty.remap_keys(remap);)*
+            }
+        }
+    };
+
+    () => {};
+}
+
+impl_remap_keys_tuples!(A, B, C, D, E, F, G, H);
+
+macro_rules! impl_remap_keys_leaf {
+    ($($self_ty:ty),+) => {$(
+        impl RemapKeys for $self_ty {
+            #[inline]
+            fn remap_keys(&mut self, _remap: &KeyRemap) {}
+        }
+    )+};
+}
+
+impl_remap_keys_leaf!(
+    (),
+    bool,
+    char,
+    str,
+    String,
+    f32,
+    f64,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize
+);
+
+impl<T: ?Sized> RemapKeys for PhantomData<T> {
+    #[inline]
+    fn remap_keys(&mut self, _remap: &KeyRemap) {}
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: RemapKeys,
+{
+    /// Replace the keys in all nodes that have a replacement in `remap`.
+    pub fn remap_keys(&mut self, remap: &KeyRemap) {
+        if remap.is_empty() {
+            return;
+        }
+
+        for node in self.nodes_dyn_mut() {
+            node.remap_keys(remap);
+        }
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    /// Find the keys that would be left dangling by
+    /// [`Nodes::remove_from_source`]. Each pair is the key of a node that
+    /// isn't from `source`, followed by the key it has to a node that is.
+    ///
+    /// ```
+    /// use typed_nodes::{graph::VisitKeys, make_bounds, Key, Nodes, Provenance};
+    ///
+    /// make_bounds!(ItemBounds: VisitKeys + 'static);
+    ///
+    /// #[derive(VisitKeys)]
+    /// struct Item {
+    ///     name: String,
+    ///     upgrade: Option<Key<Item>>,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<(), ItemBounds>::new();
+    ///
+    /// let jetpack = nodes.insert(Item { name: "jetpack".into(), upgrade: None });
+    /// nodes.set_provenance(jetpack, Provenance::from_source("mods/space.lua"));
+    ///
+    /// let boots = nodes.insert(Item { name: "boots".into(), upgrade: Some(jetpack) });
+    ///
+    /// let dangling = nodes.references_to_source("mods/space.lua");
+    /// assert!(dangling == [(boots.into(), jetpack.into())]);
+    /// ```
+    pub fn references_to_source(&self, source: &str) -> Vec<(DynKey, DynKey)> {
+        let removed: HashSet<_> = self.keys_from_source(source).into_iter().collect();
+        let mut references = Vec::new();
+
+        if removed.is_empty() {
+            return references;
+        }
+
+        for (key, node) in self.iter_dyn_sorted() {
+            if removed.contains(&key) {
+                continue;
+            }
+
+            node.visit_keys(&mut |child| {
+                if removed.contains(&child) {
+                    references.push((key, child));
+                }
+            });
+        }
+
+        references
+    }
+}
+
+/// Nodes that can be hashed and walked by [`Nodes::stable_hash`].
+///
+/// It's implemented for all types that implement [`Hash`] and [`VisitKeys`].
+pub trait HashNode: VisitKeys + 'static {
+    /// Feed the node's content into `state`.
+    fn hash_node(&self, state: &mut dyn Hasher);
+}
+
+impl<T> HashNode for T
+where
+    T: Hash + VisitKeys + 'static,
+{
+    #[inline]
+    fn hash_node(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state);
+    }
+}
+
+make_bounds!(pub HashBounds: HashNode);
+
+/// Nodes that can be both walked and remapped, such as by
+/// [`Nodes::rewrite`].
+///
+/// It's implemented for all types that implement [`VisitKeys`] and
+/// [`RemapKeys`].
+pub trait RemapNode: VisitKeys + RemapKeys + 'static {}
+
+impl<T> RemapNode for T where T: VisitKeys + RemapKeys + 'static {}
+
+make_bounds!(pub RemapBounds: RemapNode);
+
+#[cfg(feature = "std")]
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: HashNode,
+{
+    /// Hash the nodes that can be reached from `roots`, by walking the graph
+    /// breadth first in the order the keys are visited.
+    ///
+    /// The result only depends on the nodes' content and the graph's shape,
+    /// so it can be used for checking if a graph has changed. Keys are hashed
+    /// by their slot when they are part of the nodes' [`Hash`]
+    /// implementations, which is the same as long as the nodes are inserted
+    /// in the same order. The hash is not guaranteed to be the same between
+    /// different builds of the program.
+    ///
+    /// This is only available with the `std` feature.
+    ///
+    /// ```
+    /// use typed_nodes::{graph::{HashBounds, VisitKeys}, Key, Nodes};
+    ///
+    /// #[derive(Hash, VisitKeys)]
+    /// struct Person {
+    ///     name: String,
+    ///     friend: Option<Key<Person>>,
+    /// }
+    ///
+    /// fn make_nodes(bob_name: &str) -> (Nodes<(), HashBounds>, Key<Person>) {
+    ///     let mut nodes = Nodes::new();
+    ///     let bob = nodes.insert(Person { name: bob_name.into(), friend: None });
+    ///     let alice = nodes.insert(Person { name: "Alice".into(), friend: Some(bob) });
+    ///     (nodes, alice)
+    /// }
+    ///
+    /// let (nodes_a, alice_a) = make_nodes("Bob");
+    /// let (nodes_b, alice_b) = make_nodes("Bob");
+    /// let (nodes_c, alice_c) = make_nodes("Robert");
+    ///
+    /// let hash_a = nodes_a.stable_hash([alice_a.into()]);
+    /// assert_eq!(hash_a, nodes_b.stable_hash([alice_b.into()]));
+    /// assert_ne!(hash_a, nodes_c.stable_hash([alice_c.into()]));
+    /// ```
+    pub fn stable_hash(&self, roots: impl IntoIterator<Item = DynKey>) -> u64 {
+        hash_graph(self, roots)
+    }
+}
+
+/// Find groups of nodes, reachable from `roots`, that are the roots of
+/// structurally identical subgraphs. The nodes in a group have equal content
+/// and refer to nodes that are identical in the same way, but they are
+/// separate copies instead of shared nodes. Groups that are only part of
+/// another group's subgraphs are left out.
+///
+/// The nodes are compared by what their [`Hash`] implementations write, with
+/// each key replaced by the group of the node it points to. The groups are
+/// found in a single pass, starting from the leaves, and nodes that are part
+/// of a cycle are never grouped. The groups, and the nodes in them, are in
+/// the order they are found when walking the graph breadth first.
+///
+/// ```
+/// use typed_nodes::{
+///     graph::{find_isomorphic_duplicates, HashBounds, VisitKeys},
+///     DynKey, Key, Nodes,
+/// };
+///
+/// #[derive(Hash, VisitKeys)]
+/// enum Asset {
+///     Texture(&'static str),
+///     Material { roughness: u8, texture: Key<Asset> },
+///     Model(Vec<Key<Asset>>),
+/// }
+///
+/// let mut nodes = Nodes::<(), HashBounds>::new();
+/// let texture = nodes.insert(Asset::Texture("wood.png"));
+/// let material = nodes.insert(Asset::Material { roughness: 5, texture });
+///
+/// // A copy of the material, with its own copy of the texture.
+/// let texture_copy = nodes.insert(Asset::Texture("wood.png"));
+/// let material_copy = nodes.insert(Asset::Material { roughness: 5, texture: texture_copy });
+///
+/// let model = nodes.insert(Asset::Model(vec![material, material_copy]));
+///
+/// let duplicates = find_isomorphic_duplicates(&nodes, [model.into()]);
+/// assert!(duplicates == [vec![DynKey::from(material), material_copy.into()]]);
+/// ```
+pub fn find_isomorphic_duplicates<I, B>(
+    nodes: &Nodes<I, B>,
+    roots: impl IntoIterator<Item = DynKey>,
+) -> Vec<Vec<DynKey>>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: HashNode,
+{
+    let keys = walk_all(nodes, roots);
+    let classes = structure_classes(nodes, &keys);
+
+    let mut groups: Vec<Vec<DynKey>> = Vec::new();
+    let mut group_indices: HashMap<usize, usize> = HashMap::default();
+
+    for &key in &keys {
+        let Some(&Some(class)) = classes.get(&key) else {
+            continue;
+        };
+
+        if nodes.get_dyn(key).is_none() {
+            continue;
+        }
+
+        let index = *group_indices.entry(class).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[index].push(key);
+    }
+
+    let mut covered: HashSet<DynKey> = HashSet::default();
+    let mut duplicates = Vec::new();
+
+    for group in groups {
+        if group.len() < 2 || group.iter().all(|key| covered.contains(key)) {
+            continue;
+        }
+
+        // Each node is only walked through once, when it's first covered.
+        let mut stack = Vec::new();
+        for &key in &group {
+            if let Some(node) = nodes.get_dyn(key) {
+                node.visit_keys(&mut |child| stack.push(child));
+            }
+        }
+
+        while let Some(key) = stack.pop() {
+            if covered.insert(key) {
+                if let Some(node) = nodes.get_dyn(key) {
+                    node.visit_keys(&mut |child| stack.push(child));
+                }
+            }
+        }
+
+        duplicates.push(group);
+    }
+
+    duplicates
+}
+
+/// Sort `keys` into classes of structurally identical subgraphs, by walking
+/// them in postorder. Nodes with the same class have the same type, their
+/// [`Hash`] implementations write the same data, and their keys point to
+/// nodes with the same classes. Nodes that are part of, or lead to, a cycle
+/// get the class `None`.
+fn structure_classes<I, B>(nodes: &Nodes<I, B>, keys: &[DynKey]) -> HashMap<DynKey, Option<usize>>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: HashNode,
+{
+    let children = |key: DynKey| {
+        let mut children = Vec::new();
+        if let Some(node) = nodes.get_dyn(key) {
+            node.visit_keys(&mut |child| children.push(child));
+        }
+        children
+    };
+
+    // `None` while a node is on the stack, and `Some` when it's finished.
+    let mut classes: HashMap<DynKey, Option<Option<usize>>> = HashMap::default();
+    let mut structures: HashMap<(core::any::TypeId, Vec<u8>), usize> = HashMap::default();
+
+    for &root in keys {
+        if classes.contains_key(&root) {
+            continue;
+        }
+
+        let mut stack = alloc::vec![(root, children(root), 0)];
+        classes.insert(root, None);
+
+        while let Some((key, key_children, next_child)) = stack.last_mut() {
+            if let Some(&child) = key_children.get(*next_child) {
+                *next_child += 1;
+
+                if !classes.contains_key(&child) {
+                    classes.insert(child, None);
+                    stack.push((child, children(child), 0));
+                }
+
+                continue;
+            }
+
+            let key = *key;
+            let child_classes: Option<Vec<(u64, usize)>> = key_children
+                .iter()
+                .map(|child| {
+                    let class = classes.get(child).copied().flatten().flatten()?;
+                    Some((slot_bits(*child), class))
+                })
+                .collect();
+
+            let class = child_classes.map(|child_classes| {
+                let mut writer = StructureWriter {
+                    bytes: Vec::new(),
+                    children: &child_classes,
+                    next_child: 0,
+                };
+
+                match nodes.get_dyn(key) {
+                    Some(node) => {
+                        writer.bytes.push(1);
+                        node.hash_node(&mut writer);
+                    }
+                    // Missing or reserved nodes.
+                    None => writer.bytes.push(0),
+                }
+
+                for &(_, class) in &child_classes {
+                    writer.bytes.extend_from_slice(&class.to_le_bytes());
+                }
+
+                let next_class = structures.len();
+                *structures
+                    .entry((key.node_type, writer.bytes))
+                    .or_insert(next_class)
+            });
+
+            classes.insert(key, Some(class));
+            stack.pop();
+        }
+    }
+
+    classes
+        .into_iter()
+        .map(|(key, class)| (key, class.flatten()))
+        .collect()
+}
+
+/// The bits that a key's slot writes when it's hashed.
+fn slot_bits(key: DynKey) -> u64 {
+    use slotmap::Key as _;
+
+    key.slot.data().as_ffi()
+}
+
+/// Collects the data that a node's [`Hash`] implementation writes, with the
+/// slots of the node's keys replaced by the classes of the nodes they point
+/// to. The keys are expected in the same order as they are visited.
+struct StructureWriter<'a> {
+    bytes: Vec<u8>,
+    children: &'a [(u64, usize)],
+    next_child: usize,
+}
+
+impl Hasher for StructureWriter<'_> {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        match self.children.get(self.next_child) {
+            Some(&(slot, class)) if slot == value => {
+                self.next_child += 1;
+                self.bytes.extend_from_slice(&(class as u64).to_le_bytes());
+            }
+            _ => self.bytes.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+/// Hash the nodes that can be reached from `roots`. See
+/// [`Nodes::stable_hash`].
+#[cfg(feature = "std")]
+fn hash_graph<I, B>(nodes: &Nodes<I, B>, roots: impl IntoIterator<Item = DynKey>) -> u64
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: HashNode,
+{
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut walk = Walk::default();
+
+    for root in roots {
+        hasher.write_usize(walk.index_of(root));
+    }
+
+    while let Some(key) = walk.queue.pop_front() {
+        let Some(node) = nodes.get_dyn(key) else {
+            // Missing or reserved nodes.
+            hasher.write_u8(0);
+            continue;
+        };
+
+        hasher.write_u8(1);
+        key.node_type.hash(&mut hasher);
+        node.hash_node(&mut hasher);
+        node.visit_keys(&mut |child| hasher.write_usize(walk.index_of(child)));
+    }
+
+    hasher.finish()
+}
+
+/// Nodes that can be compared by [`diff`].
+///
+/// It's implemented for all types that implement [`PartialEq`] and
+/// [`VisitKeys`].
+pub trait EqNode: VisitKeys + 'static {
+    /// Compare with `other`, which is equal only if it's of the same type.
+    fn eq_node(&self, other: &dyn Any) -> bool;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> EqNode for T
+where
+    T: PartialEq + VisitKeys + 'static,
+{
+    #[inline]
+    fn eq_node(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+make_bounds!(pub EqBounds: EqNode);
+
+/// The differences between two graphs, as found by [`diff`].
+pub struct GraphDiff<'a, I> {
+    /// Nodes that are only in the second graph, with their keys in that graph.
+    pub added: Vec<(&'a I, DynKey)>,
+    /// Nodes that are only in the first graph, with their keys in that graph.
+    pub removed: Vec<(&'a I, DynKey)>,
+    /// Nodes that are in both graphs, but are not equal, with their keys in
+    /// the first and the second graph.
+    pub changed: Vec<(&'a I, DynKey, DynKey)>,
+}
+
+impl<I> GraphDiff<'_, I> {
+    /// Check if the graphs had no differences.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the nodes that can be reached from `roots_a` in `a` with the ones
+/// that can be reached from `roots_b` in `b`.
+///
+/// Nodes are matched by their IDs and type, so nodes without IDs are only
+/// compared as part of the nodes that refer to them. Keys are compared by
+/// their slots when they are part of the nodes' [`PartialEq`]
+/// implementations, which is the same as long as the nodes are inserted in
+/// the same order. The differences are listed in the order the nodes are
+/// found when walking the graphs.
+///
+/// ```
+/// use typed_nodes::{graph::{diff, EqBounds, VisitKeys}, DynKey, Key, Nodes};
+///
+/// #[derive(PartialEq, VisitKeys)]
+/// struct Material {
+///     density: f64,
+/// }
+///
+/// #[derive(PartialEq, VisitKeys)]
+/// struct Beam {
+///     materials: Vec<Key<Material>>,
+/// }
+///
+/// fn make_nodes(materials: &[(&str, f64)]) -> (Nodes<String, EqBounds>, DynKey) {
+///     let mut nodes = Nodes::new();
+///     let materials = materials
+///         .iter()
+///         .map(|&(name, density)| nodes.insert_with_id(name.into(), Material { density }).0)
+///         .collect();
+///     let (beam, _) = nodes.insert_with_id("beam".into(), Beam { materials });
+///     (nodes, beam.into())
+/// }
+///
+/// let (a, root_a) = make_nodes(&[("steel", 7.85), ("wood", 0.6)]);
+/// let (b, root_b) = make_nodes(&[("steel", 7.9), ("glass", 2.5)]);
+/// let diff = diff(&a, &b, [root_a], [root_b]);
+///
+/// let ids = |changes: &[(&String, DynKey)]| -> Vec<String> {
+///     changes.iter().map(|(id, _)| id.to_string()).collect()
+/// };
+/// assert_eq!(ids(&diff.added), ["glass"]);
+/// assert_eq!(ids(&diff.removed), ["wood"]);
+/// assert_eq!(diff.changed.len(), 1);
+/// assert_eq!(diff.changed[0].0, "steel");
+/// ```
+pub fn diff<'a, I, B>(
+    a: &'a Nodes<I, B>,
+    b: &'a Nodes<I, B>,
+    roots_a: impl IntoIterator<Item = DynKey>,
+    roots_b: impl IntoIterator<Item = DynKey>,
+) -> GraphDiff<'a, I>
+where
+    I: Hash + Eq + 'static,
+    B: Bounds,
+    B::DynSelf: EqNode,
+{
+    let nodes_a = identified_nodes(a, roots_a);
+    let nodes_b = identified_nodes(b, roots_b);
+
+    let keys_a: HashMap<_, _> = nodes_a
+        .iter()
+        .map(|&(id, key)| ((key.node_type, id), key))
+        .collect();
+    let keys_b: HashMap<_, _> = nodes_b
+        .iter()
+        .map(|&(id, key)| ((key.node_type, id), key))
+        .collect();
+
+    let mut diff = GraphDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+
+    for &(id, key_a) in &nodes_a {
+        let Some(&key_b) = keys_b.get(&(key_a.node_type, id)) else {
+            diff.removed.push((id, key_a));
+            continue;
+        };
+
+        let is_equal = match (a.get_dyn(key_a), b.get_dyn(key_b)) {
+            (Some(node_a), Some(node_b)) => node_a.eq_node(node_b.as_any()),
+            (None, None) => true,
+            _ => false,
+        };
+
+        if !is_equal {
+            diff.changed.push((id, key_a, key_b));
+        }
+    }
+
+    for &(id, key_b) in &nodes_b {
+        if !keys_a.contains_key(&(key_b.node_type, id)) {
+            diff.added.push((id, key_b));
+        }
+    }
+
+    diff
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Mark the node at `key` as a root of the graph. The roots are used by
+    /// [`Nodes::reachable_keys`], [`Nodes::collect_garbage`] and
+    /// [`Nodes::to_dot`], and are typically the nodes that the rest of the
+    /// application has handles to. Returns `false` if it was already a root.
+    ///
+    /// A node stops being a root when it's removed.
+    #[inline]
+    pub fn add_root(&mut self, key: impl Into<DynKey>) -> bool {
+        self.roots.insert(key.into())
+    }
+
+    /// Stop treating the node at `key` as a root. Returns `false` if it
+    /// wasn't a root.
+    #[inline]
+    pub fn remove_root(&mut self, key: impl Into<DynKey>) -> bool {
+        self.roots.remove(&key.into())
+    }
+
+    /// Check if the node at `key` is a root.
+    #[inline]
+    pub fn is_root(&self, key: impl Into<DynKey>) -> bool {
+        self.roots.contains(&key.into())
+    }
+
+    /// Iterate over the roots, sorted by key.
+    #[inline]
+    pub fn roots(&self) -> Roots<'_> {
+        Roots {
+            inner: self.roots.iter(),
+        }
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    /// Find the nodes that can be reached from the roots, including the
+    /// roots, in breadth first order. See [`Nodes::add_root`].
+    ///
+    /// ```
+    /// use typed_nodes::{graph::VisitKeys, make_bounds, DynKey, Key, Nodes};
+    ///
+    /// make_bounds!(RoomBounds: VisitKeys + 'static);
+    ///
+    /// #[derive(VisitKeys)]
+    /// struct Room {
+    ///     exits: Vec<Key<Room>>,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<(), RoomBounds>::new();
+    /// let cellar = nodes.insert(Room { exits: vec![] });
+    /// let hall = nodes.insert(Room { exits: vec![cellar] });
+    /// let attic = nodes.insert(Room { exits: vec![hall] });
+    ///
+    /// nodes.add_root(hall);
+    /// assert!(nodes.reachable_keys() == [DynKey::from(hall), cellar.into()]);
+    ///
+    /// assert_eq!(nodes.collect_garbage(), 1);
+    /// assert!(nodes.get(attic).is_none());
+    /// ```
+    pub fn reachable_keys(&self) -> Vec<DynKey> {
+        self.reachable_keys_from(self.roots.iter().copied())
+    }
+
+    /// Find the nodes that can be reached from `roots`, including `roots`,
+    /// in breadth first order. Keys to slots without nodes are not included,
+    /// unless the slots are reserved.
+    pub fn reachable_keys_from(&self, roots: impl IntoIterator<Item = DynKey>) -> Vec<DynKey> {
+        let mut keys = walk_all(self, roots);
+        keys.retain(|&key| self.slot_state(key) != SlotState::Vacant);
+        keys
+    }
+
+    /// Remove all nodes that can't be reached from the roots, and return
+    /// the number of removed nodes. Reserved slots are kept.
+    ///
+    /// All nodes are removed if there are no roots, so make sure to add
+    /// them with [`Nodes::add_root`] first.
+    pub fn collect_garbage(&mut self) -> usize {
+        let reachable: HashSet<_> = walk_all(self, self.roots.iter().copied())
+            .into_iter()
+            .collect();
+        let unreachable: Vec<_> = self
+            .dyn_keys()
+            .filter(|key| !reachable.contains(key))
+            .collect();
+
+        unreachable
+            .into_iter()
+            .filter(|&key| self.remove_dyn(key))
+            .count()
+    }
+
+    /// Write the nodes that can be reached from the roots as a graph in the
+    /// Graphviz DOT format. See [`Nodes::to_dot_from`].
+    pub fn to_dot(&self) -> String {
+        self.to_dot_from(self.roots.iter().copied())
+    }
+
+    /// Write the nodes that can be reached from `roots` as a graph in the
+    /// Graphviz DOT format. Each node is labeled with its type and slot,
+    /// roots have a double border, and keys to missing nodes lead to
+    /// dashed nodes.
+    ///
+    /// ```
+    /// use typed_nodes::{graph::VisitKeys, make_bounds, Key, Nodes};
+    ///
+    /// make_bounds!(RoomBounds: VisitKeys + 'static);
+    ///
+    /// #[derive(VisitKeys)]
+    /// struct Room {
+    ///     exits: Vec<Key<Room>>,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<(), RoomBounds>::new();
+    /// let cellar = nodes.insert(Room { exits: vec![] });
+    /// let hall = nodes.insert(Room { exits: vec![cellar] });
+    /// nodes.add_root(hall);
+    ///
+    /// let dot = nodes.to_dot();
+    /// assert!(dot.starts_with("digraph {"));
+    /// assert!(dot.contains("n0 -> n1;"));
+    /// ```
+    pub fn to_dot_from(&self, roots: impl IntoIterator<Item = DynKey>) -> String {
+        use core::fmt::Write;
+
+        let mut walk = Walk::default();
+        let mut edges = Vec::new();
+        let mut dot = String::from("digraph {\n");
+
+        for root in roots {
+            walk.index_of(root);
+        }
+
+        while let Some(key) = walk.queue.pop_front() {
+            let index = walk.index_of(key);
+            let type_name = self.type_name(key.node_type).unwrap_or("<unknown>");
+            let label = alloc::format!("{type_name}\n{:?}", slotmap::Key::data(&key.slot));
+            let mut attributes = alloc::format!("label={label:?}, shape=box");
+
+            if self.is_root(key) {
+                attributes.push_str(", peripheries=2");
+            }
+
+            if let Some(node) = self.get_dyn(key) {
+                node.visit_keys(&mut |child| edges.push((index, walk.index_of(child))));
+            } else {
+                attributes.push_str(", style=dashed");
+            }
+
+            // Writing to a `String` doesn't fail.
+            let _ = writeln!(dot, "    n{index} [{attributes}];");
+        }
+
+        for (from, to) in edges {
+            let _ = writeln!(dot, "    n{from} -> n{to};");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Check if `to` can be reached by following keys from `from`. A node can
+/// always reach itself.
+///
+/// ```
+/// use typed_nodes::{graph::{is_reachable, VisitKeys}, make_bounds, Key, Nodes};
+///
+/// make_bounds!(RoomBounds: VisitKeys + 'static);
+///
+/// #[derive(VisitKeys)]
+/// struct Room {
+///     exits: Vec<Key<Room>>,
+/// }
+///
+/// let mut nodes = Nodes::<(), RoomBounds>::new();
+/// let cellar = nodes.insert(Room { exits: vec![] });
+/// let hall = nodes.insert(Room { exits: vec![cellar] });
+/// let attic = nodes.insert(Room { exits: vec![hall] });
+///
+/// assert!(is_reachable(&nodes, attic.into(), cellar.into()));
+/// assert!(!is_reachable(&nodes, cellar.into(), attic.into()));
+/// ```
+pub fn is_reachable<I, B>(nodes: &Nodes<I, B>, from: DynKey, to: DynKey) -> bool
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let mut walk = Walk::default();
+    walk.index_of(from);
+
+    while let Some(key) = walk.queue.pop_front() {
+        if key == to {
+            return true;
+        }
+
+        if let Some(node) = nodes.get_dyn(key) {
+            node.visit_keys(&mut |child| {
+                walk.index_of(child);
+            });
+        }
+    }
+
+    false
+}
+
+/// Find the shortest chain of keys from `from` to `to`, including both of
+/// them. It's useful for showing how a node can be reached, such as when it
+/// turns out to be invalid. Returns `None` if `to` can't be reached from
+/// `from`.
+///
+/// ```
+/// use typed_nodes::{graph::{find_path, VisitKeys}, make_bounds, DynKey, Key, Nodes};
+///
+/// make_bounds!(AssetBounds: VisitKeys + 'static);
+///
+/// #[derive(VisitKeys)]
+/// enum Asset {
+///     Texture,
+///     Material(Key<Asset>),
+///     Model(Vec<Key<Asset>>),
+/// }
+///
+/// let mut nodes = Nodes::<(), AssetBounds>::new();
+/// let texture = nodes.insert(Asset::Texture);
+/// let material = nodes.insert(Asset::Material(texture));
+/// let model = nodes.insert(Asset::Model(vec![material]));
+/// nodes.remove(texture);
+///
+/// // The texture is gone, but the path to it can still be found.
+/// let path = find_path(&nodes, model.into(), texture.into()).unwrap();
+/// assert!(path == [DynKey::from(model), material.into(), texture.into()]);
+///
+/// assert!(find_path(&nodes, material.into(), model.into()).is_none());
+/// ```
+pub fn find_path<I, B>(nodes: &Nodes<I, B>, from: DynKey, to: DynKey) -> Option<Vec<DynKey>>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let mut walk = Walk::default();
+    let mut parents: HashMap<DynKey, DynKey> = HashMap::default();
+    walk.index_of(from);
+
+    while let Some(key) = walk.queue.pop_front() {
+        if key == to {
+            let mut path = alloc::vec![key];
+
+            while let Some(&parent) = parents.get(path.last()?) {
+                path.push(parent);
+            }
+
+            path.reverse();
+            return Some(path);
+        }
+
+        if let Some(node) = nodes.get_dyn(key) {
+            node.visit_keys(&mut |child| {
+                if !walk.indices.contains_key(&child) {
+                    parents.insert(child, key);
+                    walk.index_of(child);
+                }
+            });
+        }
+    }
+
+    None
+}
+
+/// Find the dominators of the nodes that can be reached from `root`. A node
+/// dominates another node if all paths from `root` to the other node go
+/// through it. The immediate dominator of a node is the closest node that
+/// dominates it, which can be seen as its single owner.
+///
+/// ```
+/// use typed_nodes::{graph::{dominators, VisitKeys}, make_bounds, DynKey, Key, Nodes};
+///
+/// make_bounds!(AssetBounds: VisitKeys + 'static);
+///
+/// #[derive(VisitKeys)]
+/// enum Asset {
+///     Texture,
+///     Material(Vec<Key<Asset>>),
+///     Scene(Vec<Key<Asset>>),
+/// }
+///
+/// let mut nodes = Nodes::<(), AssetBounds>::new();
+/// let shared = nodes.insert(Asset::Texture);
+/// let owned = nodes.insert(Asset::Texture);
+/// let wood = nodes.insert(Asset::Material(vec![shared, owned]));
+/// let stone = nodes.insert(Asset::Material(vec![shared]));
+/// let scene = nodes.insert(Asset::Scene(vec![wood, stone]));
+///
+/// let dominators = dominators(&nodes, scene.into());
+///
+/// assert!(dominators.immediate_dominator(owned.into()) == Some(wood.into()));
+/// assert!(dominators.immediate_dominator(shared.into()) == Some(scene.into()));
+/// assert!(dominators.dominates(wood.into(), owned.into()));
+/// assert!(!dominators.dominates(wood.into(), shared.into()));
+///
+/// let chain: Vec<DynKey> = dominators.dominators(owned.into()).collect();
+/// assert!(chain == [owned.into(), wood.into(), scene.into()]);
+/// ```
+pub fn dominators<I, B>(nodes: &Nodes<I, B>, root: DynKey) -> Dominators
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let order = reverse_postorder(nodes, root);
+    let indices: HashMap<_, _> = order
+        .iter()
+        .enumerate()
+        .map(|(index, &key)| (key, index))
+        .collect();
+
+    let mut predecessors = alloc::vec![Vec::new(); order.len()];
+    for (index, &key) in order.iter().enumerate() {
+        if let Some(node) = nodes.get_dyn(key) {
+            node.visit_keys(&mut |child| predecessors[indices[&child]].push(index));
+        }
+    }
+
+    // The iterative algorithm by Cooper, Harvey and Kennedy, where the nodes
+    // are numbered in reverse postorder.
+    let mut immediate: Vec<Option<usize>> = alloc::vec![None; order.len()];
+    immediate[0] = Some(0);
+
+    let intersect = |immediate: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while a > b {
+                a = immediate[a].expect("processed nodes should have dominators");
+            }
+            while b > a {
+                b = immediate[b].expect("processed nodes should have dominators");
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for index in 1..order.len() {
+            let mut new_immediate = None;
+
+            for &predecessor in &predecessors[index] {
+                if immediate[predecessor].is_none() {
+                    continue;
+                }
+
+                new_immediate = Some(match new_immediate {
+                    Some(current) => intersect(&immediate, predecessor, current),
+                    None => predecessor,
+                });
+            }
+
+            if new_immediate != immediate[index] {
+                immediate[index] = new_immediate;
+                changed = true;
+            }
+        }
+    }
+
+    Dominators {
+        root,
+        immediate: order
+            .iter()
+            .zip(immediate)
+            .skip(1)
+            .filter_map(|(&key, dominator)| Some((key, order[dominator?])))
+            .collect(),
+    }
+}
+
+/// Sort the keys that can be reached from `root` in reverse postorder, with
+/// `root` first.
+fn reverse_postorder<I, B>(nodes: &Nodes<I, B>, root: DynKey) -> Vec<DynKey>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let children = |key: DynKey| {
+        let mut children = Vec::new();
+        if let Some(node) = nodes.get_dyn(key) {
+            node.visit_keys(&mut |child| children.push(child));
+        }
+        children
+    };
+
+    let mut visited: HashSet<DynKey> = HashSet::default();
+    let mut postorder = Vec::new();
+    let mut stack = alloc::vec![(root, children(root), 0)];
+    visited.insert(root);
+
+    while let Some((key, key_children, next_child)) = stack.last_mut() {
+        if let Some(&child) = key_children.get(*next_child) {
+            *next_child += 1;
+
+            if visited.insert(child) {
+                stack.push((child, children(child), 0));
+            }
+        } else {
+            postorder.push(*key);
+            stack.pop();
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+/// The dominators of the nodes that can be reached from a root, as found by
+/// [`dominators`].
+pub struct Dominators {
+    root: DynKey,
+    immediate: HashMap<DynKey, DynKey>,
+}
+
+impl Dominators {
+    /// The node the dominators were found from.
+    #[inline]
+    pub fn root(&self) -> DynKey {
+        self.root
+    }
+
+    /// Check if `key` can be reached from the root.
+    #[inline]
+    pub fn is_reachable(&self, key: DynKey) -> bool {
+        key == self.root || self.immediate.contains_key(&key)
+    }
+
+    /// Get the closest node that dominates `key`, other than `key` itself.
+    /// Returns `None` for the root and for nodes that can't be reached from
+    /// it.
+    #[inline]
+    pub fn immediate_dominator(&self, key: DynKey) -> Option<DynKey> {
+        self.immediate.get(&key).copied()
+    }
+
+    /// Iterate over the nodes that dominate `key`, starting with `key`
+    /// itself and ending with the root. It's empty if `key` can't be
+    /// reached from the root.
+    pub fn dominators(&self, key: DynKey) -> DominatorsIter<'_> {
+        DominatorsIter {
+            dominators: self,
+            next: self.is_reachable(key).then_some(key),
+        }
+    }
+
+    /// Check if `dominator` dominates `key`. A node dominates itself.
+    pub fn dominates(&self, dominator: DynKey, key: DynKey) -> bool {
+        self.dominators(key).any(|key| key == dominator)
+    }
+}
+
+/// Iterates over the dominators of a node. See [`Dominators::dominators`].
+pub struct DominatorsIter<'a> {
+    dominators: &'a Dominators,
+    next: Option<DynKey>,
+}
+
+impl<'a> Iterator for DominatorsIter<'a> {
+    type Item = DynKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.next?;
+        self.next = self.dominators.immediate_dominator(key);
+        Some(key)
+    }
+}
+
+/// Iterates over the roots of the graph. See [`Nodes::roots`].
+pub struct Roots<'a> {
+    inner: alloc::collections::btree_set::Iter<'a, DynKey>,
+}
+
+impl<'a> Iterator for Roots<'a> {
+    type Item = DynKey;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().copied()
+    }
+}
+
+/// Find the keys that can be reached from `roots`, in breadth first order,
+/// including keys to missing nodes.
+fn walk_all<I, B>(nodes: &Nodes<I, B>, roots: impl IntoIterator<Item = DynKey>) -> Vec<DynKey>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let mut walk = Walk::default();
+    let mut found = Vec::new();
+
+    for root in roots {
+        walk.index_of(root);
+    }
+
+    while let Some(key) = walk.queue.pop_front() {
+        if let Some(node) = nodes.get_dyn(key) {
+            node.visit_keys(&mut |child| {
+                walk.index_of(child);
+            });
+        }
+
+        found.push(key);
+    }
+
+    found
+}
+
+/// Find the nodes that can be reached from `roots` and have IDs, in the
+/// order they are found.
+fn identified_nodes<I, B>(
+    nodes: &Nodes<I, B>,
+    roots: impl IntoIterator<Item = DynKey>,
+) -> Vec<(&I, DynKey)>
+where
+    I: Hash + Eq + 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let indices: HashMap<_, _> = walk_all(nodes, roots)
+        .into_iter()
+        .enumerate()
+        .map(|(index, key)| (key, index))
+        .collect();
+
+    let mut identified: Vec<_> = nodes
+        .iter_ids_dyn()
+        .filter_map(|(id, key)| Some((indices.get(&key)?, id, key)))
+        .collect();
+    identified.sort_by_key(|&(&index, _, _)| index);
+
+    identified
+        .into_iter()
+        .map(|(_, id, key)| (id, key))
+        .collect()
+}
+
+/// Assigns an index to each key in the order they are found, and keeps track
+/// of the ones that haven't been visited yet.
+#[derive(Default)]
+struct Walk {
+    indices: HashMap<DynKey, usize>,
+    queue: VecDeque<DynKey>,
+}
+
+impl Walk {
+    fn index_of(&mut self, key: DynKey) -> usize {
+        let next_index = self.indices.len();
+
+        *self.indices.entry(key).or_insert_with(|| {
+            self.queue.push_back(key);
+            next_index
+        })
+    }
+}
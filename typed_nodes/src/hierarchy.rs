@@ -0,0 +1,199 @@
+//! Parent and child relationships between nodes.
+//!
+//! Many node graphs are trees, where each node has a parent and a list of
+//! children. The relationships are stored next to the nodes in [`Nodes`], so
+//! the nodes themselves don't have to keep track of them, and they are
+//! cleaned up when a node is removed.
+//!
+//! ```
+//! use typed_nodes::{DynKey, Nodes};
+//!
+//! struct Entity {
+//!     name: &'static str,
+//! }
+//!
+//! let mut nodes = Nodes::<()>::new();
+//! let world = nodes.insert(Entity { name: "world" });
+//! let player = nodes.insert(Entity { name: "player" });
+//! let sword = nodes.insert(Entity { name: "sword" });
+//!
+//! assert!(nodes.set_parent(player, world));
+//! assert!(nodes.set_parent(sword, player));
+//!
+//! // A node can't become its own ancestor.
+//! assert!(!nodes.set_parent(world, sword));
+//!
+//! assert!(nodes.children(world).eq([DynKey::from(player)]));
+//! assert!(nodes.ancestors(sword).eq([DynKey::from(player), world.into()]));
+//!
+//! // The children of a removed node become roots.
+//! nodes.remove(player);
+//! assert!(nodes.children(world).next().is_none());
+//! assert!(nodes.parent(sword).is_none());
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{bounds::Bounds, DynKey, HashMap, Nodes};
+
+/// The parent and child relationships in a [`Nodes`] collection.
+#[derive(Default)]
+pub(crate) struct Hierarchy {
+    parents: HashMap<DynKey, DynKey>,
+    children: HashMap<DynKey, Vec<DynKey>>,
+}
+
+impl Hierarchy {
+    fn is_ancestor(&self, ancestor: DynKey, key: DynKey) -> bool {
+        Ancestors {
+            hierarchy: Some(self),
+            next: self.parents.get(&key).copied(),
+        }
+        .any(|key| key == ancestor)
+    }
+
+    fn set_parent(&mut self, child: DynKey, parent: DynKey) {
+        self.remove_parent(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+    }
+
+    fn remove_parent(&mut self, child: DynKey) -> Option<DynKey> {
+        let parent = self.parents.remove(&child)?;
+
+        if let Some(siblings) = self.children.get_mut(&parent) {
+            siblings.retain(|&sibling| sibling != child);
+
+            if siblings.is_empty() {
+                self.children.remove(&parent);
+            }
+        }
+
+        Some(parent)
+    }
+
+    /// Detach `key` from its parent and make its children roots.
+    pub(crate) fn remove_key(&mut self, key: DynKey) {
+        if self.parents.is_empty() {
+            return;
+        }
+
+        self.remove_parent(key);
+
+        for child in self.children.remove(&key).unwrap_or_default() {
+            self.parents.remove(&child);
+        }
+    }
+
+    /// Move the parent and children of `from` to `into`. The parent of
+    /// `into` is kept if it already has one.
+    pub(crate) fn replace_key(&mut self, from: DynKey, into: DynKey) {
+        if self.parents.is_empty() {
+            return;
+        }
+
+        if let Some(parent) = self.remove_parent(from) {
+            if !self.parents.contains_key(&into) && !self.is_ancestor(into, parent) {
+                self.set_parent(into, parent);
+            }
+        }
+
+        for child in self.children.remove(&from).unwrap_or_default() {
+            self.parents.remove(&child);
+
+            if child != into && !self.is_ancestor(child, into) {
+                self.set_parent(child, into);
+            }
+        }
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Make `parent` the parent of `child`, and add `child` last to the
+    /// children of `parent`. Any previous parent is replaced.
+    ///
+    /// Returns `false` and leaves the hierarchy unchanged if `child` is the
+    /// same node as `parent` or one of its ancestors.
+    pub fn set_parent(&mut self, child: impl Into<DynKey>, parent: impl Into<DynKey>) -> bool {
+        let child = child.into();
+        let parent = parent.into();
+
+        let hierarchy = self.hierarchy.get_or_insert_with(Hierarchy::default);
+
+        if child == parent || hierarchy.is_ancestor(child, parent) {
+            return false;
+        }
+
+        hierarchy.set_parent(child, parent);
+        true
+    }
+
+    /// Remove `child` from its parent and return the parent's key.
+    #[inline]
+    pub fn remove_parent(&mut self, child: impl Into<DynKey>) -> Option<DynKey> {
+        self.hierarchy.as_mut()?.remove_parent(child.into())
+    }
+
+    /// Get the parent of `key`, if it has one.
+    #[inline]
+    pub fn parent(&self, key: impl Into<DynKey>) -> Option<DynKey> {
+        self.hierarchy.as_ref()?.parents.get(&key.into()).copied()
+    }
+
+    /// Iterate over the children of `key`, in the order they were added.
+    #[inline]
+    pub fn children(&self, key: impl Into<DynKey>) -> Children<'_> {
+        Children {
+            inner: self
+                .hierarchy
+                .as_ref()
+                .and_then(|hierarchy| hierarchy.children.get(&key.into()))
+                .map_or(&[][..], |children| &children[..])
+                .iter(),
+        }
+    }
+
+    /// Iterate over the ancestors of `key`, starting with its parent.
+    #[inline]
+    pub fn ancestors(&self, key: impl Into<DynKey>) -> Ancestors<'_> {
+        Ancestors {
+            hierarchy: self.hierarchy.as_ref(),
+            next: self.parent(key),
+        }
+    }
+}
+
+/// Iterates over the children of a node. It's created by [`Nodes::children`].
+pub struct Children<'a> {
+    inner: core::slice::Iter<'a, DynKey>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = DynKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().copied()
+    }
+}
+
+/// Iterates over the ancestors of a node. It's created by
+/// [`Nodes::ancestors`].
+pub struct Ancestors<'a> {
+    hierarchy: Option<&'a Hierarchy>,
+    next: Option<DynKey>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = DynKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.next?;
+        self.next = self.hierarchy?.parents.get(&key).copied();
+
+        Some(key)
+    }
+}
@@ -0,0 +1,273 @@
+//! Secondary indexes for finding nodes by a value derived from them.
+//!
+//! A [`FieldIndex`] maps a value, such as a field, to the keys of all nodes
+//! of one type that have that value. It's added to a [`Nodes`] collection
+//! with [`Nodes::add_index`] and kept up to date as nodes are inserted,
+//! replaced and removed.
+//!
+//! ```
+//! use typed_nodes::Nodes;
+//!
+//! struct Material {
+//!     shader: &'static str,
+//! }
+//!
+//! let mut nodes = Nodes::<()>::new();
+//! nodes.add_index(|material: &Material| material.shader);
+//!
+//! let stone = nodes.insert(Material { shader: "lit" });
+//! let grass = nodes.insert(Material { shader: "lit" });
+//! let sky = nodes.insert(Material { shader: "unlit" });
+//!
+//! let index = nodes.index::<Material, &str>().unwrap();
+//! assert_eq!(index.keys_where("lit").count(), 2);
+//!
+//! nodes.update(grass, |grass| grass.shader = "foliage");
+//! nodes.remove(sky);
+//!
+//! let index = nodes.index::<Material, &str>().unwrap();
+//! assert!(index.keys_where("lit").eq([stone]));
+//! assert!(index.keys_where("foliage").eq([grass]));
+//! assert!(index.keys_where("unlit").next().is_none());
+//! ```
+
+use alloc::collections::BTreeSet;
+use core::{
+    any::{Any, TypeId},
+    borrow::Borrow,
+    hash::Hash,
+};
+
+use downcast_rs::{impl_downcast, Downcast};
+
+use crate::{
+    bounds::{BoundedBy, Bounds, SideBoundedBy},
+    node_group::BoxedNodeGroup,
+    DynKey, HashMap, Key, Nodes,
+};
+
+/// An index from values of type `V` to the keys of nodes of type `T` with
+/// that value. The values are calculated with a function that is given to
+/// [`Nodes::add_index`].
+///
+/// The index is updated when nodes are inserted, replaced, updated with
+/// [`Nodes::update`], or removed. It doesn't see changes that are made
+/// through mutable references, such as from [`Nodes::get_mut`].
+pub struct FieldIndex<T, V> {
+    value_of: fn(&T) -> V,
+    keys: HashMap<V, BTreeSet<Key<T>>>,
+    values: HashMap<Key<T>, V>,
+}
+
+impl<T, V> FieldIndex<T, V>
+where
+    V: Hash + Eq + Clone,
+{
+    fn new(value_of: fn(&T) -> V) -> Self {
+        Self {
+            value_of,
+            keys: HashMap::with_hasher(Default::default()),
+            values: HashMap::with_hasher(Default::default()),
+        }
+    }
+
+    /// Iterate over the keys of the nodes with the value `value`, in key
+    /// order.
+    pub fn keys_where<Q>(&self, value: &Q) -> KeysWhere<'_, T>
+    where
+        Q: ?Sized + Hash + Eq,
+        V: Borrow<Q>,
+    {
+        KeysWhere {
+            inner: self.keys.get(value).map(BTreeSet::iter),
+        }
+    }
+
+    /// Get the indexed value for `key`.
+    #[inline]
+    pub fn value(&self, key: Key<T>) -> Option<&V> {
+        self.values.get(&key)
+    }
+
+    /// Iterate over the distinct values in the index, in arbitrary order.
+    #[inline]
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.keys.keys()
+    }
+
+    fn insert(&mut self, key: Key<T>, node: &T) {
+        self.remove(key);
+
+        let value = (self.value_of)(node);
+        self.keys.entry(value.clone()).or_default().insert(key);
+        self.values.insert(key, value);
+    }
+
+    fn remove(&mut self, key: Key<T>) {
+        let Some(value) = self.values.remove(&key) else {
+            return;
+        };
+
+        if let Some(keys) = self.keys.get_mut(&value) {
+            keys.remove(&key);
+
+            if keys.is_empty() {
+                self.keys.remove(&value);
+            }
+        }
+    }
+}
+
+/// A type erased [`FieldIndex`]. The group bounds decide which other traits
+/// it has.
+#[doc(hidden)]
+pub trait NodeIndex: Downcast {
+    /// Index `node` with `key`, replacing any previous value.
+    fn update(&mut self, key: DynKey, node: &dyn Any);
+
+    /// Remove `key` from the index.
+    fn remove_key(&mut self, key: DynKey);
+}
+impl_downcast!(NodeIndex);
+
+impl<T, V> NodeIndex for FieldIndex<T, V>
+where
+    T: 'static,
+    V: Hash + Eq + Clone + 'static,
+{
+    fn update(&mut self, key: DynKey, node: &dyn Any) {
+        if let (Some(key), Some(node)) = (key.into_static(), node.downcast_ref()) {
+            self.insert(key, node);
+        }
+    }
+
+    fn remove_key(&mut self, key: DynKey) {
+        if let Some(key) = key.into_static() {
+            self.remove(key);
+        }
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Add an index of the nodes of type `T`, by the value `value_of`
+    /// returns for them. It replaces any previous index with the same node
+    /// and value types. The current nodes are indexed right away.
+    ///
+    /// The values have to fulfill the same group bounds as the nodes, such
+    /// as being [`Send`] and [`Sync`] with
+    /// [`SendSyncBounds`][crate::bounds::SendSyncBounds].
+    pub fn add_index<T, V>(&mut self, value_of: fn(&T) -> V)
+    where
+        T: BoundedBy<I, B>,
+        V: Hash + Eq + Clone + SideBoundedBy<B::GroupBounds>,
+    {
+        let mut index = FieldIndex::new(value_of);
+
+        for (key, node) in self.iter::<T>() {
+            index.insert(key, node);
+        }
+
+        self.indexes
+            .get_or_insert_with(Default::default)
+            .insert((TypeId::of::<T>(), TypeId::of::<V>()), V::box_index(index));
+    }
+
+    /// Get the index of nodes of type `T` by values of type `V`, if it has
+    /// been added with [`Nodes::add_index`].
+    pub fn index<T, V>(&self) -> Option<&FieldIndex<T, V>>
+    where
+        T: BoundedBy<I, B>,
+        V: Hash + Eq + Clone + SideBoundedBy<B::GroupBounds>,
+    {
+        V::downcast_index_ref(
+            self.indexes
+                .as_ref()?
+                .get(&(TypeId::of::<T>(), TypeId::of::<V>()))?,
+        )
+    }
+
+    /// Remove the index of nodes of type `T` by values of type `V`. Returns
+    /// `true` if there was such an index.
+    pub fn remove_index<T, V>(&mut self) -> bool
+    where
+        T: BoundedBy<I, B>,
+        V: 'static,
+    {
+        self.indexes.as_mut().is_some_and(|indexes| {
+            indexes
+                .remove(&(TypeId::of::<T>(), TypeId::of::<V>()))
+                .is_some()
+        })
+    }
+
+    /// Change the node at `key` with `update` and update its indexes
+    /// afterwards. The node is also marked as changed, as with
+    /// [`Nodes::mark_changed`]. Returns the result of `update`, or `None` if
+    /// there is no node for `key`.
+    pub fn update<T, R>(&mut self, key: Key<T>, update: impl FnOnce(&mut T) -> R) -> Option<R>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let result = update(self.get_mut(key)?);
+        self.update_indexes(key);
+        self.mark_changed(key);
+
+        Some(result)
+    }
+
+    /// Index the node at `key` again, after it has been inserted or changed.
+    pub(crate) fn update_indexes<T>(&mut self, key: Key<T>)
+    where
+        T: BoundedBy<I, B>,
+    {
+        let Some(indexes) = self.indexes.as_mut().filter(|indexes| !indexes.is_empty()) else {
+            return;
+        };
+
+        let Some(node) = self
+            .node_groups
+            .get(&TypeId::of::<T>())
+            .and_then(|group| group.downcast_ref::<I, T>())
+            .and_then(|group| group.get(key))
+        else {
+            return;
+        };
+
+        for ((node_type, _), index) in indexes {
+            if *node_type == TypeId::of::<T>() {
+                index.update(key.into(), node);
+            }
+        }
+    }
+
+    /// Remove the node at `key` from its indexes.
+    pub(crate) fn remove_from_indexes(&mut self, key: DynKey) {
+        let Some(indexes) = &mut self.indexes else {
+            return;
+        };
+
+        for ((node_type, _), index) in indexes {
+            if *node_type == key.node_type {
+                index.remove_key(key);
+            }
+        }
+    }
+}
+
+/// Iterates over the keys with a value in a [`FieldIndex`]. It's created by
+/// [`FieldIndex::keys_where`].
+pub struct KeysWhere<'a, T> {
+    inner: Option<alloc::collections::btree_set::Iter<'a, Key<T>>>,
+}
+
+impl<'a, T> Iterator for KeysWhere<'a, T> {
+    type Item = Key<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next().copied()
+    }
+}
@@ -0,0 +1,430 @@
+//! A small server for inspecting the nodes from another program.
+//!
+//! This module is only available with the `inspect` feature. An
+//! [`Inspector`] listens for TCP connections, and is polled with the nodes
+//! at regular intervals, such as once per frame. It doesn't start any
+//! threads, so the nodes don't have to be shared.
+//!
+//! The clients send requests as JSON objects, one per line, and get one
+//! response line for each request. The request's `id` field, if any, is
+//! copied to the response. These are the supported requests:
+//!
+//! * `{"method": "types"}` - the name, number of nodes and number of
+//!   reserved slots for each node type.
+//! * `{"method": "nodes", "type": "name"}` - the keys of all nodes, or only
+//!   the ones of the type with the given name if `type` is set.
+//! * `{"method": "node", "key": {"type": "name", "slot": 1}}` - the keys the
+//!   node refers to, its tags, provenance, annotation, parent and children.
+//! * `{"method": "subscribe"}` - start receiving the journal entries that
+//!   are passed to [`Inspector::poll`], as `{"event": {...}}` lines.
+//!
+//! Keys are objects with the type name and the slot of the node. A response
+//! has either a `result` field or an `error` field.
+//!
+//! A client is disconnected if it sends a line that is longer than
+//! [`MAX_REQUEST_LENGTH`], or if it doesn't read its responses and events,
+//! so more than [`MAX_PENDING_OUTPUT`] bytes are waiting to be sent to it.
+//! At most [`MAX_REQUESTS_PER_POLL`] requests are answered for each client
+//! in one poll.
+//!
+//! ```
+//! use std::io::{BufRead, BufReader, Write};
+//!
+//! use typed_nodes::{graph::VisitKeys, inspect::Inspector, make_bounds, Key, Nodes};
+//!
+//! make_bounds!(GraphBounds: VisitKeys + 'static);
+//!
+//! #[derive(VisitKeys)]
+//! struct Room {
+//!     exits: Vec<Key<Room>>,
+//! }
+//!
+//! let mut nodes = Nodes::<(), GraphBounds>::new();
+//! nodes.enable_journal();
+//! let hall = nodes.insert(Room { exits: vec![] });
+//! nodes.insert(Room { exits: vec![hall] });
+//!
+//! let mut inspector = Inspector::bind("127.0.0.1:0").unwrap();
+//! let mut client = std::net::TcpStream::connect(inspector.local_addr().unwrap()).unwrap();
+//! client.write_all(b"{\"id\": 1, \"method\": \"types\"}\n").unwrap();
+//!
+//! let mut response = String::new();
+//! let mut reader = BufReader::new(client.try_clone().unwrap());
+//! client.set_nonblocking(true).unwrap();
+//!
+//! while response.is_empty() {
+//!     let journal = nodes.take_journal();
+//!     inspector.poll(&nodes, &journal).unwrap();
+//!     let _ = reader.read_line(&mut response);
+//! }
+//!
+//! let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+//! assert_eq!(response["id"], 1);
+//! assert_eq!(response["result"][0]["nodes"], 2);
+//! ```
+
+use std::{
+    any::TypeId,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::UNIX_EPOCH,
+};
+
+use serde_json::{json, Map, Value};
+use slotmap::{Key as _, KeyData};
+
+use crate::{
+    bounds::Bounds,
+    graph::VisitKeys,
+    journal::{JournalEntry, JournalEvent},
+    node_group::DynNodeGroup,
+    DynKey, Nodes,
+};
+
+/// The maximum length of a request line, in bytes.
+pub const MAX_REQUEST_LENGTH: usize = 64 * 1024;
+
+/// The maximum number of bytes that can wait to be sent to a client.
+pub const MAX_PENDING_OUTPUT: usize = 16 * 1024 * 1024;
+
+/// The maximum number of requests that are answered for each client in one
+/// poll. The remaining requests are answered in the following polls.
+pub const MAX_REQUESTS_PER_POLL: usize = 64;
+
+/// A server for inspecting nodes. See the [module documentation](self) for
+/// the protocol.
+pub struct Inspector {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl Inspector {
+    /// Start listening for clients on `address`.
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// The address the inspector is listening on. It's useful for finding
+    /// the port when binding to port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Accept new clients, answer their requests and send `journal` to the
+    /// clients that have subscribed to it. This never blocks. Clients that
+    /// have disconnected, that send invalid data, or that exceed the buffer
+    /// limits, are removed.
+    ///
+    /// Failing to accept a client doesn't affect the connected clients. The
+    /// error is logged as a warning with the `tracing` feature.
+    ///
+    /// The journal entries are usually taken with [`Nodes::take_journal`]
+    /// right before polling.
+    pub fn poll<I, B>(&mut self, nodes: &Nodes<I, B>, journal: &[JournalEntry]) -> io::Result<()>
+    where
+        I: 'static,
+        B: Bounds,
+        B::DynSelf: VisitKeys,
+    {
+        loop {
+            let result = self
+                .listener
+                .accept()
+                .and_then(|(stream, _)| stream.set_nonblocking(true).map(|_| stream));
+
+            match result {
+                Ok(stream) => {
+                    self.clients.push(Client {
+                        stream,
+                        incoming: Vec::new(),
+                        outgoing: Vec::new(),
+                        subscribed: false,
+                    });
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_error) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = %_error, "failed to accept an inspector client");
+
+                    // The listener may keep failing, such as when there
+                    // are too many open files, so it's tried again next
+                    // time.
+                    break;
+                }
+            }
+        }
+
+        let events: Vec<_> = journal.iter().map(event_json).collect();
+        self.clients
+            .retain_mut(|client| match client.poll(nodes, &events) {
+                Ok(()) => true,
+                Err(_error) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %_error, "disconnected an inspector client");
+
+                    false
+                }
+            });
+
+        Ok(())
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    subscribed: bool,
+}
+
+impl Client {
+    fn poll<I, B>(&mut self, nodes: &Nodes<I, B>, events: &[Value]) -> io::Result<()>
+    where
+        I: 'static,
+        B: Bounds,
+        B::DynSelf: VisitKeys,
+    {
+        let mut buffer = [0; 1024];
+        let mut requests = 0;
+
+        'read: loop {
+            // The requests are answered as they arrive, so only an
+            // unfinished line is kept. The rest is read in the next poll,
+            // after the limit is reached.
+            while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+                if requests == MAX_REQUESTS_PER_POLL {
+                    break 'read;
+                }
+
+                let line: Vec<_> = self.incoming.drain(..=end).collect();
+                let request: Value = serde_json::from_slice(&line)?;
+                let response = self.respond(nodes, &request);
+                self.send(&response)?;
+                requests += 1;
+            }
+
+            if self.incoming.len() > MAX_REQUEST_LENGTH {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the request is too long",
+                ));
+            }
+
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(length) => self.incoming.extend_from_slice(&buffer[..length]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if self.subscribed {
+            for event in events {
+                self.send(&json!({ "event": event }))?;
+            }
+        }
+
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(length) => {
+                    self.outgoing.drain(..length);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queue `message` to be sent. The client is disconnected if it has too
+    /// much unsent data.
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        serde_json::to_writer(&mut self.outgoing, message)?;
+        self.outgoing.push(b'\n');
+
+        if self.outgoing.len() > MAX_PENDING_OUTPUT {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                "the client is not reading its responses",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn respond<I, B>(&mut self, nodes: &Nodes<I, B>, request: &Value) -> Value
+    where
+        I: 'static,
+        B: Bounds,
+        B::DynSelf: VisitKeys,
+    {
+        let result = match request["method"].as_str() {
+            Some("types") => Ok(types_json(nodes)),
+            Some("nodes") => nodes_json(nodes, &request["type"]),
+            Some("node") => node_json(nodes, &request["key"]),
+            Some("subscribe") => {
+                self.subscribed = true;
+                Ok(Value::Null)
+            }
+            Some(method) => Err(format!("unknown method `{method}`")),
+            None => Err("expected a `method` field".to_string()),
+        };
+
+        let mut response = Map::new();
+
+        if let Some(id) = request.get("id") {
+            response.insert("id".into(), id.clone());
+        }
+
+        match result {
+            Ok(result) => response.insert("result".into(), result),
+            Err(error) => response.insert("error".into(), error.into()),
+        };
+
+        Value::Object(response)
+    }
+}
+
+fn types_json<I, B>(nodes: &Nodes<I, B>) -> Value
+where
+    I: 'static,
+    B: Bounds,
+{
+    nodes
+        .stats()
+        .into_iter()
+        .map(|stats| {
+            json!({
+                "type": stats.type_name,
+                "nodes": stats.nodes,
+                "reserved": stats.reserved,
+            })
+        })
+        .collect()
+}
+
+fn nodes_json<I, B>(nodes: &Nodes<I, B>, type_name: &Value) -> Result<Value, String>
+where
+    I: 'static,
+    B: Bounds,
+{
+    let keys: Vec<_> = match type_name {
+        Value::Null => nodes.iter_dyn_sorted().map(|(key, _)| key).collect(),
+        type_name => {
+            let type_id = find_type(nodes, type_name)?;
+            nodes
+                .iter_dyn_sorted()
+                .map(|(key, _)| key)
+                .filter(|key| key.node_type == type_id)
+                .collect()
+        }
+    };
+
+    Ok(keys.into_iter().map(|key| key_json(nodes, key)).collect())
+}
+
+fn node_json<I, B>(nodes: &Nodes<I, B>, key: &Value) -> Result<Value, String>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    let node_type = find_type(nodes, &key["type"])?;
+    let slot = key["slot"]
+        .as_u64()
+        .ok_or_else(|| "expected the key to have a `slot` number".to_string())?;
+    let key = DynKey {
+        slot: KeyData::from_ffi(slot).into(),
+        node_type,
+    };
+
+    let node = nodes
+        .get_dyn(key)
+        .ok_or_else(|| "there is no node with the key".to_string())?;
+
+    let mut references = Vec::new();
+    node.visit_keys(&mut |reference| references.push(key_json(nodes, reference)));
+
+    let tags: Vec<_> = nodes
+        .tags
+        .iter()
+        .filter(|(_, keys)| keys.contains(&key))
+        .map(|(tag, _)| tag.as_str())
+        .collect();
+
+    Ok(json!({
+        "key": key_json(nodes, key),
+        "references": references,
+        "tags": tags,
+        "provenance": nodes.provenance(key).map(ToString::to_string),
+        "annotation": nodes.annotation(key),
+        "parent": nodes.parent(key).map(|parent| key_json(nodes, parent)),
+        "children": nodes
+            .children(key)
+            .map(|child| key_json(nodes, child))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn find_type<I, B>(nodes: &Nodes<I, B>, type_name: &Value) -> Result<TypeId, String>
+where
+    I: 'static,
+    B: Bounds,
+{
+    let type_name = type_name
+        .as_str()
+        .ok_or_else(|| "expected a type name".to_string())?;
+
+    nodes
+        .node_groups
+        .iter()
+        .find(|(_, group)| group.node_type_name() == type_name)
+        .map(|(&type_id, _)| type_id)
+        .ok_or_else(|| format!("unknown type `{type_name}`"))
+}
+
+fn key_json<I, B>(nodes: &Nodes<I, B>, key: DynKey) -> Value
+where
+    I: 'static,
+    B: Bounds,
+{
+    json!({
+        "type": nodes.type_name(key.node_type),
+        "slot": key.slot.data().as_ffi(),
+    })
+}
+
+fn event_json(entry: &JournalEntry) -> Value {
+    let event = match entry.event {
+        JournalEvent::Insert => "insert",
+        JournalEvent::Replace => "replace",
+        JournalEvent::Remove => "remove",
+    };
+
+    let time = entry
+        .time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64());
+
+    json!({
+        "event": event,
+        "key": {
+            "type": entry.type_name,
+            "slot": entry.key.slot.data().as_ffi(),
+        },
+        "time": time,
+    })
+}
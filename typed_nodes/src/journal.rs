@@ -0,0 +1,302 @@
+//! A record of the nodes that are inserted, replaced and removed.
+//!
+//! The journal is disabled by default, and is enabled with
+//! [`Nodes::enable_journal`]. The recorded entries can then be taken with
+//! [`Nodes::take_journal`], such as for sending them to an inspector or for
+//! storing them in a log.
+//!
+//! Changes to the nodes themselves, such as through [`Nodes::get_mut`], are
+//! not recorded, and neither are nodes that are moved out and back with
+//! [`Nodes::take`] and [`Nodes::put_back`].
+//!
+//! ```
+//! use typed_nodes::{journal::JournalEvent, DynKey, Nodes};
+//!
+//! struct Npc {
+//!     name: &'static str,
+//! }
+//!
+//! let mut nodes = Nodes::<()>::new();
+//! nodes.insert(Npc { name: "Guard" });
+//!
+//! nodes.enable_journal();
+//! let merchant = nodes.insert(Npc { name: "Merchant" });
+//! nodes.replace(merchant, Npc { name: "Smith" });
+//! nodes.remove(merchant);
+//!
+//! let journal = nodes.take_journal();
+//! let events: Vec<_> = journal.iter().map(|entry| entry.event).collect();
+//!
+//! assert_eq!(events, [JournalEvent::Insert, JournalEvent::Replace, JournalEvent::Remove]);
+//! assert!(journal.iter().all(|entry| entry.key == DynKey::from(merchant)));
+//! assert!(journal[0].type_name.ends_with("Npc"));
+//! assert!(nodes.take_journal().is_empty());
+//! ```
+//!
+//! Each node can also have a change tick, that is set when it's inserted,
+//! replaced, updated with [`Nodes::update`], or borrowed mutably, such as
+//! with [`Nodes::get_mut`]. The ticks are recorded after enabling them with
+//! [`Nodes::enable_change_ticks`], and can be used for finding out if a node
+//! may have changed since it was last seen, without keeping a journal.
+//!
+//! ```
+//! use typed_nodes::Nodes;
+//!
+//! struct Npc {
+//!     name: &'static str,
+//! }
+//!
+//! let mut nodes = Nodes::<()>::new();
+//! nodes.enable_change_ticks();
+//! let guard = nodes.insert(Npc { name: "Guard" });
+//! let merchant = nodes.insert(Npc { name: "Merchant" });
+//! let seen = nodes.change_tick(merchant).unwrap();
+//!
+//! nodes.update(guard, |guard| guard.name = "Captain");
+//! assert_eq!(nodes.change_tick(merchant), Some(seen));
+//!
+//! nodes.update(merchant, |merchant| merchant.name = "Smith");
+//! assert!(nodes.change_tick(merchant).unwrap() > seen);
+//! assert_eq!(nodes.change_tick(merchant), Some(nodes.last_change_tick()));
+//!
+//! // Mutable access counts as a change, even if nothing is written.
+//! let seen = nodes.change_tick(guard).unwrap();
+//! nodes.get_mut(guard).unwrap();
+//! assert!(nodes.change_tick(guard).unwrap() > seen);
+//!
+//! let seen = nodes.change_tick(merchant).unwrap();
+//! nodes.iter_mut::<Npc>().for_each(|(_, npc)| npc.name = "Villager");
+//! assert!(nodes.change_tick(merchant).unwrap() > seen);
+//! ```
+
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    node_group::DynNodeGroup,
+    DynKey, HashMap, Key, Nodes,
+};
+
+/// A change that is recorded in the journal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JournalEvent {
+    /// A node was inserted, either in a new slot or in a reserved slot.
+    Insert,
+    /// A node was replaced with another node.
+    Replace,
+    /// A node was removed.
+    Remove,
+}
+
+/// An entry in the journal, from [`Nodes::take_journal`].
+#[derive(Clone, Copy)]
+pub struct JournalEntry {
+    /// What happened to the node.
+    pub event: JournalEvent,
+    /// The key of the node.
+    pub key: DynKey,
+    /// The name of the node's type, as given by [`core::any::type_name`].
+    pub type_name: &'static str,
+    /// When the event happened.
+    ///
+    /// This is only available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub time: std::time::SystemTime,
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Start recording inserted, replaced and removed nodes. It does
+    /// nothing if the journal is already enabled.
+    pub fn enable_journal(&mut self) {
+        self.journal.get_or_insert_with(Vec::new);
+    }
+
+    /// Stop recording changes and return the entries that haven't been
+    /// taken yet.
+    pub fn disable_journal(&mut self) -> Vec<JournalEntry> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    /// Check if changes are being recorded.
+    #[inline]
+    pub fn is_journal_enabled(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Take the entries that have been recorded since the journal was
+    /// enabled or last taken, in the order they were recorded. The journal
+    /// stays enabled.
+    pub fn take_journal(&mut self) -> Vec<JournalEntry> {
+        self.journal
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Start giving the nodes change ticks, for [`Nodes::change_tick`]. The
+    /// nodes that are already in the collection get the tick 0. It does
+    /// nothing if the ticks are already enabled.
+    pub fn enable_change_ticks(&mut self) {
+        self.change_ticks.get_or_insert_with(ChangeTicks::default);
+    }
+
+    /// Check if the nodes are given change ticks.
+    #[inline]
+    pub fn are_change_ticks_enabled(&self) -> bool {
+        self.change_ticks.is_some()
+    }
+
+    /// Get the change tick of the node at `key`, or `None` if there is no
+    /// node or if the ticks aren't enabled with
+    /// [`Nodes::enable_change_ticks`]. The tick is increased each time any node is inserted, replaced
+    /// or updated with [`Nodes::update`], so a node has changed if its tick
+    /// is different from before. Nodes that haven't been changed since they
+    /// were loaded from a snapshot have the tick 0.
+    ///
+    /// Handing out a mutable reference also counts as a change, since the
+    /// node may be changed through it. This includes [`Nodes::get_mut`],
+    /// [`Nodes::get_dyn_mut`], [`Nodes::get_mut_and_rest`] and
+    /// [`Nodes::put_back`]. [`Nodes::iter_mut`] changes the tick of all
+    /// nodes of its type, and [`Nodes::iter_dyn_mut`] and
+    /// [`Nodes::nodes_dyn_mut`] change the tick of all nodes.
+    pub fn change_tick(&self, key: impl Into<DynKey>) -> Option<u64> {
+        let change_ticks = self.change_ticks.as_ref()?;
+        let key = self.resolve_dyn(key.into());
+        self.get_dyn(key)?;
+
+        Some(change_ticks.get(key))
+    }
+
+    /// The tick of the latest change to any node, or 0 if the ticks aren't
+    /// enabled.
+    #[inline]
+    pub fn last_change_tick(&self) -> u64 {
+        self.change_ticks
+            .as_ref()
+            .map_or(0, |change_ticks| change_ticks.last)
+    }
+
+    /// Give the node at `key` a new change tick, such as after changing it
+    /// through a mutable reference. Returns `false` if there is no node.
+    pub fn mark_changed(&mut self, key: impl Into<DynKey>) -> bool {
+        let key = self.resolve_dyn(key.into());
+
+        if self.get_dyn(key).is_none() {
+            return false;
+        }
+
+        self.set_change_tick(key);
+        true
+    }
+
+    #[inline]
+    pub(crate) fn set_change_tick(&mut self, key: DynKey) {
+        if let Some(change_ticks) = &mut self.change_ticks {
+            change_ticks.set(key);
+        }
+    }
+
+    /// Give all nodes of the type with `type_id` a new change tick.
+    #[inline]
+    pub(crate) fn set_type_change_tick(&mut self, type_id: TypeId) {
+        if let Some(change_ticks) = &mut self.change_ticks {
+            change_ticks.set_type(type_id);
+        }
+    }
+
+    #[inline]
+    fn record_change_tick(&mut self, event: JournalEvent, key: DynKey) {
+        let Some(change_ticks) = &mut self.change_ticks else {
+            return;
+        };
+
+        match event {
+            JournalEvent::Insert | JournalEvent::Replace => change_ticks.set(key),
+            JournalEvent::Remove => change_ticks.remove(key),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record<T>(&mut self, event: JournalEvent, key: Key<T>)
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.record_change_tick(event, key.into());
+
+        if let Some(journal) = &mut self.journal {
+            journal.push(JournalEntry::new(
+                event,
+                key.into(),
+                core::any::type_name::<T>(),
+            ));
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_dyn(&mut self, event: JournalEvent, key: DynKey) {
+        self.record_change_tick(event, key);
+
+        if let Some(journal) = &mut self.journal {
+            let type_name = self
+                .node_groups
+                .get(&key.node_type)
+                .map_or("<unknown>", |group| group.node_type_name());
+
+            journal.push(JournalEntry::new(event, key, type_name));
+        }
+    }
+}
+
+/// The change ticks of the nodes, for [`Nodes::change_tick`].
+#[derive(Default)]
+pub(crate) struct ChangeTicks {
+    nodes: HashMap<DynKey, u64>,
+    /// Ticks for all nodes of a type, from when they were all borrowed
+    /// mutably at once.
+    types: HashMap<TypeId, u64>,
+    last: u64,
+}
+
+impl ChangeTicks {
+    /// The tick of the node at `key`, which is the latest of its own tick
+    /// and the tick of its type.
+    fn get(&self, key: DynKey) -> u64 {
+        let node_tick = self.nodes.get(&key).copied().unwrap_or(0);
+        let type_tick = self.types.get(&key.node_type).copied().unwrap_or(0);
+
+        node_tick.max(type_tick)
+    }
+
+    /// Give the node at `key` a new tick.
+    pub(crate) fn set(&mut self, key: DynKey) {
+        self.last += 1;
+        self.nodes.insert(key, self.last);
+    }
+
+    /// Give all nodes of the type with `type_id` a new tick.
+    pub(crate) fn set_type(&mut self, type_id: TypeId) {
+        self.last += 1;
+        self.types.insert(type_id, self.last);
+    }
+
+    fn remove(&mut self, key: DynKey) {
+        self.nodes.remove(&key);
+    }
+}
+
+impl JournalEntry {
+    fn new(event: JournalEvent, key: DynKey, type_name: &'static str) -> Self {
+        Self {
+            event,
+            key,
+            type_name,
+            #[cfg(feature = "std")]
+            time: std::time::SystemTime::now(),
+        }
+    }
+}
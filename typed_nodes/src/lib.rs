@@ -1,20 +1,91 @@
-use std::{any::TypeId, borrow::Borrow, collections::HashMap, hash::Hash, marker::PhantomData};
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use bounds::{BoundedBy, Bounds};
+extern crate alloc;
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use core::{any::TypeId, borrow::Borrow, hash::Hash, marker::PhantomData};
+
+use alias::{AliasMap, Aliases};
+use bounds::{BoundedBy, Bounds, SideBoundedBy};
+use graph::KeyRemap;
+use hierarchy::Hierarchy;
+use journal::{ChangeTicks, JournalEntry, JournalEvent};
+use names::Names;
 use node_group::{BoxedNodeGroup, DynNodeGroup, GroupBounds, NodeGroup};
-pub use node_group::{DynKey, Key, ReservedKey};
+pub use node_group::{DynKey, GroupFactory, GroupTypeError, Key, ReservedKey, SlotState, WeakKey};
+pub use provenance::Provenance;
+use redirects::Redirects;
+pub use reservation::Reservation;
+use reservation::Reservations;
+pub use side_table::SideTable;
 
+mod alias;
+#[cfg(feature = "snapshot")]
+pub mod bake;
 pub mod bounds;
+pub mod eval;
+pub mod graph;
+pub mod hierarchy;
+pub mod index;
+#[cfg(feature = "inspect")]
+pub mod inspect;
+pub mod journal;
+#[cfg(feature = "lua")]
 pub mod mlua;
+mod names;
 mod node_group;
+pub mod pass;
+mod provenance;
+pub mod query;
+mod redirects;
+mod reservation;
+mod side_table;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod stats;
+#[cfg(feature = "test_util")]
+pub mod test_util;
 
+type HashMap<K, V> = hashbrown::HashMap<K, V, HashState>;
+type HashSet<T> = hashbrown::HashSet<T, HashState>;
+#[cfg(feature = "std")]
+type HashState = ahash::RandomState;
+// There's no source of randomness without `std`, so the keys are fixed.
+#[cfg(not(feature = "std"))]
+type HashState = core::hash::BuildHasherDefault<ahash::AHasher>;
 type BoxedGroupOf<B> = <<B as Bounds>::GroupBounds as GroupBounds>::BoxedGroup<B>;
+type DynAliasMapOf<B> = <<B as Bounds>::GroupBounds as GroupBounds>::DynAliasMap;
+type DynIndexOf<B> = <<B as Bounds>::GroupBounds as GroupBounds>::DynIndex;
+type AliasMaps<B> = HashMap<(TypeId, TypeId), Box<DynAliasMapOf<B>>>;
+type Indexes<B> = HashMap<(TypeId, TypeId), Box<DynIndexOf<B>>>;
+type GroupValues<'a, B> = hashbrown::hash_map::Values<'a, TypeId, BoxedGroupOf<B>>;
+type GroupValuesMut<'a, B> = hashbrown::hash_map::ValuesMut<'a, TypeId, BoxedGroupOf<B>>;
+type FlatMapGroups<G, T, I> = core::iter::FlatMap<G, I, fn(T) -> I>;
 
 /// A set of nodes of different types.
 ///
 /// The nodes can be inserted and found with an arbitrary ID.
 pub struct Nodes<I = (), B: Bounds = bounds::AnyBounds> {
-    node_groups: ahash::HashMap<TypeId, BoxedGroupOf<B>>,
+    node_groups: HashMap<TypeId, BoxedGroupOf<B>>,
+    tags: BTreeMap<String, BTreeSet<DynKey>>,
+    // The side maps are allocated when they are first used, so the nodes
+    // don't have to be removed from them otherwise.
+    provenance: Option<HashMap<DynKey, Provenance>>,
+    annotations: Option<HashMap<DynKey, String>>,
+    aliases: Option<AliasMaps<B>>,
+    names: Option<Names>,
+    hierarchy: Option<Hierarchy>,
+    indexes: Option<Indexes<B>>,
+    reservations: Reservations,
+    redirects: Option<Redirects>,
+    roots: BTreeSet<DynKey>,
+    journal: Option<Vec<JournalEntry>>,
+    change_ticks: Option<ChangeTicks>,
     key_type: PhantomData<fn(I)>,
 }
 
@@ -27,85 +98,1164 @@ where
     pub fn new() -> Self {
         Self {
             node_groups: HashMap::with_hasher(Default::default()),
+            tags: BTreeMap::new(),
+            provenance: None,
+            annotations: None,
+            aliases: None,
+            names: None,
+            hierarchy: None,
+            indexes: None,
+            reservations: Reservations::default(),
+            redirects: None,
+            roots: BTreeSet::new(),
+            journal: None,
+            change_ticks: None,
             key_type: PhantomData,
         }
     }
 
+    /// Make sure there is a node group for type `T`, even if no nodes of
+    /// type `T` have been inserted yet. Returns `true` if a new group was
+    /// created.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Shader;
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// assert!(nodes.register_type::<Shader>());
+    /// assert!(!nodes.register_type::<Shader>());
+    ///
+    /// assert_eq!(nodes.stats()[0].nodes, 0);
+    /// ```
+    #[inline]
+    pub fn register_type<T>(&mut self) -> bool
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.register_with(TypeId::of::<T>(), GroupFactory::new::<T>())
+    }
+
+    /// Make sure there is a node group for `type_id`, and create it with
+    /// `factory` if there isn't. This is for code that only knows the node
+    /// type from a registry or other type erased source. Returns `true` if a
+    /// new group was created.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factory` creates groups for another type than `type_id`.
+    pub fn register_with(&mut self, type_id: TypeId, factory: GroupFactory<I, B>) -> bool {
+        assert!(
+            factory.type_id() == type_id,
+            "the group factory for {} doesn't match the type ID",
+            factory.type_name()
+        );
+
+        match self.node_groups.entry(type_id) {
+            hashbrown::hash_map::Entry::Occupied(_) => false,
+            hashbrown::hash_map::Entry::Vacant(entry) => {
+                entry.insert(factory.create());
+                true
+            }
+        }
+    }
+
+    /// Insert a node and return its key.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a [`GroupTypeError`] if the node group for `T` has an
+    /// unexpected type. Use [`Nodes::try_insert`] to handle that case.
     #[inline]
     pub fn insert<T>(&mut self, node: T) -> Key<T>
     where
         T: BoundedBy<I, B>,
     {
-        self.node_groups
+        self.try_insert(node)
+            .unwrap_or_else(|(_, error)| panic!("{error}"))
+    }
+
+    /// Insert a node and return its key, or return the node and a
+    /// [`GroupTypeError`] if the node group for `T` has an unexpected type.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Track;
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let key = nodes.try_insert(Track).ok().unwrap();
+    ///
+    /// assert!(matches!(nodes.try_get(key), Ok(Some(Track))));
+    /// ```
+    pub fn try_insert<T>(&mut self, node: T) -> Result<Key<T>, (T, GroupTypeError)>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let group = self
+            .node_groups
             .entry(TypeId::of::<T>())
-            .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()))
-            .downcast_mut::<I, T>()
-            .expect("node group should be possible to downcast")
-            .insert(node)
+            .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()));
+
+        let key = match group.try_group_mut::<I, T>() {
+            Ok(group) => group.insert(node),
+            Err(error) => return Err((node, error)),
+        };
+
+        self.update_indexes(key);
+        self.record(JournalEvent::Insert, key);
+        Ok(key)
     }
 
     /// Insert a value in a reserved slot. Reservations can be made with [`Nodes::reserve_with_id`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reserved slot has been removed. Use
+    /// [`Nodes::try_insert_reserved`] to handle that case.
     #[inline]
     pub fn insert_reserved<T>(&mut self, key: ReservedKey<T>, node: T) -> Key<T>
     where
         T: BoundedBy<I, B>,
     {
-        self.node_groups
-            .entry(TypeId::of::<T>())
-            .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()))
-            .downcast_mut::<I, T>()
-            .expect("node group should be possible to downcast")
-            .insert_reserved(key, node)
+        match self.try_insert_reserved(key, node) {
+            Ok(key) => key,
+            Err(_) => panic!("reserved slot was removed"),
+        }
+    }
+
+    /// Insert a value in a reserved slot, or return it if the slot has been
+    /// removed.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Track;
+    ///
+    /// let mut nodes = Nodes::<&str>::new();
+    /// let (reserved_key, _) = nodes.reserve_with_id::<Track>("intro");
+    /// nodes.remove(reserved_key.key());
+    ///
+    /// assert!(nodes.try_insert_reserved(reserved_key, Track).is_err());
+    /// assert!(nodes.get_key::<Track, _>("intro").is_none());
+    /// ```
+    pub fn try_insert_reserved<T>(&mut self, key: ReservedKey<T>, node: T) -> Result<Key<T>, T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let Some(group) = self.node_groups.get_mut(&TypeId::of::<T>()) else {
+            return Err(node);
+        };
+
+        let key = group.group_mut::<I, T>().try_insert_reserved(key, node)?;
+
+        self.update_indexes(key);
+        self.record(JournalEvent::Insert, key);
+        Ok(key)
     }
 
+    /// Get the node at `key`, or at the key it has been redirected to with
+    /// [`Nodes::redirect`].
+    ///
+    /// # Panics
+    ///
+    /// Panics with a [`GroupTypeError`] if the node group for `T` has an
+    /// unexpected type. Use [`Nodes::try_get`] to handle that case.
     #[inline]
     pub fn get<T>(&self, key: Key<T>) -> Option<&T>
     where
         T: BoundedBy<I, B>,
     {
-        self.node_groups
-            .get(&TypeId::of::<T>())?
-            .downcast_ref::<I, T>()
-            .expect("node group should be possible to downcast")
-            .get(key)
+        self.try_get(key).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Get the node at `key`, like [`Nodes::get`], or return a
+    /// [`GroupTypeError`] if the node group for `T` has an unexpected type.
+    #[inline]
+    pub fn try_get<T>(&self, key: Key<T>) -> Result<Option<&T>, GroupTypeError>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let key = self.resolve(key);
+
+        let Some(group) = self.node_groups.get(&TypeId::of::<T>()) else {
+            return Ok(None);
+        };
+
+        Ok(group.try_group_ref::<I, T>()?.get(key))
     }
 
+    /// Get the node at `key` mutably, or at the key it has been redirected
+    /// to with [`Nodes::redirect`]. The node gets a new change tick, as if
+    /// it was changed. See [`Nodes::change_tick`].
     #[inline]
     pub fn get_mut<T>(&mut self, key: Key<T>) -> Option<&mut T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let key = self.resolve(key);
+
+        let node = self
+            .node_groups
+            .get_mut(&TypeId::of::<T>())?
+            .group_mut::<I, T>()
+            .get_mut(key)?;
+
+        if let Some(change_ticks) = &mut self.change_ticks {
+            change_ticks.set(key.into());
+        }
+
+        Some(node)
+    }
+
+    /// Iterate over the keys of all nodes of type `T`. The keys can be
+    /// collected first, to mutate the nodes in a second pass.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Enemy {
+    ///     health: u32,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// nodes.insert(Enemy { health: 10 });
+    /// nodes.insert(Enemy { health: 20 });
+    ///
+    /// let keys: Vec<_> = nodes.keys::<Enemy>().collect();
+    /// for key in keys {
+    ///     nodes.get_mut(key).unwrap().health += 5;
+    /// }
+    ///
+    /// assert!(nodes.keys::<Enemy>().all(|key| nodes.get(key).unwrap().health > 10));
+    /// ```
+    #[inline]
+    pub fn keys<T>(&self) -> Keys<'_, T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        Keys {
+            inner: self
+                .node_groups
+                .get(&TypeId::of::<T>())
+                .map(|group| group.group_ref::<I, T>().keys()),
+        }
+    }
+
+    /// The number of nodes of type `T`, not counting reserved slots.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Enemy;
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let guard = nodes.insert(Enemy);
+    /// nodes.insert(Enemy);
+    /// let reservation = nodes.reserve::<Enemy>();
+    ///
+    /// assert_eq!(nodes.filled_len::<Enemy>(), 2);
+    /// assert_eq!(nodes.reserved_len::<Enemy>(), 1);
+    ///
+    /// nodes.take(guard);
+    /// nodes.fulfill(reservation, Enemy).ok().unwrap();
+    ///
+    /// assert_eq!(nodes.filled_len::<Enemy>(), 2);
+    /// assert_eq!(nodes.reserved_len::<Enemy>(), 1);
+    /// ```
+    #[inline]
+    pub fn filled_len<T>(&self) -> usize
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.node_groups
+            .get(&TypeId::of::<T>())
+            .map_or(0, |group| group.group_ref::<I, T>().filled_len())
+    }
+
+    /// The number of reserved slots for nodes of type `T`, that haven't
+    /// been filled yet. See [`Nodes::filled_len`].
+    #[inline]
+    pub fn reserved_len<T>(&self) -> usize
     where
         T: BoundedBy<I, B>,
     {
         self.node_groups
+            .get(&TypeId::of::<T>())
+            .map_or(0, |group| group.group_ref::<I, T>().reserved_len())
+    }
+
+    /// Iterate over the keys and nodes of type `T`. Unlike
+    /// [`Nodes::iter_dyn`], it goes directly through the nodes of `T`,
+    /// without type erasure.
+    ///
+    /// The iterator knows its exact length, even with reserved slots in
+    /// between the nodes.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Enemy {
+    ///     health: u32,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// nodes.insert(Enemy { health: 10 });
+    /// nodes.reserve::<Enemy>();
+    /// nodes.insert(Enemy { health: 20 });
+    ///
+    /// for (_, enemy) in nodes.iter_mut::<Enemy>() {
+    ///     enemy.health += 5;
+    /// }
+    ///
+    /// let total: u32 = nodes.iter::<Enemy>().map(|(_, enemy)| enemy.health).sum();
+    /// assert_eq!(total, 40);
+    ///
+    /// let mut enemies = nodes.iter::<Enemy>();
+    /// assert_eq!(enemies.len(), 2);
+    /// assert_eq!(enemies.next().unwrap().1.health, 15);
+    /// assert_eq!(enemies.len(), 1);
+    /// assert_eq!(enemies.next().unwrap().1.health, 25);
+    /// assert!(enemies.next().is_none());
+    /// ```
+    #[inline]
+    pub fn iter<T>(&self) -> Iter<'_, T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        Iter {
+            inner: self
+                .node_groups
+                .get(&TypeId::of::<T>())
+                .map(|group| group.group_ref::<I, T>().iter()),
+        }
+    }
+
+    /// Iterate over the keys and mutable nodes of type `T`. See
+    /// [`Nodes::iter`]. All nodes of type `T` get a new change tick, as if
+    /// they were changed.
+    #[inline]
+    pub fn iter_mut<T>(&mut self) -> IterMut<'_, T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.set_type_change_tick(TypeId::of::<T>());
+
+        IterMut {
+            inner: self
+                .node_groups
+                .get_mut(&TypeId::of::<T>())
+                .map(|group| group.group_mut::<I, T>().iter_mut()),
+        }
+    }
+
+    /// Replace the node at `key` with `node` and return the old node. The key
+    /// and all references to it stay valid. A reserved slot, such as after
+    /// [`Nodes::take`], is filled with `node`, while `node` is dropped if
+    /// the slot has been removed.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Script {
+    ///     source: String,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let script = nodes.insert(Script { source: "print(1)".into() });
+    ///
+    /// let old = nodes.replace(script, Script { source: "print(2)".into() }).unwrap();
+    ///
+    /// assert_eq!(old.source, "print(1)");
+    /// assert_eq!(nodes.get(script).unwrap().source, "print(2)");
+    /// ```
+    #[inline]
+    pub fn replace<T>(&mut self, key: Key<T>, node: T) -> Option<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let old_node = self
+            .node_groups
             .get_mut(&TypeId::of::<T>())?
-            .downcast_mut::<I, T>()
-            .expect("node group should be possible to downcast")
-            .get_mut(key)
+            .group_mut::<I, T>()
+            .replace(key, node);
+
+        self.update_indexes(key);
+
+        if old_node.is_some() {
+            self.record(JournalEvent::Replace, key);
+        } else if self.slot_state(key) == SlotState::Filled {
+            self.record(JournalEvent::Insert, key);
+        }
+
+        old_node
     }
 
+    /// Move the node at `key` out of its slot, but keep the slot reserved.
+    /// The key and any IDs stay valid, so the node can be moved back with
+    /// [`Nodes::put_back`]. [`Nodes::get`] returns `None` for the key in the
+    /// meantime.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Mesh {
+    ///     vertices: Vec<[f32; 3]>,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let mesh = nodes.insert(Mesh { vertices: vec![[0.0, 1.0, 0.0]] });
+    ///
+    /// let mut taken = nodes.take(mesh).unwrap();
+    /// assert!(nodes.get(mesh).is_none());
+    ///
+    /// taken.vertices.push([1.0, 0.0, 0.0]);
+    /// assert!(nodes.put_back(mesh, taken).is_ok());
+    ///
+    /// assert_eq!(nodes.get(mesh).unwrap().vertices.len(), 2);
+    /// ```
     #[inline]
-    pub fn remove<T>(&mut self, key: Key<T>) -> Option<T>
+    pub fn take<T>(&mut self, key: Key<T>) -> Option<T>
     where
         T: BoundedBy<I, B>,
     {
+        let (_, node) = self
+            .node_groups
+            .get_mut(&TypeId::of::<T>())?
+            .group_mut::<I, T>()
+            .take(key)?;
+
+        self.remove_from_indexes(key.into());
+        Some(node)
+    }
+
+    /// Move a node back into the slot it was taken from with
+    /// [`Nodes::take`]. The node is returned as an error if the slot isn't
+    /// reserved, such as if it has been removed or filled again. The node
+    /// gets a new change tick, since it may have been changed while it was
+    /// taken.
+    #[inline]
+    pub fn put_back<T>(&mut self, key: Key<T>, node: T) -> Result<(), T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let Some(group) = self.node_groups.get_mut(&TypeId::of::<T>()) else {
+            return Err(node);
+        };
+
+        group.group_mut::<I, T>().put_back(key, node)?;
+
+        self.update_indexes(key);
+        self.set_change_tick(key.into());
+        Ok(())
+    }
+
+    /// Borrow the node at `key` mutably, while still being able to read the
+    /// other nodes. Use [`NodeAndRest::split`] to get the node and the rest
+    /// of the nodes.
+    ///
+    /// The node is moved out of its slot until the returned value is
+    /// dropped, so it's not accessible through the rest of the nodes in the
+    /// meantime. It's lost if the returned value is leaked. The node gets a
+    /// new change tick when it's moved back, as with [`Nodes::put_back`].
+    ///
+    /// ```
+    /// use typed_nodes::{Key, Nodes};
+    ///
+    /// struct Sum {
+    ///     value: u32,
+    ///     terms: Vec<Key<Sum>>,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let a = nodes.insert(Sum { value: 2, terms: vec![] });
+    /// let b = nodes.insert(Sum { value: 3, terms: vec![] });
+    /// let sum = nodes.insert(Sum { value: 0, terms: vec![a, b] });
+    ///
+    /// {
+    ///     let mut node_and_rest = nodes.get_mut_and_rest(sum).unwrap();
+    ///     let (node, rest) = node_and_rest.split();
+    ///
+    ///     assert!(rest.get(sum).is_none());
+    ///     node.value = node.terms.iter().map(|&term| rest.get(term).unwrap().value).sum();
+    /// }
+    ///
+    /// assert_eq!(nodes.get(sum).unwrap().value, 5);
+    /// ```
+    pub fn get_mut_and_rest<T>(&mut self, key: Key<T>) -> Option<NodeAndRest<'_, T, I, B>>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let taken = self
+            .node_groups
+            .get_mut(&TypeId::of::<T>())?
+            .group_mut::<I, T>()
+            .take(key)?;
+
+        Some(NodeAndRest {
+            taken: Some(taken),
+            nodes: self,
+        })
+    }
+
+    /// Check if the slot for `key` has a node, is reserved for a node that
+    /// hasn't been inserted yet, or doesn't exist.
+    ///
+    /// ```
+    /// use typed_nodes::{Nodes, SlotState};
+    ///
+    /// struct Quest;
+    ///
+    /// let mut nodes = Nodes::<&str>::new();
+    /// let (reserved_key, _) = nodes.reserve_with_id::<Quest>("rescue");
+    /// let key = reserved_key.key();
+    ///
+    /// assert_eq!(nodes.slot_state(key), SlotState::Reserved);
+    ///
+    /// nodes.insert_reserved(reserved_key, Quest);
+    /// assert_eq!(nodes.slot_state(key), SlotState::Filled);
+    ///
+    /// nodes.remove(key);
+    /// assert_eq!(nodes.slot_state(key), SlotState::Vacant);
+    /// ```
+    pub fn slot_state(&self, key: impl Into<DynKey>) -> SlotState {
+        let key = key.into();
+
         self.node_groups
+            .get(&key.node_type)
+            .map_or(SlotState::Vacant, |group| group.slot_state_dyn(key))
+    }
+
+    /// Remove the node at `key` and return it. A reserved slot is also
+    /// removed, together with its IDs, but there is no node to return.
+    #[inline]
+    pub fn remove<T>(&mut self, key: Key<T>) -> Option<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let group = self
+            .node_groups
+            .get_mut(&TypeId::of::<T>())?
+            .group_mut::<I, T>();
+
+        let node = group.remove(key);
+
+        if node.is_some() || group.remove_reserved(key) {
+            self.remove_metadata(key.into());
+        }
+
+        if node.is_some() {
+            self.record(JournalEvent::Remove, key);
+        }
+
+        node
+    }
+
+    /// Remove a node without knowing its type. Returns `true` if there was a
+    /// node to remove. A reserved slot is also removed, like with
+    /// [`Nodes::remove`].
+    #[inline]
+    pub fn remove_dyn(&mut self, key: DynKey) -> bool {
+        let Some(group) = self.node_groups.get_mut(&key.node_type) else {
+            return false;
+        };
+
+        if group.remove_dyn(key) {
+            self.remove_metadata(key);
+            self.record_dyn(JournalEvent::Remove, key);
+            true
+        } else {
+            if group.remove_reserved_dyn(key) {
+                self.remove_metadata(key);
+            }
+
+            false
+        }
+    }
+
+    /// Mark the node for `key` with `tag`. Tags are stored separately from
+    /// the nodes, so any node can have any number of tags, and they are
+    /// removed when the node is removed.
+    ///
+    /// ```
+    /// use typed_nodes::{DynKey, Nodes};
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let alice: DynKey = nodes.insert("Alice").into();
+    /// let bob: DynKey = nodes.insert("Bob").into();
+    ///
+    /// nodes.tag(alice, "root");
+    /// nodes.tag(bob, "debug");
+    /// nodes.tag(alice, "debug");
+    ///
+    /// assert!(nodes.keys_with_tag("root").eq([alice]));
+    /// assert_eq!(nodes.keys_with_tag("debug").count(), 2);
+    ///
+    /// assert_eq!(nodes.remove_tagged("debug"), 2);
+    /// assert!(nodes.get_dyn(alice).is_none());
+    /// assert_eq!(nodes.keys_with_tag("root").count(), 0);
+    /// ```
+    pub fn tag(&mut self, key: impl Into<DynKey>, tag: impl Into<String>) {
+        self.tags.entry(tag.into()).or_default().insert(key.into());
+    }
+
+    /// Remove `tag` from the node for `key`. Returns `true` if it was tagged.
+    pub fn untag(&mut self, key: impl Into<DynKey>, tag: &str) -> bool {
+        let Some(keys) = self.tags.get_mut(tag) else {
+            return false;
+        };
+
+        let was_tagged = keys.remove(&key.into());
+
+        if keys.is_empty() {
+            self.tags.remove(tag);
+        }
+
+        was_tagged
+    }
+
+    /// Check if the node for `key` is tagged with `tag`.
+    pub fn has_tag(&self, key: impl Into<DynKey>, tag: &str) -> bool {
+        self.tags
+            .get(tag)
+            .is_some_and(|keys| keys.contains(&key.into()))
+    }
+
+    /// Iterate over the keys of all nodes that are tagged with `tag`.
+    pub fn keys_with_tag(&self, tag: &str) -> KeysWithTag<'_> {
+        KeysWithTag {
+            inner: self.tags.get(tag).map(|keys| keys.iter()),
+        }
+    }
+
+    /// Remove all nodes that are tagged with `tag`, and return how many were
+    /// removed.
+    pub fn remove_tagged(&mut self, tag: &str) -> usize {
+        let Some(keys) = self.tags.remove(tag) else {
+            return 0;
+        };
+
+        keys.into_iter().filter(|&key| self.remove_dyn(key)).count()
+    }
+
+    /// Record where the node for `key` came from. This is done automatically
+    /// for nodes that are parsed from Lua tables.
+    pub fn set_provenance(&mut self, key: impl Into<DynKey>, provenance: Provenance) {
+        self.provenance
+            .get_or_insert_with(Default::default)
+            .insert(key.into(), provenance);
+    }
+
+    /// Get information about where the node for `key` came from, if it was
+    /// recorded.
+    pub fn provenance(&self, key: impl Into<DynKey>) -> Option<&Provenance> {
+        self.provenance.as_ref()?.get(&key.into())
+    }
+
+    /// Attach a note to the node for `key`, such as a description from its
+    /// author. Nodes that are parsed from Lua tables get the `__doc` or
+    /// `__meta` field of the table as their annotation, if it's a string.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// #[typed_nodes(deny_unknown_fields)]
+    /// struct Door {
+    ///     locked: bool,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let value = lua
+    ///     .load("{locked = true, __doc = 'The key is in the cellar.'}")
+    ///     .eval()
+    ///     .unwrap();
+    /// let door = Key::<Door>::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    ///
+    /// assert_eq!(nodes.annotation(door), Some("The key is in the cellar."));
+    /// ```
+    pub fn set_annotation(&mut self, key: impl Into<DynKey>, annotation: impl Into<String>) {
+        self.annotations
+            .get_or_insert_with(Default::default)
+            .insert(key.into(), annotation.into());
+    }
+
+    /// Get the note that is attached to the node for `key`, if any.
+    pub fn annotation(&self, key: impl Into<DynKey>) -> Option<&str> {
+        self.annotations
+            .as_ref()?
+            .get(&key.into())
+            .map(String::as_str)
+    }
+
+    /// Remove the note that is attached to the node for `key`, and return it.
+    pub fn remove_annotation(&mut self, key: impl Into<DynKey>) -> Option<String> {
+        self.annotations.as_mut()?.remove(&key.into())
+    }
+
+    /// Remove all nodes that were parsed from `source`, and return how many
+    /// were removed. See [`Context::with_source`][mlua::Context::with_source]
+    /// for how the source is recorded.
+    ///
+    /// Other nodes may still have keys to the removed nodes. Use
+    /// [`Nodes::references_to_source`] to check for that first.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Item {
+    ///     name: String,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    ///
+    /// let value = lua.load(r#"{name = "sword"}"#).eval().unwrap();
+    /// let mut context = Context::new(&lua, &mut nodes).with_source("base.lua");
+    /// let sword = Key::<Item>::from_lua(value, &mut context).unwrap();
+    ///
+    /// let value = lua.load(r#"{{name = "laser"}, {name = "jetpack"}}"#).eval().unwrap();
+    /// let mut context = Context::new(&lua, &mut nodes).with_source("mods/space.lua");
+    /// Vec::<Key<Item>>::from_lua(value, &mut context).unwrap();
+    ///
+    /// assert_eq!(nodes.remove_from_source("mods/space.lua"), 2);
+    /// assert!(nodes.iter_dyn().map(|(key, _)| key).eq([sword.into()]));
+    /// ```
+    pub fn remove_from_source(&mut self, source: &str) -> usize {
+        self.keys_from_source(source)
+            .into_iter()
+            .filter(|&key| self.remove_dyn(key))
+            .count()
+    }
+
+    /// Get the keys of all nodes that were parsed from `source`.
+    pub(crate) fn keys_from_source(&self, source: &str) -> Vec<DynKey> {
+        self.provenance
+            .iter()
+            .flatten()
+            .filter(|(_, provenance)| provenance.source() == Some(source))
+            .map(|(&key, _)| key)
+            .collect()
+    }
+
+    /// Assign an alias ID of type `J` to the node for `key`, in addition to
+    /// its main ID. Each alias type is a separate namespace, so a node can
+    /// be found by its Lua table ID as well as by a name, for example. Like
+    /// the main IDs, aliases are only unique for nodes of type `T`. Returns
+    /// the key that previously had the alias, if any.
+    ///
+    /// The IDs have to fulfill the same group bounds as the nodes, such as
+    /// being [`Send`] and [`Sync`] with [`SendSyncBounds`][bounds::SendSyncBounds].
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Material {
+    ///     density: f64,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<u32>::new();
+    /// let (steel, _) = nodes.insert_with_id(7, Material { density: 7.85 });
+    /// nodes.alias_id(steel, String::from("steel"));
+    ///
+    /// assert!(nodes.get_key::<Material, _>(&7) == Some(steel));
+    /// assert!(nodes.get_key_by_alias::<Material, String, _>("steel") == Some(steel));
+    ///
+    /// nodes.remove(steel);
+    /// assert!(nodes.get_key_by_alias::<Material, String, _>("steel").is_none());
+    /// ```
+    pub fn alias_id<T, J>(&mut self, key: Key<T>, id: J) -> Option<Key<T>>
+    where
+        T: 'static,
+        J: Hash + Eq + Clone + SideBoundedBy<B::GroupBounds>,
+    {
+        let aliases = self
+            .aliases
+            .get_or_insert_with(Default::default)
+            .entry((TypeId::of::<T>(), TypeId::of::<J>()))
+            .or_insert_with(|| J::box_aliases(Aliases::new()));
+
+        J::downcast_aliases_mut(aliases)
+            .expect("alias map should be possible to downcast")
+            .insert(id, key.into())
+            .and_then(DynKey::into_static)
+    }
+
+    /// Remove the alias `id` of type `J` from node type `T`, and return the
+    /// key it referred to.
+    pub fn remove_alias<T, J, Q>(&mut self, id: &Q) -> Option<Key<T>>
+    where
+        T: 'static,
+        J: Hash + Eq + Clone + Borrow<Q> + SideBoundedBy<B::GroupBounds>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let aliases = self
+            .aliases
+            .as_mut()?
+            .get_mut(&(TypeId::of::<T>(), TypeId::of::<J>()))?;
+
+        J::downcast_aliases_mut(aliases)
+            .expect("alias map should be possible to downcast")
+            .remove(id)
+            .and_then(DynKey::into_static)
+    }
+
+    /// Find the key for the alias `id` of type `J` and node type `T`. See
+    /// [`Nodes::alias_id`].
+    pub fn get_key_by_alias<T, J, Q>(&self, id: &Q) -> Option<Key<T>>
+    where
+        T: 'static,
+        J: Hash + Eq + Clone + Borrow<Q> + SideBoundedBy<B::GroupBounds>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let aliases = self
+            .aliases
+            .as_ref()?
+            .get(&(TypeId::of::<T>(), TypeId::of::<J>()))?;
+
+        J::downcast_aliases_ref(aliases)
+            .expect("alias map should be possible to downcast")
+            .get(id)
+            .and_then(DynKey::into_static)
+    }
+
+    /// Remove the tags, provenance, annotation, aliases, names, indexes,
+    /// hierarchy links and root status for a removed node.
+    fn remove_metadata(&mut self, key: DynKey) {
+        if let Some(provenance) = &mut self.provenance {
+            provenance.remove(&key);
+        }
+
+        if let Some(annotations) = &mut self.annotations {
+            annotations.remove(&key);
+        }
+
+        if let Some(hierarchy) = &mut self.hierarchy {
+            hierarchy.remove_key(key);
+        }
+
+        self.remove_from_indexes(key);
+
+        if let Some(redirects) = &mut self.redirects {
+            redirects.remove_target(key);
+        }
+
+        self.roots.remove(&key);
+
+        if let Some(aliases) = &mut self.aliases {
+            for ((node_type, _), aliases) in aliases {
+                if *node_type == key.node_type {
+                    aliases.remove_key(key);
+                }
+            }
+        }
+
+        if let Some(names) = &mut self.names {
+            names.remove_key(key);
+        }
+
+        if self.tags.is_empty() {
+            return;
+        }
+
+        self.tags.retain(|_, keys| {
+            keys.remove(&key);
+            !keys.is_empty()
+        });
+    }
+
+    /// Remove the node at `from` and let lookups with `from` find the node
+    /// at `to` instead. This merges the two nodes, like in a union-find
+    /// structure, so keys that were redirected to `from` will also be
+    /// redirected to `to`. IDs and aliases for `from` will refer to `to`.
+    ///
+    /// Only [`Nodes::get`], [`Nodes::get_mut`] and their dynamic variants
+    /// follow redirections. Use [`Nodes::resolve`] to get the key of the node
+    /// a key refers to. The redirection is removed when the node at `to` is
+    /// removed.
+    ///
+    /// Returns the removed node, or `None` if the keys already refer to the
+    /// same node, or if there was no node at `from`. Nothing is redirected
+    /// if `from` is neither filled nor reserved.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Class {
+    ///     members: Vec<&'static str>,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let a = nodes.insert(Class { members: vec!["a"] });
+    /// let b = nodes.insert(Class { members: vec!["b"] });
+    /// let c = nodes.insert(Class { members: vec!["c"] });
+    ///
+    /// let removed = nodes.redirect(a, b).unwrap();
+    /// nodes.get_mut(b).unwrap().members.extend(removed.members);
+    ///
+    /// let removed = nodes.redirect(b, c).unwrap();
+    /// nodes.get_mut(a).unwrap().members.extend(removed.members);
+    ///
+    /// assert!(nodes.resolve(a) == c);
+    /// assert!(nodes.resolve(b) == c);
+    /// assert_eq!(nodes.get(a).unwrap().members, ["c", "b", "a"]);
+    /// ```
+    pub fn redirect<T>(&mut self, from: Key<T>, to: Key<T>) -> Option<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let from = self.resolve(from);
+        let to = self.resolve(to);
+
+        if from == to || self.slot_state(from) == SlotState::Vacant {
+            return None;
+        }
+
+        // The keys that are redirected to `from` are moved first, so they
+        // aren't removed with the rest of its metadata.
+        self.redirects
+            .get_or_insert_with(Redirects::default)
+            .insert(from.into(), to.into());
+        self.merge(from, to)
+    }
+
+    /// Get the key that `key` has been redirected to with
+    /// [`Nodes::redirect`], or `key` itself if it hasn't been redirected.
+    #[inline]
+    pub fn resolve<T>(&self, key: Key<T>) -> Key<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.resolve_dyn(key.into())
+            .into_static()
+            .expect("the key should be redirected to a node of the same type")
+    }
+
+    /// Get the key that `key` has been redirected to with
+    /// [`Nodes::redirect`], or `key` itself if it hasn't been redirected.
+    #[inline]
+    pub fn resolve_dyn(&self, key: DynKey) -> DynKey {
+        self.redirects
+            .as_ref()
+            .and_then(|redirects| redirects.get(key))
+            .unwrap_or(key)
+    }
+
+    /// Remove nodes of type `T` that are equal to an earlier node of the
+    /// same type. IDs and aliases that were assigned to the removed nodes
+    /// will refer to the remaining nodes instead, while the returned
+    /// [`KeyRemap`] can be used for updating any other references to them.
+    ///
+    /// ```
+    /// use typed_nodes::{graph::RemapKeys, Key, Nodes};
+    ///
+    /// #[derive(PartialEq, Eq, Hash, RemapKeys)]
+    /// enum Uint {
+    ///     Literal(u32),
+    ///     Add(Key<Uint>, Key<Uint>),
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let one = nodes.insert(Uint::Literal(1));
+    /// let another_one = nodes.insert(Uint::Literal(1));
+    /// let mut sum = Uint::Add(one, another_one);
+    ///
+    /// let remap = nodes.dedup::<Uint>();
+    /// sum.remap_keys(&remap);
+    ///
+    /// assert!(sum == Uint::Add(one, one));
+    /// assert!(nodes.get(another_one).is_none());
+    /// ```
+    pub fn dedup<T>(&mut self) -> KeyRemap
+    where
+        T: BoundedBy<I, B> + Eq + Hash,
+    {
+        let mut remap = KeyRemap::default();
+
+        let Some(group) = self.node_groups.get_mut(&TypeId::of::<T>()) else {
+            return remap;
+        };
+
+        let duplicates = group.group_mut::<I, T>().dedup();
+
+        for (from, into) in duplicates {
+            self.merge_metadata(from.into(), into.into());
+            self.record(JournalEvent::Remove, from);
+            remap.insert(from, into);
+        }
+
+        remap
+    }
+
+    /// Remove the node at `from` and let IDs and aliases for it refer to
+    /// `into` instead. References in other nodes are not updated.
+    pub(crate) fn merge<T>(&mut self, from: Key<T>, into: Key<T>) -> Option<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let node = self
+            .node_groups
             .get_mut(&TypeId::of::<T>())?
-            .downcast_mut::<I, T>()
-            .expect("node group should be possible to downcast")
-            .remove(key)
+            .group_mut::<I, T>()
+            .merge(from, into);
+
+        self.merge_metadata(from.into(), into.into());
+
+        if node.is_some() {
+            self.record(JournalEvent::Remove, from);
+        }
+
+        node
+    }
+
+    /// Move the aliases, names, the place in the hierarchy and the root
+    /// status from `from` to `into`, and remove the rest of the metadata for
+    /// `from`.
+    fn merge_metadata(&mut self, from: DynKey, into: DynKey) {
+        if let Some(aliases) = &mut self.aliases {
+            for ((node_type, _), aliases) in aliases {
+                if *node_type == from.node_type {
+                    aliases.replace_key(from, into);
+                }
+            }
+        }
+
+        if let Some(names) = &mut self.names {
+            names.replace_key(from, into);
+        }
+
+        if let Some(hierarchy) = &mut self.hierarchy {
+            hierarchy.replace_key(from, into);
+        }
+
+        if self.roots.contains(&from) {
+            self.roots.insert(into);
+        }
+
+        self.remove_metadata(from);
+    }
+
+    /// Remove the reserved slot for `reserved_key` and let any IDs for it
+    /// refer to `into` instead.
+    #[cfg(feature = "lua")]
+    #[inline]
+    pub(crate) fn merge_reserved<T>(&mut self, reserved_key: ReservedKey<T>, into: Key<T>)
+    where
+        T: BoundedBy<I, B>,
+    {
+        if let Some(group) = self.node_groups.get_mut(&TypeId::of::<T>()) {
+            group.group_mut::<I, T>().merge_reserved(reserved_key, into);
+        }
     }
 
     #[inline]
     pub fn get_dyn(&self, key: DynKey) -> Option<&B::DynSelf> {
+        let key = self.resolve_dyn(key);
         self.node_groups.get(&key.node_type)?.get_dyn(key)
     }
 
+    /// Get the node at `key` mutably, without knowing its type. The node
+    /// gets a new change tick, as with [`Nodes::get_mut`].
     #[inline]
     pub fn get_dyn_mut(&mut self, key: DynKey) -> Option<&mut B::DynSelf> {
-        self.node_groups.get_mut(&key.node_type)?.get_dyn_mut(key)
+        let key = self.resolve_dyn(key);
+        let node = self.node_groups.get_mut(&key.node_type)?.get_dyn_mut(key)?;
+
+        if let Some(change_ticks) = &mut self.change_ticks {
+            change_ticks.set(key);
+        }
+
+        Some(node)
+    }
+
+    /// Get the node at `key` as a `T`, if it's a node of that type. This is
+    /// the same as converting the key with [`DynKey::into_static`] and
+    /// calling [`Nodes::get`].
+    ///
+    /// ```
+    /// use typed_nodes::{DynKey, Nodes};
+    ///
+    /// struct Sword;
+    /// struct Shield {
+    ///     armor: u32,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let sword: DynKey = nodes.insert(Sword).into();
+    /// let shield: DynKey = nodes.insert(Shield { armor: 5 }).into();
+    ///
+    /// assert!(shield.is::<Shield>());
+    /// assert_eq!(nodes.get_dyn_as::<Shield>(shield).unwrap().armor, 5);
+    /// assert!(nodes.get_dyn_as::<Shield>(sword).is_none());
+    /// ```
+    #[inline]
+    pub fn get_dyn_as<T>(&self, key: DynKey) -> Option<&T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.get(key.into_static()?)
+    }
+
+    /// Get the node at `key` mutably as a `T`, if it's a node of that type.
+    /// See [`Nodes::get_dyn_as`].
+    #[inline]
+    pub fn get_dyn_as_mut<T>(&mut self, key: DynKey) -> Option<&mut T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.get_mut(key.into_static()?)
+    }
+
+    /// Iterate over the types that have a node group, in arbitrary order.
+    /// A type has a group after a node of that type has been inserted or
+    /// reserved, or after it has been registered with
+    /// [`Nodes::register_type`]. The group may be empty.
+    ///
+    /// ```
+    /// use core::any::TypeId;
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Sound;
+    /// struct Image;
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// nodes.insert(Sound);
+    /// nodes.register_type::<Image>();
+    ///
+    /// assert_eq!(nodes.type_ids().count(), 2);
+    /// assert!(nodes.type_ids().any(|type_id| type_id == TypeId::of::<Sound>()));
+    ///
+    /// let image_name = nodes.type_name(TypeId::of::<Image>()).unwrap();
+    /// assert!(image_name.ends_with("Image"));
+    /// ```
+    #[inline]
+    pub fn type_ids(&self) -> TypeIds<'_, B> {
+        TypeIds {
+            inner: self.node_groups.keys(),
+        }
+    }
+
+    /// Get the name of the node type `type_id`, as given by
+    /// [`core::any::type_name`], if it has a node group.
+    #[inline]
+    pub fn type_name(&self, type_id: TypeId) -> Option<&'static str> {
+        Some(self.node_groups.get(&type_id)?.node_type_name())
     }
 
+    /// Iterate over the keys of all nodes, of all types. See
+    /// [`Nodes::keys`] for a single type.
     #[inline]
-    pub fn iter_dyn(&self) -> IterDyn<B> {
+    pub fn dyn_keys(&self) -> DynKeys<'_, B> {
+        DynKeys {
+            inner: self.iter_dyn(),
+        }
+    }
+
+    #[inline]
+    pub fn iter_dyn(&self) -> IterDyn<'_, B> {
         IterDyn {
             inner: self
                 .node_groups
@@ -114,8 +1264,41 @@ where
         }
     }
 
+    /// Iterate over all nodes in a deterministic order, unlike
+    /// [`Nodes::iter_dyn`]. The nodes are sorted by type name and then by
+    /// key, so the order only depends on the node types and the order the
+    /// nodes were inserted in. This is useful for dumps and other outputs
+    /// that have to be reproducible.
+    ///
+    /// ```
+    /// use typed_nodes::{DynKey, Nodes};
+    ///
+    /// struct Sword;
+    /// struct Axe;
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let sword = nodes.insert(Sword);
+    /// let axe_1 = nodes.insert(Axe);
+    /// let axe_2 = nodes.insert(Axe);
+    ///
+    /// let keys: Vec<DynKey> = nodes.iter_dyn_sorted().map(|(key, _)| key).collect();
+    /// assert!(keys == [axe_1.into(), axe_2.into(), sword.into()]);
+    /// ```
+    pub fn iter_dyn_sorted(&self) -> IterDynSorted<'_, B> {
+        let mut groups: Vec<_> = self.node_groups.values().collect();
+        groups.sort_by_key(|group| group.node_type_name());
+
+        IterDynSorted {
+            inner: groups.into_iter().flat_map(DynNodeGroup::<B>::iter_dyn),
+        }
+    }
+
+    /// Iterate over the keys and mutable nodes of all types. All nodes get
+    /// a new change tick, as with [`Nodes::iter_mut`].
     #[inline]
-    pub fn iter_dyn_mut(&mut self) -> IterDynMut<B> {
+    pub fn iter_dyn_mut(&mut self) -> IterDynMut<'_, B> {
+        self.mark_all_types_changed();
+
         IterDynMut {
             inner: self
                 .node_groups
@@ -124,8 +1307,18 @@ where
         }
     }
 
+    fn mark_all_types_changed(&mut self) {
+        let Some(change_ticks) = &mut self.change_ticks else {
+            return;
+        };
+
+        for &type_id in self.node_groups.keys() {
+            change_ticks.set_type(type_id);
+        }
+    }
+
     #[inline]
-    pub fn nodes_dyn(&self) -> NodesDyn<B> {
+    pub fn nodes_dyn(&self) -> NodesDyn<'_, B> {
         NodesDyn {
             inner: self
                 .node_groups
@@ -134,8 +1327,12 @@ where
         }
     }
 
+    /// Iterate over the mutable nodes of all types. All nodes get a new
+    /// change tick, as with [`Nodes::iter_mut`].
     #[inline]
-    pub fn nodes_dyn_mut(&mut self) -> NodesDynMut<B> {
+    pub fn nodes_dyn_mut(&mut self) -> NodesDynMut<'_, B> {
+        self.mark_all_types_changed();
+
         NodesDynMut {
             inner: self
                 .node_groups
@@ -158,12 +1355,16 @@ where
     where
         T: BoundedBy<I, B>,
     {
-        self.node_groups
+        let (key, old_key) = self
+            .node_groups
             .entry(TypeId::of::<T>())
             .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()))
-            .downcast_mut::<I, T>()
-            .expect("node group should be possible to downcast")
-            .insert_with_id(id, node)
+            .group_mut::<I, T>()
+            .insert_with_id(id, node);
+
+        self.update_indexes(key);
+        self.record(JournalEvent::Insert, key);
+        (key, old_key)
     }
 
     /// Reserves a node slot for `id` and node type `T` that can be filled
@@ -178,11 +1379,38 @@ where
         self.node_groups
             .entry(TypeId::of::<T>())
             .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()))
-            .downcast_mut::<I, T>()
-            .expect("node group should be possible to downcast")
+            .group_mut::<I, T>()
             .reserve_with_id(id)
     }
 
+    /// Make `id` refer to the existing node at `key`, in addition to any
+    /// other IDs it already has. Returns the key `id` was previously assigned
+    /// to, if any.
+    #[cfg(feature = "lua")]
+    #[inline]
+    pub(crate) fn assign_id<T>(&mut self, key: Key<T>, id: I) -> Option<Key<T>>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.node_groups
+            .get_mut(&TypeId::of::<T>())?
+            .group_mut::<I, T>()
+            .assign_id(id, key)
+    }
+
+    /// Iterate over all IDs and the keys they are assigned to, for all node
+    /// types.
+    pub(crate) fn iter_ids_dyn(&self) -> impl Iterator<Item = (&I, DynKey)> {
+        self.node_groups.values().flat_map(|group| {
+            group.iter_ids_dyn().map(|(id, key)| {
+                let id = id
+                    .downcast_ref()
+                    .expect("the ID type should be the same in all node groups");
+                (id, key)
+            })
+        })
+    }
+
     /// Find the key for `id` and node type `T`. The node may not have been
     /// inserted yet if it was reserved with [`Nodes::reserve_with_id`], so
     /// [`Nodes::get`] may still return `None`.
@@ -195,8 +1423,7 @@ where
     {
         self.node_groups
             .get(&TypeId::of::<T>())?
-            .downcast_ref::<I, T>()
-            .expect("node group should be possible to downcast")
+            .group_ref::<I, T>()
             .get_key(id)
     }
 }
@@ -208,32 +1435,237 @@ where
     fn default() -> Self {
         Self {
             node_groups: Default::default(),
+            tags: Default::default(),
+            provenance: None,
+            annotations: None,
+            aliases: None,
+            names: None,
+            hierarchy: None,
+            indexes: None,
+            reservations: Default::default(),
+            redirects: None,
+            roots: Default::default(),
+            journal: None,
+            change_ticks: None,
             key_type: Default::default(),
         }
     }
 }
 
+pub struct KeysWithTag<'a> {
+    inner: Option<alloc::collections::btree_set::Iter<'a, DynKey>>,
+}
+
+impl<'a> Iterator for KeysWithTag<'a> {
+    type Item = DynKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next().copied()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner
+            .as_ref()
+            .map_or((0, Some(0)), Iterator::size_hint)
+    }
+}
+
+impl<'a> DoubleEndedIterator for KeysWithTag<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next_back().copied()
+    }
+}
+
+impl<'a> ExactSizeIterator for KeysWithTag<'a> {}
+
+/// A node that has been moved out of its slot with
+/// [`Nodes::get_mut_and_rest`], together with the rest of the nodes. The
+/// node is moved back when this is dropped.
+pub struct NodeAndRest<'a, T, I, B>
+where
+    T: BoundedBy<I, B>,
+    I: 'static,
+    B: Bounds,
+{
+    taken: Option<(ReservedKey<T>, T)>,
+    nodes: &'a mut Nodes<I, B>,
+}
+
+impl<'a, T, I, B> NodeAndRest<'a, T, I, B>
+where
+    T: BoundedBy<I, B>,
+    I: 'static,
+    B: Bounds,
+{
+    /// Get the node mutably and the rest of the nodes immutably.
+    #[inline]
+    pub fn split(&mut self) -> (&mut T, &Nodes<I, B>) {
+        let (_, node) = self
+            .taken
+            .as_mut()
+            .expect("the node should not be moved back yet");
+        (node, self.nodes)
+    }
+}
+
+impl<'a, T, I, B> Drop for NodeAndRest<'a, T, I, B>
+where
+    T: BoundedBy<I, B>,
+    I: 'static,
+    B: Bounds,
+{
+    fn drop(&mut self) {
+        if let Some((reserved_key, node)) = self.taken.take() {
+            // The node was never really removed, so it's not recorded as
+            // inserted.
+            let result = self.nodes.put_back(reserved_key.key(), node);
+            assert!(result.is_ok(), "reserved slot was removed");
+        }
+    }
+}
+
+pub struct Keys<'a, T> {
+    inner: Option<node_group::GroupKeys<'a, T>>,
+}
+
+impl<'a, T> Iterator for Keys<'a, T> {
+    type Item = Key<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner
+            .as_ref()
+            .map_or((0, Some(0)), Iterator::size_hint)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Keys<'a, T> {}
+
+pub struct Iter<'a, T> {
+    inner: Option<node_group::GroupIter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Key<T>, &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner
+            .as_ref()
+            .map_or((0, Some(0)), Iterator::size_hint)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+pub struct IterMut<'a, T> {
+    inner: Option<node_group::GroupIterMut<'a, T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Key<T>, &'a mut T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.as_mut()?.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner
+            .as_ref()
+            .map_or((0, Some(0)), Iterator::size_hint)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+pub struct TypeIds<'a, B: Bounds> {
+    inner: hashbrown::hash_map::Keys<'a, TypeId, BoxedGroupOf<B>>,
+}
+
+impl<'a, B: Bounds> Iterator for TypeIds<'a, B> {
+    type Item = TypeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().copied()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, B: Bounds> ExactSizeIterator for TypeIds<'a, B> {}
+
+pub struct DynKeys<'a, B: Bounds> {
+    inner: IterDyn<'a, B>,
+}
+
+impl<'a, B: Bounds> Iterator for DynKeys<'a, B> {
+    type Item = DynKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, _) = self.inner.next()?;
+        Some(key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
 pub struct IterDyn<'a, B: Bounds> {
-    inner: std::iter::FlatMap<
-        std::collections::hash_map::Values<'a, TypeId, BoxedGroupOf<B>>,
+    inner: FlatMapGroups<GroupValues<'a, B>, &'a BoxedGroupOf<B>, node_group::IterDyn<'a, B>>,
+}
+
+impl<'a, B: Bounds> Iterator for IterDyn<'a, B> {
+    type Item = (DynKey, &'a B::DynSelf);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+pub struct IterDynSorted<'a, B: Bounds> {
+    inner: FlatMapGroups<
+        alloc::vec::IntoIter<&'a BoxedGroupOf<B>>,
+        &'a BoxedGroupOf<B>,
         node_group::IterDyn<'a, B>,
-        fn(&BoxedGroupOf<B>) -> node_group::IterDyn<B>,
     >,
 }
 
-impl<'a, B: Bounds> Iterator for IterDyn<'a, B> {
+impl<'a, B: Bounds> Iterator for IterDynSorted<'a, B> {
     type Item = (DynKey, &'a B::DynSelf);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 pub struct IterDynMut<'a, B: Bounds> {
-    inner: std::iter::FlatMap<
-        std::collections::hash_map::ValuesMut<'a, TypeId, BoxedGroupOf<B>>,
+    inner: FlatMapGroups<
+        GroupValuesMut<'a, B>,
+        &'a mut BoxedGroupOf<B>,
         node_group::IterDynMut<'a, B>,
-        fn(&mut BoxedGroupOf<B>) -> node_group::IterDynMut<B>,
     >,
 }
 
@@ -243,14 +1675,14 @@ impl<'a, B: Bounds> Iterator for IterDynMut<'a, B> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 pub struct NodesDyn<'a, B: Bounds> {
-    inner: std::iter::FlatMap<
-        std::collections::hash_map::Values<'a, TypeId, BoxedGroupOf<B>>,
-        node_group::NodesDyn<'a, B>,
-        fn(&BoxedGroupOf<B>) -> node_group::NodesDyn<B>,
-    >,
+    inner: FlatMapGroups<GroupValues<'a, B>, &'a BoxedGroupOf<B>, node_group::NodesDyn<'a, B>>,
 }
 
 impl<'a, B: Bounds> Iterator for NodesDyn<'a, B> {
@@ -259,13 +1691,17 @@ impl<'a, B: Bounds> Iterator for NodesDyn<'a, B> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 pub struct NodesDynMut<'a, B: Bounds> {
-    inner: std::iter::FlatMap<
-        std::collections::hash_map::ValuesMut<'a, TypeId, BoxedGroupOf<B>>,
+    inner: FlatMapGroups<
+        GroupValuesMut<'a, B>,
+        &'a mut BoxedGroupOf<B>,
         node_group::NodesDynMut<'a, B>,
-        fn(&mut BoxedGroupOf<B>) -> node_group::NodesDynMut<B>,
     >,
 }
 
@@ -275,6 +1711,10 @@ impl<'a, B: Bounds> Iterator for NodesDynMut<'a, B> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 #[cfg(doctest)]
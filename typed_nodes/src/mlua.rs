@@ -1,35 +1,780 @@
 use std::{
+    any::{Any, TypeId},
+    collections::{hash_map::DefaultHasher, BTreeSet},
     fmt::Display,
-    sync::atomic::{AtomicI64, Ordering},
+    hash::{Hash, Hasher},
+    path::{Component, Path},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
 };
 
-use mlua::Value;
+use mlua::{Table, Value};
 
+pub use bytes::*;
+pub use config::*;
+pub use dyn_registry::*;
+pub use expand::*;
+pub use flags::*;
 pub use from_lua::*;
 pub use generate_lua::*;
+pub use lazy::*;
+pub use maybe::*;
+pub use parse_error::*;
+pub use recovery::*;
+pub use reference::*;
+pub use stream::*;
+pub use type_registry::*;
+pub use value_ref::*;
 pub use visit_lua::*;
 
-use crate::{bounds::Bounds, Nodes};
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    DynKey, Key, Nodes, Provenance, ReservedKey, SlotState,
+};
 
+mod bytes;
+mod config;
+mod dyn_registry;
+mod expand;
+mod flags;
 mod from_lua;
 mod generate_lua;
+mod instrument;
+mod lazy;
+pub mod literals;
+mod maybe;
+mod parse_error;
+mod recovery;
+mod reference;
+mod stream;
+mod type_registry;
+mod value_ref;
 mod visit_lua;
 
 const TABLE_ID_KEY: &str = "_node_table_id";
+const NODE_NAME_KEY: &str = "_node_name";
+const ANNOTATION_KEYS: [&str; 2] = ["__doc", "__meta"];
 pub static TABLE_ID_SOURCE: TableIdSource = TableIdSource::new();
 
 pub struct Context<'lua, B: Bounds> {
     lua: &'lua mlua::Lua,
     nodes: &'lua mut Nodes<TableId, B>,
+    used_fields: Vec<UsedFields>,
+    warnings: Vec<String>,
+    pending_names: BTreeSet<(String, TypeId)>,
+    interned: ahash::HashMap<(TypeId, u64), Vec<DynKey>>,
+    config: ContextConfig,
+    source: Option<Arc<str>>,
+    fallbacks: ahash::HashMap<TypeId, Box<dyn Any>>,
+    insert_placeholder: Option<InsertPlaceholder<B>>,
+    node_count: usize,
+    element_count: usize,
+    depth: usize,
+    node_types: ahash::HashMap<TableId, (TypeId, &'static str)>,
+    inline_types: ahash::HashMap<TableId, &'static str>,
+    mixed_tables: ahash::HashSet<TableId>,
+    node_table: Option<TableId>,
 }
 
 impl<'lua, B: Bounds> Context<'lua, B> {
+    /// Create a context with the default options. See [`Context::builder`]
+    /// for setting other options.
     pub fn new(lua: &'lua mlua::Lua, nodes: &'lua mut Nodes<TableId, B>) -> Self {
-        Self { lua, nodes }
+        Self::with_config(lua, nodes, ContextConfig::default())
+    }
+
+    /// Create a [`ContextBuilder`] for setting options.
+    pub fn builder(
+        lua: &'lua mlua::Lua,
+        nodes: &'lua mut Nodes<TableId, B>,
+    ) -> ContextBuilder<'lua, B> {
+        ContextBuilder::new(lua, nodes)
+    }
+
+    /// Create a context with the options in `config`.
+    pub fn with_config(
+        lua: &'lua mlua::Lua,
+        nodes: &'lua mut Nodes<TableId, B>,
+        config: ContextConfig,
+    ) -> Self {
+        Self {
+            lua,
+            nodes,
+            used_fields: Vec::new(),
+            warnings: Vec::new(),
+            pending_names: BTreeSet::new(),
+            interned: Default::default(),
+            source: config.source.clone(),
+            config,
+            fallbacks: Default::default(),
+            insert_placeholder: None,
+            node_count: 0,
+            element_count: 0,
+            depth: 0,
+            node_types: Default::default(),
+            inline_types: Default::default(),
+            mixed_tables: Default::default(),
+            node_table: None,
+        }
+    }
+
+    /// Set the name of the script or file that is being parsed. It's recorded
+    /// in the [`Provenance`] of each node that is parsed from a table. This
+    /// is a shortcut for [`ContextBuilder::source`].
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Enemy {
+    ///     health: u32,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::new(&lua, &mut nodes).with_source("enemies.lua");
+    ///
+    /// let value = lua.load("{health = 10}").eval().unwrap();
+    /// let enemy = Key::<Enemy>::from_lua(value, &mut context).unwrap();
+    ///
+    /// assert_eq!(nodes.provenance(enemy).unwrap().source(), Some("enemies.lua"));
+    /// ```
+    pub fn with_source(mut self, source: impl Into<Arc<str>>) -> Self {
+        let source = source.into();
+        self.config.source = Some(source.clone());
+        self.source = Some(source);
+        self
+    }
+
+    /// The name of the script or file that is being parsed, if it's set. It's
+    /// the name of the included file while parsing with
+    /// [`Context::parse_include`].
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// The options for this context.
+    pub fn config(&self) -> &ContextConfig {
+        &self.config
+    }
+
+    /// Convert `value` to Lua and parse it as `T`. This is the same as
+    /// calling [`FromLua::from_lua`], except that any state that belongs to a
+    /// single parse is reset afterwards, even if it failed.
+    ///
+    /// A context can be used for parsing any number of values. Tables that
+    /// have already been parsed as nodes, with the same context or any other
+    /// context with the same [`Nodes`], result in the same keys, so the
+    /// parsed values can refer to each other. Named nodes, interned nodes and
+    /// warnings are also shared between all values that are parsed with the
+    /// same context.
+    ///
+    /// Any [`ParseError`] that is returned gets the current source as its
+    /// location, if it's set and the error doesn't already have a location.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Weapon {
+    ///     damage: u32,
+    /// }
+    ///
+    /// #[derive(FromLua)]
+    /// struct Enemy {
+    ///     weapon: Key<Weapon>,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::new(&lua, &mut nodes);
+    ///
+    /// let sword: mlua::Table = lua.load("{damage = 5}").eval().unwrap();
+    /// lua.globals().set("sword", sword.clone()).unwrap();
+    ///
+    /// let sword_key: Key<Weapon> = context.parse(sword).unwrap();
+    /// let orc: mlua::Table = lua.load("{weapon = sword}").eval().unwrap();
+    /// let goblin: mlua::Table = lua.load("{weapon = sword}").eval().unwrap();
+    ///
+    /// let orc: Enemy = context.parse(orc).unwrap();
+    /// let goblin: Enemy = context.parse(goblin).unwrap();
+    ///
+    /// assert!(orc.weapon == sword_key);
+    /// assert!(goblin.weapon == sword_key);
+    /// ```
+    pub fn parse<T>(&mut self, value: impl mlua::IntoLua<'lua>) -> mlua::Result<T>
+    where
+        T: FromLua<'lua, B>,
+    {
+        let value = value.into_lua(self.lua)?;
+        let used_fields_depth = self.used_fields.len();
+
+        let result = T::from_lua(value, self);
+        self.used_fields.truncate(used_fields_depth);
+
+        result.map_err(|error| self.add_error_location(error))
+    }
+
+    /// Count a new node, and return an error if there are too many.
+    pub(crate) fn count_node(&mut self) -> mlua::Result<()> {
+        self.node_count += 1;
+
+        match self.config.max_nodes {
+            Some(max_nodes) if self.node_count > max_nodes => Err(Error::too_many_nodes(max_nodes)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count an element in a sequence or map, and return an error if there
+    /// are too many.
+    pub(crate) fn count_element(&mut self) -> mlua::Result<()> {
+        self.element_count += 1;
+
+        match self.config.max_elements {
+            Some(max_elements) if self.element_count > max_elements => {
+                Err(Error::too_many_elements(max_elements))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Enter a nested table, and return an error if the tables are nested
+    /// too deeply. Each successful call must be followed by
+    /// [`Context::exit_table`].
+    pub(crate) fn enter_table(&mut self) -> mlua::Result<()> {
+        let max_depth = self.config.max_depth();
+
+        if self.depth >= max_depth {
+            return Err(Error::too_deep(max_depth));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Leave a table that was entered with [`Context::enter_table`].
+    pub(crate) fn exit_table(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Return an error if `string` is too long.
+    pub(crate) fn check_string_length(&self, string: &mlua::String) -> mlua::Result<()> {
+        let length = string.as_bytes().len();
+
+        match self.config.max_string_length {
+            Some(max_length) if length > max_length => {
+                Err(Error::string_too_long(length, max_length))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Expand `text` with the [`StringExpander`] that is set with
+    /// [`ContextBuilder::expand_strings`]. It's returned as it is if there's
+    /// no expander.
+    pub fn expand_string(&self, text: String) -> mlua::Result<String> {
+        match self.config.string_expander {
+            Some(expander) => expander(&text),
+            None => Ok(text),
+        }
+    }
+
+    /// Recover from `error` when parsing the node for `reserved_key`, if a
+    /// fallback for `T` has been set or placeholders are enabled. The error
+    /// is returned otherwise.
+    pub(crate) fn recover<T>(
+        &mut self,
+        error: mlua::Error,
+        reserved_key: ReservedKey<T>,
+        id: TableId,
+        used_fields_depth: usize,
+    ) -> mlua::Result<Key<T>>
+    where
+        T: BoundedBy<TableId, B>,
+    {
+        let fallback = self
+            .fallbacks
+            .get(&TypeId::of::<T>())
+            .and_then(|fallback| fallback.downcast_ref::<fn(Placeholder) -> T>())
+            .copied();
+
+        if fallback.is_none() && self.insert_placeholder.is_none() {
+            return Err(error);
+        }
+
+        self.used_fields.truncate(used_fields_depth);
+
+        let mut parse_error = ParseError::from_lua_error(&error);
+        if let (None, Some(source)) = (parse_error.location(), &self.source) {
+            parse_error = parse_error.with_location(&**source);
+        }
+        let placeholder = Placeholder::new(std::any::type_name::<T>(), parse_error);
+
+        let key = match (fallback, self.insert_placeholder) {
+            (Some(fallback), _) => {
+                let key = self
+                    .nodes
+                    .insert_reserved(reserved_key, fallback(placeholder));
+                instrument::node_inserted::<T>();
+                key
+            }
+            (None, Some(insert_placeholder)) => {
+                insert_placeholder(self.nodes, id, placeholder);
+                reserved_key.key()
+            }
+            (None, None) => unreachable!("recovery should be enabled"),
+        };
+
+        self.record_provenance(key, Some(id));
+
+        Ok(key)
+    }
+
+    /// Set the location of the [`ParseError`] in `error` to the current
+    /// source, unless it already has one.
+    fn add_error_location(&self, error: mlua::Error) -> mlua::Error {
+        let Some(source) = &self.source else {
+            return error;
+        };
+
+        match ParseError::find(&error) {
+            Some(parse_error) if parse_error.location().is_none() => {
+                mlua::Error::from_parse_error(parse_error.clone().with_location(&**source))
+            }
+            _ => error,
+        }
     }
+
+    /// Record that the table `table_id` is parsed as a node of type `T`, and
+    /// return an error if it has already been parsed as another node type
+    /// with this context. Multi-typed tables are allowed if
+    /// [`ContextBuilder::multi_typed_tables`] is set.
+    pub(crate) fn record_node_type<T: 'static>(&mut self, table_id: TableId) -> mlua::Result<()> {
+        let node_type = std::any::type_name::<T>();
+        let &mut (first_type_id, first_type) = self
+            .node_types
+            .entry(table_id)
+            .or_insert((TypeId::of::<T>(), node_type));
+
+        if self.config.check_table_usage {
+            if let Some(&inline_type) = self.inline_types.get(&table_id) {
+                self.warn_mixed_usage(table_id, node_type, inline_type);
+            }
+        }
+
+        if first_type_id == TypeId::of::<T>() || self.config.multi_typed_tables {
+            Ok(())
+        } else {
+            Err(Error::conflicting_node_types(
+                table_id, first_type, node_type,
+            ))
+        }
+    }
+
+    /// Record that `table` is parsed as a value of type `T`. This is called
+    /// by derived [`FromLua`] implementations and can be called by custom
+    /// implementations that parse tables.
+    ///
+    /// It's only used when [`ContextBuilder::check_table_usage`] is set, to
+    /// warn about tables that are parsed both as nodes and as inline values.
+    /// The parsed copies are separate and changes to one of them don't show
+    /// in the other, which is rarely intended.
+    pub fn record_inline_table<T>(&mut self, table: &mlua::Table<'lua>) -> mlua::Result<()> {
+        if !self.config.check_table_usage {
+            return Ok(());
+        }
+
+        let table_id = TableId::get_or_assign(table)?;
+
+        // The table is the node that is being parsed, so it's not inline.
+        if self.node_table.take() == Some(table_id) {
+            return Ok(());
+        }
+
+        let inline_type = std::any::type_name::<T>();
+        self.inline_types.entry(table_id).or_insert(inline_type);
+
+        if let Some(&(_, node_type)) = self.node_types.get(&table_id) {
+            self.warn_mixed_usage(table_id, node_type, inline_type);
+        }
+
+        Ok(())
+    }
+
+    /// Warn about a table that is parsed both as a node and as an inline
+    /// value, unless it has already been reported.
+    fn warn_mixed_usage(&mut self, table_id: TableId, node_type: &str, inline_type: &str) {
+        if self.mixed_tables.insert(table_id) {
+            self.warn(format_args!(
+                "the table {table_id} is parsed both as a {node_type} node and as an inline \
+                {inline_type}, so changes to one of them will not affect the other"
+            ));
+        }
+    }
+
+    /// Make the node at `key` possible to find by the name it was given with
+    /// a generated `define(name)` method, if `table` has one.
+    pub(crate) fn record_defined_name<T: 'static>(
+        &mut self,
+        key: Key<T>,
+        table: &Table<'lua>,
+    ) -> mlua::Result<()> {
+        let Some(name) = table.raw_get::<_, Option<String>>(NODE_NAME_KEY)? else {
+            return Ok(());
+        };
+
+        if let Some(old_key) = self.nodes.set_name(key, name.clone()) {
+            if old_key != key {
+                self.warn(format_args!(
+                    "the name {name:?} is defined for more than one {}, only the last one is kept",
+                    std::any::type_name::<T>()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attach the `__doc` or `__meta` field of `table` to the node at `key`,
+    /// if it has one of them.
+    pub(crate) fn record_annotation<T: 'static>(
+        &mut self,
+        key: Key<T>,
+        table: &Table<'lua>,
+    ) -> mlua::Result<()> {
+        for name in ANNOTATION_KEYS {
+            if let Some(annotation) = table.raw_get::<_, Option<String>>(name)? {
+                self.nodes.set_annotation(key, annotation);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record where a new node came from, unless it's already known.
+    pub(crate) fn record_provenance<T: 'static>(&mut self, key: Key<T>, table_id: Option<TableId>) {
+        if self.nodes.provenance(key).is_none() {
+            self.nodes
+                .set_provenance(key, Provenance::new(self.source.clone(), table_id));
+        }
+    }
+
+    /// Insert `node` into its reserved slot, unless an equal node of the same
+    /// type has already been interned while parsing. The reserved slot is
+    /// removed and the existing key is returned in that case, so any
+    /// references to the reserved slot from within `node` will not be valid.
+    pub fn intern<T>(&mut self, node: T, reserved_key: ReservedKey<T>) -> Key<T>
+    where
+        T: BoundedBy<TableId, B> + Eq + Hash,
+    {
+        let mut hasher = DefaultHasher::new();
+        node.hash(&mut hasher);
+
+        let candidates = self
+            .interned
+            .entry((TypeId::of::<T>(), hasher.finish()))
+            .or_default();
+
+        for candidate in candidates.iter() {
+            let key = candidate
+                .into_static()
+                .expect("interned keys should have the same type as the node");
+
+            if self.nodes.get(key) == Some(&node) {
+                self.nodes.merge_reserved(reserved_key, key);
+                return key;
+            }
+        }
+
+        let key = self.nodes.insert_reserved(reserved_key, node);
+        candidates.push(key.into());
+        key
+    }
+
+    /// Insert `node` and make it possible to reference it as `name`, for
+    /// example from fields with the `#[typed_nodes(reference)]` attribute.
+    /// Names are only unique for nodes of type `T`. A node that was referenced
+    /// before it was inserted is placed in the already reserved slot.
+    ///
+    /// The name is given to the node with [`Nodes::set_name`], so it can
+    /// still be referenced from later contexts for the same nodes. That
+    /// includes the reserved slot, which can be filled by a later context if
+    /// this one doesn't insert the node.
+    pub fn insert_named<T>(&mut self, name: &str, node: T) -> Key<T>
+    where
+        T: BoundedBy<TableId, B>,
+    {
+        self.pending_names
+            .remove(&(name.to_owned(), TypeId::of::<T>()));
+
+        if let Some(key) = self.get_named::<T>(name) {
+            if self.nodes.slot_state(key) == SlotState::Reserved {
+                return self.nodes.insert_reserved(ReservedKey::from_key(key), node);
+            }
+        }
+
+        let key = self.nodes.insert(node);
+        self.nodes.set_name(key, name);
+        key
+    }
+
+    /// Find the key for the node of type `T` that was inserted or reserved as
+    /// `name`. A reserved node may not have been inserted yet, so
+    /// [`Nodes::get`] may still return `None`.
+    pub fn get_named<T>(&self, name: &str) -> Option<Key<T>>
+    where
+        T: BoundedBy<TableId, B>,
+    {
+        self.nodes.get_key_by_name(name)
+    }
+
+    /// Find the key for the node of type `T` that was inserted or reserved as
+    /// `name`, or reserve a slot for it if it's not known yet. The node is
+    /// expected to be inserted with [`Context::insert_named`] before
+    /// [`Context::finish`] is called.
+    pub fn get_or_reserve_named<T>(&mut self, name: &str) -> Key<T>
+    where
+        T: BoundedBy<TableId, B>,
+    {
+        let key = match self.get_named(name) {
+            Some(key) if self.nodes.slot_state(key) == SlotState::Filled => return key,
+            // The slot may have been reserved by an earlier context.
+            Some(key) if self.nodes.slot_state(key) == SlotState::Reserved => key,
+            _ => {
+                let key = self.nodes.reserve_untracked::<T>().key();
+                self.nodes.set_name(key, name);
+                key
+            }
+        };
+
+        self.pending_names
+            .insert((name.to_owned(), TypeId::of::<T>()));
+        key
+    }
+
+    /// Check that all referenced names have been inserted, and return an
+    /// error that lists the ones that haven't.
+    pub fn finish(&mut self) -> mlua::Result<()> {
+        if self.pending_names.is_empty() {
+            return Ok(());
+        }
+
+        let mut names: Vec<_> = self.pending_names.iter().map(|(name, _)| &**name).collect();
+        names.dedup();
+
+        Err(Error::unresolved_references(&names))
+    }
+
+    /// Evaluate the Lua file at `path` and return its value, so it can be
+    /// parsed with the same context. The path is relative to the include
+    /// directory, which has to be set with [`ContextBuilder::include_dir`],
+    /// and it's not allowed to refer to anything outside of it.
+    ///
+    /// This makes it possible to split the input into multiple files,
+    /// without giving the scripts access to `require` or the file system.
+    /// The files are evaluated in the same Lua state, so their values can
+    /// refer to each other's tables and the same tables result in the same
+    /// nodes.
+    pub fn eval_include(&mut self, path: impl AsRef<Path>) -> mlua::Result<Value<'lua>> {
+        let path = path.as_ref();
+
+        let Some(include_dir) = self.config.include_dir() else {
+            return Err(Error::includes_disabled());
+        };
+
+        let is_contained = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+        if !is_contained {
+            return Err(Error::invalid_include_path(path));
+        }
+
+        let source = std::fs::read(include_dir.join(path)).map_err(mlua::Error::external)?;
+        let name = path.to_string_lossy();
+        self.lua
+            .load(&source)
+            .set_name(&*name)
+            .eval()
+            .map_err(|error| parse_error::chunk_error(error, &name, &source))
+    }
+
+    /// Evaluate the Lua file at `path` with [`Context::eval_include`] and
+    /// parse its value as `T`. The file's path is recorded as the source of
+    /// the parsed nodes.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Weapon {
+    ///     damage: u32,
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join("typed_nodes_include_example");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("sword.lua"), "return {damage = 5}").unwrap();
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::builder(&lua, &mut nodes).include_dir(&dir).build();
+    ///
+    /// let sword: Key<Weapon> = context.parse_include("sword.lua").unwrap();
+    /// assert!(context.parse_include::<Key<Weapon>>("../secrets.lua").is_err());
+    ///
+    /// assert_eq!(nodes.get(sword).unwrap().damage, 5);
+    /// assert_eq!(nodes.provenance(sword).unwrap().source(), Some("sword.lua"));
+    /// ```
+    pub fn parse_include<T>(&mut self, path: impl AsRef<Path>) -> mlua::Result<T>
+    where
+        T: FromLua<'lua, B>,
+    {
+        let path = path.as_ref();
+        let value = self.eval_include(path)?;
+
+        let source = self.source.replace(path.to_string_lossy().into());
+        let result = self.parse(value);
+        self.source = source;
+
+        result
+    }
+
+    /// Parse each value in the sequence part of `table` as `T` and pass it to
+    /// `f`, without collecting them first. Parsing stops at the first error.
+    pub fn for_each_element<T>(
+        &mut self,
+        table: &Table<'lua>,
+        mut f: impl FnMut(T) -> mlua::Result<()>,
+    ) -> mlua::Result<()>
+    where
+        T: FromLua<'lua, B>,
+    {
+        for (index, value) in table.clone().sequence_values().enumerate() {
+            self.count_element()?;
+            let element = T::from_lua(value?, self).map_err(|mut error| {
+                error.add_context_index(index + 1);
+                error
+            })?;
+
+            f(element)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create an error for an unknown enum variant, with a suggested
+    /// constructor if [`ContextBuilder::constructor_hints`] is set and one of
+    /// the `expected` variants is similar to `variant`.
+    pub fn invalid_variant(&self, variant: &str, expected: &[&str]) -> mlua::Error {
+        let error = mlua::Error::invalid_variant(variant, expected);
+
+        let Some(suggestion) = self
+            .config
+            .constructor_hints
+            .as_ref()
+            .and_then(|hints| hints.suggest(variant, expected))
+        else {
+            return error;
+        };
+
+        mlua::Error::from_parse_error(
+            ParseError::from_lua_error(&error)
+                .with_help(format_args!("did you mean {suggestion}?")),
+        )
+    }
+
+    /// Report a non-fatal issue with the parsed value, such as the use of a
+    /// deprecated variant name.
+    pub fn warn(&mut self, message: impl Display) {
+        self.warnings.push(message.to_string());
+    }
+
+    /// The warnings that have been reported so far.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Remove and return the warnings that have been reported so far.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Start keeping track of which fields are read from `table`, until
+    /// [`Context::check_unknown_fields`] is called. Tables that are already
+    /// tracked, such as when parsing flattened fields, are only checked by the
+    /// outermost tracker.
+    pub fn track_used_fields(&mut self, table: &Table) {
+        let table = table.to_pointer() as usize;
+        let is_tracked = self.used_fields.iter().any(|used| used.table == table);
+
+        self.used_fields.push(UsedFields {
+            table,
+            names: Vec::new(),
+            is_checked: !is_tracked,
+        });
+    }
+
+    /// Mark `name` as a known field in `table`, if it's tracked.
+    pub fn mark_field_used(&mut self, table: &Table, name: &'static str) {
+        if self.used_fields.is_empty() {
+            return;
+        }
+
+        let table = table.to_pointer() as usize;
+        if let Some(used) = self.used_fields.iter_mut().find(|used| used.table == table) {
+            used.names.push(name);
+        }
+    }
+
+    /// Stop tracking the most recently tracked table and return an error if
+    /// `result` is OK but the table has fields that weren't read.
+    pub fn check_unknown_fields<T>(
+        &mut self,
+        table: &Table,
+        result: mlua::Result<T>,
+    ) -> mlua::Result<T> {
+        let used = self
+            .used_fields
+            .pop()
+            .expect("fields should be tracked before they are checked");
+        debug_assert_eq!(used.table, table.to_pointer() as usize);
+
+        let value = result?;
+
+        if used.is_checked {
+            for pair in table.clone().pairs::<Value, Value>() {
+                let (key, _) = pair?;
+
+                let name = match &key {
+                    Value::String(name) => name.to_string_lossy(),
+                    key => return Err(Error::unknown_key(key)),
+                };
+
+                let is_internal = name == TABLE_ID_KEY
+                    || name == NODE_NAME_KEY
+                    || ANNOTATION_KEYS.contains(&&*name);
+
+                if !is_internal && !used.names.contains(&&*name) {
+                    return Err(Error::unknown_field(&name, &used.names));
+                }
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+struct UsedFields {
+    table: usize,
+    names: Vec<&'static str>,
+    is_checked: bool,
 }
 
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TableId(mlua::Integer);
 
 impl TableId {
@@ -51,6 +796,12 @@ impl TableId {
     }
 }
 
+impl Display for TableId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 pub struct TableIdSource(AtomicI64);
 
 impl TableIdSource {
@@ -63,47 +814,204 @@ impl TableIdSource {
     }
 }
 
+impl Default for TableIdSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait Error: Sized + From<mlua::Error> + Display {
     fn custom<T>(message: T) -> Self
     where
         T: Display;
 
+    /// Create an error from a structured [`ParseError`]. The default
+    /// implementation only keeps its message.
+    fn from_parse_error(error: ParseError) -> Self {
+        Self::custom(error)
+    }
+
+    /// Get the structured [`ParseError`] in this error, if it has one.
+    fn as_parse_error(&self) -> Option<&ParseError> {
+        None
+    }
+
+    /// Get a copy of the structured [`ParseError`] in this error, or create
+    /// one from its message.
+    fn to_parse_error(&self) -> ParseError {
+        self.as_parse_error()
+            .cloned()
+            .unwrap_or_else(|| ParseError::new(self))
+    }
+
     fn invalid_length(length: usize, expected: usize) -> Self {
-        Self::custom(format_args!("invalid length {length}, expected {expected}"))
+        Self::from_parse_error(
+            ParseError::new(format_args!("invalid length {length}, expected {expected}"))
+                .with_expected(expected)
+                .with_found(length),
+        )
     }
 
     fn invalid_type(value: &mlua::Value, expected: &str) -> Self {
         let name = value.type_name();
-        Self::custom(format_args!("unexpected {name}, expected {expected}"))
+        Self::from_parse_error(
+            ParseError::new(format_args!("unexpected {name}, expected {expected}"))
+                .with_expected(expected)
+                .with_found(name),
+        )
     }
 
     fn invalid_variant(variant: &str, expected: &[&str]) -> Self {
-        if expected.is_empty() {
-            Self::custom(format_args!(
+        let error = if expected.is_empty() {
+            ParseError::new(format_args!(
                 "unexpected enumeration variant \"{variant}\", none where expected"
             ))
         } else {
-            let expected = expected
-                .into_iter()
-                .map(|name| format!("\"{name}\""))
-                .collect::<Vec<_>>()
-                .join(", ");
+            let expected = quoted_list(expected);
 
-            Self::custom(format_args!(
+            ParseError::new(format_args!(
                 "unexpected enumeration variant \"{variant}\", expected one of {expected}"
             ))
-        }
+            .with_expected(format_args!("one of {expected}"))
+        };
+
+        Self::from_parse_error(error.with_found(format_args!("\"{variant}\"")))
+    }
+
+    fn invalid_code(code: mlua::Integer, expected: &[mlua::Integer]) -> Self {
+        let expected = expected
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let error = if expected.is_empty() {
+            ParseError::new(format_args!(
+                "unexpected enumeration code {code}, none where expected"
+            ))
+        } else {
+            ParseError::new(format_args!(
+                "unexpected enumeration code {code}, expected one of {expected}"
+            ))
+            .with_expected(format_args!("one of {expected}"))
+        };
+
+        Self::from_parse_error(error.with_found(code))
+    }
+
+    fn unknown_field(field: &str, expected: &[&str]) -> Self {
+        let error = if expected.is_empty() {
+            ParseError::new(format_args!(
+                "unknown field \"{field}\", none were expected"
+            ))
+        } else {
+            let expected = quoted_list(expected);
+
+            ParseError::new(format_args!(
+                "unknown field \"{field}\", expected one of {expected}"
+            ))
+            .with_expected(format_args!("one of {expected}"))
+        };
+
+        Self::from_parse_error(error.with_found(format_args!("\"{field}\"")))
+    }
+
+    fn too_many_nodes(limit: usize) -> Self {
+        Self::from_parse_error(
+            ParseError::new(format_args!("too many nodes, the limit is {limit}"))
+                .with_expected(format_args!("at most {limit} nodes")),
+        )
+    }
+
+    fn too_many_elements(limit: usize) -> Self {
+        Self::from_parse_error(
+            ParseError::new(format_args!("too many elements, the limit is {limit}"))
+                .with_expected(format_args!("at most {limit} elements")),
+        )
+    }
+
+    fn too_deep(limit: usize) -> Self {
+        Self::from_parse_error(
+            ParseError::new(format_args!(
+                "tables are nested too deeply, the limit is {limit} levels"
+            ))
+            .with_expected(format_args!("at most {limit} levels")),
+        )
+    }
+
+    fn string_too_long(length: usize, limit: usize) -> Self {
+        Self::from_parse_error(
+            ParseError::new(format_args!(
+                "string is too long, it's {length} bytes and the limit is {limit}"
+            ))
+            .with_expected(format_args!("at most {limit} bytes"))
+            .with_found(format_args!("{length} bytes")),
+        )
+    }
+
+    fn unresolved_references(names: &[&str]) -> Self {
+        let names = quoted_list(names);
+
+        Self::custom(format_args!("unresolved references: {names}"))
+    }
+
+    fn includes_disabled() -> Self {
+        Self::custom("includes are disabled, set an include directory to enable them")
+    }
+
+    fn invalid_include_path(path: &Path) -> Self {
+        let path = path.display();
+        Self::custom(format_args!(
+            "invalid include path \"{path}\", it has to be relative and can't contain \"..\""
+        ))
+    }
+
+    fn conflicting_node_types(table_id: TableId, first: &str, second: &str) -> Self {
+        Self::from_parse_error(
+            ParseError::new(format_args!(
+                "the table {table_id} is parsed as both {first} and {second}"
+            ))
+            .with_expected(first)
+            .with_found(second),
+        )
+    }
+
+    fn unknown_key(key: &mlua::Value) -> Self {
+        let name = key.type_name();
+        Self::from_parse_error(
+            ParseError::new(format_args!("unexpected {name} key, expected field names"))
+                .with_expected("field names")
+                .with_found(format_args!("{name} key")),
+        )
     }
 
     fn add_context_field_name(&mut self, name: &str) {
-        *self = Self::custom(format_args!("in {name}, {self}"))
+        let error = self.to_parse_error();
+        *self = Self::from_parse_error(error.in_segment(PathSegment::Field(name.to_owned())));
     }
 
     fn add_context_index(&mut self, index: usize) {
-        *self = Self::custom(format_args!("in [{index}], {self}"))
+        let error = self.to_parse_error();
+        *self = Self::from_parse_error(error.in_segment(PathSegment::Index(index)));
+    }
+
+    /// Add the node that was parsed from the table `table_id` to the path.
+    fn add_context_node(&mut self, table_id: TableId, node_type: &'static str) {
+        let error = self.to_parse_error();
+        *self = Self::from_parse_error(error.in_segment(PathSegment::Node {
+            table_id,
+            node_type,
+        }));
     }
 }
 
+fn quoted_list(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| format!("\"{name}\""))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl Error for Box<dyn std::error::Error> {
     fn custom<T>(message: T) -> Self
     where
@@ -111,6 +1019,14 @@ impl Error for Box<dyn std::error::Error> {
     {
         message.to_string().into()
     }
+
+    fn from_parse_error(error: ParseError) -> Self {
+        Box::new(error)
+    }
+
+    fn as_parse_error(&self) -> Option<&ParseError> {
+        self.downcast_ref()
+    }
 }
 
 impl Error for mlua::Error {
@@ -118,6 +1034,18 @@ impl Error for mlua::Error {
     where
         T: Display,
     {
-        mlua::Error::RuntimeError(message.to_string())
+        Self::from_parse_error(ParseError::new(message))
+    }
+
+    fn from_parse_error(error: ParseError) -> Self {
+        mlua::Error::external(error)
+    }
+
+    fn as_parse_error(&self) -> Option<&ParseError> {
+        ParseError::find(self)
+    }
+
+    fn to_parse_error(&self) -> ParseError {
+        ParseError::from_lua_error(self)
     }
 }
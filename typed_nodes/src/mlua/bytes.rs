@@ -0,0 +1,177 @@
+use std::{ops::Deref, rc::Rc, sync::Arc};
+
+use mlua::Value;
+
+use crate::bounds::Bounds;
+
+use super::{Context, VisitString};
+
+/// Types that can be parsed from the raw bytes of a Lua string. It's used for
+/// fields with the `#[typed_nodes(bytes)]` attribute, where the bytes are
+/// copied directly from the Lua string, without validating them as UTF-8 or
+/// going through an intermediate `String`. [`LuaStr`] and [`mlua::String`]
+/// borrow the string from Lua instead of copying it.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua}, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Image {
+///     #[typed_nodes(bytes)]
+///     data: Vec<u8>,
+///     #[typed_nodes(bytes)]
+///     thumbnail: Option<Box<[u8]>>,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let value = lua.load(r#"{data = "\x89PNG\xff"}"#).eval().unwrap();
+/// let image = Image::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+///
+/// assert_eq!(image.data, b"\x89PNG\xff");
+/// assert!(image.thumbnail.is_none());
+/// ```
+pub trait FromLuaBytes<'lua, B>: Sized
+where
+    B: Bounds,
+{
+    /// Try to convert from a Lua string.
+    fn from_lua_bytes(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self>;
+}
+
+macro_rules! impl_from_lua_bytes {
+    ($($self_ty:ty),+) => {$(
+        impl<'lua, B> FromLuaBytes<'lua, B> for $self_ty
+        where
+            B: Bounds,
+        {
+            fn from_lua_bytes(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+                VisitString::visit(value, context, |value, context| {
+                    context.check_string_length(&value)?;
+                    Ok(value.as_bytes().into())
+                })
+            }
+        }
+    )+};
+}
+
+impl_from_lua_bytes!(Vec<u8>, Box<[u8]>, Rc<[u8]>, Arc<[u8]>);
+
+impl<'lua, T, B> FromLuaBytes<'lua, B> for Option<T>
+where
+    T: FromLuaBytes<'lua, B>,
+    B: Bounds,
+{
+    fn from_lua_bytes(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_lua_bytes(value, context).map(Some),
+        }
+    }
+}
+
+impl<'lua, B> FromLuaBytes<'lua, B> for mlua::String<'lua>
+where
+    B: Bounds,
+{
+    fn from_lua_bytes(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        VisitString::visit(value, context, |value, context| {
+            context.check_string_length(&value)?;
+            Ok(value)
+        })
+    }
+}
+
+/// A string that is borrowed from Lua, for reading its bytes without copying
+/// them first. It can only be used while the Lua state is borrowed, so it's
+/// meant for `parse_with` functions and other code that turns the bytes into
+/// something else, or copies them once with [`LuaStr::to_owned_bytes`].
+///
+/// ```
+/// use typed_nodes::{
+///     bounds::AnyBounds,
+///     mlua::{Context, FromLua, FromLuaBytes, LuaStr},
+///     Nodes,
+/// };
+///
+/// #[derive(FromLua)]
+/// struct Blob {
+///     #[typed_nodes(parse_with = parse_checksum)]
+///     data: u32,
+/// }
+///
+/// fn parse_checksum<'lua>(
+///     value: mlua::Value<'lua>,
+///     context: &mut Context<'lua, AnyBounds>,
+/// ) -> mlua::Result<u32> {
+///     let data = LuaStr::from_lua_bytes(value, context)?;
+///     Ok(data.iter().map(|&byte| u32::from(byte)).sum())
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let value = lua.load(r#"{data = "\x01\x02\xff"}"#).eval().unwrap();
+/// let blob = Blob::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+///
+/// assert_eq!(blob.data, 258);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LuaStr<'lua> {
+    string: mlua::String<'lua>,
+}
+
+impl<'lua> LuaStr<'lua> {
+    /// Borrow the bytes of the string, without copying them.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.string.as_bytes()
+    }
+
+    /// Borrow the string as a `&str`, or return an error if it's not valid
+    /// UTF-8.
+    #[inline]
+    pub fn to_str(&self) -> mlua::Result<&str> {
+        self.string.to_str()
+    }
+
+    /// Copy the bytes into an owned buffer, such as a `Vec<u8>` or an
+    /// `Arc<[u8]>`.
+    #[inline]
+    pub fn to_owned_bytes<T>(&self) -> T
+    where
+        T: for<'a> From<&'a [u8]>,
+    {
+        self.as_bytes().into()
+    }
+
+    /// Get the Lua string.
+    #[inline]
+    pub fn into_inner(self) -> mlua::String<'lua> {
+        self.string
+    }
+}
+
+impl Deref for LuaStr<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for LuaStr<'_> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<'lua, B> FromLuaBytes<'lua, B> for LuaStr<'lua>
+where
+    B: Bounds,
+{
+    fn from_lua_bytes(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        mlua::String::from_lua_bytes(value, context).map(|string| Self { string })
+    }
+}
@@ -0,0 +1,541 @@
+use std::{
+    any::{Any, TypeId},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    Nodes,
+};
+
+use super::{
+    insert_placeholder, ConstructorHints, Context, InsertPlaceholder, Placeholder, StringExpander,
+    TableId,
+};
+
+/// The default value for [`ContextBuilder::max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Options for a [`Context`]. They are set with a [`ContextBuilder`] and
+/// can't be changed after the context is created.
+#[derive(Clone, Debug, Default)]
+pub struct ContextConfig {
+    pub(crate) source: Option<Arc<str>>,
+    pub(crate) include_dir: Option<PathBuf>,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) max_elements: Option<usize>,
+    pub(crate) max_string_length: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) multi_typed_tables: bool,
+    pub(crate) check_table_usage: bool,
+    pub(crate) patch_defined_nodes: bool,
+    pub(crate) string_expander: Option<StringExpander>,
+    pub(crate) constructor_hints: Option<Arc<ConstructorHints>>,
+}
+
+impl ContextConfig {
+    /// The name of the script or file that is being parsed, if it's set.
+    #[inline]
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// The directory that [`Context::eval_include`] reads files from, if
+    /// includes are enabled.
+    #[inline]
+    pub fn include_dir(&self) -> Option<&Path> {
+        self.include_dir.as_deref()
+    }
+
+    /// The maximum number of nodes that can be parsed, if it's limited.
+    #[inline]
+    pub fn max_nodes(&self) -> Option<usize> {
+        self.max_nodes
+    }
+
+    /// The maximum total number of elements in sequences and maps that can
+    /// be parsed, if it's limited.
+    #[inline]
+    pub fn max_elements(&self) -> Option<usize> {
+        self.max_elements
+    }
+
+    /// The maximum length of a string, in bytes, if it's limited.
+    #[inline]
+    pub fn max_string_length(&self) -> Option<usize> {
+        self.max_string_length
+    }
+
+    /// The maximum number of tables that can be nested inside each other
+    /// while parsing. It's [`DEFAULT_MAX_DEPTH`] if it's not set.
+    #[inline]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// Whether the same table can be parsed as nodes of different types.
+    #[inline]
+    pub fn multi_typed_tables(&self) -> bool {
+        self.multi_typed_tables
+    }
+
+    /// Whether tables that are parsed both as nodes and as inline values are
+    /// reported as warnings.
+    #[inline]
+    pub fn check_table_usage(&self) -> bool {
+        self.check_table_usage
+    }
+
+    /// Whether tables with the same defined name as an existing node patch
+    /// that node, instead of becoming new nodes.
+    #[inline]
+    pub fn patch_defined_nodes(&self) -> bool {
+        self.patch_defined_nodes
+    }
+
+    /// The function that expands strings in `#[typed_nodes(expand)]` fields,
+    /// if it's set.
+    #[inline]
+    pub fn string_expander(&self) -> Option<StringExpander> {
+        self.string_expander
+    }
+}
+
+/// A builder for a [`Context`] with non-default options, created with
+/// [`Context::builder`].
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Enemy {
+///     health: u32,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::builder(&lua, &mut nodes)
+///     .source("enemies.lua")
+///     .build();
+///
+/// assert_eq!(context.config().source(), Some("enemies.lua"));
+///
+/// let value = lua.load("{health = 10}").eval().unwrap();
+/// Key::<Enemy>::from_lua(value, &mut context).unwrap();
+/// ```
+pub struct ContextBuilder<'lua, B: Bounds> {
+    lua: &'lua mlua::Lua,
+    nodes: &'lua mut Nodes<TableId, B>,
+    config: ContextConfig,
+    fallbacks: ahash::HashMap<TypeId, Box<dyn Any>>,
+    insert_placeholder: Option<InsertPlaceholder<B>>,
+}
+
+impl<'lua, B: Bounds> ContextBuilder<'lua, B> {
+    #[inline]
+    pub(crate) fn new(lua: &'lua mlua::Lua, nodes: &'lua mut Nodes<TableId, B>) -> Self {
+        Self {
+            lua,
+            nodes,
+            config: ContextConfig::default(),
+            fallbacks: Default::default(),
+            insert_placeholder: None,
+        }
+    }
+
+    /// Set the name of the script or file that is being parsed. It's
+    /// recorded in the [`Provenance`][crate::Provenance] of each node that is
+    /// parsed from a table.
+    #[inline]
+    pub fn source(mut self, source: impl Into<Arc<str>>) -> Self {
+        self.config.source = Some(source.into());
+        self
+    }
+
+    /// Enable [`Context::eval_include`] and let it read files from `dir`.
+    /// Includes are disabled by default.
+    #[inline]
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.include_dir = Some(dir.into());
+        self
+    }
+
+    /// Limit how many nodes can be parsed with the context. Parsing fails
+    /// with an error when the limit is exceeded.
+    ///
+    /// The limits are meant for parsing untrusted input, together with a
+    /// limit on the Lua code itself, such as a memory limit. They are counted
+    /// for all values that are parsed with the same context.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Enemy {
+    ///     name: String,
+    ///     weapons: Vec<Key<Weapon>>,
+    /// }
+    ///
+    /// #[derive(FromLua)]
+    /// struct Weapon {
+    ///     damage: u32,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::builder(&lua, &mut nodes)
+    ///     .max_nodes(2)
+    ///     .max_string_length(10)
+    ///     .build();
+    ///
+    /// let value: mlua::Table = lua
+    ///     .load(r#"{name = "orc", weapons = {{damage = 1}, {damage = 2}, {damage = 3}}}"#)
+    ///     .eval()
+    ///     .unwrap();
+    /// let error = context.parse::<Enemy>(value).err().unwrap();
+    /// assert!(error.to_string().contains("in weapons, in [3], too many nodes"));
+    ///
+    /// let value: mlua::Table = lua
+    ///     .load(r#"{name = "a very long name", weapons = {}}"#)
+    ///     .eval()
+    ///     .unwrap();
+    /// let error = context.parse::<Enemy>(value).err().unwrap();
+    /// assert!(error.to_string().contains("in name, string is too long"));
+    /// ```
+    #[inline]
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.config.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Limit the total number of elements in sequences and maps that can be
+    /// parsed with the context. See [`max_nodes`][Self::max_nodes] for
+    /// details.
+    #[inline]
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.config.max_elements = Some(max_elements);
+        self
+    }
+
+    /// Limit the length of each string, in bytes. See
+    /// [`max_nodes`][Self::max_nodes] for details.
+    #[inline]
+    pub fn max_string_length(mut self, max_string_length: usize) -> Self {
+        self.config.max_string_length = Some(max_string_length);
+        self
+    }
+
+    /// Limit how deeply tables can be nested inside each other, counting
+    /// both inline values and nodes. The parsers recurse for each level, so
+    /// this protects against running out of stack space. A table that
+    /// contains itself as an inline value, such as through a `Box` or `Vec`
+    /// field, is always cut off by this limit.
+    ///
+    /// The limit is [`DEFAULT_MAX_DEPTH`] by default, unlike the other
+    /// limits, since recursive types would otherwise crash on cyclic tables.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct List {
+    ///     name: String,
+    ///     next: Option<Box<List>>,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::builder(&lua, &mut nodes).max_depth(3).build();
+    ///
+    /// let value: mlua::Table = lua
+    ///     .load("{name = 'a', next = {name = 'b', next = {name = 'c'}}}")
+    ///     .eval()
+    ///     .unwrap();
+    /// assert!(context.parse::<List>(value).is_ok());
+    ///
+    /// let value: mlua::Table = lua
+    ///     .load("local t = {name = 'a'}; t.next = t; return t")
+    ///     .eval()
+    ///     .unwrap();
+    /// let error = context.parse::<List>(value).err().unwrap();
+    /// assert!(error.to_string().contains("nested too deeply"));
+    /// ```
+    #[inline]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Allow the same table to be parsed as nodes of different types, such as
+    /// when it's referenced as both `Key<A>` and `Key<B>`. Each type gets its
+    /// own node. Parsing fails with an error that names both types by
+    /// default, since it's usually a mistake.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Sprite {
+    ///     image: String,
+    /// }
+    ///
+    /// #[derive(FromLua)]
+    /// struct Icon {
+    ///     image: String,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let table: mlua::Table = lua.load(r#"{image = "coin.png"}"#).eval().unwrap();
+    ///
+    /// let mut context = Context::new(&lua, &mut nodes);
+    /// context.parse::<Key<Sprite>>(table.clone()).unwrap();
+    /// let error = context.parse::<Key<Icon>>(table.clone()).err().unwrap();
+    /// assert!(error.to_string().contains("is parsed as both"));
+    ///
+    /// let mut context = Context::builder(&lua, &mut nodes)
+    ///     .multi_typed_tables()
+    ///     .build();
+    /// context.parse::<Key<Sprite>>(table.clone()).unwrap();
+    /// context.parse::<Key<Icon>>(table).unwrap();
+    /// ```
+    #[inline]
+    pub fn multi_typed_tables(mut self) -> Self {
+        self.config.multi_typed_tables = true;
+        self
+    }
+
+    /// Warn about tables that are parsed both as nodes, such as `Key<T>`, and
+    /// as inline values, such as `T`. Each use produces a separate copy of
+    /// the table's content, so changes to the node aren't seen in the inline
+    /// value. The warnings are available from [`Context::warnings`].
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// struct Color {
+    ///     red: f32,
+    ///     green: f32,
+    ///     blue: f32,
+    /// }
+    ///
+    /// #[derive(FromLua)]
+    /// struct Theme {
+    ///     background: Key<Color>,
+    ///     text: Color,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let table: mlua::Table = lua
+    ///     .load(
+    ///         r#"
+    ///         local white = {red = 1, green = 1, blue = 1}
+    ///         return {background = white, text = white}
+    ///         "#,
+    ///     )
+    ///     .eval()
+    ///     .unwrap();
+    ///
+    /// let mut context = Context::builder(&lua, &mut nodes)
+    ///     .check_table_usage()
+    ///     .build();
+    /// context.parse::<Key<Theme>>(table).unwrap();
+    ///
+    /// assert_eq!(context.warnings().len(), 1);
+    /// ```
+    #[inline]
+    pub fn check_table_usage(mut self) -> Self {
+        self.config.check_table_usage = true;
+        self
+    }
+
+    /// Patch existing nodes with tables that have the same defined name, as
+    /// given by a generated `define(name)` method, instead of inserting new
+    /// nodes. This makes it possible to parse a base script and then an
+    /// override script into the same nodes, where the override only sets the
+    /// fields it changes. The fields are updated with
+    /// [`FromLua::patch_from_lua`](super::FromLua::patch_from_lua).
+    ///
+    /// Fields that are `nil` in the override are kept as they are, so it's
+    /// not possible to unset an optional field this way.
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     mlua::{Context, FromLua, GenerateLua, LuaModule},
+    ///     Key, Nodes,
+    /// };
+    ///
+    /// #[derive(FromLua, GenerateLua)]
+    /// #[typed_nodes(lua_define)]
+    /// struct Weapon {
+    ///     name: String,
+    ///     damage: u32,
+    ///     stats: Stats,
+    /// }
+    ///
+    /// #[derive(FromLua, GenerateLua)]
+    /// struct Stats {
+    ///     weight: f32,
+    ///     range: f32,
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Weapon::generate_lua(&mut module);
+    /// Stats::generate_lua(&mut module);
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let my_lib = module.load_into_table(&lua).unwrap();
+    /// lua.globals().set("my_lib", my_lib).unwrap();
+    ///
+    /// let mut nodes = Nodes::new();
+    ///
+    /// let base: mlua::Table = lua
+    ///     .load(
+    ///         r#"my_lib.Weapon.new("Sword", 10, my_lib.Stats.new(2.0, 1.5)):define("sword")"#,
+    ///     )
+    ///     .eval()
+    ///     .unwrap();
+    /// let sword: Key<Weapon> = Context::new(&lua, &mut nodes).parse(base).unwrap();
+    ///
+    /// let overrides: mlua::Table = lua
+    ///     .load(r#"{_node_name = "sword", damage = 12, stats = {range = 2.0}}"#)
+    ///     .eval()
+    ///     .unwrap();
+    /// let mut context = Context::builder(&lua, &mut nodes)
+    ///     .patch_defined_nodes()
+    ///     .build();
+    /// let patched: Key<Weapon> = context.parse(overrides).unwrap();
+    ///
+    /// assert!(patched == sword);
+    /// assert_eq!(nodes.filled_len::<Weapon>(), 1);
+    ///
+    /// let sword = nodes.get(sword).unwrap();
+    /// assert_eq!(sword.name, "Sword");
+    /// assert_eq!(sword.damage, 12);
+    /// assert_eq!(sword.stats.weight, 2.0);
+    /// assert_eq!(sword.stats.range, 2.0);
+    /// ```
+    #[inline]
+    pub fn patch_defined_nodes(mut self) -> Self {
+        self.config.patch_defined_nodes = true;
+        self
+    }
+
+    /// Expand the strings in fields with the `#[typed_nodes(expand)]`
+    /// attribute with `expander`, such as for replacing `${ASSET_ROOT}` in
+    /// paths. [`expand_env_vars`](super::expand_env_vars) expands environment
+    /// variables. The strings are parsed as they are by default. See
+    /// [`FromLuaExpanded`](super::FromLuaExpanded) for an example.
+    #[inline]
+    pub fn expand_strings(mut self, expander: StringExpander) -> Self {
+        self.config.string_expander = Some(expander);
+        self
+    }
+
+    /// Suggest generated Lua constructors when a table has an unknown enum
+    /// tag, such as `my_lib.Shape.circle(radius)` for `{type = "circel"}`.
+    /// The hints are created with [`LuaModule::constructor_hints`].
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     mlua::{Context, FromLua, GenerateLua, LuaModule},
+    ///     Nodes,
+    /// };
+    ///
+    /// #[derive(FromLua, GenerateLua)]
+    /// enum Shape {
+    ///     Circle { radius: f32 },
+    ///     Square { side: f32 },
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Shape::generate_lua(&mut module);
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::builder(&lua, &mut nodes)
+    ///     .constructor_hints(module.constructor_hints("my_lib"))
+    ///     .build();
+    ///
+    /// let value: mlua::Table = lua.load(r#"{type = "circel", radius = 1}"#).eval().unwrap();
+    /// let error = context.parse::<Shape>(value).err().unwrap();
+    ///
+    /// assert!(error
+    ///     .to_string()
+    ///     .ends_with("did you mean my_lib.Shape.circle(radius)?"));
+    /// ```
+    ///
+    /// [`LuaModule::constructor_hints`]: super::LuaModule::constructor_hints
+    #[inline]
+    pub fn constructor_hints(mut self, hints: ConstructorHints) -> Self {
+        self.config.constructor_hints = Some(Arc::new(hints));
+        self
+    }
+
+    /// Insert a [`Placeholder`] with the error when a node can't be parsed
+    /// from a table, instead of returning the error. The slot of the node
+    /// stays reserved, so its key doesn't point to a node.
+    #[inline]
+    pub fn placeholders(mut self) -> Self
+    where
+        Placeholder: BoundedBy<TableId, B>,
+    {
+        self.insert_placeholder = Some(insert_placeholder::<B>);
+        self
+    }
+
+    /// Insert the result of `fallback` when a node of type `T` can't be parsed
+    /// from a table, instead of returning the error. This takes precedence
+    /// over [`placeholders`][Self::placeholders], and makes the key point to
+    /// a node of the right type.
+    ///
+    /// ```
+    /// use typed_nodes::{mlua::{Context, FromLua, Placeholder}, Key, Nodes};
+    ///
+    /// #[derive(FromLua)]
+    /// enum Weapon {
+    ///     Sword { damage: u32 },
+    ///     #[typed_nodes(skip)]
+    ///     Broken(String),
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::builder(&lua, &mut nodes)
+    ///     .fallback(|placeholder: Placeholder| Weapon::Broken(placeholder.error().to_string()))
+    ///     .build();
+    ///
+    /// let value: mlua::Table = lua.load(r#"{type = "Sword", damage = "a lot"}"#).eval().unwrap();
+    /// let weapon: Key<Weapon> = context.parse(value).unwrap();
+    ///
+    /// assert!(matches!(nodes.get(weapon), Some(Weapon::Broken(_))));
+    /// ```
+    #[inline]
+    pub fn fallback<T>(mut self, fallback: fn(Placeholder) -> T) -> Self
+    where
+        T: 'static,
+    {
+        self.fallbacks.insert(TypeId::of::<T>(), Box::new(fallback));
+        self
+    }
+
+    /// Replace all options with the ones in `config`.
+    #[inline]
+    pub fn config(mut self, config: ContextConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Create the context.
+    #[inline]
+    pub fn build(self) -> Context<'lua, B> {
+        let mut context = Context::with_config(self.lua, self.nodes, self.config);
+        context.fallbacks = self.fallbacks;
+        context.insert_placeholder = self.insert_placeholder;
+
+        context
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    marker::PhantomData,
+    sync::{PoisonError, RwLock},
+};
+
+use mlua::Value;
+
+use crate::bounds::{AnyBounds, Bounds};
+
+use super::{Context, Error, VisitTable};
+
+/// A function that parses a Lua value as a boxed trait object, such as
+/// `Box<dyn Shape>`. See [`DynRegistry`].
+pub type DynParser<T, B = AnyBounds> =
+    for<'lua> fn(Value<'lua>, &mut Context<'lua, B>) -> mlua::Result<Box<T>>;
+
+/// A set of parsers for boxed trait objects, by tag. It's like a tagged enum
+/// that can be extended after it's been defined, such as by plugins. Fields
+/// with the `#[typed_nodes(dyn_registry = REGISTRY)]` attribute are parsed
+/// with a registry, via [`FromDynRegistry`].
+///
+/// The tag is read from the `type` field of the table, by default. The
+/// registered types are parsed from the whole table, so they should accept
+/// the tag as an unknown field.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, DynRegistry, FromLua}, Nodes};
+///
+/// trait Shape {
+///     fn area(&self) -> f32;
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Circle {
+///     radius: f32,
+/// }
+///
+/// impl Shape for Circle {
+///     fn area(&self) -> f32 {
+///         3.0 * self.radius * self.radius
+///     }
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Square {
+///     side: f32,
+/// }
+///
+/// impl Shape for Square {
+///     fn area(&self) -> f32 {
+///         self.side * self.side
+///     }
+/// }
+///
+/// static SHAPES: DynRegistry<dyn Shape> = DynRegistry::new();
+///
+/// #[derive(FromLua)]
+/// struct Drawing {
+///     #[typed_nodes(dyn_registry = SHAPES)]
+///     shapes: Vec<Box<dyn Shape>>,
+/// }
+///
+/// SHAPES.register("circle", |value, context| Ok(Box::new(Circle::from_lua(value, context)?)));
+/// SHAPES.register("square", |value, context| Ok(Box::new(Square::from_lua(value, context)?)));
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let value = lua
+///     .load("{shapes = {{type = 'circle', radius = 1}, {type = 'square', side = 2}}}")
+///     .eval()
+///     .unwrap();
+/// let drawing = Drawing::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+///
+/// let areas: Vec<_> = drawing.shapes.iter().map(|shape| shape.area()).collect();
+/// assert_eq!(areas, [3.0, 4.0]);
+/// ```
+pub struct DynRegistry<T: ?Sized, B: Bounds = AnyBounds> {
+    tag_name: &'static str,
+    parsers: RwLock<BTreeMap<Cow<'static, str>, DynParser<T, B>>>,
+    phantom: PhantomData<fn() -> Box<T>>,
+}
+
+impl<T: ?Sized, B: Bounds> DynRegistry<T, B> {
+    /// Create an empty registry that reads the tag from the `type` field.
+    pub const fn new() -> Self {
+        Self::with_tag_name("type")
+    }
+
+    /// Create an empty registry that reads the tag from the `tag_name` field.
+    pub const fn with_tag_name(tag_name: &'static str) -> Self {
+        Self {
+            tag_name,
+            parsers: RwLock::new(BTreeMap::new()),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Add a parser for values with the tag `tag`. Any previous parser for
+    /// the same tag is replaced and returned.
+    pub fn register(
+        &self,
+        tag: impl Into<Cow<'static, str>>,
+        parser: DynParser<T, B>,
+    ) -> Option<DynParser<T, B>> {
+        self.parsers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(tag.into(), parser)
+    }
+
+    /// Check if there's a parser for values with the tag `tag`.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.parsers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains_key(tag)
+    }
+
+    /// Parse `value` with the parser for its tag.
+    pub fn parse<'lua>(
+        &self,
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<Box<T>> {
+        let table = VisitTable::visit(value, context, |table, _| Ok(table))?;
+        let tag = table.get::<_, Option<mlua::String>>(self.tag_name)?;
+        let tag = tag.as_ref().map(mlua::String::to_string_lossy);
+
+        // The lock is released before parsing, so the parser can register
+        // more types.
+        let parser = {
+            let parsers = self.parsers.read().unwrap_or_else(PoisonError::into_inner);
+
+            match tag.as_deref().and_then(|tag| parsers.get(tag)) {
+                Some(&parser) => parser,
+                None => {
+                    let expected: Vec<_> = parsers.keys().map(|tag| &**tag).collect();
+                    return Err(
+                        context.invalid_variant(tag.as_deref().unwrap_or("<nil>"), &expected)
+                    );
+                }
+            }
+        };
+
+        parser(Value::Table(table), context)
+    }
+}
+
+impl<T: ?Sized, B: Bounds> Default for DynRegistry<T, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Types that can be parsed with a [`DynRegistry`]. It's used for fields with
+/// the `#[typed_nodes(dyn_registry = REGISTRY)]` attribute, and is implemented
+/// for `Box<T>`, as well as `Option` and `Vec` of such types.
+pub trait FromDynRegistry<'lua, T: ?Sized, B>: Sized
+where
+    B: Bounds,
+{
+    /// Convert from a Lua value, using `registry` for the trait objects.
+    fn from_dyn_registry(
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+        registry: &DynRegistry<T, B>,
+    ) -> mlua::Result<Self>;
+}
+
+impl<'lua, T, B> FromDynRegistry<'lua, T, B> for Box<T>
+where
+    T: ?Sized,
+    B: Bounds,
+{
+    fn from_dyn_registry(
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+        registry: &DynRegistry<T, B>,
+    ) -> mlua::Result<Self> {
+        registry.parse(value, context)
+    }
+}
+
+impl<'lua, T, U, B> FromDynRegistry<'lua, T, B> for Option<U>
+where
+    T: ?Sized,
+    U: FromDynRegistry<'lua, T, B>,
+    B: Bounds,
+{
+    fn from_dyn_registry(
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+        registry: &DynRegistry<T, B>,
+    ) -> mlua::Result<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            value => U::from_dyn_registry(value, context, registry).map(Some),
+        }
+    }
+}
+
+impl<'lua, T, U, B> FromDynRegistry<'lua, T, B> for Vec<U>
+where
+    T: ?Sized,
+    U: FromDynRegistry<'lua, T, B>,
+    B: Bounds,
+{
+    fn from_dyn_registry(
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+        registry: &DynRegistry<T, B>,
+    ) -> mlua::Result<Self> {
+        VisitTable::visit(value, context, |value, context| {
+            value
+                .sequence_values()
+                .enumerate()
+                .map(|(index, value)| {
+                    context.count_element()?;
+                    U::from_dyn_registry(value?, context, registry).map_err(|mut error| {
+                        error.add_context_index(index + 1);
+                        error
+                    })
+                })
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,148 @@
+use mlua::Value;
+
+use crate::bounds::{BoundedBy, Bounds};
+
+use super::{Context, Error, FromLua, TableId, VisitTable};
+
+/// A function that expands templates in strings, such as environment
+/// variables. It's set with
+/// [`ContextBuilder::expand_strings`](super::ContextBuilder::expand_strings).
+pub type StringExpander = fn(&str) -> mlua::Result<String>;
+
+/// Types with strings that can be expanded with the context's
+/// [`StringExpander`]. It's used for fields with the
+/// `#[typed_nodes(expand)]` attribute, and is implemented for `String`, as
+/// well as `Option` and `Vec` of such types. The strings are parsed as they
+/// are if the context doesn't have an expander.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua}, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Material {
+///     #[typed_nodes(expand)]
+///     texture: String,
+///     #[typed_nodes(expand)]
+///     normal_map: Option<String>,
+///     name: String,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::builder(&lua, &mut nodes)
+///     .expand_strings(|text| Ok(text.replace("${ASSET_ROOT}", "assets")))
+///     .build();
+///
+/// let value: mlua::Table = lua
+///     .load(r#"{texture = "${ASSET_ROOT}/stone.png", name = "${ASSET_ROOT}"}"#)
+///     .eval()
+///     .unwrap();
+/// let material: Material = context.parse(value).unwrap();
+///
+/// assert_eq!(material.texture, "assets/stone.png");
+/// assert_eq!(material.normal_map, None);
+/// assert_eq!(material.name, "${ASSET_ROOT}");
+/// ```
+pub trait FromLuaExpanded<'lua, B>: Sized
+where
+    B: Bounds,
+{
+    /// Convert from a Lua value and expand its strings.
+    fn from_lua_expanded(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self>;
+}
+
+impl<'lua, B> FromLuaExpanded<'lua, B> for String
+where
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua_expanded(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        let text = String::from_lua(value, context)?;
+        context.expand_string(text)
+    }
+}
+
+impl<'lua, T, B> FromLuaExpanded<'lua, B> for Option<T>
+where
+    T: FromLuaExpanded<'lua, B>,
+    B: Bounds,
+{
+    fn from_lua_expanded(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_lua_expanded(value, context).map(Some),
+        }
+    }
+}
+
+impl<'lua, T, B> FromLuaExpanded<'lua, B> for Vec<T>
+where
+    T: FromLuaExpanded<'lua, B>,
+    B: Bounds,
+{
+    fn from_lua_expanded(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        VisitTable::visit(value, context, |value, context| {
+            value
+                .sequence_values()
+                .enumerate()
+                .map(|(index, value)| {
+                    context.count_element()?;
+                    T::from_lua_expanded(value?, context).map_err(|mut error| {
+                        error.add_context_index(index + 1);
+                        error
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+/// A [`StringExpander`] that replaces `${NAME}` with the value of the
+/// environment variable `NAME`. It's an error if the variable isn't set, or
+/// if a `${` isn't closed. `$$` is replaced with a single `$`.
+///
+/// ```
+/// use typed_nodes::mlua::expand_env_vars;
+///
+/// std::env::set_var("ASSET_ROOT", "/opt/game/assets");
+///
+/// assert_eq!(
+///     expand_env_vars("${ASSET_ROOT}/stone.png").unwrap(),
+///     "/opt/game/assets/stone.png"
+/// );
+/// assert_eq!(expand_env_vars("$$5").unwrap(), "$5");
+/// assert!(expand_env_vars("${ASSET_ROOT").is_err());
+/// ```
+pub fn expand_env_vars(text: &str) -> mlua::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let Some(end) = after.find('}') else {
+                return Err(mlua::Error::runtime(format!(
+                    "missing `}}` after `${{` in {text:?}"
+                )));
+            };
+
+            let name = &after[..end];
+            let value = std::env::var(name).map_err(|error| {
+                mlua::Error::runtime(format!("could not expand `${{{name}}}`: {error}"))
+            })?;
+
+            result.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
@@ -0,0 +1,106 @@
+use std::ops::BitOr;
+
+use mlua::Value;
+
+use crate::bounds::Bounds;
+
+use super::{Context, Error, VisitTable};
+
+/// Parse a combination of named flags, from a string like `"read|write"` or
+/// a sequence like `{"read", "write"}`. The values of the flags are combined
+/// with `|`. This is what structs with the `#[typed_nodes(flags(...))]`
+/// attribute are parsed with.
+///
+/// The attribute lists the names and values of the flags, and the struct
+/// must have a single unnamed field with the type of the values. The
+/// `GenerateLua` derive adds the names as constants to the struct's table,
+/// such as `Permissions.READ` for `read`.
+///
+/// ```
+/// use typed_nodes::{
+///     mlua::{Context, FromLua, GenerateLua, LuaModule},
+///     Nodes,
+/// };
+///
+/// #[derive(FromLua, GenerateLua, PartialEq, Debug)]
+/// #[typed_nodes(flags(read = 0b001, write = 0b010, execute = 0b100))]
+/// struct Permissions(u8);
+///
+/// let mut module = LuaModule::new();
+/// Permissions::generate_lua(&mut module);
+///
+/// let lua = mlua::Lua::new();
+/// let my_lib = module.load_into_table(&lua).unwrap();
+/// lua.globals().set("my_lib", my_lib).unwrap();
+///
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::new(&lua, &mut nodes);
+///
+/// let value: mlua::Value = lua.load(r#""read|write""#).eval().unwrap();
+/// assert_eq!(context.parse::<Permissions>(value).unwrap(), Permissions(0b011));
+///
+/// let value: mlua::Value = lua
+///     .load("{my_lib.Permissions.READ, my_lib.Permissions.EXECUTE}")
+///     .eval()
+///     .unwrap();
+/// assert_eq!(context.parse::<Permissions>(value).unwrap(), Permissions(0b101));
+///
+/// let value: mlua::Value = lua.load(r#""read|delete""#).eval().unwrap();
+/// assert!(context.parse::<Permissions>(value).is_err());
+/// ```
+pub fn from_lua_flags<'lua, T, B>(
+    value: Value<'lua>,
+    context: &mut Context<'lua, B>,
+    flags: &[(&str, T)],
+) -> mlua::Result<T>
+where
+    T: Copy + Default + BitOr<Output = T>,
+    B: Bounds,
+{
+    match value {
+        Value::String(string) => {
+            context.check_string_length(&string)?;
+
+            string
+                .to_str()?
+                .split('|')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .try_fold(T::default(), |combined, name| {
+                    Ok(combined | find_flag(name, flags)?)
+                })
+        }
+        value @ Value::Table(_) => VisitTable::visit(value, context, |table, context| {
+            table.sequence_values().enumerate().try_fold(
+                T::default(),
+                |combined, (index, value)| {
+                    context.count_element()?;
+
+                    let flag = match value? {
+                        Value::String(name) => find_flag(name.to_str()?, flags),
+                        value => Err(mlua::Error::invalid_type(&value, "a flag name")),
+                    };
+
+                    flag.map(|flag| combined | flag).map_err(|mut error| {
+                        error.add_context_index(index + 1);
+                        error
+                    })
+                },
+            )
+        }),
+        value => Err(mlua::Error::invalid_type(
+            &value,
+            "a string or a sequence of flag names",
+        )),
+    }
+}
+
+fn find_flag<T: Copy>(name: &str, flags: &[(&str, T)]) -> mlua::Result<T> {
+    match flags.iter().find(|&&(flag_name, _)| flag_name == name) {
+        Some(&(_, flag)) => Ok(flag),
+        None => {
+            let expected: Vec<_> = flags.iter().map(|&(flag_name, _)| flag_name).collect();
+            Err(mlua::Error::invalid_variant(name, &expected))
+        }
+    }
+}
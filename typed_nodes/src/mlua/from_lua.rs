@@ -2,18 +2,22 @@ use std::{
     borrow::Cow,
     collections::HashMap,
     hash::{BuildHasher, Hash},
+    marker::PhantomData,
 };
 
 use mlua::Value;
 
 use crate::{
     bounds::{BoundedBy, Bounds},
-    Key,
+    Key, ReservedKey, WeakKey,
 };
 
 pub use typed_nodes_macros::FromLua;
 
-use super::{Context, Error, TableId, VisitTable};
+use super::{
+    instrument::{self, NodeSpan},
+    Context, Error, TableId, VisitTable, NODE_NAME_KEY,
+};
 
 pub trait FromLua<'lua, B>: Sized + BoundedBy<TableId, B>
 where
@@ -21,6 +25,90 @@ where
 {
     /// Try to convert from any Lua value.
     fn from_lua(value: mlua::Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self>;
+
+    /// Update `self` with the content of a Lua value, such as when a defined
+    /// node is overridden with [`ContextBuilder::patch_defined_nodes`]. The
+    /// default is to replace `self` with the parsed value. Derived structs
+    /// with named fields only update the fields that are set in the table,
+    /// and patch them recursively.
+    ///
+    /// [`ContextBuilder::patch_defined_nodes`]: super::ContextBuilder::patch_defined_nodes
+    #[inline]
+    fn patch_from_lua(
+        &mut self,
+        value: mlua::Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<()> {
+        *self = Self::from_lua(value, context)?;
+        Ok(())
+    }
+
+    /// Insert a node that was parsed from a table into its reserved slot.
+    /// Types with the `#[typed_nodes(intern)]` attribute use
+    /// [`Context::intern`] to reuse equal nodes instead.
+    #[inline]
+    fn insert_parsed(
+        node: Self,
+        reserved_key: ReservedKey<Self>,
+        context: &mut Context<'lua, B>,
+    ) -> Key<Self> {
+        context.nodes.insert_reserved(reserved_key, node)
+    }
+}
+
+/// Types that are parsed as nodes when they appear at the root of a graph.
+/// It's implemented by the derive macro for types with the
+/// `#[typed_nodes(is_node)]` attribute.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua, FromLuaNode}, Key, Nodes};
+///
+/// #[derive(FromLua)]
+/// #[typed_nodes(is_node)]
+/// struct Person {
+///     name: String,
+///     friend: Option<Key<Person>>,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let value = lua.load(r#"
+///     local alice = {name = "Alice"}
+///     alice.friend = {name = "Bob", friend = alice}
+///     return alice
+/// "#).eval().unwrap();
+///
+/// let alice = Person::from_lua_node(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+/// let bob = nodes.get(alice).unwrap().friend.unwrap();
+///
+/// // The root is registered, so Bob's reference points back to the same node.
+/// assert!(nodes.get(bob).unwrap().friend == Some(alice));
+/// ```
+pub trait FromLuaNode<'lua, B>: FromLua<'lua, B>
+where
+    B: Bounds,
+{
+    /// Parse `value` and insert it into the context's nodes. A table that has
+    /// already been parsed as this node type results in the existing key.
+    fn from_lua_node(
+        value: mlua::Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<Key<Self>> {
+        match value {
+            Value::Table(table) => table_to_node(table, context),
+            value => {
+                context.count_node()?;
+                let _span = NodeSpan::enter::<Self>(None);
+
+                let node = Self::from_lua(value, context)?;
+                let key = context.nodes.insert(node);
+                instrument::node_inserted::<Self>();
+                context.record_provenance(key, None);
+
+                Ok(key)
+            }
+        }
+    }
 }
 
 impl<'lua, T, B> FromLua<'lua, B> for Key<T>
@@ -30,20 +118,117 @@ where
     Self: BoundedBy<TableId, B>,
 {
     fn from_lua(value: mlua::Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
-        VisitTable::visit(value, context, |value, context| {
-            let id = TableId::get_or_assign(&value)?;
+        VisitTable::visit(value, context, table_to_node)
+    }
+}
 
-            if let Some(key) = context.nodes.get_key(&id) {
-                return Ok(key);
-            }
+impl<'lua, T, B> FromLua<'lua, B> for WeakKey<T>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: mlua::Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        VisitTable::visit(value, context, table_to_node).map(Key::downgrade)
+    }
+}
+
+fn table_to_node<'lua, T, B>(
+    table: mlua::Table<'lua>,
+    context: &mut Context<'lua, B>,
+) -> mlua::Result<Key<T>>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+{
+    let id = TableId::get_or_assign(&table)?;
+    context.record_node_type::<T>(id)?;
 
-            // Reserve a slot in case of circular references.
-            let (reserved_key, _) = context.nodes.reserve_with_id(id);
-            let node = T::from_lua(Value::Table(value), &mut *context)?;
+    if let Some(key) = context.nodes.get_key(&id) {
+        return Ok(key);
+    }
 
-            Ok(context.nodes.insert_reserved(reserved_key, node))
-        })
+    if context.config.patch_defined_nodes {
+        if let Some(key) = patch_defined_node(&table, id, context)? {
+            return Ok(key);
+        }
     }
+
+    context.count_node()?;
+    let _span = NodeSpan::enter::<T>(Some(id));
+
+    // Reserve a slot in case of circular references.
+    let (reserved_key, _) = context.nodes.reserve_with_id(id);
+    let used_fields_depth = context.used_fields.len();
+    context.node_table = Some(id);
+    let node = T::from_lua(Value::Table(table.clone()), &mut *context);
+    context.node_table = None;
+
+    let node = match node {
+        Ok(node) => node,
+        Err(error) => {
+            return context
+                .recover(error, reserved_key, id, used_fields_depth)
+                .map_err(|mut error| {
+                    error.add_context_node(id, std::any::type_name::<T>());
+                    error
+                });
+        }
+    };
+
+    let key = T::insert_parsed(node, reserved_key, context);
+    instrument::node_inserted::<T>();
+    context.record_provenance(key, Some(id));
+    context.record_defined_name(key, &table)?;
+    context.record_annotation(key, &table)?;
+
+    Ok(key)
+}
+
+/// Patch the existing node that has the same defined name as `table`, if
+/// there is one.
+fn patch_defined_node<'lua, T, B>(
+    table: &mlua::Table<'lua>,
+    id: TableId,
+    context: &mut Context<'lua, B>,
+) -> mlua::Result<Option<Key<T>>>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+{
+    let Some(name) = table.raw_get::<_, Option<String>>(NODE_NAME_KEY)? else {
+        return Ok(None);
+    };
+    let Some(key) = context.nodes.get_key_by_name::<T>(&name) else {
+        return Ok(None);
+    };
+
+    // Further references to the table, including circular ones, point to the
+    // existing node.
+    context.nodes.assign_id(key, id);
+
+    let Some(mut node) = context.nodes.take(key) else {
+        return Ok(Some(key));
+    };
+
+    let _span = NodeSpan::enter::<T>(Some(id));
+    context.node_table = Some(id);
+    let result = node.patch_from_lua(Value::Table(table.clone()), &mut *context);
+    context.node_table = None;
+
+    let put_back = context.nodes.put_back(key, node);
+    assert!(
+        put_back.is_ok(),
+        "the patched node's slot should still be reserved"
+    );
+
+    result.map_err(|mut error| {
+        error.add_context_node(id, std::any::type_name::<T>());
+        error
+    })?;
+    context.record_annotation(key, table)?;
+
+    Ok(Some(key))
 }
 
 impl<'lua, T, B> FromLua<'lua, B> for Vec<T>
@@ -58,6 +243,7 @@ where
                 .sequence_values()
                 .enumerate()
                 .map(|(index, value)| {
+                    context.count_element()?;
                     T::from_lua(value?, context).map_err(|mut error| {
                         error.add_context_index(index + 1);
                         error
@@ -68,6 +254,68 @@ where
     }
 }
 
+/// Parse the arguments that were passed to a generated Lua constructor as a
+/// `Vec`. This is what `#[typed_nodes(lua_variadic)]` fields are parsed
+/// with. The arguments are packed with `table.pack`, so `nil` arguments
+/// result in errors for their positions, instead of ending the sequence
+/// early. Plain sequences are parsed like any other `Vec`.
+///
+/// ```
+/// use typed_nodes::{
+///     mlua::{Context, FromLua, GenerateLua, LuaModule},
+///     Nodes,
+/// };
+///
+/// #[derive(FromLua, GenerateLua)]
+/// struct List {
+///     #[typed_nodes(lua_variadic)]
+///     items: Vec<u32>,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut module = LuaModule::new();
+/// List::generate_lua(&mut module);
+/// lua.globals()
+///     .set("my_lib", module.load_into_table(&lua).unwrap())
+///     .unwrap();
+///
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::new(&lua, &mut nodes);
+///
+/// let value = lua.load("my_lib.List.new(1, 2, 3)").eval().unwrap();
+/// let list = List::from_lua(value, &mut context).unwrap();
+/// assert_eq!(list.items, [1, 2, 3]);
+///
+/// let value = lua.load("my_lib.List.new(1, nil, 3)").eval().unwrap();
+/// let error = List::from_lua(value, &mut context).err().unwrap();
+/// assert!(error.to_string().contains("in items, in [2]"));
+/// ```
+pub fn from_lua_variadic<'lua, T, B>(
+    value: mlua::Value<'lua>,
+    context: &mut Context<'lua, B>,
+) -> mlua::Result<Vec<T>>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+{
+    VisitTable::visit(value, context, |table, context| {
+        let length = match table.raw_get::<_, Option<usize>>("n")? {
+            Some(length) => length,
+            None => table.raw_len(),
+        };
+
+        (1..=length)
+            .map(|index| {
+                context.count_element()?;
+                T::from_lua(table.raw_get(index)?, context).map_err(|mut error| {
+                    error.add_context_index(index);
+                    error
+                })
+            })
+            .collect()
+    })
+}
+
 impl<'lua, K, V, S, B> FromLua<'lua, B> for HashMap<K, V, S>
 where
     K: FromLua<'lua, B> + Eq + Hash,
@@ -81,6 +329,7 @@ where
             value
                 .pairs::<mlua::Value<'lua>, _>()
                 .map(|pair| {
+                    context.count_element()?;
                     let (key, value) = pair?;
                     Ok((
                         K::from_lua(key.clone(), context)?,
@@ -115,6 +364,44 @@ where
             value => T::from_lua(value, context).map(Some),
         }
     }
+
+    fn patch_from_lua(
+        &mut self,
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<()> {
+        match (self, value) {
+            (Some(inner), value) if !value.is_nil() => inner.patch_from_lua(value, context),
+            (this, value) => {
+                *this = Self::from_lua(value, context)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Boxes are parsed as their content, without inserting it into the nodes.
+/// This makes it possible to have directly recursive types that don't share
+/// their parts. A table that contains itself results in an error when it
+/// reaches [`ContextBuilder::max_depth`][super::ContextBuilder::max_depth].
+impl<'lua, T, B> FromLua<'lua, B> for Box<T>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        T::from_lua(value, context).map(Box::new)
+    }
+
+    #[inline]
+    fn patch_from_lua(
+        &mut self,
+        value: Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<()> {
+        T::patch_from_lua(self, value, context)
+    }
 }
 
 impl<'a, 'lua, T, B> FromLua<'lua, B> for Cow<'a, T>
@@ -129,6 +416,17 @@ where
     }
 }
 
+impl<'lua, T, B> FromLua<'lua, B> for PhantomData<T>
+where
+    T: ?Sized + 'static,
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(_value: Value<'lua>, _context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        Ok(PhantomData)
+    }
+}
+
 macro_rules! impl_from_lua_tuples {
     ($first:ident $(,$ty:ident)* ) => {
         impl_from_lua_tuples!($($ty),*);
@@ -199,5 +497,19 @@ macro_rules! impl_from_lua_delegate {
 }
 
 impl_from_lua_delegate!(
-    bool, String, f32, f64, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+    bool, f32, f64, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
 );
+
+impl<'lua, B> FromLua<'lua, B> for String
+where
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        if let Value::String(string) = &value {
+            context.check_string_length(string)?;
+        }
+
+        mlua::FromLua::from_lua(value, context.lua)
+    }
+}
@@ -1,34 +1,47 @@
 use core::str;
-use std::{any::TypeId, borrow::Cow, collections::BTreeMap, io::Write};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    hash::{Hash, Hasher},
+    io::Write,
+    sync::{Arc, OnceLock},
+};
 
-use ahash::AHashSet;
-use mlua::Table;
+use mlua::{Function, RegistryKey, Table};
+
+use super::{EnumInfo, TypeRegistry};
 
 pub use typed_nodes_macros::GenerateLua;
 
+/// The name and generic arguments of a type in the generated Lua code. Two
+/// signatures are equal if they have the same name and arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TypeSignature {
     pub name: &'static str,
-    pub generics: &'static [&'static TypeSignature],
+    pub generics: &'static [GenericArgument],
 }
 
 impl TypeSignature {
-    fn get_generic_key(&self) -> Cow<'static, str> {
+    /// The key that identifies the signature in the generated Lua code, such
+    /// as `Uint` or `Array(Uint,3)`. See [`TypeRegistry`] for finding types by
+    /// their keys.
+    pub fn key(&self) -> Cow<'static, str> {
         let name = self.name;
 
         if self.generics.is_empty() {
             name.into()
         } else {
-            let mut key = self
-                .generics
-                .iter()
-                .map(|generic| generic.get_generic_key())
-                .fold(format!("{name}("), |mut key, generic| {
+            let mut key = self.generics.iter().map(GenericArgument::key).fold(
+                format!("{name}("),
+                |mut key, generic| {
                     if !key.ends_with('(') {
                         key.push(',');
                     }
                     key.push_str(&generic);
                     key
-                });
+                },
+            );
 
             key.push(')');
             key.into()
@@ -41,75 +54,726 @@ impl TypeSignature {
         if self.generics.is_empty() {
             name.into()
         } else {
-            let generic_key = self.get_generic_key();
+            let generic_key = self.key();
             format!("{name}.__generic_variants[\"{generic_key}\"]").into()
         }
     }
 }
 
+/// A type or const argument in a [`TypeSignature`].
+#[derive(Clone, Copy, Debug)]
+pub enum GenericArgument {
+    Type(&'static TypeSignature),
+    /// A type that doesn't implement [`GenerateLua`], such as a parameter
+    /// with the `no_bound` attribute. It's identified by its Rust type name,
+    /// from [`core::any::type_name`].
+    TypeName(fn() -> &'static str),
+    Const(ConstArgument),
+}
+
+impl GenericArgument {
+    fn key(&self) -> Cow<'static, str> {
+        match self {
+            GenericArgument::Type(signature) => signature.key(),
+            GenericArgument::TypeName(type_name) => type_name().into(),
+            GenericArgument::Const(value) => value.to_string().into(),
+        }
+    }
+}
+
+// Function pointers can't be compared reliably, so type names are compared
+// by their values.
+impl PartialEq for GenericArgument {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Type(left), Self::Type(right)) => left == right,
+            (Self::TypeName(left), Self::TypeName(right)) => left() == right(),
+            (Self::Const(left), Self::Const(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for GenericArgument {}
+
+impl Hash for GenericArgument {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+
+        match self {
+            Self::Type(signature) => signature.hash(state),
+            Self::TypeName(type_name) => type_name().hash(state),
+            Self::Const(value) => value.hash(state),
+        }
+    }
+}
+
+/// The value of a const generic parameter. It's written as a Lua value when
+/// selecting a generic variant, such as `my_lib.Array(my_lib.Uint, 3)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConstArgument {
+    Integer(i128),
+    Bool(bool),
+    Char(char),
+}
+
+impl Display for TypeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.key())
+    }
+}
+
+impl Display for ConstArgument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstArgument::Integer(value) => value.fmt(f),
+            ConstArgument::Bool(value) => value.fmt(f),
+            ConstArgument::Char(value) => value.fmt(f),
+        }
+    }
+}
+
+/// Words that can't be used as names in Lua code.
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Check if `name` can be written as it is in variable names and field
+/// accesses.
+fn is_lua_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_correctly = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+
+    starts_correctly
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+        && !LUA_KEYWORDS.contains(&name)
+}
+
+/// Write `name` as a local variable or parameter name. Names that aren't
+/// valid identifiers get a `__` prefix and their invalid characters
+/// replaced, such as `__end` for `end`.
+fn write_variable_name(source: &mut Vec<u8>, name: &str) -> std::io::Result<()> {
+    if is_lua_identifier(name) {
+        return write!(source, "{name}");
+    }
+
+    let escaped: String = name
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    write!(source, "__{escaped}")
+}
+
+/// Write `name` as a table key in a table constructor, such as `end` in
+/// `{["end"] = 1}`.
+fn write_table_key(source: &mut Vec<u8>, name: &str) -> std::io::Result<()> {
+    if is_lua_identifier(name) {
+        write!(source, "{name}")
+    } else {
+        write!(source, "[{name:?}]")
+    }
+}
+
+/// Write an access to the field `name` in `table`, such as `table["end"]`.
+fn write_field_access(source: &mut Vec<u8>, table: &str, name: &str) -> std::io::Result<()> {
+    if is_lua_identifier(name) {
+        write!(source, "{table}.{name}")
+    } else {
+        write!(source, "{table}[{name:?}]")
+    }
+}
+
+/// Shows the type name and the scalar fields of a generated value, such as
+/// `Uint {type = "add", value = 5}`, when it's printed. Internal fields are
+/// left out.
+const TOSTRING_FUNCTION: &str = r#"
+local function __node_tostring(self)
+    local fields = {}
+    for key, value in pairs(self) do
+        local value_type = type(value)
+        local is_scalar = value_type == "string" or value_type == "number" or value_type == "boolean"
+        if is_scalar and type(key) == "string" and key:sub(1, 6) ~= "_node_" then
+            if value_type == "string" then
+                value = string.format("%q", value)
+            end
+            fields[#fields + 1] = key .. " = " .. tostring(value)
+        end
+    end
+    table.sort(fields)
+    return getmetatable(self).__generic_key .. " {" .. table.concat(fields, ", ") .. "}"
+end
+"#;
+
+/// Selects a generic variant of a metatable when it's called, such as
+/// `Array(Uint, 3)`, or returns the metatable itself when it's called
+/// without arguments. It's shared by all metatables, to keep the generated
+/// code small.
+const GENERIC_CALL_FUNCTION: &str = r#"
+local function __generic_call(self, ...)
+    local args = {...}
+    if #args == 0 then
+        return self
+    end
+
+    local key = self.__generic_key .. "("
+    for i = 1, #args do
+        if i > 1 then key = key .. "," end
+        if type(args[i]) == "table" then
+            key = key .. args[i].__generic_key
+        else
+            key = key .. tostring(args[i])
+        end
+    end
+    key = key .. ")"
+
+    local variant = self.__generic_variants and self.__generic_variants[key]
+    if variant == nil then
+        error(key .. " is not a possible instance of " .. self.__generic_key)
+    end
+
+    return variant
+end
+"#;
+
 pub trait GenerateLua {
     const TYPE_SIGNATURE: TypeSignature;
 
+    /// The accepted forms of an enum, for tools that need to know what
+    /// values the type can be parsed from. It's `None` for other types.
+    const ENUM_INFO: Option<&'static EnumInfo> = None;
+
     fn generate_lua(module: &mut LuaModule);
 }
 
 pub struct LuaModule {
     metatables: BTreeMap<&'static str, Metatable>,
-    visited_types: AHashSet<TypeId>,
+    functions: BTreeMap<&'static str, Method>,
+    visited_types: TypeRegistry,
+    collisions: Vec<MethodCollision>,
+    source: OnceLock<ModuleSource>,
 }
 
 impl LuaModule {
     pub fn new() -> Self {
         Self {
             metatables: BTreeMap::new(),
-            visited_types: AHashSet::new(),
+            functions: BTreeMap::new(),
+            visited_types: TypeRegistry::new(),
+            collisions: Vec::new(),
+            source: OnceLock::new(),
         }
     }
 
     pub fn visit_type<T: GenerateLua + 'static>(&mut self) -> bool {
-        let unvisited = self.visited_types.insert(TypeId::of::<T>());
+        let unvisited = self.visited_types.register::<T>();
 
         if unvisited {
-            self.metatables
-                .entry(T::TYPE_SIGNATURE.name)
-                .or_insert_with(Metatable::new);
+            self.metatables.entry(T::TYPE_SIGNATURE.name).or_default();
+            self.source.take();
         }
 
         unvisited
     }
 
+    /// The types that have been visited by the module, with their signatures.
+    pub fn type_registry(&self) -> &TypeRegistry {
+        &self.visited_types
+    }
+
+    /// Add a method to the metatable of `metatable`. Adding a method with
+    /// the same name more than once is recorded as a collision, since it's
+    /// usually caused by different types with the same name, such as a
+    /// `Vector` type from each of two crates. The module can't be loaded if
+    /// it has collisions. See [`LuaModule::replace_method`] for replacing a
+    /// method on purpose.
+    ///
+    /// ```
+    /// use typed_nodes::mlua::{GenerateLua, LuaModule};
+    ///
+    /// mod physics {
+    ///     use typed_nodes::mlua::GenerateLua;
+    ///
+    ///     #[derive(GenerateLua)]
+    ///     pub struct Vector {
+    ///         pub x: f32,
+    ///         pub y: f32,
+    ///     }
+    /// }
+    ///
+    /// mod graphics {
+    ///     use typed_nodes::mlua::GenerateLua;
+    ///
+    ///     #[derive(GenerateLua)]
+    ///     pub struct Vector {
+    ///         pub x: f32,
+    ///         pub y: f32,
+    ///         pub z: f32,
+    ///     }
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// physics::Vector::generate_lua(&mut module);
+    /// graphics::Vector::generate_lua(&mut module);
+    ///
+    /// let collisions: Vec<_> = module
+    ///     .iter_collisions()
+    ///     .map(|collision| (collision.metatable_key(), collision.method_name()))
+    ///     .collect();
+    /// assert_eq!(collisions, [("Vector", "new")]);
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let error = module.load_into_table(&lua).unwrap_err();
+    /// assert!(error.to_string().contains("`Vector.new` is added more than once"));
+    /// ```
     pub fn add_method(
         &mut self,
         metatable: &'static TypeSignature,
         method_name: &'static str,
         method: Method,
     ) {
-        let table = self
-            .metatables
-            .entry(metatable.name)
-            .or_insert_with(Metatable::new);
+        if self
+            .replace_method(metatable, method_name, method)
+            .is_some()
+        {
+            self.collisions.push(MethodCollision {
+                metatable_key: metatable.key(),
+                method_name,
+            });
+        }
+    }
+
+    /// Add a method to the metatable of `metatable`, like
+    /// [`LuaModule::add_method`], but replace any previous method with the same
+    /// name without recording a collision. The previous method is returned.
+    pub fn replace_method(
+        &mut self,
+        metatable: &'static TypeSignature,
+        method_name: &'static str,
+        method: Method,
+    ) -> Option<Method> {
+        self.source.take();
+        let table = self.metatables.entry(metatable.name).or_default();
 
         if metatable.generics.is_empty() {
-            table.methods.insert(method_name, method);
+            table.methods.insert(method_name, method)
         } else {
-            let generic_key = metatable.get_generic_key();
+            let generic_key = metatable.key();
             table
                 .generic_variants
                 .entry(generic_key)
-                .or_insert_with(BTreeMap::new)
-                .insert(method_name, method);
+                .or_default()
+                .insert(method_name, method)
+        }
+    }
+
+    /// Add a function to the module table, next to the metatables, such as
+    /// `my_lib.rgb(r, g, b)`. The function should be created with
+    /// [`Method::new_static`], since it's not called on a table. Any previous
+    /// function with the same name is replaced and returned. The module can't
+    /// be loaded if a function has the same name as a metatable.
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     mlua::{Context, FromLua, GenerateLua, LuaExpression, LuaModule, LuaStatement, Method},
+    ///     Nodes,
+    /// };
+    ///
+    /// #[derive(FromLua, GenerateLua)]
+    /// struct Color {
+    ///     red: f32,
+    ///     green: f32,
+    ///     blue: f32,
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Color::generate_lua(&mut module);
+    ///
+    /// let mut rgb = Method::new_static(vec!["r", "g", "b"]);
+    /// rgb.add_statement(LuaStatement::Return {
+    ///     expression: LuaExpression::MakeTable {
+    ///         fields: vec![
+    ///             ("red", Box::new(LuaExpression::Identifier { name: "r" })),
+    ///             ("green", Box::new(LuaExpression::Identifier { name: "g" })),
+    ///             ("blue", Box::new(LuaExpression::Identifier { name: "b" })),
+    ///         ],
+    ///     },
+    /// });
+    /// module.add_function("rgb", rgb);
+    ///
+    /// let lua = mlua::Lua::new();
+    /// lua.globals()
+    ///     .set("my_lib", module.load_into_table(&lua).unwrap())
+    ///     .unwrap();
+    ///
+    /// let value = lua.load("my_lib.rgb(1, 0.5, 0)").eval().unwrap();
+    /// let mut nodes = Nodes::new();
+    /// let color = Color::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    /// assert_eq!((color.red, color.green, color.blue), (1.0, 0.5, 0.0));
+    /// ```
+    pub fn add_function(&mut self, name: &'static str, function: Method) -> Option<Method> {
+        self.source.take();
+        self.functions.insert(name, function)
+    }
+
+    /// Iterate over the module level functions, by name and in alphabetical
+    /// order.
+    pub fn iter_functions(&self) -> impl Iterator<Item = (&'static str, &Method)> {
+        self.functions
+            .iter()
+            .map(|(&name, function)| (name, function))
+    }
+
+    /// Iterate over the methods that have been added more than once, in the
+    /// order they were added the second time.
+    pub fn iter_collisions(&self) -> impl Iterator<Item = &MethodCollision> {
+        self.collisions.iter()
+    }
+
+    /// Record that values of the Lua type `lua_type`, such as `"boolean"` or
+    /// `"nil"`, are accepted as they are in place of `metatable`. They can't
+    /// have methods, so they are only listed as an alias in the generated
+    /// code's annotations.
+    pub fn add_literal_form(&mut self, metatable: &'static TypeSignature, lua_type: &'static str) {
+        self.source.take();
+        self.metatables
+            .entry(metatable.name)
+            .or_default()
+            .literal_forms
+            .insert(lua_type);
+    }
+
+    /// Add a constant to the table of `metatable`, such as
+    /// `Permissions.READ = "read"` for the flags of a struct with the
+    /// `#[typed_nodes(flags(...))]` attribute, or `BlendMode.ADD = 1` for an
+    /// enum with the `#[typed_nodes(repr = integer)]` attribute. Any previous
+    /// constant with the same name is replaced.
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     mlua::{Context, FromLua, GenerateLua, LuaModule},
+    ///     Nodes,
+    /// };
+    ///
+    /// #[derive(FromLua, GenerateLua, PartialEq, Debug)]
+    /// #[typed_nodes(repr = integer)]
+    /// enum BlendMode {
+    ///     Normal = 0,
+    ///     Add = 1,
+    ///     Multiply = 2,
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// BlendMode::generate_lua(&mut module);
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let my_lib = module.load_into_table(&lua).unwrap();
+    /// lua.globals().set("my_lib", my_lib).unwrap();
+    ///
+    /// let mut nodes = Nodes::new();
+    /// let mut context = Context::new(&lua, &mut nodes);
+    ///
+    /// let value: mlua::Value = lua.load("my_lib.BlendMode.ADD").eval().unwrap();
+    /// assert_eq!(context.parse::<BlendMode>(value).unwrap(), BlendMode::Add);
+    ///
+    /// let value: mlua::Value = lua.load("2").eval().unwrap();
+    /// assert_eq!(context.parse::<BlendMode>(value).unwrap(), BlendMode::Multiply);
+    ///
+    /// let value: mlua::Value = lua.load("3").eval().unwrap();
+    /// assert!(context.parse::<BlendMode>(value).is_err());
+    /// ```
+    pub fn add_constant(
+        &mut self,
+        metatable: &'static TypeSignature,
+        name: &'static str,
+        value: LuaExpression,
+    ) {
+        self.source.take();
+        self.metatables
+            .entry(metatable.name)
+            .or_default()
+            .constants
+            .insert(name, value);
+    }
+
+    /// Iterate over the metatables that will be generated, by name and in
+    /// alphabetical order.
+    pub fn iter_metatables(&self) -> impl Iterator<Item = (&'static str, &Metatable)> {
+        self.metatables
+            .iter()
+            .map(|(&name, metatable)| (name, metatable))
+    }
+
+    /// Iterate over all methods in all metatables, including the methods of
+    /// generic variants. This is useful for generating documentation or
+    /// autocompletion data for the module.
+    ///
+    /// ```
+    /// use typed_nodes::mlua::{GenerateLua, LuaModule};
+    ///
+    /// #[derive(GenerateLua)]
+    /// struct Vector {
+    ///     x: f32,
+    ///     y: f32,
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Vector::generate_lua(&mut module);
+    ///
+    /// let methods: Vec<_> = module
+    ///     .iter_methods()
+    ///     .map(|entry| (entry.metatable, entry.name, entry.method.arguments()))
+    ///     .collect();
+    ///
+    /// assert_eq!(methods, [("Vector", "new", &["x", "y"][..])]);
+    /// ```
+    pub fn iter_methods(&self) -> impl Iterator<Item = MethodEntry<'_>> {
+        self.metatables.iter().flat_map(|(&metatable, table)| {
+            let methods = table.iter_methods().map(move |(name, method)| MethodEntry {
+                metatable,
+                generic_variant: None,
+                name,
+                method,
+            });
+
+            let variant_methods =
+                table
+                    .iter_generic_variants()
+                    .flat_map(move |(generic_key, methods)| {
+                        methods.map(move |(name, method)| MethodEntry {
+                            metatable,
+                            generic_variant: Some(generic_key),
+                            name,
+                            method,
+                        })
+                    });
+
+            methods.chain(variant_methods)
+        })
+    }
+
+    /// Collect the constructors of tagged enum variants, so they can be
+    /// suggested when a table has an unknown tag. `global_name` is the name
+    /// the module table is available as in the scripts. See
+    /// [`ContextBuilder::constructor_hints`](super::ContextBuilder::constructor_hints).
+    pub fn constructor_hints(&self, global_name: &str) -> ConstructorHints {
+        let mut constructors = BTreeMap::<_, Vec<_>>::new();
+
+        for (&metatable_name, metatable) in &self.metatables {
+            for (&method_name, method) in &metatable.methods {
+                let Some(tag) = method.tag() else {
+                    continue;
+                };
+
+                let mut arguments = Vec::new();
+                if method.info.has_self {
+                    arguments.push("self");
+                }
+                arguments.extend_from_slice(&method.info.arguments);
+                if method.info.variable_arguments {
+                    arguments.push("...");
+                }
+
+                constructors.entry(tag).or_default().push(format!(
+                    "{global_name}.{metatable_name}.{method_name}({})",
+                    arguments.join(", ")
+                ));
+            }
         }
+
+        ConstructorHints { constructors }
     }
 
+    /// Evaluate the generated Lua code in `lua` and return the module table.
+    /// The code is generated once and reused until the module is changed, but
+    /// it's compiled again for each call. See [`LuaModule::load_cached`] for
+    /// reusing the compiled code.
     pub fn load_into_table<'lua>(&self, lua: &'lua mlua::Lua) -> mlua::Result<Table<'lua>> {
+        let source = self.source()?;
+        load_source(lua, &source.code)
+    }
+
+    /// Get the generated Lua code. It can be embedded in the program and
+    /// loaded with [`load_source`], to avoid generating it at startup.
+    ///
+    /// ```
+    /// use typed_nodes::mlua::{load_source, GenerateLua, LuaModule};
+    ///
+    /// #[derive(GenerateLua)]
+    /// struct Vector {
+    ///     x: f32,
+    ///     y: f32,
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Vector::generate_lua(&mut module);
+    /// let source = module.source_code().unwrap().to_owned();
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let table = load_source(&lua, &source).unwrap();
+    /// assert!(table.contains_key("Vector").unwrap());
+    /// ```
+    pub fn source_code(&self) -> mlua::Result<&str> {
+        let source = self.source()?;
+        str::from_utf8(&source.code).map_err(mlua::Error::external)
+    }
+
+    /// Write the generated Lua code to `file_name` in the build script's
+    /// output directory, where [`include_lua_module`](crate::include_lua_module)
+    /// can find it. This moves the code generation to the build script, for
+    /// types that are defined in a dependency:
+    ///
+    /// ```no_run
+    /// # use typed_nodes::mlua::{GenerateLua, LuaModule};
+    /// # #[derive(GenerateLua)]
+    /// # struct Vector { x: f32, y: f32 }
+    /// let mut module = LuaModule::new();
+    /// Vector::generate_lua(&mut module);
+    /// module.write_to_out_dir("my_lib.lua").unwrap();
+    /// ```
+    ///
+    /// The program can then include and load the code:
+    ///
+    /// ```ignore
+    /// let my_lib = typed_nodes::mlua::load_source(
+    ///     &lua,
+    ///     typed_nodes::include_lua_module!("my_lib.lua"),
+    /// )?;
+    /// ```
+    pub fn write_to_out_dir(&self, file_name: &str) -> mlua::Result<std::path::PathBuf> {
+        let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+            mlua::Error::runtime("OUT_DIR is not set, make sure this runs in a build script")
+        })?;
+
+        let path = std::path::Path::new(&out_dir).join(file_name);
+        std::fs::write(&path, self.source_code()?)?;
+
+        Ok(path)
+    }
+
+    /// Evaluate the generated Lua code in `lua` and return a new module
+    /// table, like [`LuaModule::load_into_table`]. The compiled code is
+    /// stored in `lua` and reused by later calls with the same generated
+    /// code, even if they are made with a different, but equal, module.
+    ///
+    /// ```
+    /// use typed_nodes::mlua::{GenerateLua, LuaModule};
+    ///
+    /// #[derive(GenerateLua)]
+    /// struct Vector {
+    ///     x: f32,
+    ///     y: f32,
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Vector::generate_lua(&mut module);
+    ///
+    /// // One Lua state per sandbox, each with its own copy of the module.
+    /// for _ in 0..3 {
+    ///     let lua = mlua::Lua::new();
+    ///     let first = module.load_cached(&lua).unwrap();
+    ///     let second = module.load_cached(&lua).unwrap();
+    ///     assert!(first != second);
+    /// }
+    /// ```
+    pub fn load_cached<'lua>(&self, lua: &'lua mlua::Lua) -> mlua::Result<Table<'lua>> {
+        let source = self.source()?;
+
+        let cached = lua
+            .app_data_ref::<ModuleCache>()
+            .and_then(|cache| cache.functions.get(&source.hash).cloned());
+
+        let function: Function = if let Some(key) = cached {
+            lua.registry_value(&key)?
+        } else {
+            let function = lua.load(&*source.code).into_function()?;
+            let key = Arc::new(lua.create_registry_value(function.clone())?);
+
+            match lua.app_data_mut::<ModuleCache>() {
+                Some(mut cache) => {
+                    cache.functions.insert(source.hash, key);
+                }
+                None => {
+                    let mut cache = ModuleCache::default();
+                    cache.functions.insert(source.hash, key);
+                    lua.set_app_data(cache);
+                }
+            }
+
+            function
+        };
+
+        function.call(())
+    }
+
+    /// Get the generated Lua code, or generate it if it hasn't been generated
+    /// since the module was changed.
+    fn source(&self) -> mlua::Result<&ModuleSource> {
+        if let Some(collision) = self.collisions.first() {
+            return Err(mlua::Error::runtime(format!(
+                "`{}.{}` is added more than once, possibly by different types with the same name",
+                collision.metatable_key, collision.method_name
+            )));
+        }
+
+        if let Some(&name) = self
+            .functions
+            .keys()
+            .find(|name| self.metatables.contains_key(*name))
+        {
+            return Err(mlua::Error::runtime(format!(
+                "the function `{name}` has the same name as a metatable"
+            )));
+        }
+
+        if let Some(source) = self.source.get() {
+            return Ok(source);
+        }
+
+        let code = self.generate_source()?;
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+
+        Ok(self.source.get_or_init(|| ModuleSource {
+            code,
+            hash: hasher.finish(),
+        }))
+    }
+
+    fn generate_source(&self) -> mlua::Result<Vec<u8>> {
         let mut source = Vec::new();
+        source.extend_from_slice(TOSTRING_FUNCTION.as_bytes());
+        source.extend_from_slice(GENERIC_CALL_FUNCTION.as_bytes());
 
         for (table_name, metatable) in &self.metatables {
+            if !metatable.literal_forms.is_empty() {
+                let literal_forms = metatable
+                    .literal_forms
+                    .iter()
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join("|");
+                writeln!(
+                    &mut source,
+                    "---@alias {table_name}Value {table_name}|{literal_forms}"
+                )?;
+            }
+
             writeln!(
                 &mut source,
                 "local {table_name} = {{__generic_key = \"{table_name}\"}}"
             )?;
             writeln!(&mut source, "{table_name}.__index = {table_name}")?;
+            writeln!(&mut source, "{table_name}.__tostring = __node_tostring")?;
 
             if !metatable.generic_variants.is_empty() {
                 writeln!(&mut source, "{table_name}.__generic_variants = {{}}")?;
@@ -122,6 +786,7 @@ impl LuaModule {
                         "{table_path} = {{__generic_key = \"{generic_key}\"}}"
                     )?;
                     writeln!(&mut source, "{table_path}.__index = {table_path}")?;
+                    writeln!(&mut source, "{table_path}.__tostring = __node_tostring")?;
                 }
             }
         }
@@ -129,57 +794,97 @@ impl LuaModule {
         for (table_name, metatable) in &self.metatables {
             populate_table(&mut source, table_name, &metatable.methods)?;
 
+            for (name, value) in &metatable.constants {
+                write!(&mut source, "{table_name}[")?;
+                LuaExpression::String { value: name }.write_to(&mut source)?;
+                write!(&mut source, "] = ")?;
+                value.write_to(&mut source)?;
+                writeln!(&mut source)?;
+            }
+
             for (generic_key, variant_methods) in &metatable.generic_variants {
                 let table_path = format!("{table_name}.__generic_variants[\"{generic_key}\"]");
 
                 populate_table(&mut source, &table_path, variant_methods)?;
             }
 
-            let meta_table_name = format!("__{table_name}Meta");
-            writeln!(&mut source, "local {meta_table_name} = {{}}")?;
-            writeln!(&mut source, "{meta_table_name}.__index = {meta_table_name}")?;
-
-            let key_start = format!("{table_name}(");
-            let mut call_method_info = MethodInfo::new(vec![]);
-            call_method_info.variable_arguments = true;
-            call_method_info.write_to(&mut source, &meta_table_name, "__call", |source| {
-                writeln!(
-                    source,
-                    r#"
-local args = {{...}}
-if #args == 0 then
-    return {table_name}
-end
-
-local key = {key_start:?}
-for i = 1, #args do
-    if i > 1 then key = key .. "," end
-    key = key .. args[i].__generic_key
-end
-key = key .. ")"
-
-if {table_name}.__generic_variants[key] == nil then
-    error(key .. " is not a possible instance of " .. {table_name:?})
-end
-
-return {table_name}.__generic_variants[key]
-"#
-                )?;
-                Ok(())
-            })?;
-            writeln!(&mut source, "setmetatable({table_name}, {meta_table_name})")?;
+            writeln!(
+                &mut source,
+                "setmetatable({table_name}, {{__call = __generic_call}})"
+            )?;
         }
 
-        writeln!(&mut source, "return {{")?;
+        writeln!(&mut source, "local __module = {{")?;
         for table_name in self.metatables.keys() {
             writeln!(&mut source, "{table_name} = {table_name},")?;
         }
         writeln!(&mut source, "}}")?;
+        populate_table(&mut source, "__module", &self.functions)?;
+        writeln!(&mut source, "return __module")?;
 
         // println!("{}", str::from_utf8(&source).unwrap());
 
-        let chunk = lua.load(&source);
-        chunk.eval()
+        Ok(source)
+    }
+}
+
+/// Evaluate Lua code from [`LuaModule::source_code`] in `lua` and return the
+/// module table. It's useful for loading code that was generated at build
+/// time, such as with [`LuaModule::write_to_out_dir`].
+pub fn load_source<'lua>(
+    lua: &'lua mlua::Lua,
+    source: &(impl AsRef<[u8]> + ?Sized),
+) -> mlua::Result<Table<'lua>> {
+    lua.load(source.as_ref()).eval()
+}
+
+/// Include a file that was written with
+/// [`LuaModule::write_to_out_dir`](crate::mlua::LuaModule::write_to_out_dir)
+/// as a `&'static str`, that can be loaded with
+/// [`load_source`](crate::mlua::load_source).
+#[macro_export]
+macro_rules! include_lua_module {
+    ($file_name:expr) => {
+        include_str!(concat!(env!("OUT_DIR"), "/", $file_name)) as &'static str
+    };
+}
+
+/// A method that has been added more than once to the same metatable. See
+/// [`LuaModule::add_method`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodCollision {
+    metatable_key: Cow<'static, str>,
+    method_name: &'static str,
+}
+
+impl MethodCollision {
+    /// The signature key of the metatable, such as `Vector` or
+    /// `Array(Uint,3)`.
+    pub fn metatable_key(&self) -> &str {
+        &self.metatable_key
+    }
+
+    /// The name of the method that was added more than once.
+    pub fn method_name(&self) -> &'static str {
+        self.method_name
+    }
+}
+
+/// The generated code for a [`LuaModule`].
+struct ModuleSource {
+    code: Vec<u8>,
+    hash: u64,
+}
+
+/// The compiled module code in a Lua state, by the hash of the code.
+#[derive(Default)]
+struct ModuleCache {
+    functions: HashMap<u64, Arc<RegistryKey>>,
+}
+
+impl Default for LuaModule {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -200,6 +905,8 @@ fn populate_table(
 pub struct Metatable {
     methods: BTreeMap<&'static str, Method>,
     generic_variants: BTreeMap<Cow<'static, str>, BTreeMap<&'static str, Method>>,
+    literal_forms: BTreeSet<&'static str>,
+    constants: BTreeMap<&'static str, LuaExpression>,
 }
 
 impl Metatable {
@@ -207,10 +914,138 @@ impl Metatable {
         Self {
             methods: BTreeMap::new(),
             generic_variants: BTreeMap::new(),
+            literal_forms: BTreeSet::new(),
+            constants: BTreeMap::new(),
+        }
+    }
+
+    /// Iterate over the Lua types, such as `"boolean"` and `"nil"`, that are
+    /// accepted as literal values in place of the metatable's type, in
+    /// alphabetical order.
+    ///
+    /// ```
+    /// use typed_nodes::mlua::{GenerateLua, LuaModule};
+    ///
+    /// #[derive(GenerateLua)]
+    /// enum Visibility {
+    ///     #[typed_nodes(untagged(boolean))]
+    ///     Toggle(bool),
+    ///     #[typed_nodes(untagged(nil))]
+    ///     Inherit,
+    ///     Fade { seconds: f32 },
+    /// }
+    ///
+    /// let mut module = LuaModule::new();
+    /// Visibility::generate_lua(&mut module);
+    ///
+    /// let (_, metatable) = module.iter_metatables().next().unwrap();
+    /// let methods: Vec<_> = metatable.iter_methods().map(|(name, _)| name).collect();
+    /// let literal_forms: Vec<_> = metatable.iter_literal_forms().collect();
+    ///
+    /// assert_eq!(methods, ["fade"]);
+    /// assert_eq!(literal_forms, ["boolean", "nil"]);
+    ///
+    /// let lua = mlua::Lua::new();
+    /// assert!(module.load_into_table(&lua).is_ok());
+    /// ```
+    pub fn iter_literal_forms(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.literal_forms.iter().copied()
+    }
+
+    /// Iterate over the constants in the metatable, by name and in
+    /// alphabetical order.
+    pub fn iter_constants(&self) -> impl Iterator<Item = (&'static str, &LuaExpression)> {
+        self.constants.iter().map(|(&name, value)| (name, value))
+    }
+
+    /// Iterate over the methods that are shared by all variants of the
+    /// metatable, by name and in alphabetical order.
+    pub fn iter_methods(&self) -> impl Iterator<Item = (&'static str, &Method)> {
+        self.methods.iter().map(|(&name, method)| (name, method))
+    }
+
+    /// Iterate over the generic variants of the metatable, such as
+    /// `Array(Uint,3)`, and their methods.
+    pub fn iter_generic_variants(
+        &self,
+    ) -> impl Iterator<Item = (&str, impl Iterator<Item = (&'static str, &Method)>)> {
+        self.generic_variants.iter().map(|(generic_key, methods)| {
+            (
+                &**generic_key,
+                methods.iter().map(|(&name, method)| (name, method)),
+            )
+        })
+    }
+}
+
+/// The Lua constructors for tagged enum variants, by tag. They are created
+/// with [`LuaModule::constructor_hints`].
+#[derive(Clone, Debug, Default)]
+pub struct ConstructorHints {
+    constructors: BTreeMap<&'static str, Vec<String>>,
+}
+
+impl ConstructorHints {
+    /// Suggest the constructor for the variant in `expected` that is the
+    /// most similar to `variant`, unless none of them are similar enough.
+    pub(crate) fn suggest(&self, variant: &str, expected: &[&str]) -> Option<String> {
+        let (distance, closest) = expected
+            .iter()
+            .filter(|name| self.constructors.contains_key(*name))
+            .map(|name| (edit_distance(variant, name), *name))
+            .min()?;
+
+        if distance > (closest.chars().count() / 3).max(1) {
+            return None;
+        }
+
+        match &*self.constructors[closest] {
+            [constructor] => Some(constructor.clone()),
+            constructors => Some(format!("one of {}", constructors.join(", "))),
         }
     }
 }
 
+/// The number of inserted, removed or replaced characters between `a` and
+/// `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, &b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// A method in a [`LuaModule`], as returned by [`LuaModule::iter_methods`].
+pub struct MethodEntry<'a> {
+    /// The name of the metatable the method belongs to.
+    pub metatable: &'static str,
+    /// The generic variant the method belongs to, if it's not shared by all
+    /// variants.
+    pub generic_variant: Option<&'a str>,
+    /// The name of the method.
+    pub name: &'static str,
+    /// The method itself, with its arguments.
+    pub method: &'a Method,
+}
+
+impl Default for Metatable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Method {
     info: MethodInfo,
     body: Vec<LuaStatement>,
@@ -231,10 +1066,87 @@ impl Method {
         }
     }
 
+    /// A `define(name)` method that gives the table a name. The node that
+    /// is parsed from the table can then be found by that name, using
+    /// [`Nodes::get_key_by_name`](crate::Nodes::get_key_by_name). It's
+    /// generated for types with the `#[typed_nodes(lua_define)]` attribute.
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     mlua::{Context, FromLua, GenerateLua, LuaModule},
+    ///     Key, Nodes,
+    /// };
+    ///
+    /// #[derive(FromLua, GenerateLua)]
+    /// #[typed_nodes(lua_define)]
+    /// struct Weapon {
+    ///     damage: u32,
+    /// }
+    ///
+    /// let lua = mlua::Lua::new();
+    /// let mut module = LuaModule::new();
+    /// Weapon::generate_lua(&mut module);
+    /// lua.globals()
+    ///     .set("game", module.load_into_table(&lua).unwrap())
+    ///     .unwrap();
+    ///
+    /// let value = lua
+    ///     .load(r#"{game.Weapon.new(5):define("sword"), game.Weapon.new(2)}"#)
+    ///     .eval()
+    ///     .unwrap();
+    /// let mut nodes = Nodes::new();
+    /// Vec::<Key<Weapon>>::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    ///
+    /// let sword = nodes.get_key_by_name::<Weapon>("sword").unwrap();
+    /// assert_eq!(nodes.get(sword).unwrap().damage, 5);
+    /// ```
+    pub fn define_name() -> Self {
+        let mut method = Self::new(vec!["name"]);
+        method.add_statement(LuaStatement::SetField {
+            variable: "self",
+            field: super::NODE_NAME_KEY,
+            expression: LuaExpression::Identifier { name: "name" },
+        });
+        method.add_statement(LuaStatement::Return {
+            expression: LuaExpression::Identifier { name: "self" },
+        });
+
+        method
+    }
+
     pub fn set_variable_arguments(&mut self) {
         self.info.variable_arguments = true;
     }
 
+    /// The enum tag that the method sets on the table it creates, if any.
+    fn tag(&self) -> Option<&'static str> {
+        self.body.iter().find_map(|statement| match statement {
+            LuaStatement::Assign {
+                expression: LuaExpression::MakeTable { fields },
+                ..
+            } => fields.iter().find_map(|(_, value)| match **value {
+                LuaExpression::String { value } => Some(value),
+                _ => None,
+            }),
+            _ => None,
+        })
+    }
+
+    /// The names of the method's arguments, not including `self`.
+    pub fn arguments(&self) -> &[&'static str] {
+        &self.info.arguments
+    }
+
+    /// Whether the method is called with `:`, with the table as `self`.
+    pub fn has_self(&self) -> bool {
+        self.info.has_self
+    }
+
+    /// Whether the method takes any number of additional arguments.
+    pub fn has_variable_arguments(&self) -> bool {
+        self.info.variable_arguments
+    }
+
     pub fn add_statement(&mut self, statement: LuaStatement) {
         self.body.push(statement);
     }
@@ -261,6 +1173,11 @@ pub enum LuaStatement {
         variable: &'static str,
         expression: LuaExpression,
     },
+    SetField {
+        variable: &'static str,
+        field: &'static str,
+        expression: LuaExpression,
+    },
     Return {
         expression: LuaExpression,
     },
@@ -276,6 +1193,16 @@ impl LuaStatement {
                 expression.write_to(source)?;
                 writeln!(source)?;
             }
+            LuaStatement::SetField {
+                variable,
+                field,
+                expression,
+            } => {
+                write_field_access(source, variable, field)?;
+                write!(source, " = ")?;
+                expression.write_to(source)?;
+                writeln!(source)?;
+            }
             LuaStatement::Return { expression } => {
                 write!(source, "return ")?;
                 expression.write_to(source)?;
@@ -294,26 +1221,49 @@ pub enum LuaExpression {
     String {
         value: &'static str,
     },
+    Integer {
+        value: i64,
+    },
     MakeTable {
         fields: Vec<(&'static str, Box<LuaExpression>)>,
     },
+    MakeSequence {
+        values: Vec<LuaExpression>,
+    },
     SetMetatable {
         variable: &'static str,
         metatable: &'static TypeSignature,
     },
     MakeArgumentsTable,
+    /// Like `MakeArgumentsTable`, but with the number of arguments as `n`, so
+    /// `nil` arguments can be detected.
+    PackArguments,
 }
 
 impl LuaExpression {
     fn write_to(&self, source: &mut Vec<u8>) -> std::io::Result<()> {
         match self {
-            Self::Identifier { name } => write!(source, "{name}")?,
+            Self::Identifier { name } => write_variable_name(source, name)?,
             Self::String { value } => write!(source, "{value:?}")?,
+            Self::Integer { value } => write!(source, "{value}")?,
             Self::MakeTable { fields } => {
                 write!(source, "{{")?;
 
                 for (name, value) in fields {
-                    write!(source, " {name} = ")?;
+                    write!(source, " ")?;
+                    write_table_key(source, name)?;
+                    write!(source, " = ")?;
+                    value.write_to(source)?;
+                    write!(source, ",")?;
+                }
+
+                write!(source, " }}")?;
+            }
+            Self::MakeSequence { values } => {
+                write!(source, "{{")?;
+
+                for value in values {
+                    write!(source, " ")?;
                     value.write_to(source)?;
                     write!(source, ",")?;
                 }
@@ -328,6 +1278,7 @@ impl LuaExpression {
                 write!(source, "setmetatable({variable}, {path})")?;
             }
             Self::MakeArgumentsTable => write!(source, "{{...}}")?,
+            Self::PackArguments => write!(source, "table.pack(...)")?,
         }
 
         Ok(())
@@ -367,22 +1318,43 @@ impl MethodInfo {
     where
         F: FnOnce(&mut Vec<u8>) -> mlua::Result<()>,
     {
-        if self.has_self {
-            write!(source, "function {table_path}:{method_name}(")?;
+        // Methods with reserved names, such as `end`, are assigned as fields.
+        if is_lua_identifier(method_name) {
+            if self.has_self {
+                write!(source, "function {table_path}:{method_name}(")?;
+            } else {
+                write!(source, "function {table_path}.{method_name}(")?;
+            }
         } else {
-            write!(source, "function {table_path}.{method_name}(")?;
+            write_field_access(source, table_path, method_name)?;
+            write!(source, " = function(")?;
+
+            if self.has_self {
+                write!(source, "self")?;
+
+                if !self.arguments.is_empty() || self.variable_arguments {
+                    write!(source, ", ")?;
+                }
+            }
+        }
+
+        for (index, argument) in self.arguments.iter().enumerate() {
+            if index > 0 {
+                write!(source, ", ")?;
+            }
+
+            write_variable_name(source, argument)?;
         }
 
-        let mut arguments = self.arguments.join(", ");
         if self.variable_arguments {
-            if !arguments.is_empty() {
-                arguments += ", ";
+            if !self.arguments.is_empty() {
+                write!(source, ", ")?;
             }
 
-            arguments += "...";
+            write!(source, "...")?;
         }
 
-        writeln!(source, "{arguments})")?;
+        writeln!(source, ")")?;
         write_body(source)?;
         writeln!(source, "end")?;
 
@@ -0,0 +1,65 @@
+//! Spans and counters for the `tracing` feature. They compile to nothing
+//! without it.
+
+use super::TableId;
+
+/// A span that covers parsing a node. The time it took is recorded as
+/// `duration_us` when it's dropped.
+#[cfg(feature = "tracing")]
+pub(crate) struct NodeSpan {
+    span: tracing::span::EnteredSpan,
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl NodeSpan {
+    #[inline]
+    pub(crate) fn enter<T>(table_id: Option<TableId>) -> Self {
+        let span = tracing::debug_span!(
+            "parse_node",
+            node_type = std::any::type_name::<T>(),
+            table_id = table_id.map(tracing::field::display),
+            duration_us = tracing::field::Empty,
+        );
+
+        Self {
+            span: span.entered(),
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for NodeSpan {
+    fn drop(&mut self) {
+        let duration = u64::try_from(self.start.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.span.record("duration_us", duration);
+    }
+}
+
+/// Count an inserted node.
+#[cfg(feature = "tracing")]
+#[inline]
+pub(crate) fn node_inserted<T>() {
+    tracing::trace!(
+        monotonic_counter.typed_nodes.nodes_inserted = 1u64,
+        node_type = std::any::type_name::<T>(),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NodeSpan;
+
+#[cfg(not(feature = "tracing"))]
+impl NodeSpan {
+    #[inline(always)]
+    #[allow(clippy::extra_unused_type_parameters)]
+    pub(crate) fn enter<T>(_table_id: Option<TableId>) -> Self {
+        Self
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+#[allow(clippy::extra_unused_type_parameters)]
+pub(crate) fn node_inserted<T>() {}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use mlua::{RegistryKey, Value};
+
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    Key, Nodes, ReservedKey,
+};
+
+use super::{Context, FromLua, TableId, VisitTable};
+
+/// A key to a node that is parsed the first time it's accessed with
+/// [`Nodes::force`], instead of when its parent is parsed.
+///
+/// The table is kept in the Lua registry until then, so forcing has to be done
+/// with the same Lua state as the parsing.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua, Lazy}, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Chapter {
+///     title: String,
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Book {
+///     chapters: Vec<Lazy<Chapter>>,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let value = lua.load(r#"{chapters = {{title = "One"}, {title = "Two"}}}"#).eval().unwrap();
+/// let book = Book::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+///
+/// assert!(nodes.get(book.chapters[1].key()).is_none());
+///
+/// let chapter = nodes.force(&lua, &book.chapters[1]).unwrap();
+/// assert_eq!(nodes.get(chapter).unwrap().title, "Two");
+/// ```
+pub struct Lazy<T> {
+    pub(crate) key: Key<T>,
+    table: Arc<RegistryKey>,
+}
+
+impl<T> Lazy<T> {
+    /// The key to the node. It's reserved until the node has been forced, so
+    /// [`Nodes::get`] returns `None` until then.
+    #[inline]
+    pub fn key(&self) -> Key<T> {
+        self.key
+    }
+}
+
+impl<T> Clone for Lazy<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key,
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<'lua, T, B> FromLua<'lua, B> for Lazy<T>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        VisitTable::visit(value, context, |table, context| {
+            let id = TableId::get_or_assign(&table)?;
+            context.record_node_type::<T>(id)?;
+
+            let key = if let Some(key) = context.nodes.get_key(&id) {
+                key
+            } else {
+                // The reserved key is recreated from the key when forcing.
+                let _ = context.nodes.reserve_with_id::<T>(id);
+                context
+                    .nodes
+                    .get_key(&id)
+                    .expect("the reserved node should have a key")
+            };
+
+            Ok(Lazy {
+                key,
+                table: Arc::new(context.lua.create_registry_value(table)?),
+            })
+        })
+    }
+}
+
+impl<B: Bounds> Nodes<TableId, B> {
+    /// Parse the node behind `lazy`, unless it's already parsed, and return
+    /// its key. `lua` has to be the Lua state it was parsed with.
+    pub fn force<T>(&mut self, lua: &mlua::Lua, lazy: &Lazy<T>) -> mlua::Result<Key<T>>
+    where
+        T: for<'lua> FromLua<'lua, B>,
+    {
+        if self.get(lazy.key).is_some() {
+            return Ok(lazy.key);
+        }
+
+        let table: mlua::Table = lua.registry_value(&lazy.table)?;
+        let mut context = Context::new(lua, self);
+        let node = T::from_lua(Value::Table(table), &mut context)?;
+
+        self.try_insert_reserved(ReservedKey::from_key(lazy.key), node)
+            .map_err(|_| mlua::Error::runtime("the slot for the lazy node has been removed"))
+    }
+}
@@ -0,0 +1,310 @@
+//! Parsers for values that are easier to write as strings with units, such
+//! as durations, colors and byte sizes.
+//!
+//! The types can be used directly as field types, and the [`duration`] and
+//! [`byte_size`] functions can be used with `#[typed_nodes(parse_with)]` for
+//! fields with the standard types.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use typed_nodes::{
+//!     mlua::{
+//!         literals::{self, ByteSize, ColorLiteral, DurationLiteral},
+//!         Context, FromLua,
+//!     },
+//!     Nodes,
+//! };
+//!
+//! #[derive(FromLua)]
+//! struct Settings {
+//!     fade: DurationLiteral,
+//!     #[typed_nodes(parse_with = literals::duration)]
+//!     timeout: Duration,
+//!     background: ColorLiteral,
+//!     cache_size: ByteSize,
+//! }
+//!
+//! let lua = mlua::Lua::new();
+//! let mut nodes = Nodes::new();
+//! let value: mlua::Table = lua
+//!     .load(r##"{fade = "250ms", timeout = 1.5, background = "#ff8800", cache_size = "64MB"}"##)
+//!     .eval()
+//!     .unwrap();
+//! let settings: Settings = Context::new(&lua, &mut nodes).parse(value).unwrap();
+//!
+//! assert_eq!(settings.fade.0, Duration::from_millis(250));
+//! assert_eq!(settings.timeout, Duration::from_millis(1500));
+//! assert_eq!(settings.background, ColorLiteral::rgb(1.0, 0x88 as f32 / 255.0, 0.0));
+//! assert_eq!(settings.cache_size.0, 64_000_000);
+//! ```
+
+use std::time::Duration;
+
+use mlua::Value;
+
+use crate::bounds::{BoundedBy, Bounds};
+
+use super::{Context, Error, FromLua, ParseError, TableId, VisitTable};
+
+/// A duration, parsed from a number of seconds or from a string with units,
+/// such as `"250ms"`, `"1.5s"` or `"1h 30m"`.
+///
+/// The units are `ns`, `us` (or `µs`), `ms`, `s`, `m`, `h` and `d`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DurationLiteral(pub Duration);
+
+impl From<DurationLiteral> for Duration {
+    #[inline]
+    fn from(literal: DurationLiteral) -> Self {
+        literal.0
+    }
+}
+
+impl<'lua, B> FromLua<'lua, B> for DurationLiteral
+where
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        duration(value, context).map(Self)
+    }
+}
+
+/// Parse a [`Duration`] like a [`DurationLiteral`]. It's meant to be used
+/// with `#[typed_nodes(parse_with = typed_nodes::mlua::literals::duration)]`.
+pub fn duration<'lua, B>(
+    value: Value<'lua>,
+    context: &mut Context<'lua, B>,
+) -> mlua::Result<Duration>
+where
+    B: Bounds,
+{
+    const EXPECTED: &str = "a number of seconds or a duration string";
+
+    let seconds = match value {
+        Value::Integer(seconds) => seconds as f64,
+        Value::Number(seconds) => seconds,
+        Value::String(ref string) => {
+            context.check_string_length(string)?;
+            let text = string.to_str()?;
+
+            return parse_units(text, DURATION_UNITS)
+                .map(|nanos| Duration::from_nanos(nanos as u64))
+                .ok_or_else(|| invalid_literal(text, EXPECTED));
+        }
+        _ => return Err(mlua::Error::invalid_type(&value, EXPECTED)),
+    };
+
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid_literal(seconds, EXPECTED))
+}
+
+const DURATION_UNITS: &[(&str, f64)] = &[
+    ("ns", 1.0),
+    ("us", 1e3),
+    ("µs", 1e3),
+    ("ms", 1e6),
+    ("s", 1e9),
+    ("m", 60e9),
+    ("h", 3600e9),
+    ("d", 86400e9),
+];
+
+/// An RGBA color with channels from 0 to 1. It's parsed from a hex string,
+/// such as `"#f80"`, `"#ff8800"` or `"#ff8800cc"`, or from a sequence of
+/// three or four numbers, such as `{1, 0.5, 0}`. The alpha is 1 if it's not
+/// set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorLiteral {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+}
+
+impl ColorLiteral {
+    /// Create an opaque color.
+    #[inline]
+    pub fn rgb(red: f32, green: f32, blue: f32) -> Self {
+        Self::rgba(red, green, blue, 1.0)
+    }
+
+    /// Create a color with transparency.
+    #[inline]
+    pub fn rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    fn from_hex(text: &str) -> Option<Self> {
+        let digits = text.strip_prefix('#')?;
+
+        if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channels: Vec<u8> = match digits.len() {
+            3 | 4 => digits
+                .chars()
+                .map(|digit| digit.to_digit(16).unwrap() as u8 * 0x11)
+                .collect(),
+            6 | 8 => (0..digits.len())
+                .step_by(2)
+                .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).unwrap())
+                .collect(),
+            _ => return None,
+        };
+
+        let channel = |index: usize| {
+            channels
+                .get(index)
+                .map_or(1.0, |&value| value as f32 / 255.0)
+        };
+
+        Some(Self::rgba(channel(0), channel(1), channel(2), channel(3)))
+    }
+}
+
+impl<'lua, B> FromLua<'lua, B> for ColorLiteral
+where
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        const EXPECTED: &str = "a hex color string or a sequence of 3 or 4 numbers";
+
+        if let Value::String(string) = &value {
+            context.check_string_length(string)?;
+            let text = string.to_str()?;
+
+            return Self::from_hex(text).ok_or_else(|| invalid_literal(text, EXPECTED));
+        }
+
+        VisitTable::visit(value, context, |table, _| {
+            let length = table.raw_len();
+
+            if !(3..=4).contains(&length) {
+                return Err(mlua::Error::from_parse_error(
+                    ParseError::new(format_args!(
+                        "invalid length {length}, expected 3 or 4 color channels"
+                    ))
+                    .with_expected("3 or 4")
+                    .with_found(length),
+                ));
+            }
+
+            let channel = |index: usize| -> mlua::Result<f32> {
+                table.raw_get(index).map_err(|mut error: mlua::Error| {
+                    error.add_context_index(index);
+                    error
+                })
+            };
+            let alpha = if length == 4 { channel(4)? } else { 1.0 };
+
+            Ok(Self::rgba(channel(1)?, channel(2)?, channel(3)?, alpha))
+        })
+    }
+}
+
+/// A number of bytes, parsed from an integer or from a string with units,
+/// such as `"64MB"`, `"1.5 GiB"` or `"512B"`.
+///
+/// The units are `B`, the decimal `kB`, `MB`, `GB` and `TB`, and the binary
+/// `KiB`, `MiB`, `GiB` and `TiB`. They are not case sensitive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl From<ByteSize> for u64 {
+    #[inline]
+    fn from(size: ByteSize) -> Self {
+        size.0
+    }
+}
+
+impl<'lua, B> FromLua<'lua, B> for ByteSize
+where
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        byte_size(value, context).map(Self)
+    }
+}
+
+/// Parse a number of bytes like a [`ByteSize`]. It's meant to be used with
+/// `#[typed_nodes(parse_with = typed_nodes::mlua::literals::byte_size)]`.
+pub fn byte_size<'lua, B>(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<u64>
+where
+    B: Bounds,
+{
+    const EXPECTED: &str = "a number of bytes or a size string";
+
+    match value {
+        Value::Integer(bytes) => u64::try_from(bytes).map_err(|_| invalid_literal(bytes, EXPECTED)),
+        Value::String(ref string) => {
+            context.check_string_length(string)?;
+            let text = string.to_str()?;
+            let lowercase = text.to_ascii_lowercase();
+
+            parse_units(&lowercase, BYTE_UNITS)
+                .map(|bytes| bytes as u64)
+                .ok_or_else(|| invalid_literal(text, EXPECTED))
+        }
+        _ => Err(mlua::Error::invalid_type(&value, EXPECTED)),
+    }
+}
+
+const BYTE_UNITS: &[(&str, f64)] = &[
+    ("b", 1.0),
+    ("kb", 1e3),
+    ("mb", 1e6),
+    ("gb", 1e9),
+    ("tb", 1e12),
+    ("kib", 1024.0),
+    ("mib", 1048576.0),
+    ("gib", 1073741824.0),
+    ("tib", 1099511627776.0),
+];
+
+/// Parse one or more numbers with units, such as `"1h 30m"`, and add them
+/// together. The result is rounded to the nearest integer, and is `None` if
+/// the text is invalid or the sum doesn't fit in a `u64`.
+fn parse_units(text: &str, units: &[(&str, f64)]) -> Option<f64> {
+    let mut rest = text.trim();
+    let mut sum = 0.0;
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|character: char| !(character.is_ascii_digit() || character == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = rest[number_end..].trim_start();
+
+        let unit_end = rest
+            .find(|character: char| !character.is_alphabetic())
+            .unwrap_or(rest.len());
+        let &(_, factor) = units.iter().find(|&&(unit, _)| unit == &rest[..unit_end])?;
+        rest = rest[unit_end..].trim_start();
+
+        sum += number * factor;
+    }
+
+    let sum = sum.round();
+    (sum <= u64::MAX as f64).then_some(sum)
+}
+
+fn invalid_literal(found: impl std::fmt::Debug, expected: &str) -> mlua::Error {
+    mlua::Error::from_parse_error(
+        ParseError::new(format_args!("invalid value {found:?}, expected {expected}"))
+            .with_expected(expected)
+            .with_found(format_args!("{found:?}")),
+    )
+}
@@ -0,0 +1,101 @@
+use mlua::Value;
+
+use crate::bounds::{BoundedBy, Bounds};
+
+use super::{Context, FromLua, TableId};
+
+/// An optional value that keeps track of whether it was missing or explicitly
+/// set to `null`.
+///
+/// Lua tables can't contain `nil`, so a field that is set to `nil` is the same
+/// as a missing field. An explicitly empty value is instead represented by
+/// [`mlua::Value::NULL`], which can be made available to Lua code with
+/// [`set_null_global`].
+///
+/// ```
+/// use typed_nodes::{mlua::{set_null_global, Context, FromLua, Maybe}, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Style {
+///     color: Maybe<String>,
+///     border: Maybe<String>,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// set_null_global(&lua, "null").unwrap();
+///
+/// let value = lua.load(r#"{color = "red", border = null}"#).eval().unwrap();
+/// let style = Style::from_lua(value, &mut Context::new(&lua, &mut Nodes::new())).unwrap();
+///
+/// assert_eq!(style.color, Maybe::Value("red".to_owned()));
+/// assert_eq!(style.border, Maybe::Null);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Maybe<T> {
+    /// The value was `nil`, or the field was missing.
+    #[default]
+    Missing,
+    /// The value was `null`.
+    Null,
+    /// The value was present.
+    Value(T),
+}
+
+impl<T> Maybe<T> {
+    #[inline]
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Maybe::Missing)
+    }
+
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(self, Maybe::Null)
+    }
+
+    #[inline]
+    pub fn as_ref(&self) -> Maybe<&T> {
+        match self {
+            Maybe::Missing => Maybe::Missing,
+            Maybe::Null => Maybe::Null,
+            Maybe::Value(value) => Maybe::Value(value),
+        }
+    }
+
+    /// Converts into an [`Option`], where both missing and `null` values
+    /// become `None`.
+    #[inline]
+    pub fn value(self) -> Option<T> {
+        match self {
+            Maybe::Value(value) => Some(value),
+            Maybe::Missing | Maybe::Null => None,
+        }
+    }
+}
+
+impl<T> From<Maybe<T>> for Option<T> {
+    #[inline]
+    fn from(maybe: Maybe<T>) -> Self {
+        maybe.value()
+    }
+}
+
+impl<'lua, T, B> FromLua<'lua, B> for Maybe<T>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        match value {
+            Value::Nil => Ok(Maybe::Missing),
+            value if value.is_null() => Ok(Maybe::Null),
+            value => T::from_lua(value, context).map(Maybe::Value),
+        }
+    }
+}
+
+/// Set the global variable `name` to [`mlua::Value::NULL`], for explicitly
+/// empty [`Maybe`] values.
+pub fn set_null_global(lua: &mlua::Lua, name: &str) -> mlua::Result<()> {
+    lua.globals().raw_set(name, Value::NULL)
+}
@@ -0,0 +1,433 @@
+use std::{fmt::Display, sync::Arc};
+
+use super::TableId;
+
+/// A structured description of why a value couldn't be parsed.
+///
+/// The errors from [`Error`][super::Error] are stored as `ParseError`s
+/// inside [`mlua::Error`], so tools can inspect them without parsing the
+/// message. Use [`ParseError::find`] to get it back from an [`mlua::Error`].
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua, ParseError, PathSegment}, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Enemy {
+///     weapons: Vec<Weapon>,
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Weapon {
+///     damage: u32,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::builder(&lua, &mut nodes)
+///     .source("enemies.lua")
+///     .build();
+///
+/// let value: mlua::Table = lua.load(r#"{weapons = {{damage = 1}, "sword"}}"#).eval().unwrap();
+/// let error = context.parse::<Enemy>(value).err().unwrap();
+///
+/// let parse_error = ParseError::find(&error).unwrap();
+/// assert_eq!(
+///     parse_error.path(),
+///     [PathSegment::Field("weapons".into()), PathSegment::Index(2)]
+/// );
+/// assert_eq!(parse_error.expected(), Some("a table"));
+/// assert_eq!(parse_error.found(), Some("string"));
+/// assert_eq!(
+///     parse_error.to_string(),
+///     "in weapons, in [2], unexpected string, expected a table"
+/// );
+/// assert_eq!(
+///     parse_error.to_json(),
+///     r#"{"message":"unexpected string, expected a table","path":["weapons",2],"expected":"a table","found":"string","location":"enemies.lua","line":null}"#
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParseError {
+    message: String,
+    path: Vec<PathSegment>,
+    expected: Option<String>,
+    found: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    help: Option<String>,
+    location: Option<String>,
+    line: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source_code: Option<Arc<str>>,
+}
+
+impl ParseError {
+    /// Create an error with only a message.
+    pub fn new(message: impl Display) -> Self {
+        Self {
+            message: message.to_string(),
+            path: Vec::new(),
+            expected: None,
+            found: None,
+            help: None,
+            location: None,
+            line: None,
+            source_code: None,
+        }
+    }
+
+    /// Set what was expected, such as a type name or a list of variants.
+    pub fn with_expected(mut self, expected: impl Display) -> Self {
+        self.expected = Some(expected.to_string());
+        self
+    }
+
+    /// Set what was found instead.
+    pub fn with_found(mut self, found: impl Display) -> Self {
+        self.found = Some(found.to_string());
+        self
+    }
+
+    /// Set a hint for how to fix the error, such as a suggested
+    /// alternative. It's shown after the message.
+    pub fn with_help(mut self, help: impl Display) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// Set where the value came from, such as the name of a script.
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// Set the line in the source code where the error happened, starting
+    /// from 1.
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Set the source code the error came from. It's used for showing the
+    /// line where the error happened, if it's known.
+    pub fn with_source_code(mut self, source_code: impl Into<Arc<str>>) -> Self {
+        self.source_code = Some(source_code.into());
+        self
+    }
+
+    /// Add `segment` to the start of the path.
+    pub fn in_segment(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    /// Find the `ParseError` in `error`, or in any of the errors that caused
+    /// it.
+    pub fn find(error: &mlua::Error) -> Option<&ParseError> {
+        match error {
+            mlua::Error::ExternalError(error) => error.downcast_ref(),
+            mlua::Error::CallbackError { cause, .. } => Self::find(cause),
+            mlua::Error::WithContext { cause, .. } => Self::find(cause),
+            _ => None,
+        }
+    }
+
+    /// Get the `ParseError` in `error`, or create one with its message. The
+    /// expected and found types are also included if it's a conversion
+    /// error, and the line if it's a syntax or runtime error from a chunk.
+    pub fn from_lua_error(error: &mlua::Error) -> ParseError {
+        if let Some(parse_error) = Self::find(error) {
+            return parse_error.clone();
+        }
+
+        match error {
+            mlua::Error::FromLuaConversionError { from, to, .. } => {
+                Self::new(error).with_expected(to).with_found(from)
+            }
+            mlua::Error::SyntaxError { message, .. } | mlua::Error::RuntimeError(message) => {
+                let parse_error = Self::new(error);
+
+                match chunk_line(message) {
+                    Some(line) => parse_error.with_line(line),
+                    None => parse_error,
+                }
+            }
+            _ => Self::new(error),
+        }
+    }
+
+    /// The message, without the path.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The fields, indices and referenced nodes that lead to the value that
+    /// couldn't be parsed, starting from the outermost value.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// What was expected, if it's known.
+    pub fn expected(&self) -> Option<&str> {
+        self.expected.as_deref()
+    }
+
+    /// What was found instead, if it's known.
+    pub fn found(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+
+    /// A hint for how to fix the error, if there is one.
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Where the value came from, if it's known.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// The line in the source code where the error happened, if it's known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// The source code the error came from, if it's known.
+    pub fn source_code(&self) -> Option<&str> {
+        self.source_code.as_deref()
+    }
+
+    /// Format the error as a JSON object, with the fields `message`, `path`,
+    /// `expected`, `found`, `location` and `line`. The path is an array of
+    /// field names and indices, the line is a number, while the other fields
+    /// are strings. Any missing values are `null`.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"message\":");
+        write_json_string(&mut json, &self.message);
+
+        json.push_str(",\"path\":[");
+        for (index, segment) in self.path.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+
+            match segment {
+                PathSegment::Field(name) => write_json_string(&mut json, name),
+                PathSegment::Index(index) => json.push_str(&index.to_string()),
+                PathSegment::Node {
+                    table_id,
+                    node_type,
+                } => {
+                    json.push_str("{\"table_id\":");
+                    json.push_str(&table_id.to_string());
+                    json.push_str(",\"node_type\":");
+                    write_json_string(&mut json, node_type);
+                    json.push('}');
+                }
+            }
+        }
+        json.push(']');
+
+        for (name, value) in [
+            ("expected", self.expected.as_deref()),
+            ("found", self.found.as_deref()),
+            ("location", self.location.as_deref()),
+        ] {
+            json.push_str(",\"");
+            json.push_str(name);
+            json.push_str("\":");
+
+            match value {
+                Some(value) => write_json_string(&mut json, value),
+                None => json.push_str("null"),
+            }
+        }
+
+        json.push_str(",\"line\":");
+        match self.line {
+            Some(line) => json.push_str(&line.to_string()),
+            None => json.push_str("null"),
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, segment) in self.path.iter().enumerate() {
+            match segment {
+                PathSegment::Node { table_id, .. } => {
+                    write!(f, "while parsing node {table_id}")?;
+
+                    let mut referencing_path = self.path[..index]
+                        .iter()
+                        .filter(|segment| !matches!(segment, PathSegment::Node { .. }))
+                        .peekable();
+
+                    if referencing_path.peek().is_some() {
+                        f.write_str(" referenced from ")?;
+
+                        for (index, segment) in referencing_path.enumerate() {
+                            if index > 0 && matches!(segment, PathSegment::Field(_)) {
+                                f.write_str(".")?;
+                            }
+
+                            segment.fmt(f)?;
+                        }
+                    }
+
+                    f.write_str(", ")?;
+                }
+                segment => write!(f, "in {segment}, ")?,
+            }
+        }
+
+        self.message.fmt(f)?;
+
+        if let Some(help) = &self.help {
+            write!(f, "; {help}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Errors from evaluating Lua code with [`Context::eval_include`] include the
+/// source code and the line, so the line can be labeled in the report.
+///
+/// ```
+/// use miette::Diagnostic;
+/// use typed_nodes::{mlua::{Context, ParseError}, Nodes};
+///
+/// let dir = std::env::temp_dir().join("typed_nodes_diagnostics_example");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("broken.lua"), "return {\n  damage = = 5,\n}").unwrap();
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::<typed_nodes::bounds::AnyBounds>::builder(&lua, &mut nodes)
+///     .include_dir(&dir)
+///     .build();
+///
+/// let error = context.eval_include("broken.lua").err().unwrap();
+/// let parse_error = ParseError::find(&error).unwrap();
+/// assert_eq!(parse_error.location(), Some("broken.lua"));
+/// assert_eq!(parse_error.line(), Some(2));
+///
+/// let label = parse_error.labels().unwrap().next().unwrap();
+/// assert_eq!(label.offset(), 9);
+/// assert_eq!(label.len(), 15);
+/// ```
+///
+/// [`Context::eval_include`]: super::Context::eval_include
+#[cfg(feature = "diagnostics")]
+impl miette::Diagnostic for ParseError {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        if let Some(help) = &self.help {
+            return Some(Box::new(help));
+        }
+
+        let expected = self.expected.as_deref()?;
+        Some(Box::new(format!("expected {expected}")))
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.line?;
+        let source_code: &dyn miette::SourceCode = self.source_code.as_ref()?;
+        Some(source_code)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let line = self.line?;
+        let source_code = self.source_code.as_deref()?;
+
+        let mut offset = 0;
+        for (index, text) in source_code.split_inclusive('\n').enumerate() {
+            if index + 1 == line {
+                let text = text.trim_end_matches(['\r', '\n']);
+                let label = self.found.as_ref().map(|found| format!("found {found}"));
+
+                return Some(Box::new(std::iter::once(miette::LabeledSpan::new(
+                    label,
+                    offset,
+                    text.len(),
+                ))));
+            }
+
+            offset += text.len();
+        }
+
+        None
+    }
+}
+
+/// A step in the path to a value that couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub enum PathSegment {
+    /// A named field.
+    Field(String),
+    /// A position in a sequence, starting from 1.
+    Index(usize),
+    /// A table that was parsed as a node, through a
+    /// [`Key`][crate::Key]. The segments before it lead to the field that
+    /// referenced it.
+    Node {
+        /// The ID of the table.
+        table_id: TableId,
+        /// The name of the node type.
+        node_type: &'static str,
+    },
+}
+
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Field(name) => name.fmt(f),
+            PathSegment::Index(index) => write!(f, "[{index}]"),
+            PathSegment::Node { table_id, .. } => write!(f, "node {table_id}"),
+        }
+    }
+}
+
+/// Add the name and source code of a chunk to an error from loading or
+/// evaluating it.
+pub(crate) fn chunk_error(error: mlua::Error, name: &str, source_code: &[u8]) -> mlua::Error {
+    let error = ParseError::from_lua_error(&error)
+        .with_location(name)
+        .with_source_code(String::from_utf8_lossy(source_code));
+
+    mlua::Error::external(error)
+}
+
+/// Find the line number in an error message from a chunk, such as
+/// `[string "enemies.lua"]:3: unexpected symbol near '='`.
+fn chunk_line(message: &str) -> Option<usize> {
+    let (_, rest) = message.split_once("]:")?;
+    let (line, _) = rest.split_once(':')?;
+    line.parse().ok()
+}
+
+fn write_json_string(json: &mut String, value: &str) {
+    json.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            character if character.is_control() => {
+                json.push_str(&format!("\\u{:04x}", character as u32));
+            }
+            character => json.push(character),
+        }
+    }
+
+    json.push('"');
+}
@@ -0,0 +1,100 @@
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    Key, Nodes,
+};
+
+use super::{ParseError, TableId};
+
+/// A node that is inserted instead of a node that couldn't be parsed, when
+/// error recovery is enabled with [`ContextBuilder::placeholders`]. This
+/// makes it possible to keep going after an error, for example to show a
+/// broken document in an editor.
+///
+/// The slot of the node that couldn't be parsed stays reserved, and the
+/// placeholder is inserted with the same table ID. Use [`Placeholder::find`]
+/// to get the placeholder for a key.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua, Placeholder}, Key, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Enemy {
+///     weapon: Key<Weapon>,
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Weapon {
+///     damage: u32,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::builder(&lua, &mut nodes)
+///     .source("enemies.lua")
+///     .placeholders()
+///     .build();
+///
+/// let value: mlua::Table = lua.load(r#"{weapon = {damage = "a lot"}}"#).eval().unwrap();
+/// let enemy: Enemy = context.parse(value).unwrap();
+///
+/// assert!(nodes.get(enemy.weapon).is_none());
+///
+/// let placeholder = Placeholder::find(&nodes, enemy.weapon).unwrap();
+/// assert!(placeholder.node_type().ends_with("Weapon"));
+/// assert_eq!(placeholder.error().location(), Some("enemies.lua"));
+/// ```
+///
+/// [`ContextBuilder::placeholders`]: super::ContextBuilder::placeholders
+#[derive(Clone, Debug)]
+pub struct Placeholder {
+    node_type: &'static str,
+    error: ParseError,
+}
+
+impl Placeholder {
+    #[inline]
+    pub(crate) fn new(node_type: &'static str, error: ParseError) -> Self {
+        Self { node_type, error }
+    }
+
+    /// The name of the type that couldn't be parsed.
+    #[inline]
+    pub fn node_type(&self) -> &'static str {
+        self.node_type
+    }
+
+    /// The reason why the node couldn't be parsed.
+    #[inline]
+    pub fn error(&self) -> &ParseError {
+        &self.error
+    }
+
+    /// Get the placeholder that was inserted instead of the node at `key`.
+    pub fn find<T, B>(nodes: &Nodes<TableId, B>, key: Key<T>) -> Option<&Placeholder>
+    where
+        T: 'static,
+        B: Bounds,
+        Placeholder: BoundedBy<TableId, B>,
+    {
+        let table_id = nodes.provenance(key)?.table_id()?;
+        let placeholder_key = nodes.get_key::<Placeholder, _>(&table_id)?;
+
+        nodes.get(placeholder_key)
+    }
+}
+
+/// Inserts a [`Placeholder`] with the given table ID. It's stored as a
+/// function pointer, since the bounds are only known to be satisfied when
+/// placeholders are enabled.
+pub(crate) type InsertPlaceholder<B> = fn(&mut Nodes<TableId, B>, TableId, Placeholder);
+
+pub(crate) fn insert_placeholder<B>(
+    nodes: &mut Nodes<TableId, B>,
+    id: TableId,
+    placeholder: Placeholder,
+) where
+    B: Bounds,
+    Placeholder: BoundedBy<TableId, B>,
+{
+    nodes.insert_with_id(id, placeholder);
+}
@@ -0,0 +1,121 @@
+use mlua::Value;
+
+use crate::{bounds::Bounds, Key};
+
+use super::{Context, Error, FromLua, VisitTable};
+
+/// Types that can be parsed from node names, as well as inline values. It's
+/// used for fields with the `#[typed_nodes(reference)]` attribute, where a
+/// string is looked up with [`Context::get_or_reserve_named`]. Names may be
+/// referenced before they are inserted, as long as they are inserted before
+/// [`Context::finish`] is called.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua}, Key, Nodes};
+///
+/// #[derive(FromLua)]
+/// struct Material {
+///     density: f64,
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Beam {
+///     #[typed_nodes(reference)]
+///     material: Key<Material>,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::new(&lua, &mut nodes);
+///
+/// let value = lua.load(r#"{material = "materials/steel"}"#).eval().unwrap();
+/// let beam = Beam::from_lua(value, &mut context).unwrap();
+///
+/// // The material is defined after it's referenced.
+/// let steel = context.insert_named("materials/steel", Material { density: 7.85 });
+/// context.finish().unwrap();
+///
+/// assert!(beam.material == steel);
+///
+/// let value = lua.load(r#"{material = "materials/wood"}"#).eval().unwrap();
+/// Beam::from_lua(value, &mut context).unwrap();
+///
+/// let error = context.finish().unwrap_err();
+/// assert!(error.to_string().contains(r#"unresolved references: "materials/wood""#));
+///
+/// // The names are kept in the nodes, for the next context.
+/// let mut context = Context::new(&lua, &mut nodes);
+/// let value = lua.load(r#"{material = "materials/steel"}"#).eval().unwrap();
+/// let beam = Beam::from_lua(value, &mut context).unwrap();
+///
+/// assert!(beam.material == steel);
+/// context.finish().unwrap();
+/// ```
+pub trait FromLuaReference<'lua, B>: Sized
+where
+    B: Bounds,
+{
+    /// Try to convert from a node name or any other Lua value.
+    fn from_lua_reference(
+        value: mlua::Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<Self>;
+}
+
+impl<'lua, T, B> FromLuaReference<'lua, B> for Key<T>
+where
+    T: FromLua<'lua, B>,
+    B: Bounds,
+    Self: FromLua<'lua, B>,
+{
+    fn from_lua_reference(
+        value: mlua::Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<Self> {
+        match value {
+            Value::String(name) => Ok(context.get_or_reserve_named(name.to_str()?)),
+            value => Self::from_lua(value, context),
+        }
+    }
+}
+
+impl<'lua, T, B> FromLuaReference<'lua, B> for Option<T>
+where
+    T: FromLuaReference<'lua, B>,
+    B: Bounds,
+{
+    fn from_lua_reference(
+        value: mlua::Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_lua_reference(value, context).map(Some),
+        }
+    }
+}
+
+impl<'lua, T, B> FromLuaReference<'lua, B> for Vec<T>
+where
+    T: FromLuaReference<'lua, B>,
+    B: Bounds,
+{
+    fn from_lua_reference(
+        value: mlua::Value<'lua>,
+        context: &mut Context<'lua, B>,
+    ) -> mlua::Result<Self> {
+        VisitTable::visit(value, context, |value, context| {
+            value
+                .sequence_values()
+                .enumerate()
+                .map(|(index, value)| {
+                    context.count_element()?;
+                    T::from_lua_reference(value?, context).map_err(|mut error| {
+                        error.add_context_index(index + 1);
+                        error
+                    })
+                })
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::bounds::Bounds;
+
+use super::{Context, FromLua, VisitTable};
+
+/// Receives the elements of a sequence one by one, for fields with the
+/// `#[typed_nodes(stream)]` attribute. This makes it possible to process large
+/// sequences without collecting them into a `Vec` first.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, ElementSink, FromLua}, Nodes};
+///
+/// #[derive(Default)]
+/// struct Average {
+///     sum: f64,
+///     count: usize,
+/// }
+///
+/// impl ElementSink for Average {
+///     type Element = f64;
+///
+///     fn push_element(&mut self, element: f64) -> mlua::Result<()> {
+///         self.sum += element;
+///         self.count += 1;
+///         Ok(())
+///     }
+/// }
+///
+/// #[derive(FromLua)]
+/// struct Samples {
+///     #[typed_nodes(stream)]
+///     values: Average,
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let value = lua.load("{values = {1, 2, 3, 4}}").eval().unwrap();
+/// let samples = Samples::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+///
+/// assert_eq!(samples.values.sum / samples.values.count as f64, 2.5);
+/// ```
+pub trait ElementSink: Default {
+    /// The type of each element in the sequence.
+    type Element;
+
+    /// Receive the next element in the sequence.
+    fn push_element(&mut self, element: Self::Element) -> mlua::Result<()>;
+}
+
+/// Parse a sequence into an [`ElementSink`], one element at a time. This is
+/// what `#[typed_nodes(stream)]` fields are parsed with.
+pub fn from_lua_stream<'lua, S, B>(
+    value: mlua::Value<'lua>,
+    context: &mut Context<'lua, B>,
+) -> mlua::Result<S>
+where
+    S: ElementSink,
+    S::Element: FromLua<'lua, B>,
+    B: Bounds,
+{
+    VisitTable::visit(value, context, |table, context| {
+        let mut sink = S::default();
+        context.for_each_element(&table, |element| sink.push_element(element))?;
+        Ok(sink)
+    })
+}
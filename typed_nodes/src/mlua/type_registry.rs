@@ -0,0 +1,175 @@
+use std::{any::TypeId, borrow::Cow};
+
+use super::{GenerateLua, TypeSignature};
+
+/// A mapping between Rust types and their [`TypeSignature`]s, in both
+/// directions. Types are looked up by their signature keys, such as `Uint` or
+/// `Array(Uint,3)`, which are the same as in the generated Lua code.
+///
+/// A [`LuaModule`](super::LuaModule) registers each type it visits, so its
+/// registry covers every type in the module.
+///
+/// ```
+/// use std::any::TypeId;
+///
+/// use typed_nodes::mlua::{GenerateLua, TypeRegistry};
+///
+/// #[derive(GenerateLua)]
+/// struct Uint {
+///     value: u32,
+/// }
+///
+/// #[derive(GenerateLua)]
+/// struct Array<T, const N: usize> {
+///     items: Vec<T>,
+/// }
+///
+/// let mut registry = TypeRegistry::new();
+/// registry.register::<Uint>();
+/// registry.register::<Array<Uint, 3>>();
+///
+/// assert_eq!(registry.type_id("Array(Uint,3)"), Some(TypeId::of::<Array<Uint, 3>>()));
+/// assert_eq!(registry.type_id("Array(Uint,4)"), None);
+///
+/// let signature = registry.signature(TypeId::of::<Uint>()).unwrap();
+/// assert_eq!(*signature, Uint::TYPE_SIGNATURE);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TypeRegistry {
+    signatures: ahash::HashMap<TypeId, &'static TypeSignature>,
+    type_ids: ahash::HashMap<Cow<'static, str>, Option<TypeId>>,
+    enums: ahash::HashMap<TypeId, &'static EnumInfo>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `T` to the registry. Returns `false` if it was already registered.
+    pub fn register<T: GenerateLua + 'static>(&mut self) -> bool {
+        if let Some(enum_info) = T::ENUM_INFO {
+            self.enums.insert(TypeId::of::<T>(), enum_info);
+        }
+
+        self.insert(TypeId::of::<T>(), &T::TYPE_SIGNATURE)
+    }
+
+    /// Add a type to the registry by its ID. Returns `false` if it was
+    /// already registered.
+    pub fn insert(&mut self, type_id: TypeId, signature: &'static TypeSignature) -> bool {
+        if self.signatures.insert(type_id, signature).is_some() {
+            return false;
+        }
+
+        // Types with `lua_base_type` share their signatures with other types,
+        // so their keys don't point to any particular type.
+        self.type_ids
+            .entry(signature.key())
+            .and_modify(|existing| {
+                if *existing != Some(type_id) {
+                    *existing = None;
+                }
+            })
+            .or_insert(Some(type_id));
+
+        true
+    }
+
+    /// Get the signature of the type with `type_id`, if it's registered.
+    pub fn signature(&self, type_id: TypeId) -> Option<&'static TypeSignature> {
+        self.signatures.get(&type_id).copied()
+    }
+
+    /// Get the tag name and variants of the enum with `type_id`, if it's
+    /// registered with [`TypeRegistry::register`].
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    ///
+    /// use typed_nodes::mlua::{GenerateLua, TypeRegistry};
+    ///
+    /// #[derive(GenerateLua)]
+    /// #[typed_nodes(tag = kind)]
+    /// enum Shape {
+    ///     #[typed_nodes(alias = "round")]
+    ///     Circle { radius: f32 },
+    ///     #[typed_nodes(untagged(number))]
+    ///     Size(f32),
+    /// }
+    ///
+    /// let mut registry = TypeRegistry::new();
+    /// registry.register::<Shape>();
+    ///
+    /// let info = registry.enum_info(TypeId::of::<Shape>()).unwrap();
+    /// assert_eq!(info.tag_name, "kind");
+    ///
+    /// let tags: Vec<_> = info.variants.iter().map(|variant| variant.tag).collect();
+    /// assert_eq!(tags, [Some("circle"), None]);
+    /// assert_eq!(info.variants[0].aliases, ["round"]);
+    /// assert_eq!(info.variants[1].untagged_as, ["number"]);
+    /// ```
+    pub fn enum_info(&self, type_id: TypeId) -> Option<&'static EnumInfo> {
+        self.enums.get(&type_id).copied()
+    }
+
+    /// Get the ID of the type with the signature key `key`, such as
+    /// `Array(Uint,3)`. Returns `None` if no registered type has the key, or
+    /// if more than one type has it.
+    pub fn type_id(&self, key: &str) -> Option<TypeId> {
+        self.type_ids.get(key).copied().flatten()
+    }
+
+    /// Check if the type with `type_id` is registered.
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.signatures.contains_key(&type_id)
+    }
+
+    /// Iterate over the registered types and their signatures, in no
+    /// particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (TypeId, &'static TypeSignature)> + '_ {
+        self.signatures
+            .iter()
+            .map(|(&type_id, &signature)| (type_id, signature))
+    }
+
+    /// The number of registered types.
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Check if the registry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+}
+
+/// The forms an enum can be parsed from. It's generated by the `GenerateLua`
+/// derive as [`GenerateLua::ENUM_INFO`].
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct EnumInfo {
+    /// The name of the field that holds the variant tag, such as `type`.
+    pub tag_name: &'static str,
+    /// The variants that aren't skipped, in declaration order.
+    pub variants: &'static [VariantInfo],
+}
+
+/// A variant in an [`EnumInfo`].
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct VariantInfo {
+    /// The name of the variant in Rust.
+    pub name: &'static str,
+    /// The tag that selects the variant, if it's tagged.
+    pub tag: Option<&'static str>,
+    /// Other tags that select the variant, including the deprecated ones.
+    pub aliases: &'static [&'static str],
+    /// The deprecated tags among the aliases.
+    pub deprecated_aliases: &'static [&'static str],
+    /// The Lua types the variant is parsed from without a tag, such as
+    /// `"number"`.
+    pub untagged_as: &'static [&'static str],
+    /// If the variant is parsed from tables with unknown or missing tags.
+    pub is_default: bool,
+    /// If the variant is also parsed from its tag as a string.
+    pub has_string_form: bool,
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use mlua::{RegistryKey, Value};
+
+use crate::bounds::{BoundedBy, Bounds};
+
+use super::{Context, FromLua, TableId};
+
+/// Any Lua value, kept in the Lua registry as it is. It's useful for
+/// accepting values that don't match anything else, such as in a
+/// `#[typed_nodes(default(any))]` variant, which is used for tables with
+/// unknown tags as well as for values of any other type.
+///
+/// The value can only be retrieved from the Lua state it was parsed with.
+///
+/// ```
+/// use typed_nodes::{mlua::{Context, FromLua, LuaValueRef}, Nodes};
+///
+/// #[derive(FromLua)]
+/// enum Setting {
+///     #[typed_nodes(untagged(boolean))]
+///     Toggle(bool),
+///     Range { min: f32, max: f32 },
+///     #[typed_nodes(default(any))]
+///     Other(LuaValueRef),
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::new(&lua, &mut nodes);
+///
+/// let value = lua.load("true").eval().unwrap();
+/// assert!(matches!(Setting::from_lua(value, &mut context).unwrap(), Setting::Toggle(true)));
+///
+/// let value = lua.load("'fast'").eval().unwrap();
+/// let Setting::Other(other) = Setting::from_lua(value, &mut context).unwrap() else {
+///     panic!("expected an unknown setting");
+/// };
+/// assert_eq!(other.get(&lua).unwrap().as_str(), Some("fast"));
+///
+/// let value = lua.load("{type = 'curve', points = {}}").eval().unwrap();
+/// assert!(matches!(Setting::from_lua(value, &mut context).unwrap(), Setting::Other(_)));
+/// ```
+#[derive(Clone)]
+pub struct LuaValueRef {
+    value: Arc<RegistryKey>,
+}
+
+impl LuaValueRef {
+    /// Get the value from `lua`, which has to be the Lua state it was parsed
+    /// with.
+    pub fn get<'lua>(&self, lua: &'lua mlua::Lua) -> mlua::Result<Value<'lua>> {
+        lua.registry_value(&self.value)
+    }
+}
+
+impl<'lua, B> FromLua<'lua, B> for LuaValueRef
+where
+    B: Bounds,
+    Self: BoundedBy<TableId, B>,
+{
+    fn from_lua(value: Value<'lua>, context: &mut Context<'lua, B>) -> mlua::Result<Self> {
+        Ok(Self {
+            value: Arc::new(context.lua.create_registry_value(value)?),
+        })
+    }
+}
@@ -134,7 +134,12 @@ pub trait VisitLua<'lua, B: Bounds> {
             Value::Integer(value) => self.visit_integer(value, context),
             Value::Number(value) => self.visit_number(value, context),
             Value::String(value) => self.visit_string(value, context),
-            Value::Table(value) => self.visit_table(value, context),
+            Value::Table(value) => {
+                context.enter_table()?;
+                let result = self.visit_table(value, context);
+                context.exit_table();
+                result
+            }
             Value::Function(value) => self.visit_function(value, context),
             Value::Thread(value) => self.visit_thread(value, context),
             Value::UserData(value) => self.visit_user_data(value, context),
@@ -173,7 +178,7 @@ where
 
     #[inline(always)]
     fn expected(&self) -> String {
-        format!("a table")
+        "a table".to_owned()
     }
 
     #[inline(always)]
@@ -214,7 +219,7 @@ where
 
     #[inline(always)]
     fn expected(&self) -> String {
-        format!("an integer")
+        "an integer".to_owned()
     }
 
     #[inline(always)]
@@ -238,7 +243,7 @@ impl<F> VisitString<F> {
         visit: F,
     ) -> mlua::Result<T>
     where
-        F: FnMut(mlua::String, &mut Context<'lua, B>) -> mlua::Result<T>,
+        F: FnMut(mlua::String<'lua>, &mut Context<'lua, B>) -> mlua::Result<T>,
         B: Bounds,
     {
         let mut visitor = Self(visit);
@@ -248,20 +253,20 @@ impl<F> VisitString<F> {
 
 impl<'lua, T, F, B> VisitLua<'lua, B> for VisitString<F>
 where
-    F: FnMut(mlua::String, &mut Context<'lua, B>) -> mlua::Result<T>,
+    F: FnMut(mlua::String<'lua>, &mut Context<'lua, B>) -> mlua::Result<T>,
     B: Bounds,
 {
     type Output = T;
 
     #[inline(always)]
     fn expected(&self) -> String {
-        format!("a string")
+        "a string".to_owned()
     }
 
     #[inline(always)]
     fn visit_string(
         &mut self,
-        value: mlua::String,
+        value: mlua::String<'lua>,
         context: &mut Context<'lua, B>,
     ) -> mlua::Result<T> {
         self.0(value, context)
@@ -0,0 +1,159 @@
+use alloc::{string::String, vec::Vec};
+use core::any::TypeId;
+
+use crate::{bounds::Bounds, DynKey, HashMap, Key, Nodes};
+
+/// The names of the nodes in a [`Nodes`] collection, by node type.
+#[derive(Default)]
+pub(crate) struct Names {
+    keys: HashMap<TypeId, HashMap<String, DynKey>>,
+    names: HashMap<DynKey, Vec<String>>,
+}
+
+impl Names {
+    fn insert(&mut self, key: DynKey, name: String) -> Option<DynKey> {
+        let old_key = self
+            .keys
+            .entry(key.node_type)
+            .or_default()
+            .insert(name.clone(), key);
+
+        if old_key == Some(key) {
+            return old_key;
+        }
+
+        if let Some(old_key) = old_key {
+            self.remove_name_of(old_key, &name);
+        }
+
+        self.names.entry(key).or_default().push(name);
+
+        old_key
+    }
+
+    fn remove(&mut self, node_type: TypeId, name: &str) -> Option<DynKey> {
+        let key = self.keys.get_mut(&node_type)?.remove(name)?;
+        self.remove_name_of(key, name);
+
+        Some(key)
+    }
+
+    fn remove_name_of(&mut self, key: DynKey, name: &str) {
+        if let Some(names) = self.names.get_mut(&key) {
+            names.retain(|key_name| key_name != name);
+
+            if names.is_empty() {
+                self.names.remove(&key);
+            }
+        }
+    }
+
+    /// Remove all names of `key`.
+    pub(crate) fn remove_key(&mut self, key: DynKey) {
+        let Some(names) = self.names.remove(&key) else {
+            return;
+        };
+
+        if let Some(keys) = self.keys.get_mut(&key.node_type) {
+            for name in names {
+                keys.remove(&name);
+            }
+        }
+    }
+
+    /// Let all names of `from` refer to `into` instead.
+    pub(crate) fn replace_key(&mut self, from: DynKey, into: DynKey) {
+        let Some(names) = self.names.remove(&from) else {
+            return;
+        };
+
+        if let Some(keys) = self.keys.get_mut(&from.node_type) {
+            for name in &names {
+                keys.insert(name.clone(), into);
+            }
+        }
+
+        self.names.entry(into).or_default().extend(names);
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Give the node for `key` a name, that can be used for finding it with
+    /// [`Nodes::get_key_by_name`]. A node can have more than one name, but
+    /// each name is only used for one node of type `T`. Returns the key that
+    /// previously had the name, if any.
+    ///
+    /// Nodes that are parsed from Lua tables get the name from their
+    /// generated `define(name)` method, if any.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Material {
+    ///     density: f64,
+    /// }
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// let steel = nodes.insert(Material { density: 7.85 });
+    /// nodes.set_name(steel, "steel");
+    /// nodes.set_name(steel, "metal");
+    ///
+    /// assert!(nodes.get_key_by_name::<Material>("steel") == Some(steel));
+    /// assert!(nodes.names(steel).eq(["steel", "metal"]));
+    ///
+    /// nodes.remove(steel);
+    /// assert!(nodes.get_key_by_name::<Material>("metal").is_none());
+    /// ```
+    pub fn set_name<T>(&mut self, key: Key<T>, name: impl Into<String>) -> Option<Key<T>>
+    where
+        T: 'static,
+    {
+        self.names
+            .get_or_insert_with(Names::default)
+            .insert(key.into(), name.into())
+            .and_then(DynKey::into_static)
+    }
+
+    /// Remove the name `name` from node type `T`, and return the key it
+    /// referred to.
+    pub fn remove_name<T>(&mut self, name: &str) -> Option<Key<T>>
+    where
+        T: 'static,
+    {
+        self.names
+            .as_mut()?
+            .remove(TypeId::of::<T>(), name)
+            .and_then(DynKey::into_static)
+    }
+
+    /// Find the key for the node of type `T` with the name `name`. See
+    /// [`Nodes::set_name`].
+    pub fn get_key_by_name<T>(&self, name: &str) -> Option<Key<T>>
+    where
+        T: 'static,
+    {
+        self.names
+            .as_ref()?
+            .keys
+            .get(&TypeId::of::<T>())?
+            .get(name)
+            .copied()
+            .and_then(DynKey::into_static)
+    }
+
+    /// Iterate over the names of the node for `key`, in the order they were
+    /// given.
+    pub fn names(&self, key: impl Into<DynKey>) -> impl Iterator<Item = &str> {
+        let key = key.into();
+
+        self.names
+            .iter()
+            .flat_map(move |names| names.names.get(&key))
+            .flatten()
+            .map(String::as_str)
+    }
+}
@@ -1,38 +1,98 @@
-use std::{
-    any::TypeId,
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    any::{type_name, Any, TypeId},
     borrow::Borrow,
+    fmt::{self, Debug, Display, Formatter},
     hash::{Hash, Hasher},
     marker::PhantomData,
+    panic::{RefUnwindSafe, UnwindSafe},
 };
 
 use smallbox::{smallbox, SmallBox};
 
 use downcast_rs::{impl_downcast, Downcast};
-use slotmap::{DefaultKey, SlotMap};
-
-use crate::{BoundedBy, Bounds};
-
+use slotmap::{DefaultKey, Key as _, SlotMap};
+
+use crate::{alias::AliasMap, index::NodeIndex, stats::TypeStats, BoundedBy, Bounds, HashMap};
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        bound(
+            serialize = "I: serde::Serialize + Hash + Eq, T: serde::Serialize",
+            deserialize = "I: serde::Deserialize<'de> + Hash + Eq, T: serde::Deserialize<'de>"
+        ),
+        from = "NodeGroupFields<I, T>"
+    )
+)]
 pub struct NodeGroup<I, T> {
     nodes: SlotMap<DefaultKey, Slot<T>>,
-    id_map: ahash::HashMap<I, DefaultKey>,
+    id_map: HashMap<I, DefaultKey>,
+    /// The number of filled slots. The rest of the slots are reserved. It's
+    /// counted again when deserializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    filled: usize,
+}
+
+/// The serialized fields of a [`NodeGroup`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "I: serde::Deserialize<'de> + Hash + Eq, T: serde::Deserialize<'de>"))]
+struct NodeGroupFields<I, T> {
+    nodes: SlotMap<DefaultKey, Slot<T>>,
+    id_map: HashMap<I, DefaultKey>,
+}
+
+#[cfg(feature = "serde")]
+impl<I, T> From<NodeGroupFields<I, T>> for NodeGroup<I, T> {
+    fn from(fields: NodeGroupFields<I, T>) -> Self {
+        let filled = fields
+            .nodes
+            .values()
+            .filter(|slot| slot.as_filled().is_some())
+            .count();
+
+        Self {
+            nodes: fields.nodes,
+            id_map: fields.id_map,
+            filled,
+        }
+    }
 }
 
 impl<I, T> NodeGroup<I, T> {
     #[inline]
     #[must_use]
     pub(crate) fn insert(&mut self, node: T) -> Key<T> {
+        self.filled += 1;
         Key::new(self.nodes.insert(Slot::Filled(node)))
     }
 
     #[inline]
-    pub(crate) fn insert_reserved(&mut self, key: ReservedKey<T>, node: T) -> Key<T> {
-        let slot = self
-            .nodes
-            .get_mut(key.slot)
-            .expect("reserved slot was removed");
+    #[must_use]
+    pub(crate) fn reserve(&mut self) -> ReservedKey<T> {
+        ReservedKey::new(self.nodes.insert(Slot::Reserved))
+    }
+
+    /// Fill the reserved slot for `key` with `node`. The node is returned if
+    /// the slot has been removed.
+    #[inline]
+    pub(crate) fn try_insert_reserved(
+        &mut self,
+        key: ReservedKey<T>,
+        node: T,
+    ) -> Result<Key<T>, T> {
+        let Some(slot) = self.nodes.get_mut(key.slot) else {
+            return Err(node);
+        };
+
+        if let Slot::Reserved = slot {
+            self.filled += 1;
+        }
         *slot = Slot::Filled(node);
 
-        Key::new(key.slot)
+        Ok(Key::new(key.slot))
     }
 
     #[inline]
@@ -45,6 +105,78 @@ impl<I, T> NodeGroup<I, T> {
         self.nodes.get_mut(key.slot)?.as_filled_mut()
     }
 
+    /// Move the node out of its slot and leave the slot reserved.
+    #[inline]
+    pub(crate) fn take(&mut self, key: Key<T>) -> Option<(ReservedKey<T>, T)> {
+        let slot = self.nodes.get_mut(key.slot)?;
+
+        match core::mem::replace(slot, Slot::Reserved) {
+            Slot::Filled(node) => {
+                self.filled -= 1;
+                Some((ReservedKey::new(key.slot), node))
+            }
+            Slot::Reserved => None,
+        }
+    }
+
+    /// Replace the node in the slot for `key` and return the old node. A
+    /// reserved slot is filled with `node`, while `node` is dropped if the
+    /// slot doesn't exist.
+    #[inline]
+    pub(crate) fn replace(&mut self, key: Key<T>, node: T) -> Option<T> {
+        let slot = self.nodes.get_mut(key.slot)?;
+        let old_node = core::mem::replace(slot, Slot::Filled(node)).into_filled();
+
+        if old_node.is_none() {
+            self.filled += 1;
+        }
+
+        old_node
+    }
+
+    /// Fill the slot for `key` with `node`, if the slot is reserved.
+    /// Otherwise, `node` is returned.
+    #[inline]
+    pub(crate) fn put_back(&mut self, key: Key<T>, node: T) -> Result<(), T> {
+        match self.nodes.get_mut(key.slot) {
+            Some(slot @ Slot::Reserved) => {
+                *slot = Slot::Filled(node);
+                self.filled += 1;
+                Ok(())
+            }
+            _ => Err(node),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn filled_len(&self) -> usize {
+        self.filled
+    }
+
+    #[inline]
+    pub(crate) fn reserved_len(&self) -> usize {
+        self.nodes.len() - self.filled
+    }
+
+    #[inline]
+    pub(crate) fn keys(&self) -> GroupKeys<'_, T> {
+        GroupKeys { inner: self.iter() }
+    }
+
+    #[inline]
+    pub(crate) fn iter(&self) -> GroupIter<'_, T> {
+        GroupIter {
+            inner: FilledSlots::new(self.nodes.iter(), self.filled),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn iter_mut(&mut self) -> GroupIterMut<'_, T> {
+        GroupIterMut {
+            inner: FilledSlots::new(self.nodes.iter_mut(), self.filled),
+        }
+    }
+
     #[inline]
     pub(crate) fn remove(&mut self, key: Key<T>) -> Option<T> {
         if matches!(self.nodes.get(key.slot), Some(&Slot::Reserved) | None) {
@@ -52,8 +184,104 @@ impl<I, T> NodeGroup<I, T> {
         }
 
         self.id_map.retain(|_, &mut slot| slot != key.slot);
+        self.filled -= 1;
         self.nodes.remove(key.slot)?.into_filled()
     }
+
+    #[inline]
+    pub(crate) fn slot_state(&self, key: Key<T>) -> SlotState {
+        match self.nodes.get(key.slot) {
+            Some(Slot::Filled(_)) => SlotState::Filled,
+            Some(Slot::Reserved) => SlotState::Reserved,
+            None => SlotState::Vacant,
+        }
+    }
+
+    /// Remove the slot for `key` and its IDs, if the slot is reserved.
+    /// Returns `true` if there was a reserved slot.
+    #[inline]
+    pub(crate) fn remove_reserved(&mut self, key: Key<T>) -> bool {
+        if !matches!(self.nodes.get(key.slot), Some(&Slot::Reserved)) {
+            return false;
+        }
+
+        self.id_map.retain(|_, &mut slot| slot != key.slot);
+        self.nodes.remove(key.slot);
+        true
+    }
+}
+
+impl<I, T> NodeGroup<I, T>
+where
+    T: Eq + Hash,
+{
+    /// Remove nodes that are equal to an earlier node and return the removed
+    /// keys, paired with the keys of the nodes they were equal to. IDs that
+    /// referred to the removed nodes will refer to the remaining nodes.
+    pub(crate) fn dedup(&mut self) -> Vec<(Key<T>, Key<T>)> {
+        let mut duplicates = Vec::new();
+
+        {
+            let mut first_slots = HashMap::default();
+
+            for (slot, node) in &self.nodes {
+                if let Some(node) = node.as_filled() {
+                    let first_slot = *first_slots.entry(node).or_insert(slot);
+
+                    if first_slot != slot {
+                        duplicates.push((slot, first_slot));
+                    }
+                }
+            }
+        }
+
+        if duplicates.is_empty() {
+            return Vec::new();
+        }
+
+        let merged: HashMap<_, _> = duplicates.iter().copied().collect();
+        self.filled -= duplicates.len();
+
+        for slot in self.id_map.values_mut() {
+            if let Some(&first_slot) = merged.get(slot) {
+                *slot = first_slot;
+            }
+        }
+
+        duplicates
+            .into_iter()
+            .map(|(slot, first_slot)| {
+                self.nodes.remove(slot);
+                (Key::new(slot), Key::new(first_slot))
+            })
+            .collect()
+    }
+}
+
+impl<I, T> NodeGroup<I, T> {
+    /// Remove the reserved slot and let its IDs refer to `into` instead.
+    #[cfg(feature = "lua")]
+    pub(crate) fn merge_reserved(&mut self, reserved_key: ReservedKey<T>, into: Key<T>) {
+        self.merge(reserved_key.key(), into);
+    }
+
+    /// Remove the slot for `from` and let its IDs refer to `into` instead.
+    /// Returns the removed node, if the slot was filled.
+    pub(crate) fn merge(&mut self, from: Key<T>, into: Key<T>) -> Option<T> {
+        let node = self.nodes.remove(from.slot)?.into_filled();
+
+        if node.is_some() {
+            self.filled -= 1;
+        }
+
+        for slot in self.id_map.values_mut() {
+            if *slot == from.slot {
+                *slot = into.slot;
+            }
+        }
+
+        node
+    }
 }
 
 impl<I, T> NodeGroup<I, T>
@@ -64,6 +292,7 @@ where
     pub(crate) fn insert_with_id(&mut self, id: I, node: T) -> (Key<T>, Option<Key<T>>) {
         let slot = self.nodes.insert(Slot::Filled(node));
         let old_slot = self.id_map.insert(id, slot);
+        self.filled += 1;
 
         (Key::new(slot), old_slot.map(Key::new))
     }
@@ -77,6 +306,12 @@ where
         (ReservedKey::new(slot), old_slot.map(Key::new))
     }
 
+    #[cfg(feature = "lua")]
+    #[inline]
+    pub(crate) fn assign_id(&mut self, id: I, key: Key<T>) -> Option<Key<T>> {
+        self.id_map.insert(id, key.slot).map(Key::new)
+    }
+
     #[inline]
     pub(crate) fn get_key<J>(&self, id: &J) -> Option<Key<T>>
     where
@@ -93,6 +328,7 @@ impl<K, T> Default for NodeGroup<K, T> {
         Self {
             nodes: Default::default(),
             id_map: Default::default(),
+            filled: 0,
         }
     }
 }
@@ -122,15 +358,15 @@ impl<T> Hash for Key<T> {
 
 impl<T> Ord for Key<T> {
     #[inline]
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.slot.cmp(&other.slot)
     }
 }
 
 impl<T> PartialOrd for Key<T> {
     #[inline]
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.slot.partial_cmp(&other.slot)
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -148,20 +384,204 @@ impl<T> Copy for Key<T> {}
 impl<T> Clone for Key<T> {
     #[inline]
     fn clone(&self) -> Self {
-        Self {
-            slot: self.slot.clone(),
-            node_type: PhantomData,
-        }
+        *self
+    }
+}
+
+impl<T> Debug for Key<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Key<{}>({:?})", type_name::<T>(), self.slot.data())
+    }
+}
+
+/// Shows the type name and the slot index and version, such as
+/// `my_crate::Image(3v1)`.
+///
+/// ```
+/// use typed_nodes::{DynKey, Nodes};
+///
+/// struct Image;
+///
+/// let mut nodes = Nodes::<()>::default();
+/// let key = nodes.insert(Image);
+///
+/// assert!(key.to_string().ends_with("Image(1v1)"));
+/// assert_eq!(DynKey::from(key).to_string(), "1v1");
+/// ```
+impl<T> Display for Key<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({:?})", type_name::<T>(), self.slot.data())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Key<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(&self.slot, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Key<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        <DefaultKey as serde::Deserialize>::deserialize(deserializer).map(Key::new)
+    }
+}
+
+/// A key to a node of type `T` that may have been removed. It's treated as
+/// a non-retaining reference by the graph utilities, so [`VisitKeys`] doesn't
+/// visit it, and it has to be upgraded to a [`Key`] before it can be used.
+///
+/// A removed node's slot may be reused by another node, but the key is
+/// versioned, so it will not refer to the new node.
+///
+/// ```
+/// use typed_nodes::{Nodes, WeakKey};
+///
+/// struct Texture;
+///
+/// let mut nodes = Nodes::<()>::new();
+/// let texture = nodes.insert(Texture);
+/// let cached: WeakKey<Texture> = texture.downgrade();
+///
+/// assert!(cached.upgrade(&nodes) == Some(texture));
+///
+/// nodes.remove(texture);
+/// nodes.insert(Texture);
+/// assert!(cached.upgrade(&nodes).is_none());
+/// ```
+///
+/// [`VisitKeys`]: crate::graph::VisitKeys
+pub struct WeakKey<T> {
+    key: Key<T>,
+}
+
+impl<T> Key<T> {
+    /// Create a [`WeakKey`] to the same node.
+    #[inline]
+    pub fn downgrade(self) -> WeakKey<T> {
+        WeakKey { key: self }
+    }
+}
+
+impl<T> WeakKey<T> {
+    /// Get the key to the node, if it's still in `nodes`. Reserved slots
+    /// count as missing nodes.
+    #[inline]
+    pub fn upgrade<I, B>(&self, nodes: &crate::Nodes<I, B>) -> Option<Key<T>>
+    where
+        T: BoundedBy<I, B>,
+        I: 'static,
+        B: Bounds,
+    {
+        nodes.get(self.key).map(|_| self.key)
+    }
+
+    /// Get the key without checking if the node is still there.
+    #[inline]
+    pub fn key_unchecked(&self) -> Key<T> {
+        self.key
+    }
+}
+
+impl<T> From<Key<T>> for WeakKey<T> {
+    #[inline]
+    fn from(key: Key<T>) -> Self {
+        key.downgrade()
+    }
+}
+
+impl<T> Hash for WeakKey<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl<T> Ord for WeakKey<T> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<T> PartialOrd for WeakKey<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Eq for WeakKey<T> {}
+
+impl<T> PartialEq for WeakKey<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Copy for WeakKey<T> {}
+
+impl<T> Clone for WeakKey<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Debug for WeakKey<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WeakKey<{}>({:?})",
+            type_name::<T>(),
+            self.key.slot.data()
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for WeakKey<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(&self.key, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for WeakKey<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        <Key<T> as serde::Deserialize>::deserialize(deserializer).map(Key::downgrade)
     }
 }
 
 /// A unique key for accessing a node with a dynamic type.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DynKey {
-    slot: DefaultKey,
+    pub(crate) slot: DefaultKey,
     pub(crate) node_type: TypeId,
 }
 
+impl Hash for DynKey {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.slot.hash(state);
+        self.node_type.hash(state);
+    }
+}
+
 impl DynKey {
     #[inline]
     pub fn new<T: 'static>(key: Key<T>) -> Self {
@@ -173,12 +593,27 @@ impl DynKey {
 
     #[inline]
     pub fn into_static<T: 'static>(self) -> Option<Key<T>> {
-        if TypeId::of::<T>() == self.node_type {
+        if self.is::<T>() {
             Some(Key::new(self.slot))
         } else {
             None
         }
     }
+
+    /// Check if the key is for a node of type `T`.
+    #[inline]
+    pub fn is<T: 'static>(&self) -> bool {
+        TypeId::of::<T>() == self.node_type
+    }
+}
+
+/// Shows the slot index and version, such as `3v1`. The key doesn't know the
+/// name of its type, but [`Nodes::type_name`][crate::Nodes::type_name] can
+/// look it up.
+impl Display for DynKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.slot.data())
+    }
 }
 
 impl<T: 'static> From<Key<T>> for DynKey {
@@ -187,7 +622,19 @@ impl<T: 'static> From<Key<T>> for DynKey {
     }
 }
 
+impl<T: 'static> From<ReservedKey<T>> for DynKey {
+    fn from(key: ReservedKey<T>) -> Self {
+        Self::new(key.key())
+    }
+}
+
 /// A unique key for accessing a reserved node slot of type `T`.
+///
+/// The slot stays reserved until it's filled with
+/// [`Nodes::insert_reserved`][crate::Nodes::insert_reserved], or removed
+/// with [`Nodes::remove`][crate::Nodes::remove] and [`ReservedKey::key`].
+/// Dropping the `ReservedKey` doesn't release the slot. See
+/// [`Reservation`][crate::Reservation] for a reservation that does.
 pub struct ReservedKey<T> {
     slot: DefaultKey,
     node_type: PhantomData<fn(DefaultKey) -> T>,
@@ -201,6 +648,21 @@ impl<T> ReservedKey<T> {
             node_type: PhantomData,
         }
     }
+
+    /// Treat `key` as a reserved slot. The slot will be overwritten if it's
+    /// already filled.
+    #[cfg(feature = "lua")]
+    #[inline]
+    pub(crate) fn from_key(key: Key<T>) -> Self {
+        Self::new(key.slot)
+    }
+
+    /// The key of the reserved slot. It doesn't point to a node until the
+    /// slot is filled, but it can be stored in other nodes in the meantime.
+    #[inline]
+    pub fn key(&self) -> Key<T> {
+        Key::new(self.slot)
+    }
 }
 
 impl<T> Hash for ReservedKey<T> {
@@ -212,15 +674,15 @@ impl<T> Hash for ReservedKey<T> {
 
 impl<T> Ord for ReservedKey<T> {
     #[inline]
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.slot.cmp(&other.slot)
     }
 }
 
 impl<T> PartialOrd for ReservedKey<T> {
     #[inline]
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.slot.partial_cmp(&other.slot)
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -233,6 +695,30 @@ impl<T> PartialEq for ReservedKey<T> {
     }
 }
 
+impl<T> Debug for ReservedKey<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ReservedKey<{}>({:?})",
+            type_name::<T>(),
+            self.slot.data()
+        )
+    }
+}
+
+/// The state of the slot for a key, from [`Nodes::slot_state`][crate::Nodes::slot_state].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SlotState {
+    /// The slot has a node.
+    Filled,
+    /// The slot is reserved, but the node hasn't been inserted yet.
+    Reserved,
+    /// There is no slot for the key. It has been removed or belongs to
+    /// another collection.
+    Vacant,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Slot<T> {
     Reserved,
     Filled(T),
@@ -271,14 +757,20 @@ impl_downcast!(DynNodeGroup<B> where B: Bounds);
 pub trait DynNodeGroup<B: Bounds>: Downcast {
     fn get_dyn(&self, key: DynKey) -> Option<&B::DynSelf>;
     fn get_dyn_mut(&mut self, key: DynKey) -> Option<&mut B::DynSelf>;
-    fn iter_dyn(&self) -> IterDyn<B>;
-    fn iter_dyn_mut(&mut self) -> IterDynMut<B>;
-    fn nodes_dyn(&self) -> NodesDyn<B> {
+    fn iter_dyn(&self) -> IterDyn<'_, B>;
+    fn iter_dyn_mut(&mut self) -> IterDynMut<'_, B>;
+    fn iter_ids_dyn(&self) -> IterIdsDyn<'_>;
+    fn remove_dyn(&mut self, key: DynKey) -> bool;
+    fn remove_reserved_dyn(&mut self, key: DynKey) -> bool;
+    fn slot_state_dyn(&self, key: DynKey) -> SlotState;
+    fn node_type_name(&self) -> &'static str;
+    fn stats(&self) -> TypeStats;
+    fn nodes_dyn(&self) -> NodesDyn<'_, B> {
         NodesDyn {
             inner: self.iter_dyn(),
         }
     }
-    fn nodes_dyn_mut(&mut self) -> NodesDynMut<B> {
+    fn nodes_dyn_mut(&mut self) -> NodesDynMut<'_, B> {
         NodesDynMut {
             inner: self.iter_dyn_mut(),
         }
@@ -294,13 +786,37 @@ impl<B: Bounds> DynNodeGroup<B> for Box<dyn DynNodeGroup<B> + 'static> {
         (**self).get_dyn_mut(key)
     }
 
-    fn iter_dyn(&self) -> IterDyn<B> {
+    fn iter_dyn(&self) -> IterDyn<'_, B> {
         (**self).iter_dyn()
     }
 
-    fn iter_dyn_mut(&mut self) -> IterDynMut<B> {
+    fn iter_dyn_mut(&mut self) -> IterDynMut<'_, B> {
         (**self).iter_dyn_mut()
     }
+
+    fn iter_ids_dyn(&self) -> IterIdsDyn<'_> {
+        (**self).iter_ids_dyn()
+    }
+
+    fn remove_dyn(&mut self, key: DynKey) -> bool {
+        (**self).remove_dyn(key)
+    }
+
+    fn remove_reserved_dyn(&mut self, key: DynKey) -> bool {
+        (**self).remove_reserved_dyn(key)
+    }
+
+    fn slot_state_dyn(&self, key: DynKey) -> SlotState {
+        (**self).slot_state_dyn(key)
+    }
+
+    fn node_type_name(&self) -> &'static str {
+        (**self).node_type_name()
+    }
+
+    fn stats(&self) -> TypeStats {
+        (**self).stats()
+    }
 }
 
 impl<B: Bounds> DynNodeGroup<B> for Box<dyn DynNodeGroup<B> + Send + Sync + 'static> {
@@ -312,13 +828,81 @@ impl<B: Bounds> DynNodeGroup<B> for Box<dyn DynNodeGroup<B> + Send + Sync + 'sta
         (**self).get_dyn_mut(key)
     }
 
-    fn iter_dyn(&self) -> IterDyn<B> {
+    fn iter_dyn(&self) -> IterDyn<'_, B> {
         (**self).iter_dyn()
     }
 
-    fn iter_dyn_mut(&mut self) -> IterDynMut<B> {
+    fn iter_dyn_mut(&mut self) -> IterDynMut<'_, B> {
         (**self).iter_dyn_mut()
     }
+
+    fn iter_ids_dyn(&self) -> IterIdsDyn<'_> {
+        (**self).iter_ids_dyn()
+    }
+
+    fn remove_dyn(&mut self, key: DynKey) -> bool {
+        (**self).remove_dyn(key)
+    }
+
+    fn remove_reserved_dyn(&mut self, key: DynKey) -> bool {
+        (**self).remove_reserved_dyn(key)
+    }
+
+    fn slot_state_dyn(&self, key: DynKey) -> SlotState {
+        (**self).slot_state_dyn(key)
+    }
+
+    fn node_type_name(&self) -> &'static str {
+        (**self).node_type_name()
+    }
+
+    fn stats(&self) -> TypeStats {
+        (**self).stats()
+    }
+}
+
+impl<B: Bounds> DynNodeGroup<B>
+    for Box<dyn DynNodeGroup<B> + RefUnwindSafe + UnwindSafe + 'static>
+{
+    fn get_dyn(&self, key: DynKey) -> Option<&B::DynSelf> {
+        (**self).get_dyn(key)
+    }
+
+    fn get_dyn_mut(&mut self, key: DynKey) -> Option<&mut <B as Bounds>::DynSelf> {
+        (**self).get_dyn_mut(key)
+    }
+
+    fn iter_dyn(&self) -> IterDyn<'_, B> {
+        (**self).iter_dyn()
+    }
+
+    fn iter_dyn_mut(&mut self) -> IterDynMut<'_, B> {
+        (**self).iter_dyn_mut()
+    }
+
+    fn iter_ids_dyn(&self) -> IterIdsDyn<'_> {
+        (**self).iter_ids_dyn()
+    }
+
+    fn remove_dyn(&mut self, key: DynKey) -> bool {
+        (**self).remove_dyn(key)
+    }
+
+    fn remove_reserved_dyn(&mut self, key: DynKey) -> bool {
+        (**self).remove_reserved_dyn(key)
+    }
+
+    fn slot_state_dyn(&self, key: DynKey) -> SlotState {
+        (**self).slot_state_dyn(key)
+    }
+
+    fn node_type_name(&self) -> &'static str {
+        (**self).node_type_name()
+    }
+
+    fn stats(&self) -> TypeStats {
+        (**self).stats()
+    }
 }
 
 impl<I, T, B> DynNodeGroup<B> for NodeGroup<I, T>
@@ -335,40 +919,142 @@ where
         self.get_mut(key.into_static()?).map(T::as_dyn_mut)
     }
 
-    fn iter_dyn(&self) -> IterDyn<B> {
+    // The typed iterators are too large to fit inline, so these go directly
+    // through the slots.
+    fn iter_dyn(&self) -> IterDyn<'_, B> {
         IterDyn {
-            inner: smallbox!(self.nodes.iter().filter_map(|(key, slot)| {
+            inner: smallbox!(self.nodes.iter().filter_map(|(slot, node)| {
                 Some((
-                    DynKey {
-                        slot: key,
-                        node_type: TypeId::of::<T>(),
-                    },
-                    slot.as_filled().map(T::as_dyn_ref)?,
+                    DynKey::new(Key::<T>::new(slot)),
+                    node.as_filled()?.as_dyn_ref(),
                 ))
             })),
         }
     }
 
-    fn iter_dyn_mut(&mut self) -> IterDynMut<B> {
+    fn iter_dyn_mut(&mut self) -> IterDynMut<'_, B> {
         IterDynMut {
-            inner: smallbox!(self.nodes.iter_mut().filter_map(|(key, slot)| {
+            inner: smallbox!(self.nodes.iter_mut().filter_map(|(slot, node)| {
                 Some((
+                    DynKey::new(Key::<T>::new(slot)),
+                    node.as_filled_mut()?.as_dyn_mut(),
+                ))
+            })),
+        }
+    }
+
+    fn iter_ids_dyn(&self) -> IterIdsDyn<'_> {
+        IterIdsDyn {
+            inner: smallbox!(self.id_map.iter().map(|(id, &slot)| {
+                (
+                    id as &dyn Any,
                     DynKey {
-                        slot: key,
+                        slot,
                         node_type: TypeId::of::<T>(),
                     },
-                    slot.as_filled_mut().map(T::as_dyn_mut)?,
-                ))
+                )
             })),
         }
     }
+
+    fn remove_dyn(&mut self, key: DynKey) -> bool {
+        key.into_static().and_then(|key| self.remove(key)).is_some()
+    }
+
+    fn remove_reserved_dyn(&mut self, key: DynKey) -> bool {
+        key.into_static()
+            .is_some_and(|key| self.remove_reserved(key))
+    }
+
+    fn slot_state_dyn(&self, key: DynKey) -> SlotState {
+        key.into_static()
+            .map_or(SlotState::Vacant, |key| self.slot_state(key))
+    }
+
+    fn node_type_name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+
+    fn stats(&self) -> TypeStats {
+        TypeStats {
+            type_id: TypeId::of::<T>(),
+            type_name: core::any::type_name::<T>(),
+            nodes: self.filled_len(),
+            reserved: self.reserved_len(),
+            capacity: self.nodes.capacity(),
+            slot_bytes: self.nodes.capacity() * core::mem::size_of::<Slot<T>>(),
+            heap_bytes: None,
+        }
+    }
 }
 
 pub trait BoxedNodeGroup {
     fn downcast_ref<I: 'static, T: 'static>(&self) -> Option<&NodeGroup<I, T>>;
     fn downcast_mut<I: 'static, T: 'static>(&mut self) -> Option<&mut NodeGroup<I, T>>;
+
+    #[inline]
+    fn try_group_ref<I: 'static, T: 'static>(&self) -> Result<&NodeGroup<I, T>, GroupTypeError> {
+        self.downcast_ref().ok_or_else(GroupTypeError::new::<I, T>)
+    }
+
+    #[inline]
+    fn try_group_mut<I: 'static, T: 'static>(
+        &mut self,
+    ) -> Result<&mut NodeGroup<I, T>, GroupTypeError> {
+        self.downcast_mut().ok_or_else(GroupTypeError::new::<I, T>)
+    }
+
+    /// Downcast the group, or panic with a [`GroupTypeError`].
+    #[inline]
+    fn group_ref<I: 'static, T: 'static>(&self) -> &NodeGroup<I, T> {
+        self.try_group_ref()
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Downcast the group mutably, or panic with a [`GroupTypeError`].
+    #[inline]
+    fn group_mut<I: 'static, T: 'static>(&mut self) -> &mut NodeGroup<I, T> {
+        self.try_group_mut()
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+}
+
+/// The node group for a type didn't have the expected type. This is an
+/// internal invariant violation, that should only happen if the group was
+/// created for a different ID type or with inconsistent bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupTypeError {
+    id_type: &'static str,
+    node_type: &'static str,
 }
 
+impl GroupTypeError {
+    fn new<I, T>() -> Self {
+        Self {
+            id_type: type_name::<I>(),
+            node_type: type_name::<T>(),
+        }
+    }
+
+    /// The name of the node type that was accessed.
+    #[inline]
+    pub fn node_type(&self) -> &'static str {
+        self.node_type
+    }
+}
+
+impl Display for GroupTypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the node group for `{}` could not be downcast to a group with `{}` IDs",
+            self.node_type, self.id_type
+        )
+    }
+}
+
+impl core::error::Error for GroupTypeError {}
+
 impl<B: Bounds> BoxedNodeGroup for Box<dyn DynNodeGroup<B> + 'static> {
     fn downcast_ref<I: 'static, T: 'static>(&self) -> Option<&NodeGroup<I, T>> {
         (**self).as_any().downcast_ref()
@@ -389,14 +1075,101 @@ impl<B: Bounds> BoxedNodeGroup for Box<dyn DynNodeGroup<B> + Send + Sync + 'stat
     }
 }
 
+impl<B: Bounds> BoxedNodeGroup for Box<dyn DynNodeGroup<B> + RefUnwindSafe + UnwindSafe + 'static> {
+    fn downcast_ref<I: 'static, T: 'static>(&self) -> Option<&NodeGroup<I, T>> {
+        (**self).as_any().downcast_ref()
+    }
+
+    fn downcast_mut<I: 'static, T: 'static>(&mut self) -> Option<&mut NodeGroup<I, T>> {
+        (**self).as_any_mut().downcast_mut()
+    }
+}
+
+/// Creates empty node groups for a node type that may only be known by its
+/// [`TypeId`]. See [`Nodes::register_with`][crate::Nodes::register_with].
+pub struct GroupFactory<I, B: Bounds> {
+    type_id: TypeId,
+    type_name: &'static str,
+    create: fn() -> <B::GroupBounds as GroupBounds>::BoxedGroup<B>,
+    id_type: PhantomData<fn(I)>,
+}
+
+impl<I, B> GroupFactory<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Make a factory for groups of nodes of type `T`.
+    #[inline]
+    pub fn new<T>() -> Self
+    where
+        T: BoundedBy<I, B>,
+    {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: core::any::type_name::<T>(),
+            create: || T::box_group(NodeGroup::<I, T>::default()),
+            id_type: PhantomData,
+        }
+    }
+
+    /// The type of the nodes in the created groups.
+    #[inline]
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// The name of the type of the nodes in the created groups.
+    #[inline]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    #[inline]
+    pub(crate) fn create(&self) -> <B::GroupBounds as GroupBounds>::BoxedGroup<B> {
+        (self.create)()
+    }
+}
+
+impl<I, B: Bounds> Clone for GroupFactory<I, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I, B: Bounds> Copy for GroupFactory<I, B> {}
+
 pub trait GroupBounds {
     type BoxedGroup<B>: DynNodeGroup<B> + BoxedNodeGroup
     where
         B: Bounds<GroupBounds = Self>;
+
+    /// The type erased alias maps, with the same restrictions as the
+    /// groups.
+    type DynAliasMap: AliasMap + ?Sized;
+
+    /// The type erased indexes, with the same restrictions as the groups.
+    type DynIndex: NodeIndex + ?Sized;
 }
 
+/// The inline space for the type erased node iterators. The iterators are
+/// moved to the heap if they don't fit, so the `large_iterators` feature
+/// doubles the space for bounds where they don't.
+#[cfg(not(feature = "large_iterators"))]
+type IterSpace = smallbox::space::S4;
+#[cfg(feature = "large_iterators")]
+type IterSpace = smallbox::space::S8;
+
+/// The inline space for the type erased ID iterators. The ID maps have
+/// larger iterators than the node slots.
+#[cfg(not(feature = "large_iterators"))]
+type IterIdsSpace = smallbox::space::S8;
+#[cfg(feature = "large_iterators")]
+type IterIdsSpace = smallbox::space::S16;
+
 pub struct IterDyn<'a, B: Bounds> {
-    inner: SmallBox<dyn Iterator<Item = (DynKey, &'a B::DynSelf)> + 'a, smallbox::space::S4>,
+    inner: SmallBox<dyn Iterator<Item = (DynKey, &'a B::DynSelf)> + 'a, IterSpace>,
 }
 
 impl<'a, B: Bounds> Iterator for IterDyn<'a, B> {
@@ -405,10 +1178,14 @@ impl<'a, B: Bounds> Iterator for IterDyn<'a, B> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 pub struct IterDynMut<'a, B: Bounds> {
-    inner: SmallBox<dyn Iterator<Item = (DynKey, &'a mut B::DynSelf)> + 'a, smallbox::space::S4>,
+    inner: SmallBox<dyn Iterator<Item = (DynKey, &'a mut B::DynSelf)> + 'a, IterSpace>,
 }
 
 impl<'a, B: Bounds> Iterator for IterDynMut<'a, B> {
@@ -417,8 +1194,164 @@ impl<'a, B: Bounds> Iterator for IterDynMut<'a, B> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterates over the filled slots in a node group, using the count of
+/// filled slots for the exact length.
+struct FilledSlots<S> {
+    slots: S,
+    remaining: usize,
+}
+
+impl<S> FilledSlots<S>
+where
+    S: SlotsIterator,
+{
+    #[inline]
+    fn new(slots: S, filled: usize) -> Self {
+        Self {
+            slots,
+            remaining: filled,
+        }
+    }
+}
+
+impl<S> Iterator for FilledSlots<S>
+where
+    S: SlotsIterator,
+{
+    type Item = (DefaultKey, S::Value);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.slots.next_filled()?;
+        self.remaining -= 1;
+
+        Some(item)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<S> ExactSizeIterator for FilledSlots<S> where S: SlotsIterator {}
+
+/// Slot map iterators that can skip reserved slots.
+trait SlotsIterator {
+    type Value;
+
+    fn next_filled(&mut self) -> Option<(DefaultKey, Self::Value)>;
+}
+
+impl<'a, T> SlotsIterator for slotmap::basic::Iter<'a, DefaultKey, Slot<T>> {
+    type Value = &'a T;
+
+    #[inline]
+    fn next_filled(&mut self) -> Option<(DefaultKey, &'a T)> {
+        self.find_map(|(slot, node)| Some((slot, node.as_filled()?)))
+    }
+}
+
+impl<'a, T> SlotsIterator for slotmap::basic::IterMut<'a, DefaultKey, Slot<T>> {
+    type Value = &'a mut T;
+
+    #[inline]
+    fn next_filled(&mut self) -> Option<(DefaultKey, &'a mut T)> {
+        self.find_map(|(slot, node)| Some((slot, node.as_filled_mut()?)))
+    }
+}
+
+/// Iterates over the keys of the filled slots in a node group.
+pub(crate) struct GroupKeys<'a, T> {
+    inner: GroupIter<'a, T>,
+}
+
+impl<'a, T> Iterator for GroupKeys<'a, T> {
+    type Item = Key<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for GroupKeys<'a, T> {}
+
+/// Iterates over the keys and nodes of the filled slots in a node group.
+pub(crate) struct GroupIter<'a, T> {
+    inner: FilledSlots<slotmap::basic::Iter<'a, DefaultKey, Slot<T>>>,
+}
+
+impl<'a, T> Iterator for GroupIter<'a, T> {
+    type Item = (Key<T>, &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (slot, node) = self.inner.next()?;
+        Some((Key::new(slot), node))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for GroupIter<'a, T> {}
+
+/// Iterates over the keys and mutable nodes of the filled slots in a node
+/// group.
+pub(crate) struct GroupIterMut<'a, T> {
+    inner: FilledSlots<slotmap::basic::IterMut<'a, DefaultKey, Slot<T>>>,
+}
+
+impl<'a, T> Iterator for GroupIterMut<'a, T> {
+    type Item = (Key<T>, &'a mut T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (slot, node) = self.inner.next()?;
+        Some((Key::new(slot), node))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for GroupIterMut<'a, T> {}
+
+/// Iterates over the IDs in a node group, with the ID type erased.
+pub struct IterIdsDyn<'a> {
+    inner: SmallBox<dyn Iterator<Item = (&'a dyn Any, DynKey)> + 'a, IterIdsSpace>,
+}
+
+impl<'a> Iterator for IterIdsDyn<'a> {
+    type Item = (&'a dyn Any, DynKey);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
+impl<'a> ExactSizeIterator for IterIdsDyn<'a> {}
+
 pub struct NodesDyn<'a, B: Bounds> {
     inner: IterDyn<'a, B>,
 }
@@ -430,6 +1363,10 @@ impl<'a, B: Bounds> Iterator for NodesDyn<'a, B> {
         let (_, node) = self.inner.next()?;
         Some(node)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 pub struct NodesDynMut<'a, B: Bounds> {
@@ -443,18 +1380,90 @@ impl<'a, B: Bounds> Iterator for NodesDynMut<'a, B> {
         let (_, node) = self.inner.next()?;
         Some(node)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::bounds::{AnyBounds, GroupBoundedBy};
-
-    use super::NodeGroup;
+    use alloc::string::String;
+    use core::any::{type_name, TypeId};
+
+    use crate::{
+        bounds::{AnyBounds, GroupBoundedBy, SendSyncBounds, UnwindSafeBounds},
+        make_bounds, Nodes,
+    };
+
+    use super::{GroupTypeError, NodeGroup};
+
+    make_bounds!(DebugBounds: core::fmt::Debug + 'static);
+    make_bounds!(SendSyncDebugBounds<GroupBounds = SendSyncBounds>: core::fmt::Debug + Send + Sync + 'static);
+
+    macro_rules! assert_iterators_on_stack {
+        ($bounds:ty) => {{
+            let mut group = String::box_group::<$bounds>(NodeGroup::<String, String>::default());
+            assert!(!group.iter_dyn().inner.is_heap());
+            assert!(!group.iter_dyn_mut().inner.is_heap());
+            assert!(!group.iter_ids_dyn().inner.is_heap());
+        }};
+    }
 
     #[test]
     fn iterators_are_on_stack() {
-        let mut group = String::box_group::<AnyBounds>(NodeGroup::<String, String>::default());
-        assert!(!group.iter_dyn().inner.is_heap());
-        assert!(!group.iter_dyn_mut().inner.is_heap());
+        assert_iterators_on_stack!(AnyBounds);
+        assert_iterators_on_stack!(SendSyncBounds);
+        assert_iterators_on_stack!(UnwindSafeBounds);
+    }
+
+    #[test]
+    fn slot_counts_follow_mutations() {
+        let mut group = NodeGroup::<(), String>::default();
+        let first = group.insert("a".into());
+        let second = group.insert("a".into());
+        let reserved = group.reserve();
+        assert_eq!((group.filled_len(), group.reserved_len()), (2, 1));
+
+        let (taken, node) = group.take(first).unwrap();
+        assert_eq!((group.filled_len(), group.reserved_len()), (1, 2));
+        assert!(group.put_back(taken.key(), node).is_ok());
+        assert!(group.replace(reserved.key(), "b".into()).is_none());
+        assert_eq!((group.filled_len(), group.reserved_len()), (3, 0));
+
+        assert_eq!(group.dedup().len(), 1);
+        assert_eq!((group.filled_len(), group.reserved_len()), (2, 0));
+
+        assert!(group.remove(second).is_none());
+        assert!(group.remove(first).is_some());
+        assert_eq!((group.filled_len(), group.reserved_len()), (1, 0));
+        assert_eq!(group.iter().len(), 1);
+    }
+
+    #[test]
+    fn custom_bounds_iterators_are_on_stack() {
+        assert_iterators_on_stack!(DebugBounds);
+        assert_iterators_on_stack!(SendSyncDebugBounds);
+    }
+
+    #[test]
+    fn mismatched_group_type_is_an_error() {
+        let key = Nodes::<()>::new().insert(String::from("a"));
+
+        let mut nodes = Nodes::<()>::new();
+        nodes.node_groups.insert(
+            TypeId::of::<String>(),
+            u32::box_group::<AnyBounds>(NodeGroup::<(), u32>::default()),
+        );
+
+        let expected = GroupTypeError::new::<(), String>();
+        assert_eq!(expected.node_type(), type_name::<String>());
+        assert_eq!(nodes.try_get(key), Err(expected));
+
+        let Err((node, error)) = nodes.try_insert(String::from("b")) else {
+            panic!("the node should not be inserted");
+        };
+        assert_eq!(node, "b");
+        assert_eq!(error, expected);
     }
 }
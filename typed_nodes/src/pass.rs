@@ -0,0 +1,404 @@
+//! Passes that update the nodes of one type at a time.
+//!
+//! A [`Pass`] is a step in a pipeline, such as in a compiler, that visits each
+//! node of one type and updates it, while reading other nodes. It declares
+//! the types it writes and reads, and [`Nodes::run_pass`] takes care of
+//! visiting the nodes.
+//!
+//! ```
+//! use typed_nodes::{pass::{NodeTypes, Pass}, Key, Nodes};
+//!
+//! struct Constant {
+//!     value: f32,
+//! }
+//!
+//! struct Scale {
+//!     factor: Key<Constant>,
+//!     size: f32,
+//! }
+//!
+//! struct ApplyScale;
+//!
+//! impl Pass for ApplyScale {
+//!     type Node = Scale;
+//!
+//!     fn reads(&self) -> NodeTypes {
+//!         NodeTypes::new().with::<Constant>()
+//!     }
+//!
+//!     fn run(&mut self, _key: Key<Scale>, node: &mut Scale, rest: &Nodes) {
+//!         node.size *= rest.get(node.factor).unwrap().value;
+//!     }
+//! }
+//!
+//! let mut nodes = Nodes::new();
+//! let factor = nodes.insert(Constant { value: 2.0 });
+//! let scale = nodes.insert(Scale { factor, size: 1.5 });
+//!
+//! nodes.run_pass(&mut ApplyScale);
+//! assert_eq!(nodes.get(scale).unwrap().size, 3.0);
+//! ```
+
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::{
+    bounds::{AnyBounds, BoundedBy, Bounds},
+    graph::{KeyRemap, RemapKeys, VisitKeys},
+    DynKey, HashSet, Key, Nodes,
+};
+
+/// A step that updates each node of type [`Pass::Node`], using
+/// [`Nodes::run_pass`] or [`Nodes::run_pass_in_dependency_order`].
+pub trait Pass<I = (), B: Bounds = AnyBounds> {
+    /// The type of the nodes that the pass writes to. The pass is run once
+    /// for each of them.
+    type Node: BoundedBy<I, B>;
+
+    /// The types of the other nodes that the pass reads. Only these types
+    /// are followed when finding the dependencies of a node, in
+    /// [`Nodes::run_pass_in_dependency_order`].
+    fn reads(&self) -> NodeTypes {
+        NodeTypes::new()
+    }
+
+    /// Update `node`, which has the key `key`. The node is moved out of the
+    /// nodes while the pass runs, so it's not part of `rest`.
+    fn run(&mut self, key: Key<Self::Node>, node: &mut Self::Node, rest: &Nodes<I, B>);
+}
+
+/// What to do with a node in [`Nodes::rewrite`].
+pub enum Rewrite<T> {
+    /// Keep the node as it is.
+    Keep,
+    /// Replace the node with a new node, while keeping its key.
+    Replace(T),
+    /// Remove the node and let all references to it refer to another node
+    /// instead.
+    Redirect(Key<T>),
+    /// Remove the node. References to it are left dangling, so they no
+    /// longer point to any node.
+    Remove,
+}
+
+/// A set of node types, such as the ones a [`Pass`] reads.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeTypes {
+    types: Vec<TypeId>,
+}
+
+impl NodeTypes {
+    /// Create an empty set.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `T` to the set.
+    pub fn with<T: 'static>(mut self) -> Self {
+        self.insert(TypeId::of::<T>());
+        self
+    }
+
+    /// Add the type with `type_id` to the set. Returns `false` if it was
+    /// already in the set.
+    pub fn insert(&mut self, type_id: TypeId) -> bool {
+        if self.types.contains(&type_id) {
+            return false;
+        }
+
+        self.types.push(type_id);
+        true
+    }
+
+    /// Check if the type with `type_id` is in the set.
+    #[inline]
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.types.contains(&type_id)
+    }
+
+    /// Iterate over the types, in the order they were added.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.types.iter().copied()
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Run `pass` once for each node of type [`Pass::Node`], in the order of
+    /// [`Nodes::keys`]. Returns the number of nodes the pass was run for.
+    pub fn run_pass<P>(&mut self, pass: &mut P) -> usize
+    where
+        P: Pass<I, B> + ?Sized,
+    {
+        let keys: Vec<_> = self.keys::<P::Node>().collect();
+        self.run_pass_for_keys(pass, keys)
+    }
+
+    fn run_pass_for_keys<P>(&mut self, pass: &mut P, keys: Vec<Key<P::Node>>) -> usize
+    where
+        P: Pass<I, B> + ?Sized,
+    {
+        let mut count = 0;
+
+        for key in keys {
+            let Some(mut node_and_rest) = self.get_mut_and_rest(key) else {
+                continue;
+            };
+
+            let (node, rest) = node_and_rest.split();
+            pass.run(key, node, rest);
+            count += 1;
+        }
+
+        count
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys,
+{
+    /// Run `pass` once for each node of type [`Pass::Node`], where each node
+    /// comes after the nodes of the same type that it depends on. A node
+    /// depends on the nodes it has keys to, and on their dependencies, as
+    /// long as they are of a type that the pass reads or writes. Nodes in
+    /// cycles are visited in the order they are found. Returns the number of
+    /// nodes the pass was run for.
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     graph::{KeyRemap, RemapKeys, VisitKeys},
+    ///     make_bounds,
+    ///     pass::{NodeTypes, Pass},
+    ///     Key, Nodes,
+    /// };
+    ///
+    /// make_bounds!(ExprBounds: VisitKeys + 'static);
+    ///
+    /// #[derive(VisitKeys)]
+    /// struct Sum {
+    ///     value: u32,
+    ///     terms: Vec<Key<Term>>,
+    /// }
+    ///
+    /// #[derive(VisitKeys)]
+    /// enum Term {
+    ///     Constant(u32),
+    ///     Sum(Key<Sum>),
+    /// }
+    ///
+    /// struct Evaluate;
+    ///
+    /// impl Pass<(), ExprBounds> for Evaluate {
+    ///     type Node = Sum;
+    ///
+    ///     fn reads(&self) -> NodeTypes {
+    ///         NodeTypes::new().with::<Term>()
+    ///     }
+    ///
+    ///     fn run(&mut self, _key: Key<Sum>, node: &mut Sum, rest: &Nodes<(), ExprBounds>) {
+    ///         node.value = node
+    ///             .terms
+    ///             .iter()
+    ///             .map(|&term| match rest.get(term).unwrap() {
+    ///                 Term::Constant(value) => *value,
+    ///                 Term::Sum(sum) => rest.get(*sum).unwrap().value,
+    ///             })
+    ///             .sum();
+    ///     }
+    /// }
+    ///
+    /// let mut nodes = Nodes::<(), ExprBounds>::new();
+    /// let outer_sum = nodes.insert(Sum { value: 0, terms: vec![] });
+    ///
+    /// // The inner sum comes after the outer sum, but is evaluated first.
+    /// let terms = vec![nodes.insert(Term::Constant(2)), nodes.insert(Term::Constant(3))];
+    /// let inner_sum = nodes.insert(Sum { value: 0, terms });
+    /// let terms = vec![nodes.insert(Term::Constant(1)), nodes.insert(Term::Sum(inner_sum))];
+    /// nodes.get_mut(outer_sum).unwrap().terms = terms;
+    ///
+    /// assert_eq!(nodes.run_pass_in_dependency_order(&mut Evaluate), 2);
+    /// assert_eq!(nodes.get(outer_sum).unwrap().value, 6);
+    /// ```
+    pub fn run_pass_in_dependency_order<P>(&mut self, pass: &mut P) -> usize
+    where
+        P: Pass<I, B> + ?Sized,
+    {
+        let mut followed = pass.reads();
+        followed.insert(TypeId::of::<P::Node>());
+
+        let keys = self.dependency_order::<P::Node>(|type_id| followed.contains(type_id));
+        self.run_pass_for_keys(pass, keys)
+    }
+
+    /// Sort the nodes of type `T` after their dependencies, by walking the
+    /// graph depth first and collecting the nodes after their children.
+    fn dependency_order<T>(&self, follow: impl Fn(TypeId) -> bool) -> Vec<Key<T>>
+    where
+        T: BoundedBy<I, B>,
+    {
+        let children = |key: DynKey| {
+            let mut children = Vec::new();
+
+            if let Some(node) = self.get_dyn(key) {
+                node.visit_keys(&mut |child| {
+                    if follow(child.node_type) {
+                        children.push(child);
+                    }
+                });
+            }
+
+            children
+        };
+
+        let mut order = Vec::new();
+        let mut visited: HashSet<DynKey> = HashSet::with_hasher(Default::default());
+        let mut stack = Vec::new();
+
+        for root in self.keys::<T>() {
+            if !visited.insert(root.into()) {
+                continue;
+            }
+
+            stack.push((DynKey::from(root), children(root.into()), 0));
+
+            while let Some((key, key_children, next_child)) = stack.last_mut() {
+                if let Some(&child) = key_children.get(*next_child) {
+                    *next_child += 1;
+
+                    if visited.insert(child) {
+                        stack.push((child, children(child), 0));
+                    }
+                } else {
+                    order.extend(key.into_static::<T>());
+                    stack.pop();
+                }
+            }
+        }
+
+        order
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: VisitKeys + RemapKeys,
+{
+    /// Rewrite each node of type `T` by replacing it, redirecting its key to
+    /// another node, or removing it. The nodes are visited in dependency
+    /// order, so the nodes a node depends on are rewritten before it, and
+    /// their keys have already been redirected when `rewrite` is called.
+    ///
+    /// Redirected nodes are removed at the end, and the references to them
+    /// in all nodes are updated. IDs and aliases for them will also refer to
+    /// their replacements. The returned [`KeyRemap`] can be used for updating
+    /// any other references.
+    ///
+    /// Removed nodes are not replaced in the references to them, so any
+    /// such references are left dangling, and [`Nodes::get`] returns `None`
+    /// for them. Use [`Rewrite::Redirect`] to keep them pointing to a node.
+    ///
+    /// The nodes have to implement both [`VisitKeys`] and [`RemapKeys`],
+    /// such as with the [`RemapBounds`][crate::graph::RemapBounds] preset.
+    ///
+    /// ```
+    /// use typed_nodes::{
+    ///     graph::{RemapBounds, RemapKeys, VisitKeys},
+    ///     pass::Rewrite,
+    ///     Key, Nodes,
+    /// };
+    ///
+    /// #[derive(VisitKeys, RemapKeys)]
+    /// enum Expr {
+    ///     Constant(i32),
+    ///     Variable(String),
+    ///     Add(Key<Expr>, Key<Expr>),
+    /// }
+    ///
+    /// let mut nodes = Nodes::<(), RemapBounds>::new();
+    /// let zero = nodes.insert(Expr::Constant(0));
+    /// let one = nodes.insert(Expr::Constant(1));
+    /// let two = nodes.insert(Expr::Constant(2));
+    /// let x = nodes.insert(Expr::Variable("x".into()));
+    /// let sum = nodes.insert(Expr::Add(one, two));
+    /// let plus_zero = nodes.insert(Expr::Add(x, zero));
+    /// let root = nodes.insert(Expr::Add(sum, plus_zero));
+    ///
+    /// let remap = nodes.rewrite::<Expr>(|_key, expr, nodes| {
+    ///     let &Expr::Add(left, right) = expr else {
+    ///         return Rewrite::Keep;
+    ///     };
+    ///
+    ///     match (nodes.get(left).unwrap(), nodes.get(right).unwrap()) {
+    ///         (Expr::Constant(left), Expr::Constant(right)) => {
+    ///             Rewrite::Replace(Expr::Constant(left + right))
+    ///         }
+    ///         (_, Expr::Constant(0)) => Rewrite::Redirect(left),
+    ///         _ => Rewrite::Keep,
+    ///     }
+    /// });
+    ///
+    /// assert!(remap.get(plus_zero) == x);
+    /// assert!(nodes.get(plus_zero).is_none());
+    /// assert!(matches!(nodes.get(sum), Some(Expr::Constant(3))));
+    /// assert!(matches!(nodes.get(root), Some(&Expr::Add(left, right)) if left == sum && right == x));
+    /// ```
+    pub fn rewrite<T>(
+        &mut self,
+        mut rewrite: impl FnMut(Key<T>, &T, &Nodes<I, B>) -> Rewrite<T>,
+    ) -> KeyRemap
+    where
+        T: BoundedBy<I, B>,
+    {
+        let mut remap = KeyRemap::default();
+        let mut redirected = Vec::new();
+
+        for key in self.dependency_order::<T>(|_| true) {
+            if !remap.is_empty() {
+                if let Some(node) = self.get_dyn_mut(key.into()) {
+                    node.remap_keys(&remap);
+                }
+            }
+
+            let Some(node) = self.get(key) else {
+                continue;
+            };
+
+            match rewrite(key, node, self) {
+                Rewrite::Keep => {}
+                Rewrite::Replace(node) => {
+                    self.replace(key, node);
+                }
+                Rewrite::Redirect(into) => {
+                    // Redirecting to a node that leads back to this node
+                    // would remove both of them.
+                    let into = remap.get(into);
+                    if into != key {
+                        remap.redirect(key, into);
+                        redirected.push(key);
+                    }
+                }
+                Rewrite::Remove => {
+                    self.remove(key);
+                }
+            }
+        }
+
+        for key in redirected {
+            self.merge(key, remap.get(key));
+        }
+
+        self.remap_keys(&remap);
+
+        remap
+    }
+}
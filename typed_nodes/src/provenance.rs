@@ -0,0 +1,66 @@
+use alloc::sync::Arc;
+use core::fmt::Display;
+
+#[cfg(feature = "lua")]
+use crate::mlua::TableId;
+
+/// Information about where a node came from, such as the name of the script it
+/// was parsed from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Provenance {
+    source: Option<Arc<str>>,
+    #[cfg(feature = "lua")]
+    table_id: Option<TableId>,
+}
+
+impl Provenance {
+    #[cfg(feature = "lua")]
+    #[inline]
+    pub fn new(source: Option<Arc<str>>, table_id: Option<TableId>) -> Self {
+        Self { source, table_id }
+    }
+
+    /// Create a provenance that only has the name of the source.
+    #[inline]
+    pub fn from_source(source: impl Into<Arc<str>>) -> Self {
+        Self {
+            source: Some(source.into()),
+            #[cfg(feature = "lua")]
+            table_id: None,
+        }
+    }
+
+    /// The name of the script or file the node was parsed from, if it was
+    /// known.
+    #[inline]
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// The ID of the table the node was parsed from, if any.
+    #[cfg(feature = "lua")]
+    #[inline]
+    pub fn table_id(&self) -> Option<TableId> {
+        self.table_id
+    }
+}
+
+impl Display for Provenance {
+    #[cfg(feature = "lua")]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (&self.source, self.table_id) {
+            (Some(source), Some(table_id)) => write!(f, "{source} (table {table_id})"),
+            (Some(source), None) => write!(f, "{source}"),
+            (None, Some(table_id)) => write!(f, "table {table_id}"),
+            (None, None) => write!(f, "unknown source"),
+        }
+    }
+
+    #[cfg(not(feature = "lua"))]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{source}"),
+            None => write!(f, "unknown source"),
+        }
+    }
+}
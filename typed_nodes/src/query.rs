@@ -0,0 +1,156 @@
+//! Simple queries over nodes of multiple types.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::any::TypeId;
+
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    node_group::{self, DynNodeGroup},
+    BoxedGroupOf, DynKey, FlatMapGroups, Nodes,
+};
+
+type Filter<'a, B> = Box<dyn Fn(&<B as Bounds>::DynSelf) -> bool + 'a>;
+
+/// A query for the keys of nodes, created by [`Nodes::query`]. The query is
+/// built by limiting the node types and adding filters, and then iterated to
+/// get the keys of the matching nodes.
+///
+/// ```
+/// use typed_nodes::{DynKey, Nodes};
+///
+/// struct Light {
+///     intensity: f32,
+/// }
+///
+/// struct Camera;
+/// struct Mesh;
+///
+/// let mut nodes = Nodes::<()>::new();
+/// let light = nodes.insert(Light { intensity: 0.5 });
+/// nodes.insert(Light { intensity: 0.0 });
+/// let camera = nodes.insert(Camera);
+/// nodes.insert(Mesh);
+///
+/// let keys: Vec<DynKey> = nodes
+///     .query()
+///     .of_type::<Light>()
+///     .or_type::<Camera>()
+///     .filter(|node| {
+///         node.downcast_ref::<Light>()
+///             .map_or(true, |light| light.intensity > 0.0)
+///     })
+///     .into_iter()
+///     .collect();
+///
+/// assert!(keys == [light.into(), camera.into()]);
+/// ```
+pub struct Query<'a, I, B: Bounds> {
+    nodes: &'a Nodes<I, B>,
+    node_types: Option<Vec<TypeId>>,
+    filters: Vec<Filter<'a, B>>,
+}
+
+impl<'a, I, B> Query<'a, I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Only include nodes of type `T`. This replaces any previous node
+    /// types.
+    pub fn of_type<T>(mut self) -> Self
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.node_types = Some(alloc::vec![TypeId::of::<T>()]);
+        self
+    }
+
+    /// Include nodes of type `T`, in addition to the node types that are
+    /// already included. The nodes are visited in the order their types were
+    /// added.
+    pub fn or_type<T>(mut self) -> Self
+    where
+        T: BoundedBy<I, B>,
+    {
+        let node_types = self.node_types.get_or_insert_with(Vec::new);
+        let node_type = TypeId::of::<T>();
+
+        if !node_types.contains(&node_type) {
+            node_types.push(node_type);
+        }
+
+        self
+    }
+
+    /// Only include nodes where `filter` returns `true`. All filters have to
+    /// match if there are more than one.
+    pub fn filter(mut self, filter: impl Fn(&B::DynSelf) -> bool + 'a) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+}
+
+impl<'a, I, B> IntoIterator for Query<'a, I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    type Item = DynKey;
+    type IntoIter = QueryKeys<'a, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let groups: Vec<_> = match self.node_types {
+            Some(node_types) => node_types
+                .iter()
+                .filter_map(|node_type| self.nodes.node_groups.get(node_type))
+                .collect(),
+            None => self.nodes.node_groups.values().collect(),
+        };
+
+        QueryKeys {
+            inner: groups.into_iter().flat_map(DynNodeGroup::<B>::iter_dyn),
+            filters: self.filters,
+        }
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Start a query for the keys of nodes of one or more types. All node
+    /// types are included until [`Query::of_type`] or [`Query::or_type`] is
+    /// used, and are then visited in arbitrary order.
+    #[inline]
+    pub fn query(&self) -> Query<'_, I, B> {
+        Query {
+            nodes: self,
+            node_types: None,
+            filters: Vec::new(),
+        }
+    }
+}
+
+/// Iterates over the keys of the nodes that match a [`Query`].
+pub struct QueryKeys<'a, B: Bounds> {
+    inner: FlatMapGroups<
+        alloc::vec::IntoIter<&'a BoxedGroupOf<B>>,
+        &'a BoxedGroupOf<B>,
+        node_group::IterDyn<'a, B>,
+    >,
+    filters: Vec<Filter<'a, B>>,
+}
+
+impl<'a, B: Bounds> Iterator for QueryKeys<'a, B> {
+    type Item = DynKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.find_map(|(key, node)| {
+            self.filters
+                .iter()
+                .all(|filter| filter(node))
+                .then_some(key)
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use alloc::vec::Vec;
+
+use crate::{DynKey, HashMap};
+
+/// The keys that have been redirected with
+/// [`Nodes::redirect`][crate::Nodes::redirect], and the keys that redirect
+/// to each node, so they can be updated without going through all of them.
+#[derive(Default)]
+pub(crate) struct Redirects {
+    targets: HashMap<DynKey, DynKey>,
+    sources: HashMap<DynKey, Vec<DynKey>>,
+}
+
+impl Redirects {
+    #[inline]
+    pub(crate) fn get(&self, key: DynKey) -> Option<DynKey> {
+        if self.targets.is_empty() {
+            return None;
+        }
+
+        self.targets.get(&key).copied()
+    }
+
+    /// Redirect `from` to `into`, including the keys that are already
+    /// redirected to `from`.
+    pub(crate) fn insert(&mut self, from: DynKey, into: DynKey) {
+        let mut sources = self.sources.remove(&from).unwrap_or_default();
+
+        for source in &sources {
+            self.targets.insert(*source, into);
+        }
+
+        sources.push(from);
+        self.targets.insert(from, into);
+        self.sources.entry(into).or_default().extend(sources);
+    }
+
+    /// Remove the redirections to `key`, after its node has been removed.
+    pub(crate) fn remove_target(&mut self, key: DynKey) {
+        for source in self.sources.remove(&key).unwrap_or_default() {
+            self.targets.remove(&source);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{any::TypeId, hash::Hash};
+
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    node_group::{BoxedNodeGroup, DynNodeGroup, NodeGroup},
+    DynKey, HashMap, Key, Nodes, ReservedKey,
+};
+
+/// The smallest number of tracked reservations that triggers a search for
+/// abandoned ones.
+const MIN_SWEEP_LEN: usize = 64;
+
+/// A reserved node slot that is released if it's dropped before it's
+/// filled. It's created by [`Nodes::reserve`] or [`Nodes::reserve_id`] and
+/// filled with [`Nodes::fulfill`].
+///
+/// Dropped reservations are released the next time a reservation is made,
+/// or when [`Nodes::release_abandoned`] is called. Their slots and IDs are
+/// removed, as if they were removed with [`Nodes::remove`].
+///
+/// ```
+/// use typed_nodes::{Key, Nodes};
+///
+/// struct Room {
+///     exit: Option<Key<Room>>,
+/// }
+///
+/// let mut nodes = Nodes::<&str>::new();
+///
+/// // The hall refers to the cellar before it's inserted.
+/// let (cellar, _) = nodes.reserve_id::<Room>("cellar");
+/// let hall = nodes.insert(Room { exit: Some(cellar.key()) });
+/// let cellar = nodes.fulfill(cellar, Room { exit: Some(hall) }).ok().unwrap();
+///
+/// assert!(nodes.get(cellar).unwrap().exit == Some(hall));
+///
+/// // An unfulfilled reservation doesn't leave anything behind.
+/// let (attic, _) = nodes.reserve_id::<Room>("attic");
+/// drop(attic);
+///
+/// assert_eq!(nodes.release_abandoned(), 1);
+/// assert!(nodes.get_key::<Room, _>("attic").is_none());
+/// ```
+#[must_use = "the reserved slot is released if the reservation is dropped"]
+pub struct Reservation<T> {
+    reserved_key: ReservedKey<T>,
+    _token: Arc<()>,
+}
+
+impl<T> Reservation<T> {
+    /// The key of the reserved slot. It doesn't point to a node until the
+    /// reservation is fulfilled, but it can be stored in other nodes in the
+    /// meantime.
+    #[inline]
+    pub fn key(&self) -> Key<T> {
+        self.reserved_key.key()
+    }
+}
+
+/// Keeps track of the slots that are reserved with a [`Reservation`].
+#[derive(Default)]
+pub(crate) struct Reservations {
+    tokens: HashMap<DynKey, Weak<()>>,
+    sweep_at: usize,
+}
+
+impl Reservations {
+    fn track<T: 'static>(&mut self, reserved_key: ReservedKey<T>) -> Reservation<T> {
+        let token = Arc::new(());
+        self.tokens
+            .insert(reserved_key.key().into(), Arc::downgrade(&token));
+
+        Reservation {
+            reserved_key,
+            _token: token,
+        }
+    }
+
+    fn needs_sweep(&self) -> bool {
+        self.tokens.len() >= self.sweep_at.max(MIN_SWEEP_LEN)
+    }
+
+    /// Stop tracking and return the keys of the reservations that have been
+    /// dropped.
+    fn take_abandoned(&mut self) -> Vec<DynKey> {
+        let mut abandoned = Vec::new();
+
+        self.tokens.retain(|&key, token| {
+            if token.strong_count() == 0 {
+                abandoned.push(key);
+                false
+            } else {
+                true
+            }
+        });
+
+        self.sweep_at = self.tokens.len() * 2;
+
+        abandoned
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Reserve a slot for a node of type `T`, that is released if the
+    /// reservation is dropped before it's fulfilled.
+    pub fn reserve<T>(&mut self) -> Reservation<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        if self.reservations.needs_sweep() {
+            self.release_abandoned();
+        }
+
+        let reserved_key = self.reserve_untracked();
+        self.reservations.track(reserved_key)
+    }
+
+    /// Reserve a slot for a node of type `T`, that stays reserved until it's
+    /// filled or removed.
+    pub(crate) fn reserve_untracked<T>(&mut self) -> ReservedKey<T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.node_groups
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()))
+            .group_mut::<I, T>()
+            .reserve()
+    }
+
+    /// Insert `node` in the slot that was reserved by `reservation`. The
+    /// node is returned if the slot has been removed.
+    pub fn fulfill<T>(&mut self, reservation: Reservation<T>, node: T) -> Result<Key<T>, T>
+    where
+        T: BoundedBy<I, B>,
+    {
+        self.reservations
+            .tokens
+            .remove(&DynKey::from(reservation.key()));
+
+        self.try_insert_reserved(reservation.reserved_key, node)
+    }
+
+    /// Remove the slots and IDs of all dropped reservations that weren't
+    /// fulfilled. Returns the number of removed slots.
+    pub fn release_abandoned(&mut self) -> usize {
+        let mut released = 0;
+
+        for key in self.reservations.take_abandoned() {
+            let Some(group) = self.node_groups.get_mut(&key.node_type) else {
+                continue;
+            };
+
+            if group.remove_reserved_dyn(key) {
+                self.remove_metadata(key);
+                released += 1;
+            }
+        }
+
+        released
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: Hash + Eq + 'static,
+    B: Bounds,
+{
+    /// Reserve a slot for a node of type `T` and assign `id` to it, like
+    /// [`Nodes::reserve_with_id`]. The slot and the ID are released if the
+    /// reservation is dropped before it's fulfilled. The previous key for
+    /// `id` is returned if there was one.
+    pub fn reserve_id<T>(&mut self, id: I) -> (Reservation<T>, Option<Key<T>>)
+    where
+        T: BoundedBy<I, B>,
+    {
+        if self.reservations.needs_sweep() {
+            self.release_abandoned();
+        }
+
+        let (reserved_key, old_key) = self
+            .node_groups
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| T::box_group(NodeGroup::<I, T>::default()))
+            .group_mut::<I, T>()
+            .reserve_with_id(id);
+
+        (self.reservations.track(reserved_key), old_key)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    bounds::{BoundedBy, Bounds},
+    HashMap, Key, Nodes,
+};
+
+/// Extra values for nodes of type `T`, stored outside of the nodes
+/// themselves. It's like a map from keys to values, but can also forget the
+/// values for nodes that have been removed.
+///
+/// ```
+/// use typed_nodes::{Nodes, SideTable};
+///
+/// struct Mesh {
+///     vertices: Vec<[f32; 3]>,
+/// }
+///
+/// let mut nodes = Nodes::<()>::new();
+/// let cube = nodes.insert(Mesh { vertices: vec![[0.0; 3]; 8] });
+/// let plane = nodes.insert(Mesh { vertices: vec![[0.0; 3]; 4] });
+///
+/// let mut uploaded = SideTable::new();
+/// uploaded.insert(cube, "buffer 1");
+/// uploaded.insert(plane, "buffer 2");
+///
+/// nodes.remove(plane);
+/// uploaded.retain_existing(&nodes);
+///
+/// assert_eq!(uploaded.get(cube), Some(&"buffer 1"));
+/// assert_eq!(uploaded.get(plane), None);
+/// ```
+pub struct SideTable<T, V> {
+    values: HashMap<Key<T>, V>,
+}
+
+impl<T, V> SideTable<T, V> {
+    /// Create an empty table.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::with_hasher(Default::default()),
+        }
+    }
+
+    /// Get the value for `key`.
+    #[inline]
+    pub fn get(&self, key: Key<T>) -> Option<&V> {
+        self.values.get(&key)
+    }
+
+    /// Get a mutable reference to the value for `key`.
+    #[inline]
+    pub fn get_mut(&mut self, key: Key<T>) -> Option<&mut V> {
+        self.values.get_mut(&key)
+    }
+
+    /// Check if there is a value for `key`.
+    #[inline]
+    pub fn contains_key(&self, key: Key<T>) -> bool {
+        self.values.contains_key(&key)
+    }
+
+    /// Set the value for `key` and return the previous value, if any.
+    #[inline]
+    pub fn insert(&mut self, key: Key<T>, value: V) -> Option<V> {
+        self.values.insert(key, value)
+    }
+
+    /// Get the value for `key`, or insert the value from `make_value` if
+    /// there isn't one.
+    #[inline]
+    pub fn get_or_insert_with(&mut self, key: Key<T>, make_value: impl FnOnce() -> V) -> &mut V {
+        self.values.entry(key).or_insert_with(make_value)
+    }
+
+    /// Remove the value for `key` and return it, if any.
+    #[inline]
+    pub fn remove(&mut self, key: Key<T>) -> Option<V> {
+        self.values.remove(&key)
+    }
+
+    /// Only keep the values where `keep` returns `true`.
+    #[inline]
+    pub fn retain(&mut self, mut keep: impl FnMut(Key<T>, &mut V) -> bool) {
+        self.values.retain(|&key, value| keep(key, value));
+    }
+
+    /// Remove the values for the nodes that are no longer in `nodes`.
+    pub fn retain_existing<I, B>(&mut self, nodes: &Nodes<I, B>)
+    where
+        I: 'static,
+        B: Bounds,
+        T: BoundedBy<I, B>,
+    {
+        self.values.retain(|&key, _| nodes.get(key).is_some());
+    }
+
+    /// Remove all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// The number of values in the table.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if the table is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Iterate over the keys and values, in arbitrary order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Key<T>, &V)> {
+        self.values.iter().map(|(&key, value)| (key, value))
+    }
+}
+
+impl<T, V> Default for SideTable<T, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, V: Clone> Clone for SideTable<T, V> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+        }
+    }
+}
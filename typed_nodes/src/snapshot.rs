@@ -0,0 +1,415 @@
+//! A compact binary format for storing nodes, so they can be loaded without
+//! parsing them again.
+//!
+//! This module is only available with the `snapshot` feature. The node types
+//! have to implement [`Serialize`] and [`Deserialize`], and be registered
+//! with a name in a [`SnapshotRegistry`]. The names are what identifies the
+//! types in the snapshot, so they should stay the same between the program
+//! that makes the snapshot and the program that loads it.
+//!
+//! A snapshot contains the nodes, their IDs and any reserved slots. Keys keep
+//! referring to the same nodes after loading the snapshot, so they can be
+//! stored in the nodes and elsewhere. Tags, provenance, alias IDs,
+//! [indexes](crate::index) and the [hierarchy](crate::hierarchy) are not
+//! included.
+//!
+//! [`Key`][crate::Key] implements [`Serialize`] and [`Deserialize`] with the
+//! `serde` feature, which is enabled by this feature, so node types that
+//! contain keys can derive them. The same goes for [`DynKey`][crate::DynKey]
+//! when the `std` feature is enabled, but it can only be (de)serialized while
+//! a snapshot is being made or loaded. Its node type is stored as the
+//! registered name, which is looked up in the registry that is used for the
+//! snapshot.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use typed_nodes::{snapshot::SnapshotRegistry, Key, Nodes};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq)]
+//! enum Uint {
+//!     Literal(u32),
+//!     Add(Key<Uint>, Key<Uint>),
+//! }
+//!
+//! let mut registry = SnapshotRegistry::new();
+//! registry.register::<Uint>("Uint");
+//!
+//! let mut nodes = Nodes::<String>::new();
+//! let (one, _) = nodes.insert_with_id("one".into(), Uint::Literal(1));
+//! let two = nodes.insert(Uint::Literal(2));
+//! let sum = nodes.insert(Uint::Add(one, two));
+//!
+//! let bytes = nodes.to_snapshot(&registry).unwrap();
+//! let loaded = Nodes::<String>::from_snapshot(&bytes, &registry).unwrap();
+//!
+//! assert!(loaded.get(sum) == Some(&Uint::Add(one, two)));
+//! assert!(loaded.get_key::<Uint, _>("one") == Some(one));
+//! ```
+
+#[cfg(feature = "std")]
+use alloc::borrow::Cow;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{any::TypeId, fmt::Display, hash::Hash, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::DynKey;
+use crate::{
+    bounds::{AnyBounds, BoundedBy, Bounds},
+    node_group::{BoxedNodeGroup, DynNodeGroup, NodeGroup},
+    BoxedGroupOf, HashMap, Nodes,
+};
+
+/// The version of the snapshot format. It's increased when the format
+/// changes in an incompatible way.
+const FORMAT_VERSION: u32 = 1;
+
+/// A set of node types that can be stored in snapshots, and the names that
+/// identify them.
+pub struct SnapshotRegistry<I, B: Bounds = AnyBounds> {
+    names: HashMap<TypeId, String>,
+    types: BTreeMap<String, NodeType<B>>,
+    id_type: PhantomData<fn(I)>,
+}
+
+impl<I, B> SnapshotRegistry<I, B>
+where
+    I: Serialize + DeserializeOwned + Hash + Eq + 'static,
+    B: Bounds,
+{
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            names: HashMap::default(),
+            types: BTreeMap::new(),
+            id_type: PhantomData,
+        }
+    }
+
+    /// Register the node type `T` with the name `name`. Registering the same
+    /// type again replaces its previous name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is already registered for another type.
+    pub fn register<T>(&mut self, name: impl Into<String>) -> &mut Self
+    where
+        T: BoundedBy<I, B> + Serialize + DeserializeOwned,
+    {
+        let name = name.into();
+        let type_id = TypeId::of::<T>();
+
+        if let Some(node_type) = self.types.get(&name) {
+            assert!(
+                node_type.type_id == type_id,
+                "the node type name \"{name}\" is already registered for another type"
+            );
+        }
+
+        if let Some(old_name) = self.names.insert(type_id, name.clone()) {
+            self.types.remove(&old_name);
+        }
+
+        self.types.insert(
+            name,
+            NodeType {
+                type_id,
+                serialize: serialize_group::<I, T, B>,
+                deserialize: deserialize_group::<I, T, B>,
+            },
+        );
+
+        self
+    }
+
+    /// Get the name that the node type `type_id` is registered with.
+    #[inline]
+    pub fn name(&self, type_id: TypeId) -> Option<&str> {
+        self.names.get(&type_id).map(String::as_str)
+    }
+
+    /// Iterate over the registered node types and their names, sorted by
+    /// name.
+    pub fn types(&self) -> impl Iterator<Item = (TypeId, &str)> {
+        self.types
+            .iter()
+            .map(|(name, node_type)| (node_type.type_id, name.as_str()))
+    }
+}
+
+impl<I, B> Default for SnapshotRegistry<I, B>
+where
+    I: Serialize + DeserializeOwned + Hash + Eq + 'static,
+    B: Bounds,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct NodeType<B: Bounds> {
+    type_id: TypeId,
+    serialize: fn(&BoxedGroupOf<B>) -> postcard::Result<Vec<u8>>,
+    deserialize: fn(&[u8]) -> postcard::Result<BoxedGroupOf<B>>,
+}
+
+fn serialize_group<I, T, B>(group: &BoxedGroupOf<B>) -> postcard::Result<Vec<u8>>
+where
+    I: Serialize + Hash + Eq + 'static,
+    T: BoundedBy<I, B> + Serialize,
+    B: Bounds,
+{
+    let group = group.group_ref::<I, T>();
+
+    postcard::to_allocvec(group)
+}
+
+fn deserialize_group<I, T, B>(bytes: &[u8]) -> postcard::Result<BoxedGroupOf<B>>
+where
+    I: DeserializeOwned + Hash + Eq + 'static,
+    T: BoundedBy<I, B> + DeserializeOwned,
+    B: Bounds,
+{
+    let group: NodeGroup<I, T> = postcard::from_bytes(bytes)?;
+    Ok(T::box_group(group))
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot<'a> {
+    version: u32,
+    #[serde(borrow)]
+    groups: Vec<SnapshotGroup<'a>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotGroup<'a> {
+    name: &'a str,
+    #[serde(serialize_with = "serialize_bytes")]
+    data: &'a [u8],
+}
+
+pub(crate) fn serialize_bytes<S>(bytes: &&[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_bytes(bytes)
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: Hash + Eq + 'static,
+    B: Bounds,
+{
+    /// Store the nodes in a snapshot. All node types have to be registered
+    /// in `registry`. See the [`snapshot`][crate::snapshot] module for more
+    /// details.
+    pub fn to_snapshot(&self, registry: &SnapshotRegistry<I, B>) -> Result<Vec<u8>, SnapshotError> {
+        #[cfg(feature = "std")]
+        let _session = KeySession::start(registry);
+
+        let mut groups = Vec::with_capacity(self.node_groups.len());
+
+        for (type_id, group) in &self.node_groups {
+            let Some(name) = registry.names.get(type_id) else {
+                return Err(SnapshotError::UnregisteredType(group.node_type_name()));
+            };
+
+            let data = (registry.types[name].serialize)(group)?;
+            groups.push((name.as_str(), data));
+        }
+
+        // Makes the snapshot the same every time for the same nodes.
+        groups.sort_unstable_by_key(|&(name, _)| name);
+
+        let snapshot = Snapshot {
+            version: FORMAT_VERSION,
+            groups: groups
+                .iter()
+                .map(|(name, data)| SnapshotGroup { name, data })
+                .collect(),
+        };
+
+        Ok(postcard::to_allocvec(&snapshot)?)
+    }
+
+    /// Load nodes from a snapshot that was made with
+    /// [`Nodes::to_snapshot`]. All node types in the snapshot have to be
+    /// registered in `registry`.
+    pub fn from_snapshot(
+        bytes: &[u8],
+        registry: &SnapshotRegistry<I, B>,
+    ) -> Result<Self, SnapshotError> {
+        let snapshot: Snapshot = postcard::from_bytes(bytes)?;
+
+        if snapshot.version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+
+        #[cfg(feature = "std")]
+        let _session = KeySession::start(registry);
+
+        let mut nodes = Self::new();
+
+        for group in snapshot.groups {
+            let Some(node_type) = registry.types.get(group.name) else {
+                return Err(SnapshotError::UnknownTypeName(group.name.to_string()));
+            };
+
+            nodes
+                .node_groups
+                .insert(node_type.type_id, (node_type.deserialize)(group.data)?);
+        }
+
+        Ok(nodes)
+    }
+}
+
+/// The node type names that are used for (de)serializing [`DynKey`] in the
+/// current thread, while a snapshot is being made or loaded.
+#[cfg(feature = "std")]
+struct KeySession {
+    names: HashMap<TypeId, String>,
+    type_ids: HashMap<String, TypeId>,
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static KEY_SESSION: core::cell::RefCell<Option<KeySession>> = const { core::cell::RefCell::new(None) };
+}
+
+#[cfg(feature = "std")]
+impl KeySession {
+    /// Start a session with the types in `registry`. The previous session is
+    /// restored when the guard is dropped.
+    fn start<I, B: Bounds>(registry: &SnapshotRegistry<I, B>) -> KeySessionGuard {
+        let session = KeySession {
+            names: registry.names.clone(),
+            type_ids: registry
+                .types
+                .iter()
+                .map(|(name, node_type)| (name.clone(), node_type.type_id))
+                .collect(),
+        };
+
+        KeySessionGuard {
+            previous: KEY_SESSION.with(|current| current.replace(Some(session))),
+        }
+    }
+
+    fn with<T>(f: impl FnOnce(Option<&KeySession>) -> T) -> T {
+        KEY_SESSION.with(|current| f(current.borrow().as_ref()))
+    }
+}
+
+#[cfg(feature = "std")]
+struct KeySessionGuard {
+    previous: Option<KeySession>,
+}
+
+#[cfg(feature = "std")]
+impl Drop for KeySessionGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        KEY_SESSION.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+#[cfg(feature = "std")]
+impl Serialize for DynKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::Error;
+
+        KeySession::with(|session| {
+            let session = session.ok_or_else(|| {
+                S::Error::custom("a DynKey can only be serialized as part of a snapshot")
+            })?;
+
+            let name = session
+                .names
+                .get(&self.node_type)
+                .ok_or_else(|| S::Error::custom("the node type of a DynKey is not registered"))?;
+
+            (name.as_str(), self.slot).serialize(serializer)
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for DynKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let (name, slot): (Cow<'de, str>, _) = Deserialize::deserialize(deserializer)?;
+
+        KeySession::with(|session| {
+            let session = session.ok_or_else(|| {
+                D::Error::custom("a DynKey can only be deserialized as part of a snapshot")
+            })?;
+
+            let &node_type = session.type_ids.get(&*name).ok_or_else(|| {
+                D::Error::custom(format_args!("unknown node type name \"{name}\""))
+            })?;
+
+            Ok(DynKey { slot, node_type })
+        })
+    }
+}
+
+/// An error from making or loading a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// A node type wasn't registered. It has the type's name from
+    /// [`core::any::type_name`].
+    UnregisteredType(&'static str),
+    /// The snapshot has a node type name that isn't registered.
+    UnknownTypeName(String),
+    /// The snapshot was made with an incompatible version of the format.
+    UnsupportedVersion(u32),
+    /// The nodes couldn't be encoded or decoded.
+    Encoding(postcard::Error),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::UnregisteredType(name) => {
+                write!(f, "the node type {name} is not registered")
+            }
+            SnapshotError::UnknownTypeName(name) => {
+                write!(f, "unknown node type name \"{name}\"")
+            }
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot format version {version}")
+            }
+            SnapshotError::Encoding(error) => error.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            SnapshotError::Encoding(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<postcard::Error> for SnapshotError {
+    #[inline]
+    fn from(error: postcard::Error) -> Self {
+        SnapshotError::Encoding(error)
+    }
+}
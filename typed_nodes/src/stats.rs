@@ -0,0 +1,248 @@
+//! Statistics about the nodes in a [`Nodes`] collection.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    string::String,
+    vec::Vec,
+};
+use core::{any::TypeId, mem::size_of};
+
+use crate::{bounds::Bounds, node_group::DynNodeGroup, DynKey, Key, Nodes, WeakKey};
+
+/// Statistics for the nodes of a single type, from [`Nodes::stats`] or
+/// [`Nodes::stats_with_memory`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeStats {
+    /// The type of the nodes.
+    pub type_id: TypeId,
+    /// The name of the type of the nodes.
+    pub type_name: &'static str,
+    /// The number of nodes.
+    pub nodes: usize,
+    /// The number of reserved slots that haven't been filled.
+    pub reserved: usize,
+    /// The number of slots that have been allocated.
+    pub capacity: usize,
+    /// The size of the allocated slots, in bytes.
+    pub slot_bytes: usize,
+    /// The approximate amount of heap memory the nodes use, in bytes, not
+    /// counting the slots themselves. It's only known when the nodes
+    /// implement [`MemoryUsage`].
+    pub heap_bytes: Option<usize>,
+}
+
+impl TypeStats {
+    /// The total approximate memory usage, in bytes, if the heap memory usage
+    /// is known.
+    #[inline]
+    pub fn total_bytes(&self) -> Option<usize> {
+        Some(self.slot_bytes + self.heap_bytes?)
+    }
+}
+
+/// Values that can report their approximate heap memory usage. Add it to the
+/// bounds of a [`Nodes`] collection to use [`Nodes::stats_with_memory`].
+///
+/// ```
+/// use typed_nodes::{make_bounds, stats::MemoryUsage, Nodes};
+///
+/// make_bounds!(MeshBounds: MemoryUsage + 'static);
+///
+/// struct Mesh {
+///     name: String,
+///     vertices: Vec<[f32; 3]>,
+/// }
+///
+/// impl MemoryUsage for Mesh {
+///     fn heap_bytes(&self) -> usize {
+///         self.name.heap_bytes() + self.vertices.heap_bytes()
+///     }
+/// }
+///
+/// let mut nodes = Nodes::<(), MeshBounds>::new();
+/// nodes.insert(Mesh {
+///     name: String::with_capacity(8),
+///     vertices: Vec::with_capacity(10),
+/// });
+///
+/// let stats = nodes.stats_with_memory();
+/// assert_eq!(stats[0].nodes, 1);
+/// assert_eq!(stats[0].heap_bytes, Some(8 + 10 * 12));
+/// ```
+pub trait MemoryUsage {
+    /// The approximate number of bytes this value has allocated on the heap.
+    fn heap_bytes(&self) -> usize;
+}
+
+macro_rules! impl_memory_usage_inline {
+    ($($ty:ty),+) => {$(
+        impl MemoryUsage for $ty {
+            #[inline]
+            fn heap_bytes(&self) -> usize {
+                0
+            }
+        }
+    )+};
+}
+
+impl_memory_usage_inline!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    DynKey
+);
+
+impl<T> MemoryUsage for Key<T> {
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+impl<T> MemoryUsage for WeakKey<T> {
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+impl MemoryUsage for String {
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for Option<T> {
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_bytes)
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for Box<T> {
+    #[inline]
+    fn heap_bytes(&self) -> usize {
+        size_of::<T>() + (**self).heap_bytes()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for Vec<T> {
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_bytes).sum::<usize>()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for VecDeque<T> {
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_bytes).sum::<usize>()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for BTreeSet<T> {
+    fn heap_bytes(&self) -> usize {
+        self.len() * size_of::<T>() + self.iter().map(T::heap_bytes).sum::<usize>()
+    }
+}
+
+impl<K: MemoryUsage, V: MemoryUsage> MemoryUsage for BTreeMap<K, V> {
+    fn heap_bytes(&self) -> usize {
+        self.iter()
+            .map(|(key, value)| size_of::<(K, V)>() + key.heap_bytes() + value.heap_bytes())
+            .sum()
+    }
+}
+
+impl<T: MemoryUsage, const N: usize> MemoryUsage for [T; N] {
+    fn heap_bytes(&self) -> usize {
+        self.iter().map(T::heap_bytes).sum()
+    }
+}
+
+impl<T: ?Sized + ToOwned> MemoryUsage for Cow<'_, T>
+where
+    T::Owned: MemoryUsage,
+{
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(owned) => owned.heap_bytes(),
+        }
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+{
+    /// Count the nodes and slots of each node type. The result is sorted by
+    /// type name.
+    ///
+    /// ```
+    /// use typed_nodes::Nodes;
+    ///
+    /// struct Enemy;
+    ///
+    /// let mut nodes = Nodes::<()>::new();
+    /// nodes.insert(Enemy);
+    /// nodes.insert(Enemy);
+    /// nodes.reserve_with_id::<Enemy>(());
+    ///
+    /// let stats = nodes.stats();
+    /// assert_eq!(stats.len(), 1);
+    /// assert_eq!(stats[0].nodes, 2);
+    /// assert_eq!(stats[0].reserved, 1);
+    /// assert_eq!(stats[0].heap_bytes, None);
+    /// ```
+    pub fn stats(&self) -> Vec<TypeStats> {
+        let mut stats: Vec<_> = self
+            .node_groups
+            .values()
+            .map(DynNodeGroup::<B>::stats)
+            .collect();
+        stats.sort_by_key(|stats| stats.type_name);
+
+        stats
+    }
+}
+
+impl<I, B> Nodes<I, B>
+where
+    I: 'static,
+    B: Bounds,
+    B::DynSelf: MemoryUsage,
+{
+    /// Count the nodes and slots of each node type, like [`Nodes::stats`],
+    /// and add up their heap memory usage.
+    pub fn stats_with_memory(&self) -> Vec<TypeStats> {
+        let mut stats: Vec<_> = self
+            .node_groups
+            .values()
+            .map(|group| {
+                let mut stats = group.stats();
+                stats.heap_bytes = Some(group.nodes_dyn().map(|node| node.heap_bytes()).sum());
+                stats
+            })
+            .collect();
+        stats.sort_by_key(|stats| stats.type_name);
+
+        stats
+    }
+}
@@ -0,0 +1,307 @@
+//! Helpers for testing [`FromLua`] and [`GenerateLua`] implementations.
+//!
+//! This module is only available with the `test_util` feature. It takes care
+//! of setting up a Lua state, a [`Context`] and a [`Nodes`] collection, so
+//! tests can focus on the Lua source and the expected result.
+//!
+//! ```
+//! use typed_nodes::{mlua::FromLua, test_util::{assert_from_lua_eq, Fixture}, Key};
+//!
+//! #[derive(FromLua, Debug, PartialEq)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! assert_from_lua_eq("{x = 1, y = 2}", Point { x: 1, y: 2 });
+//!
+//! let mut fixture = Fixture::new();
+//! let key: Key<Point> = fixture.parse("{x = 3, y = 4}").unwrap();
+//! assert_eq!(fixture.nodes().get(key), Some(&Point { x: 3, y: 4 }));
+//! ```
+
+use std::fmt::Debug;
+
+use mlua::{Lua, Table, Value};
+
+use crate::{
+    bounds::{AnyBounds, Bounds},
+    mlua::{Context, FromLua, GenerateLua, LuaModule, TableId},
+    Nodes,
+};
+
+/// Evaluate `source` as a Lua expression and parse the result as `T`. The
+/// nodes that were inserted while parsing are returned together with the
+/// value.
+pub fn parse_str<T>(source: &str) -> mlua::Result<(T, Nodes<TableId>)>
+where
+    T: for<'lua> FromLua<'lua, AnyBounds>,
+{
+    let mut fixture = Fixture::new();
+    let value = fixture.parse(source)?;
+    Ok((value, fixture.into_nodes()))
+}
+
+/// Evaluate `source` as a Lua expression, parse the result as `T` and assert
+/// that it's equal to `expected`.
+///
+/// Panics with the parsing error if the value couldn't be parsed.
+#[track_caller]
+pub fn assert_from_lua_eq<T>(source: &str, expected: T)
+where
+    T: for<'lua> FromLua<'lua, AnyBounds> + PartialEq + Debug,
+{
+    match parse_str::<T>(source) {
+        Ok((value, _)) => assert_eq!(value, expected, "parsed from `{source}`"),
+        Err(error) => panic!("could not parse `{source}`: {error}"),
+    }
+}
+
+/// A Lua state and a set of nodes, ready to parse values into.
+pub struct Fixture<B: Bounds = AnyBounds> {
+    lua: Lua,
+    nodes: Nodes<TableId, B>,
+}
+
+impl<B: Bounds> Fixture<B> {
+    pub fn new() -> Self {
+        Self {
+            lua: Lua::new(),
+            nodes: Nodes::new(),
+        }
+    }
+
+    /// Generate the Lua module for `T` and make it available as the global
+    /// variable `global_name`.
+    ///
+    /// Panics if the generated code can't be loaded.
+    #[track_caller]
+    pub fn with_module<T>(self, global_name: &str) -> Self
+    where
+        T: GenerateLua + 'static,
+    {
+        let mut module = LuaModule::new();
+        T::generate_lua(&mut module);
+
+        let table = module
+            .load_into_table(&self.lua)
+            .expect("the generated Lua module should be possible to load");
+        self.lua
+            .globals()
+            .set(global_name, table)
+            .expect("the module table should be possible to assign to a global");
+
+        self
+    }
+
+    /// Evaluate `source` as a Lua expression and parse the result as `T`. Any
+    /// nodes are inserted into the fixture's node set.
+    pub fn parse<T>(&mut self, source: &str) -> mlua::Result<T>
+    where
+        T: for<'lua> FromLua<'lua, B>,
+    {
+        let value = self.lua.load(source).eval()?;
+        T::from_lua(value, &mut Context::new(&self.lua, &mut self.nodes))
+    }
+
+    pub fn lua(&self) -> &Lua {
+        &self.lua
+    }
+
+    pub fn nodes(&self) -> &Nodes<TableId, B> {
+        &self.nodes
+    }
+
+    pub fn nodes_mut(&mut self) -> &mut Nodes<TableId, B> {
+        &mut self.nodes
+    }
+
+    pub fn into_nodes(self) -> Nodes<TableId, B> {
+        self.nodes
+    }
+}
+
+impl<B: Bounds> Default for Fixture<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds Lua values from a sequence of bytes, for fuzzing [`FromLua`]
+/// implementations. Every byte sequence gives a valid value, so the bytes
+/// can come directly from a fuzzer.
+///
+/// The values are trees of tables with at most `max_depth` levels, where
+/// any table that has been created so far may be reused. That includes the
+/// table that is being filled and its ancestors, so the values may contain
+/// cycles, which the parsers must stop at with an error. Strings are picked
+/// from a list of names, such as field and variant names, or taken from the
+/// raw bytes.
+///
+/// ```
+/// use typed_nodes::{
+///     mlua::{Context, FromLua},
+///     test_util::ArbitraryValues,
+///     Nodes,
+/// };
+///
+/// #[derive(FromLua)]
+/// enum Shape {
+///     Circle { radius: f32 },
+///     Group { children: Vec<Shape> },
+/// }
+///
+/// let lua = mlua::Lua::new();
+/// let mut nodes = Nodes::new();
+/// let mut context = Context::new(&lua, &mut nodes);
+///
+/// let names = ["type", "circle", "group", "radius", "children"];
+/// let mut values = ArbitraryValues::new(b"some fuzzer input").with_names(&names);
+///
+/// while !values.is_empty() {
+///     let value = values.value(&lua).unwrap();
+///     let _ = context.parse::<Shape>(value);
+/// }
+/// ```
+pub struct ArbitraryValues<'a> {
+    data: &'a [u8],
+    names: &'a [&'a str],
+    max_depth: usize,
+}
+
+impl<'a> ArbitraryValues<'a> {
+    /// Build values from `data`, with no names and a maximum depth of 8.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            names: &[],
+            max_depth: 8,
+        }
+    }
+
+    /// Pick strings and table keys from `names`.
+    pub fn with_names(mut self, names: &'a [&'a str]) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Limit how deeply tables can be nested.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Check if all bytes have been used. Any further values will be `nil`.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Build the next value.
+    pub fn value<'lua>(&mut self, lua: &'lua Lua) -> mlua::Result<Value<'lua>> {
+        self.value_at_depth(lua, 0, &mut Vec::new())
+    }
+
+    fn value_at_depth<'lua>(
+        &mut self,
+        lua: &'lua Lua,
+        depth: usize,
+        tables: &mut Vec<Table<'lua>>,
+    ) -> mlua::Result<Value<'lua>> {
+        let kinds = if depth < self.max_depth { 8 } else { 6 };
+
+        let value = match self.byte() % kinds {
+            0 => Value::Nil,
+            1 => Value::Boolean(self.byte() & 1 == 1),
+            2 => Value::Integer(self.integer()),
+            3 => Value::Number(self.number()),
+            4 => match self.name() {
+                Some(name) => Value::String(lua.create_string(name)?),
+                None => Value::Nil,
+            },
+            5 => Value::String(lua.create_string(self.bytes())?),
+            6 if !tables.is_empty() => {
+                let index = self.byte() as usize % tables.len();
+                Value::Table(tables[index].clone())
+            }
+            _ => Value::Table(self.table(lua, depth + 1, tables)?),
+        };
+
+        Ok(value)
+    }
+
+    fn table<'lua>(
+        &mut self,
+        lua: &'lua Lua,
+        depth: usize,
+        tables: &mut Vec<Table<'lua>>,
+    ) -> mlua::Result<Table<'lua>> {
+        let table = lua.create_table()?;
+        // The table can be reused while it's being filled, to make cycles.
+        tables.push(table.clone());
+
+        let length = self.byte() % 8;
+        let mut next_index = 1;
+
+        for _ in 0..length {
+            let key = match self.byte() % 3 {
+                0 => {
+                    next_index += 1;
+                    Value::Integer(next_index - 1)
+                }
+                1 => match self.name() {
+                    Some(name) => Value::String(lua.create_string(name)?),
+                    None => continue,
+                },
+                _ => Value::Integer(self.integer()),
+            };
+            let value = self.value_at_depth(lua, depth, tables)?;
+
+            table.raw_set(key, value)?;
+        }
+
+        Ok(table)
+    }
+
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => {
+                self.data = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    fn integer(&mut self) -> mlua::Integer {
+        match self.byte() {
+            byte @ 0..=127 => byte as mlua::Integer - 16,
+            _ => mlua::Integer::from_le_bytes(core::array::from_fn(|_| self.byte())),
+        }
+    }
+
+    fn number(&mut self) -> mlua::Number {
+        match self.byte() % 8 {
+            0 => mlua::Number::NAN,
+            1 => mlua::Number::INFINITY,
+            2 => mlua::Number::NEG_INFINITY,
+            3 => -0.0,
+            4..=5 => self.integer() as mlua::Number / 4.0,
+            _ => mlua::Number::from_bits(u64::from_le_bytes(core::array::from_fn(|_| self.byte()))),
+        }
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        if self.names.is_empty() {
+            return None;
+        }
+
+        Some(self.names[self.byte() as usize % self.names.len()])
+    }
+
+    fn bytes(&mut self) -> &'a [u8] {
+        let length = (self.byte() as usize % 16).min(self.data.len());
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        bytes
+    }
+}
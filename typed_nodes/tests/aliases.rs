@@ -0,0 +1,72 @@
+use typed_nodes::Nodes;
+
+#[derive(Debug, PartialEq)]
+struct Item(&'static str);
+
+#[test]
+fn remove_node_with_many_aliases() {
+    let mut nodes = Nodes::<()>::new();
+    let sword = nodes.insert(Item("sword"));
+    let shield = nodes.insert(Item("shield"));
+
+    nodes.alias_id(sword, "blade");
+    nodes.alias_id(sword, "weapon");
+    nodes.alias_id(shield, "buckler");
+
+    nodes.remove(sword);
+
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("blade").is_none());
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("weapon").is_none());
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("buckler") == Some(shield));
+}
+
+#[test]
+fn moved_alias_stays_after_remove() {
+    let mut nodes = Nodes::<()>::new();
+    let sword = nodes.insert(Item("sword"));
+    let axe = nodes.insert(Item("axe"));
+
+    nodes.alias_id(sword, "weapon");
+    assert!(nodes.alias_id(axe, "weapon") == Some(sword));
+
+    nodes.remove(sword);
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("weapon") == Some(axe));
+
+    nodes.remove(axe);
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("weapon").is_none());
+}
+
+#[test]
+fn removed_alias_stays_removed() {
+    let mut nodes = Nodes::<()>::new();
+    let sword = nodes.insert(Item("sword"));
+
+    nodes.alias_id(sword, "weapon");
+    assert!(nodes.remove_alias::<Item, &str, _>("weapon") == Some(sword));
+
+    // Assigning it again after removing it shouldn't be affected by the
+    // old assignment.
+    let axe = nodes.insert(Item("axe"));
+    nodes.alias_id(axe, "weapon");
+    nodes.remove(sword);
+
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("weapon") == Some(axe));
+}
+
+#[test]
+fn redirect_moves_aliases() {
+    let mut nodes = Nodes::<()>::new();
+    let sword = nodes.insert(Item("sword"));
+    let blade = nodes.insert(Item("blade"));
+
+    nodes.alias_id(sword, "weapon");
+    nodes.alias_id(blade, "edge");
+    nodes.redirect(sword, blade);
+
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("weapon") == Some(blade));
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("edge") == Some(blade));
+
+    nodes.remove(blade);
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("weapon").is_none());
+    assert!(nodes.get_key_by_alias::<Item, &str, _>("edge").is_none());
+}
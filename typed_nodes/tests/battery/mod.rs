@@ -0,0 +1,125 @@
+//! Types with nested enums, untagged variants, keys, tuples and recursive
+//! boxes, that are parsed from arbitrary Lua values. It's shared by the
+//! `fuzz_battery` test and the `from_lua` fuzz target.
+
+// The fields are only parsed, never read.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use typed_nodes::{
+    mlua::{Context, FromLua},
+    test_util::ArbitraryValues,
+    Key, Nodes,
+};
+
+#[derive(FromLua)]
+enum Shape {
+    Circle {
+        radius: f32,
+    },
+    Rect {
+        size: (f32, f32),
+    },
+    Group {
+        children: Vec<Shape>,
+        transform: Option<Box<Transform>>,
+    },
+    #[typed_nodes(untagged(number))]
+    Dot(f64),
+    #[typed_nodes(untagged(string))]
+    Named(String),
+}
+
+#[derive(FromLua)]
+enum Transform {
+    #[typed_nodes(untagged(table))]
+    Translate(f32, f32),
+    Scale {
+        factor: f32,
+    },
+    Chain {
+        first: Box<Transform>,
+        second: Box<Transform>,
+    },
+}
+
+#[derive(FromLua)]
+#[typed_nodes(is_node)]
+struct Layer {
+    name: String,
+    shapes: Vec<Key<Shape>>,
+    parent: Option<Key<Layer>>,
+    tags: HashMap<String, u8>,
+}
+
+#[derive(FromLua)]
+struct Link {
+    name: Option<String>,
+    next: Option<Box<Link>>,
+}
+
+#[derive(FromLua)]
+struct Scene {
+    layers: Vec<Key<Layer>>,
+    background: (u8, u8, u8),
+    visible: bool,
+}
+
+const NAMES: &[&str] = &[
+    "type",
+    "circle",
+    "rect",
+    "group",
+    "scale",
+    "chain",
+    "radius",
+    "size",
+    "children",
+    "transform",
+    "factor",
+    "first",
+    "second",
+    "name",
+    "shapes",
+    "parent",
+    "tags",
+    "layers",
+    "background",
+    "visible",
+    "next",
+];
+
+/// Parse the values that are built from `data` as each of the types. Errors
+/// are expected, but nothing should panic, and cyclic values must result in
+/// errors.
+pub fn parse_all(data: &[u8]) {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::new();
+    let mut context = Context::new(&lua, &mut nodes);
+    let mut values = ArbitraryValues::new(data).with_names(NAMES);
+
+    while !values.is_empty() {
+        let value = values
+            .value(&lua)
+            .expect("the values should be possible to build");
+
+        let _ = context.parse::<Shape>(value.clone());
+        let _ = context.parse::<Transform>(value.clone());
+        let _ = context.parse::<Key<Shape>>(value.clone());
+        let _ = context.parse::<Key<Layer>>(value.clone());
+        let _ = context.parse::<Scene>(value.clone());
+        let _ = context.parse::<Link>(value.clone());
+        let _ = context.parse::<(Shape, Option<Key<Layer>>)>(value.clone());
+
+        // A link that refers to itself never ends, so it must be an error.
+        if let mlua::Value::Table(table) = value {
+            table
+                .raw_set("next", table.clone())
+                .expect("the table should be possible to modify");
+            assert!(context.parse::<Link>(mlua::Value::Table(table)).is_err());
+        }
+    }
+
+    let _ = context.finish();
+}
@@ -0,0 +1,53 @@
+use std::marker::PhantomData;
+
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, TableId},
+    Nodes,
+};
+
+trait Unit: 'static {
+    const FACTOR: f64;
+}
+
+struct Meters;
+
+impl Unit for Meters {
+    const FACTOR: f64 = 1.0;
+}
+
+fn parse_length<'lua, U: Unit>(
+    value: mlua::Value<'lua>,
+    context: &mut Context<'lua, AnyBounds>,
+) -> mlua::Result<f64> {
+    f64::from_lua(value, context).map(|value| value * U::FACTOR)
+}
+
+#[derive(FromLua)]
+#[typed_nodes(no_bound(U))]
+struct Length<U: Unit> {
+    #[typed_nodes(parse_with = parse_length::<U>)]
+    value: f64,
+    unit: PhantomData<U>,
+}
+
+#[derive(FromLua)]
+#[typed_nodes(bound = "T: FromLua<'lua, AnyBounds>")]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[test]
+fn custom_bounds() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+    let mut context = Context::new(&lua, &mut nodes);
+
+    let value = lua.load("{value = 2}").eval().unwrap();
+    let length = Length::<Meters>::from_lua(value, &mut context).unwrap();
+    assert_eq!(length.value, 2.0);
+
+    let value = lua.load("{value = 3}").eval().unwrap();
+    let wrapper = Wrapper::<u32>::from_lua(value, &mut context).unwrap();
+    assert_eq!(wrapper.value, 3);
+}
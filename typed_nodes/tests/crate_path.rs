@@ -0,0 +1,44 @@
+use mlua::Lua;
+
+mod framework {
+    pub use typed_nodes as nodes;
+}
+
+mod shapes {
+    use crate::framework::nodes::mlua::{FromLua, GenerateLua};
+
+    // Hides the `typed_nodes` crate in this module, so the derives only
+    // compile if they use the path from the `crate` attribute.
+    #[allow(dead_code)]
+    mod typed_nodes {}
+
+    #[derive(FromLua, GenerateLua, Debug, PartialEq)]
+    #[typed_nodes(crate = "crate::framework::nodes")]
+    pub enum Shape {
+        Circle { radius: f64 },
+        Square { side: f64 },
+    }
+}
+
+use framework::nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule},
+    Nodes,
+};
+use shapes::Shape;
+
+#[test]
+fn derives_with_crate_path() {
+    let lua = Lua::new();
+
+    let mut module = LuaModule::new();
+    Shape::generate_lua(&mut module);
+    lua.globals()
+        .set("shapes", module.load_into_table(&lua).unwrap())
+        .unwrap();
+
+    let value = lua.load("shapes.Shape.circle(2)").eval().unwrap();
+    let mut nodes = Nodes::new();
+    let shape = Shape::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    assert_eq!(shape, Shape::Circle { radius: 2.0 });
+}
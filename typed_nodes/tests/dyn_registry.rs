@@ -0,0 +1,92 @@
+use mlua::Lua;
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, DynRegistry, FromLua, TableId},
+    Nodes,
+};
+
+trait Effect {
+    fn apply(&self, value: f32) -> f32;
+}
+
+#[derive(FromLua)]
+struct Gain {
+    amount: f32,
+}
+
+impl Effect for Gain {
+    fn apply(&self, value: f32) -> f32 {
+        value * self.amount
+    }
+}
+
+#[derive(FromLua)]
+struct Offset {
+    by: f32,
+}
+
+impl Effect for Offset {
+    fn apply(&self, value: f32) -> f32 {
+        value + self.by
+    }
+}
+
+static EFFECTS: DynRegistry<dyn Effect> = DynRegistry::with_tag_name("kind");
+
+fn register_effects() {
+    EFFECTS.register("gain", |value, context| {
+        Ok(Box::new(Gain::from_lua(value, context)?))
+    });
+    EFFECTS.register("offset", |value, context| {
+        Ok(Box::new(Offset::from_lua(value, context)?))
+    });
+}
+
+#[derive(FromLua)]
+struct Channel {
+    #[typed_nodes(dyn_registry = EFFECTS)]
+    main: Box<dyn Effect>,
+    #[typed_nodes(dyn_registry = EFFECTS)]
+    extra: Option<Box<dyn Effect>>,
+    #[typed_nodes(dyn_registry = EFFECTS)]
+    chain: Vec<Box<dyn Effect>>,
+}
+
+fn parse<T: for<'lua> FromLua<'lua, AnyBounds>>(source: &str) -> mlua::Result<T> {
+    let lua = Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+    let value = lua.load(source).eval()?;
+    T::from_lua(value, &mut Context::new(&lua, &mut nodes))
+}
+
+#[test]
+fn registry_fields() {
+    register_effects();
+
+    let channel: Channel = parse(
+        r#"{
+            main = {kind = "gain", amount = 2},
+            chain = {{kind = "offset", by = 1}, {kind = "gain", amount = 3}},
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(channel.main.apply(1.0), 2.0);
+    assert!(channel.extra.is_none());
+    let chained = channel
+        .chain
+        .iter()
+        .fold(1.0, |value, effect| effect.apply(value));
+    assert_eq!(chained, 6.0);
+}
+
+#[test]
+fn unknown_tag() {
+    register_effects();
+
+    let error = parse::<Channel>(r#"{main = {kind = "echo"}, chain = {}}"#)
+        .err()
+        .unwrap();
+
+    assert!(error.to_string().contains("echo"), "{error}");
+}
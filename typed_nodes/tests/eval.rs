@@ -0,0 +1,213 @@
+use typed_nodes::{
+    eval::{EvalCache, EvalError, Evaluate, Evaluator},
+    pass::Pass,
+    Key, Nodes,
+};
+
+enum Expr {
+    Constant(i32),
+    Add(Key<Expr>, Key<Expr>),
+}
+
+impl Evaluate for Expr {
+    type Output = i32;
+
+    fn evaluate(&self, evaluator: &mut Evaluator<'_, ()>) -> Result<i32, EvalError> {
+        match *self {
+            Expr::Constant(value) => Ok(value),
+            Expr::Add(left, right) => Ok(evaluator.evaluate(left)? + evaluator.evaluate(right)?),
+        }
+    }
+}
+
+/// Formats the expression instead of computing it.
+struct Format;
+
+impl Evaluate<Format> for Expr {
+    type Output = String;
+
+    fn evaluate(&self, evaluator: &mut Evaluator<'_, Format>) -> Result<String, EvalError> {
+        match *self {
+            Expr::Constant(value) => Ok(value.to_string()),
+            Expr::Add(left, right) => Ok(format!(
+                "({} + {})",
+                evaluator.evaluate(left)?,
+                evaluator.evaluate(right)?
+            )),
+        }
+    }
+}
+
+struct Double;
+
+impl Pass for Double {
+    type Node = Expr;
+
+    fn run(&mut self, _key: Key<Expr>, node: &mut Expr, _rest: &Nodes) {
+        if let Expr::Constant(value) = node {
+            *value *= 2;
+        }
+    }
+}
+
+fn evaluate_sum(nodes: &Nodes, sum: Key<Expr>, cache: EvalCache) -> (i32, EvalCache) {
+    let mut evaluator = Evaluator::with_cache(nodes, (), cache);
+    let value = evaluator.evaluate(sum).unwrap();
+
+    (value, evaluator.into_cache())
+}
+
+fn make_sum(nodes: &mut Nodes) -> (Key<Expr>, Key<Expr>) {
+    nodes.enable_change_ticks();
+    let one = nodes.insert(Expr::Constant(1));
+    let two = nodes.insert(Expr::Constant(2));
+    let sum = nodes.insert(Expr::Add(one, two));
+
+    (one, sum)
+}
+
+#[test]
+fn get_mut_invalidates() {
+    let mut nodes = Nodes::new();
+    let (one, sum) = make_sum(&mut nodes);
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+
+    *nodes.get_mut(one).unwrap() = Expr::Constant(10);
+
+    let (value, _) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 12);
+}
+
+#[test]
+fn get_dyn_mut_invalidates() {
+    let mut nodes = Nodes::new();
+    let (one, sum) = make_sum(&mut nodes);
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+
+    *nodes
+        .get_dyn_mut(one.into())
+        .unwrap()
+        .downcast_mut::<Expr>()
+        .unwrap() = Expr::Constant(10);
+
+    let (value, _) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 12);
+}
+
+#[test]
+fn iter_mut_invalidates() {
+    let mut nodes = Nodes::new();
+    let (_, sum) = make_sum(&mut nodes);
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+
+    for (_, expr) in nodes.iter_mut::<Expr>() {
+        if let Expr::Constant(value) = expr {
+            *value += 1;
+        }
+    }
+
+    let (value, _) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 5);
+}
+
+#[test]
+fn nodes_dyn_mut_invalidates() {
+    let mut nodes = Nodes::new();
+    let (_, sum) = make_sum(&mut nodes);
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+
+    for node in nodes.nodes_dyn_mut() {
+        if let Some(Expr::Constant(value)) = node.downcast_mut::<Expr>() {
+            *value += 1;
+        }
+    }
+
+    let (value, _) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 5);
+}
+
+#[test]
+fn get_mut_and_rest_invalidates() {
+    let mut nodes = Nodes::new();
+    let (_, sum) = make_sum(&mut nodes);
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+
+    assert_eq!(nodes.run_pass(&mut Double), 3);
+
+    let (value, _) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 6);
+}
+
+#[test]
+fn put_back_invalidates() {
+    let mut nodes = Nodes::new();
+    let (one, sum) = make_sum(&mut nodes);
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+
+    let _ = nodes.take(one).unwrap();
+    assert!(nodes.put_back(one, Expr::Constant(10)).is_ok());
+
+    let (value, _) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 12);
+}
+
+#[test]
+fn unchanged_nodes_stay_cached() {
+    let mut nodes = Nodes::new();
+    let (one, sum) = make_sum(&mut nodes);
+    let other = nodes.insert(Expr::Add(sum, one));
+    let (_, cache) = evaluate_sum(&nodes, other, EvalCache::new());
+    assert_eq!(cache.len(), 4);
+
+    nodes.get_mut(other).unwrap();
+
+    let evaluator = Evaluator::with_cache(&nodes, (), cache);
+    assert_eq!(evaluator.cache().len(), 3);
+    assert_eq!(evaluator.cached(sum), Some(&3));
+}
+
+#[test]
+fn cache_with_different_outputs() {
+    let mut nodes = Nodes::new();
+    let (one, sum) = make_sum(&mut nodes);
+
+    let (value, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+    assert_eq!(value, 3);
+
+    let mut evaluator = Evaluator::with_cache(&nodes, Format, cache);
+    assert_eq!(evaluator.evaluate(sum), Ok("(1 + 2)".to_owned()));
+    let cache = evaluator.into_cache();
+    assert_eq!(cache.len(), 3);
+
+    let (value, cache) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 3);
+
+    // Both values of the sum depend on `one`.
+    nodes.replace(one, Expr::Constant(10));
+
+    let mut evaluator = Evaluator::with_cache(&nodes, Format, cache);
+    assert_eq!(evaluator.cache().len(), 1);
+    assert_eq!(evaluator.evaluate(sum), Ok("(10 + 2)".to_owned()));
+
+    let (value, _) = evaluate_sum(&nodes, sum, evaluator.into_cache());
+    assert_eq!(value, 12);
+}
+
+#[test]
+fn values_without_change_ticks_are_invalid() {
+    let mut nodes = Nodes::new();
+    let one = nodes.insert(Expr::Constant(1));
+    let two = nodes.insert(Expr::Constant(2));
+    let sum = nodes.insert(Expr::Add(one, two));
+    let (_, cache) = evaluate_sum(&nodes, sum, EvalCache::new());
+    assert_eq!(cache.len(), 3);
+
+    // The values from before the ticks were enabled can't be trusted.
+    nodes.replace(one, Expr::Constant(10));
+    nodes.enable_change_ticks();
+    let (value, cache) = evaluate_sum(&nodes, sum, cache);
+    assert_eq!(value, 12);
+
+    let evaluator = Evaluator::with_cache(&nodes, (), cache);
+    assert_eq!(evaluator.cache().len(), 3);
+}
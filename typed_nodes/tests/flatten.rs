@@ -0,0 +1,96 @@
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, TableId},
+    Nodes,
+};
+
+#[derive(FromLua, Debug, PartialEq)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[derive(FromLua, Debug, PartialEq)]
+struct Size {
+    width: i32,
+    height: i32,
+}
+
+#[derive(FromLua, Debug, PartialEq)]
+#[typed_nodes(deny_unknown_fields)]
+struct Rect {
+    #[typed_nodes(flatten)]
+    position: Position,
+    #[typed_nodes(flatten)]
+    size: Size,
+    name: Option<String>,
+}
+
+#[derive(FromLua, Debug, PartialEq)]
+#[typed_nodes(deny_unknown_fields)]
+enum Shape {
+    Rect {
+        #[typed_nodes(flatten)]
+        rect: Rect,
+    },
+    Point {
+        #[typed_nodes(flatten)]
+        position: Position,
+    },
+}
+
+fn parse<T: for<'lua> FromLua<'lua, AnyBounds>>(source: &str) -> mlua::Result<T> {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+    let value = lua.load(source).eval()?;
+    T::from_lua(value, &mut Context::new(&lua, &mut nodes))
+}
+
+#[test]
+fn flatten_multiple() {
+    let rect: Rect = parse("{x = 1, y = 2, width = 3, height = 4}").unwrap();
+
+    assert_eq!(
+        rect,
+        Rect {
+            position: Position { x: 1, y: 2 },
+            size: Size {
+                width: 3,
+                height: 4
+            },
+            name: None,
+        }
+    );
+}
+
+#[test]
+fn deny_unknown_fields() {
+    let error = parse::<Rect>("{x = 1, y = 2, width = 3, height = 4, depth = 5}")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("unknown field \"depth\""));
+
+    let error = parse::<Rect>("{x = 1, y = 2, width = 3, height = 4, 5}")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("unexpected integer key"));
+}
+
+#[test]
+fn deny_unknown_fields_in_variant() {
+    let shape: Shape = parse("{type = 'point', x = 1, y = 2}").unwrap();
+    assert_eq!(
+        shape,
+        Shape::Point {
+            position: Position { x: 1, y: 2 }
+        }
+    );
+
+    let shape: Shape = parse("{type = 'rect', x = 1, y = 2, width = 3, height = 4}").unwrap();
+    assert!(matches!(shape, Shape::Rect { .. }));
+
+    let error = parse::<Shape>("{type = 'point', x = 1, y = 2, width = 3}")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("unknown field \"width\""));
+}
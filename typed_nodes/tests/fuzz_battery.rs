@@ -0,0 +1,31 @@
+#![cfg(feature = "test_util")]
+
+mod battery;
+
+/// A small xorshift generator, so the inputs are the same on every run.
+fn pseudo_random_bytes(seed: u64, length: usize) -> Vec<u8> {
+    let mut state = seed | 1;
+
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn parse_pseudo_random_values() {
+    for seed in 0..500 {
+        battery::parse_all(&pseudo_random_bytes(seed, 256));
+    }
+}
+
+#[test]
+fn parse_edge_cases() {
+    battery::parse_all(&[]);
+    battery::parse_all(&[7; 64]);
+    battery::parse_all(&[255; 64]);
+}
@@ -0,0 +1,81 @@
+use std::borrow::Cow;
+
+use mlua::Lua;
+use typed_nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule},
+    Nodes,
+};
+
+#[derive(FromLua, GenerateLua)]
+enum Uint {
+    #[typed_nodes(untagged(integer), skip_method)]
+    Literal(u32),
+}
+
+#[derive(FromLua, GenerateLua)]
+struct Repeat<T, const N: usize> {
+    value: T,
+}
+
+#[derive(FromLua)]
+struct Named<'a> {
+    name: Cow<'a, str>,
+}
+
+#[test]
+fn const_generic_variants() {
+    let lua = Lua::new();
+
+    let mut module = LuaModule::new();
+    Uint::generate_lua(&mut module);
+    Repeat::<Uint, 3>::generate_lua(&mut module);
+    lua.globals()
+        .set("my_lib", module.load_into_table(&lua).unwrap())
+        .unwrap();
+
+    let value = lua
+        .load("my_lib.Repeat(my_lib.Uint, 3).new(5)")
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let repeat = Repeat::<Uint, 3>::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    assert!(matches!(repeat.value, Uint::Literal(5)));
+
+    let result: mlua::Result<mlua::Value> = lua.load("my_lib.Repeat(my_lib.Uint, 4)").eval();
+    assert!(result.is_err());
+}
+
+#[test]
+fn lifetime_parameters() {
+    let lua = Lua::new();
+    let value = lua.load(r#"{name = "alice"}"#).eval().unwrap();
+    let mut nodes = Nodes::new();
+    let named = Named::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    assert_eq!(named.name, "alice");
+}
+
+struct Meters;
+struct Feet;
+
+#[derive(FromLua, GenerateLua)]
+#[typed_nodes(no_bound(U))]
+struct Length<U: 'static> {
+    value: Uint,
+    unit: std::marker::PhantomData<U>,
+}
+
+#[test]
+fn unbounded_type_parameters_in_signature() {
+    let meters = Length::<Meters>::TYPE_SIGNATURE;
+    let feet = Length::<Feet>::TYPE_SIGNATURE;
+
+    assert_ne!(meters, feet);
+    assert!(meters.key().starts_with("Length("));
+    assert!(meters.key().ends_with("Meters)"));
+    assert!(feet.key().ends_with("Feet)"));
+
+    let mut module = LuaModule::new();
+    Length::<Meters>::generate_lua(&mut module);
+    Length::<Feet>::generate_lua(&mut module);
+    module.load_into_table(&Lua::new()).unwrap();
+}
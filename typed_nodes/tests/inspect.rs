@@ -0,0 +1,103 @@
+#![cfg(feature = "inspect")]
+
+use std::{io::Write, net::TcpStream};
+
+use typed_nodes::{
+    graph::VisitKeys,
+    inspect::{Inspector, MAX_REQUESTS_PER_POLL, MAX_REQUEST_LENGTH},
+    make_bounds, Nodes,
+};
+
+make_bounds!(GraphBounds: VisitKeys + 'static);
+
+#[derive(VisitKeys)]
+struct Room;
+
+fn connect(inspector: &mut Inspector, nodes: &Nodes<(), GraphBounds>) -> TcpStream {
+    let clients = inspector.client_count();
+    let client = TcpStream::connect(inspector.local_addr().unwrap()).unwrap();
+
+    while inspector.client_count() == clients {
+        inspector.poll(nodes, &[]).unwrap();
+    }
+
+    client
+}
+
+#[test]
+fn disconnect_too_long_request() {
+    let nodes = Nodes::<(), GraphBounds>::new();
+    let mut inspector = Inspector::bind("127.0.0.1:0").unwrap();
+    let _quiet_client = connect(&mut inspector, &nodes);
+    let mut client = connect(&mut inspector, &nodes);
+
+    client
+        .write_all(&vec![b' '; MAX_REQUEST_LENGTH + 1])
+        .unwrap();
+
+    while inspector.client_count() == 2 {
+        inspector.poll(&nodes, &[]).unwrap();
+    }
+
+    assert_eq!(inspector.client_count(), 1);
+}
+
+#[test]
+fn disconnect_client_that_does_not_read() {
+    let mut nodes = Nodes::<(), GraphBounds>::new();
+    let mut inspector = Inspector::bind("127.0.0.1:0").unwrap();
+    let _quiet_client = connect(&mut inspector, &nodes);
+    let mut client = connect(&mut inspector, &nodes);
+
+    client.write_all(b"{\"method\": \"subscribe\"}\n").unwrap();
+
+    nodes.enable_journal();
+    for _ in 0..1000 {
+        nodes.insert(Room);
+    }
+    let journal = nodes.take_journal();
+
+    // The events are sent again in each poll, until the client has too
+    // much unread data.
+    for _ in 0..10_000 {
+        inspector.poll(&nodes, &journal).unwrap();
+
+        if inspector.client_count() == 1 {
+            break;
+        }
+    }
+
+    assert_eq!(inspector.client_count(), 1);
+}
+
+#[test]
+fn disconnect_client_that_sends_requests_without_reading() {
+    let mut nodes = Nodes::<(), GraphBounds>::new();
+    for _ in 0..1000 {
+        nodes.insert(Room);
+    }
+
+    let mut inspector = Inspector::bind("127.0.0.1:0").unwrap();
+    let _quiet_client = connect(&mut inspector, &nodes);
+    let mut client = connect(&mut inspector, &nodes);
+
+    // Keeps sending requests until it's disconnected, but never reads the
+    // responses.
+    let sender = std::thread::spawn(move || {
+        let requests = b"{\"method\": \"nodes\"}\n".repeat(MAX_REQUESTS_PER_POLL);
+        while client.write_all(&requests).is_ok() {}
+    });
+
+    for _ in 0..10_000 {
+        inspector.poll(&nodes, &[]).unwrap();
+
+        if inspector.client_count() == 1 {
+            break;
+        }
+    }
+
+    assert_eq!(inspector.client_count(), 1);
+
+    drop(inspector);
+    sender.join().unwrap();
+}
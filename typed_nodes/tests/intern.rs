@@ -0,0 +1,48 @@
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, TableId},
+    Key, Nodes,
+};
+
+#[derive(FromLua, PartialEq, Eq, Hash)]
+#[typed_nodes(intern)]
+enum Uint {
+    Literal(u32),
+    Add { left: Key<Uint>, right: Key<Uint> },
+}
+
+#[derive(FromLua, PartialEq, Eq, Hash)]
+#[typed_nodes(intern)]
+struct Literal {
+    value: u32,
+}
+
+#[test]
+fn intern_equal_nodes() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+    let mut context = Context::<AnyBounds>::new(&lua, &mut nodes);
+
+    let value = lua
+        .load("{{value = 1}, {value = 2}, {value = 1}}")
+        .eval()
+        .unwrap();
+    let keys = Vec::<Key<Literal>>::from_lua(value, &mut context).unwrap();
+
+    assert!(keys[0] == keys[2]);
+    assert!(keys[0] != keys[1]);
+
+    let value = lua
+        .load(
+            r#"{
+                {type = "add", left = {type = "literal", 1}, right = {type = "literal", 2}},
+                {type = "add", left = {type = "literal", 1}, right = {type = "literal", 2}},
+            }"#,
+        )
+        .eval()
+        .unwrap();
+    let keys = Vec::<Key<Uint>>::from_lua(value, &mut context).unwrap();
+
+    assert!(keys[0] == keys[1]);
+    assert_eq!(nodes.dedup::<Uint>().len(), 0);
+}
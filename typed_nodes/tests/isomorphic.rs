@@ -0,0 +1,48 @@
+use typed_nodes::{
+    graph::{find_isomorphic_duplicates, HashBounds, VisitKeys},
+    DynKey, Key, Nodes,
+};
+
+#[derive(Hash, VisitKeys)]
+enum Expr {
+    Number(u32),
+    Add(Key<Expr>, Key<Expr>),
+    Loop(Option<Key<Expr>>),
+}
+
+#[test]
+fn copies_with_different_insertion_order() {
+    let mut nodes = Nodes::<(), HashBounds>::new();
+    let one = nodes.insert(Expr::Number(1));
+    let two = nodes.insert(Expr::Number(2));
+    let sum = nodes.insert(Expr::Add(one, two));
+
+    let two_copy = nodes.insert(Expr::Number(2));
+    let one_copy = nodes.insert(Expr::Number(1));
+    let sum_copy = nodes.insert(Expr::Add(one_copy, two_copy));
+
+    // Same content, but the operands are swapped.
+    let swapped = nodes.insert(Expr::Add(two_copy, one_copy));
+
+    let root = nodes.insert(Expr::Add(sum, sum_copy));
+    let other_root = nodes.insert(Expr::Add(swapped, one));
+
+    let duplicates = find_isomorphic_duplicates(&nodes, [root.into(), other_root.into()]);
+    assert!(duplicates == [vec![DynKey::from(sum), sum_copy.into()]]);
+}
+
+#[test]
+fn cycles_are_not_grouped() {
+    let mut nodes = Nodes::<(), HashBounds>::new();
+    let first = nodes.insert(Expr::Loop(None));
+    let second = nodes.insert(Expr::Loop(Some(first)));
+    *nodes.get_mut(first).unwrap() = Expr::Loop(Some(second));
+
+    let other_first = nodes.insert(Expr::Loop(None));
+    let other_second = nodes.insert(Expr::Loop(Some(other_first)));
+    *nodes.get_mut(other_first).unwrap() = Expr::Loop(Some(other_second));
+
+    let root = nodes.insert(Expr::Add(first, other_first));
+
+    assert!(find_isomorphic_duplicates(&nodes, [root.into()]).is_empty());
+}
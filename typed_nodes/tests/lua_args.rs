@@ -0,0 +1,64 @@
+use mlua::Lua;
+use typed_nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule},
+    Key, Nodes,
+};
+
+#[derive(FromLua, GenerateLua)]
+#[typed_nodes(lua_args(radius, center))]
+struct Circle {
+    center: (f32, f32),
+    radius: f32,
+}
+
+#[derive(FromLua, GenerateLua)]
+enum Shape {
+    #[typed_nodes(lua_args(shape, offset))]
+    Move {
+        offset: (f32, f32),
+        shape: Key<Shape>,
+    },
+    Point {},
+}
+
+fn load_module() -> Lua {
+    let lua = Lua::new();
+    let mut module = LuaModule::new();
+    Circle::generate_lua(&mut module);
+    Shape::generate_lua(&mut module);
+    lua.globals()
+        .set("my_lib", module.load_into_table(&lua).unwrap())
+        .unwrap();
+
+    lua
+}
+
+#[test]
+fn struct_arguments() {
+    let lua = load_module();
+
+    let value = lua.load("my_lib.Circle.new(2, {1, 3})").eval().unwrap();
+    let mut nodes = Nodes::new();
+    let circle = Circle::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    assert_eq!(circle.radius, 2.0);
+    assert_eq!(circle.center, (1.0, 3.0));
+}
+
+#[test]
+fn variant_arguments() {
+    let lua = load_module();
+
+    let value = lua
+        .load("my_lib.Shape.move(my_lib.Shape.point(), {4, 5})")
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let shape = Shape::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    let Shape::Move { offset, shape } = shape else {
+        panic!("expected a move");
+    };
+    assert_eq!(offset, (4.0, 5.0));
+    assert!(matches!(nodes.get(shape), Some(Shape::Point {})));
+}
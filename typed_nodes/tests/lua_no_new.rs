@@ -0,0 +1,80 @@
+use mlua::Lua;
+use typed_nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule},
+    Key, Nodes,
+};
+
+#[derive(FromLua, GenerateLua)]
+enum List {
+    New {},
+}
+
+#[derive(FromLua, GenerateLua)]
+#[typed_nodes(lua_no_new)]
+enum Optional {
+    Some {
+        value: u32,
+    },
+    #[typed_nodes(lua_base_type(List), lua_method = "get")]
+    ListGet {
+        #[typed_nodes(lua_self)]
+        list: Key<List>,
+        index: usize,
+    },
+}
+
+#[derive(FromLua, GenerateLua)]
+#[typed_nodes(lua_no_new)]
+struct Settings {
+    verbose: bool,
+}
+
+fn load_module() -> Lua {
+    let lua = Lua::new();
+    let mut module = LuaModule::new();
+    Optional::generate_lua(&mut module);
+    Settings::generate_lua(&mut module);
+    lua.globals()
+        .set("my_lib", module.load_into_table(&lua).unwrap())
+        .unwrap();
+
+    lua
+}
+
+#[test]
+fn enum_methods_of_other_types_are_kept() {
+    let lua = load_module();
+
+    let is_hidden: bool = lua.load("my_lib.Optional.some == nil").eval().unwrap();
+    assert!(is_hidden);
+
+    let value = lua.load("my_lib.List.new():get(2)").eval().unwrap();
+    let mut nodes = Nodes::new();
+    let optional = Optional::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    let Optional::ListGet { list, index } = optional else {
+        panic!("expected a list lookup");
+    };
+    assert!(matches!(nodes.get(list), Some(List::New {})));
+    assert_eq!(index, 2);
+
+    let value = lua.load("{type = 'some', value = 5}").eval().unwrap();
+    let optional = Optional::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    assert!(matches!(optional, Optional::Some { value: 5 }));
+}
+
+#[test]
+fn struct_without_new() {
+    let lua = load_module();
+
+    let is_hidden: bool = lua
+        .load("my_lib.Settings ~= nil and my_lib.Settings.new == nil")
+        .eval()
+        .unwrap();
+    assert!(is_hidden);
+
+    let value = lua.load("{verbose = true}").eval().unwrap();
+    let mut nodes = Nodes::new();
+    let settings = Settings::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+    assert!(settings.verbose);
+}
@@ -0,0 +1,74 @@
+use typed_nodes::{
+    mlua::{Context, FromLua, ParseError, PathSegment},
+    Key, Nodes,
+};
+
+#[derive(FromLua)]
+struct Level {
+    rooms: Vec<Key<Room>>,
+}
+
+#[derive(FromLua)]
+struct Room {
+    exit: Option<Key<Room>>,
+    size: u32,
+}
+
+#[test]
+fn nested_reference_paths() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::new();
+    let mut context = Context::new(&lua, &mut nodes);
+
+    let value: mlua::Table = lua
+        .load(r#"{rooms = {{size = 1}, {size = 2, exit = {size = "large"}}}}"#)
+        .eval()
+        .unwrap();
+    let error = context.parse::<Level>(value).err().unwrap();
+    let parse_error = ParseError::find(&error).unwrap();
+
+    let table_ids: Vec<_> = parse_error
+        .path()
+        .iter()
+        .filter_map(|segment| match segment {
+            PathSegment::Node {
+                table_id,
+                node_type,
+            } => {
+                assert!(node_type.ends_with("Room"));
+                Some(*table_id)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(table_ids.len(), 2);
+
+    assert_eq!(
+        parse_error.to_string(),
+        format!(
+            "in rooms, in [2], while parsing node {} referenced from rooms[2], \
+            in exit, while parsing node {} referenced from rooms[2].exit, \
+            in size, {}",
+            table_ids[0],
+            table_ids[1],
+            parse_error.message(),
+        )
+    );
+}
+
+#[test]
+fn valid_references_are_unchanged() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::new();
+    let mut context = Context::new(&lua, &mut nodes);
+
+    let value: mlua::Table = lua
+        .load(r#"local hall = {size = 3}; return {rooms = {hall, {size = 1, exit = hall}}}"#)
+        .eval()
+        .unwrap();
+    let level = context.parse::<Level>(value).unwrap();
+
+    let exit = nodes.get(level.rooms[1]).unwrap().exit;
+    assert!(exit == Some(level.rooms[0]));
+    assert_eq!(nodes.get(level.rooms[0]).unwrap().size, 3);
+}
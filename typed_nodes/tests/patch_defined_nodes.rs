@@ -0,0 +1,124 @@
+use mlua::Lua;
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, GenerateLua, LuaModule, TableId},
+    Key, Nodes,
+};
+
+#[derive(FromLua, GenerateLua, Debug, PartialEq)]
+#[typed_nodes(lua_define)]
+struct Unit {
+    name: String,
+    health: u32,
+    armor: Option<Armor>,
+    speed: Speed,
+}
+
+#[derive(FromLua, GenerateLua, Debug, PartialEq)]
+struct Armor {
+    value: u32,
+}
+
+#[derive(FromLua, GenerateLua, Debug, PartialEq)]
+struct Speed {
+    walk: f32,
+    run: f32,
+}
+
+fn setup() -> Lua {
+    let mut module = LuaModule::new();
+    Unit::generate_lua(&mut module);
+    Armor::generate_lua(&mut module);
+    Speed::generate_lua(&mut module);
+
+    let lua = Lua::new();
+    let game = module.load_into_table(&lua).unwrap();
+    lua.globals().set("game", game).unwrap();
+    lua
+}
+
+fn parse_base(lua: &Lua, nodes: &mut Nodes<TableId, AnyBounds>) -> Key<Unit> {
+    let value: mlua::Value = lua
+        .load(r#"game.Unit.new("Knight", 100, nil, game.Speed.new(1, 2)):define("knight")"#)
+        .eval()
+        .unwrap();
+    Context::new(lua, nodes).parse(value).unwrap()
+}
+
+fn parse_override(
+    lua: &Lua,
+    nodes: &mut Nodes<TableId, AnyBounds>,
+    source: &str,
+) -> mlua::Result<Key<Unit>> {
+    let value: mlua::Value = lua.load(source).eval()?;
+    Context::builder(lua, nodes)
+        .patch_defined_nodes()
+        .build()
+        .parse(value)
+}
+
+#[test]
+fn patch_set_fields() {
+    let lua = setup();
+    let mut nodes = Nodes::new();
+    let knight = parse_base(&lua, &mut nodes);
+
+    let patched = parse_override(
+        &lua,
+        &mut nodes,
+        r#"{_node_name = "knight", armor = {value = 5}, speed = {run = 3}}"#,
+    )
+    .unwrap();
+
+    assert!(patched == knight);
+    assert_eq!(nodes.filled_len::<Unit>(), 1);
+    assert_eq!(
+        nodes.get(knight),
+        Some(&Unit {
+            name: "Knight".into(),
+            health: 100,
+            armor: Some(Armor { value: 5 }),
+            speed: Speed {
+                walk: 1.0,
+                run: 3.0
+            },
+        })
+    );
+}
+
+#[test]
+fn unknown_name_inserts_new_node() {
+    let lua = setup();
+    let mut nodes = Nodes::new();
+    let knight = parse_base(&lua, &mut nodes);
+
+    let archer = parse_override(
+        &lua,
+        &mut nodes,
+        r#"game.Unit.new("Archer", 60, nil, game.Speed.new(1, 2)):define("archer")"#,
+    )
+    .unwrap();
+
+    assert!(archer != knight);
+    assert_eq!(nodes.filled_len::<Unit>(), 2);
+    assert_eq!(nodes.get(knight).unwrap().name, "Knight");
+    assert_eq!(nodes.get(archer).unwrap().name, "Archer");
+}
+
+#[test]
+fn invalid_patch_keeps_node() {
+    let lua = setup();
+    let mut nodes = Nodes::new();
+    let knight = parse_base(&lua, &mut nodes);
+
+    let error = parse_override(
+        &lua,
+        &mut nodes,
+        r#"{_node_name = "knight", health = "lots"}"#,
+    )
+    .err()
+    .unwrap();
+
+    assert!(error.to_string().contains("health"), "{error}");
+    assert_eq!(nodes.get(knight).unwrap().health, 100);
+}
@@ -0,0 +1,68 @@
+use mlua::Lua;
+use typed_nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule, ParseError, PathSegment},
+    Nodes,
+};
+
+#[derive(FromLua, GenerateLua, Debug, PartialEq)]
+enum Position {
+    #[typed_nodes(untagged(table))]
+    Vec2 {
+        x: f32,
+        y: f32,
+    },
+    Origin {},
+}
+
+#[derive(FromLua, Debug, PartialEq)]
+enum Range {
+    #[typed_nodes(untagged(table))]
+    Between(u32, u32),
+}
+
+fn parse<T>(lua: &Lua, source: &str) -> mlua::Result<T>
+where
+    T: for<'lua> FromLua<'lua, typed_nodes::bounds::AnyBounds>,
+{
+    let value = lua.load(source).eval()?;
+    let mut nodes = Nodes::new();
+    T::from_lua(value, &mut Context::new(lua, &mut nodes))
+}
+
+#[test]
+fn named_fields_from_sequence() {
+    let lua = Lua::new();
+
+    assert_eq!(
+        parse::<Position>(&lua, "{1.5, 2}").unwrap(),
+        Position::Vec2 { x: 1.5, y: 2.0 }
+    );
+    assert_eq!(
+        parse::<Range>(&lua, "{3, 7}").unwrap(),
+        Range::Between(3, 7)
+    );
+}
+
+#[test]
+fn errors_have_element_index() {
+    let lua = Lua::new();
+    let error = parse::<Position>(&lua, "{1, 'up'}").unwrap_err();
+    let parse_error = ParseError::find(&error).unwrap();
+
+    assert_eq!(parse_error.path(), [PathSegment::Index(2)]);
+}
+
+#[test]
+fn generated_constructor_makes_sequence() {
+    let lua = Lua::new();
+    let mut module = LuaModule::new();
+    Position::generate_lua(&mut module);
+    lua.globals()
+        .set("my_lib", module.load_into_table(&lua).unwrap())
+        .unwrap();
+
+    assert_eq!(
+        parse::<Position>(&lua, "my_lib.Position.vec_2(3, 4)").unwrap(),
+        Position::Vec2 { x: 3.0, y: 4.0 }
+    );
+}
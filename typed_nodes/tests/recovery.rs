@@ -0,0 +1,59 @@
+use typed_nodes::{
+    mlua::{Context, FromLua, Placeholder},
+    Key, Nodes,
+};
+
+#[derive(FromLua)]
+#[typed_nodes(deny_unknown_fields)]
+struct Level {
+    enemies: Vec<Key<Enemy>>,
+    name: String,
+}
+
+#[derive(FromLua)]
+#[typed_nodes(deny_unknown_fields)]
+struct Enemy {
+    health: u32,
+}
+
+#[test]
+fn placeholders_keep_siblings() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::new();
+    let mut context = Context::builder(&lua, &mut nodes).placeholders().build();
+
+    let value: mlua::Table = lua
+        .load(
+            r#"{
+                name = "intro",
+                enemies = {{health = 10}, {health = 5, armor = 2}, {health = 3}},
+            }"#,
+        )
+        .eval()
+        .unwrap();
+    let level: Level = context.parse(value).unwrap();
+    context.finish().unwrap();
+
+    assert_eq!(level.name, "intro");
+    assert_eq!(nodes.get(level.enemies[0]).unwrap().health, 10);
+    assert!(nodes.get(level.enemies[1]).is_none());
+    assert_eq!(nodes.get(level.enemies[2]).unwrap().health, 3);
+
+    let placeholder = Placeholder::find(&nodes, level.enemies[1]).unwrap();
+    assert!(placeholder.error().message().contains("armor"));
+    assert!(Placeholder::find(&nodes, level.enemies[0]).is_none());
+}
+
+#[test]
+fn errors_without_recovery() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::new();
+    let mut context = Context::new(&lua, &mut nodes);
+
+    let value: mlua::Table = lua
+        .load(r#"{name = "intro", enemies = {{health = "full"}}}"#)
+        .eval()
+        .unwrap();
+
+    assert!(context.parse::<Level>(value).is_err());
+}
@@ -0,0 +1,117 @@
+use mlua::Lua;
+use typed_nodes::{
+    bounds::SendSyncBounds,
+    mlua::{Context, FromLua},
+    Nodes,
+};
+
+#[derive(FromLua)]
+struct List {
+    name: String,
+    next: Option<Box<List>>,
+}
+
+#[derive(FromLua)]
+struct Tree {
+    children: Vec<Tree>,
+}
+
+#[derive(FromLua)]
+#[typed_nodes(sync)]
+enum Expression {
+    Number {
+        value: f64,
+    },
+    Add {
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+}
+
+impl Expression {
+    fn eval(&self) -> f64 {
+        match self {
+            Expression::Number { value } => *value,
+            Expression::Add { left, right } => left.eval() + right.eval(),
+        }
+    }
+}
+
+#[test]
+fn boxed_fields() {
+    let lua = Lua::new();
+    let value = lua
+        .load(r#"{name = "first", next = {name = "last"}}"#)
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let list = List::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    assert_eq!(list.name, "first");
+    let next = list.next.unwrap();
+    assert_eq!(next.name, "last");
+    assert!(next.next.is_none());
+}
+
+#[test]
+fn boxed_variants() {
+    let lua = Lua::new();
+    let value = lua
+        .load(
+            r#"{
+                type = "add",
+                left = {type = "number", value = 1},
+                right = {type = "add", left = {type = "number", value = 2}, right = {type = "number", value = 3}},
+            }"#,
+        )
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::<_, SendSyncBounds>::new();
+    let expression = Expression::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    assert_eq!(expression.eval(), 6.0);
+}
+
+#[test]
+fn self_referencing_box() {
+    let lua = Lua::new();
+    let value = lua
+        .load("local list = {name = 'loop'}; list.next = list; return list")
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let error = List::from_lua(value, &mut Context::new(&lua, &mut nodes))
+        .err()
+        .unwrap();
+
+    assert!(error.to_string().contains("nested too deeply"), "{error}");
+}
+
+#[test]
+fn ancestor_referencing_sequence() {
+    let lua = Lua::new();
+    let value = lua
+        .load("local root = {children = {{children = {}}}}; root.children[1].children[1] = root; return root")
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let error = Tree::from_lua(value, &mut Context::new(&lua, &mut nodes))
+        .err()
+        .unwrap();
+
+    assert!(error.to_string().contains("nested too deeply"), "{error}");
+}
+
+#[test]
+fn shared_tables_are_not_cycles() {
+    let lua = Lua::new();
+    let value = lua
+        .load("local leaf = {children = {}}; return {children = {leaf, {children = {leaf}}}}")
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let tree = Tree::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    assert_eq!(tree.children.len(), 2);
+    assert_eq!(tree.children[1].children.len(), 1);
+}
@@ -0,0 +1,78 @@
+use typed_nodes::Nodes;
+
+#[derive(Debug, PartialEq)]
+struct Class(&'static str);
+
+#[test]
+fn chained_redirects_follow_the_last_target() {
+    let mut nodes = Nodes::<()>::new();
+    let a = nodes.insert(Class("a"));
+    let b = nodes.insert(Class("b"));
+    let c = nodes.insert(Class("c"));
+    let d = nodes.insert(Class("d"));
+
+    nodes.redirect(a, b);
+    nodes.redirect(b, c);
+    nodes.redirect(c, d);
+
+    assert!([a, b, c].iter().all(|&key| nodes.resolve(key) == d));
+    assert_eq!(nodes.get(a), Some(&Class("d")));
+}
+
+#[test]
+fn removing_the_target_removes_the_redirects() {
+    let mut nodes = Nodes::<()>::new();
+    let a = nodes.insert(Class("a"));
+    let b = nodes.insert(Class("b"));
+    let c = nodes.insert(Class("c"));
+    let other = nodes.insert(Class("other"));
+    let other_target = nodes.insert(Class("other target"));
+
+    nodes.redirect(a, b);
+    nodes.redirect(b, c);
+    nodes.redirect(other, other_target);
+    nodes.remove(c);
+
+    assert!(nodes.resolve(a) == a);
+    assert!(nodes.resolve(b) == b);
+    assert!(nodes.get(a).is_none());
+    assert!(nodes.resolve(other) == other_target);
+}
+
+#[test]
+fn redirect_to_a_redirected_key() {
+    let mut nodes = Nodes::<()>::new();
+    let a = nodes.insert(Class("a"));
+    let b = nodes.insert(Class("b"));
+    let c = nodes.insert(Class("c"));
+
+    nodes.redirect(b, c);
+    nodes.redirect(a, b);
+
+    assert!(nodes.resolve(a) == c);
+    nodes.remove(c);
+    assert!(nodes.resolve(a) == a);
+}
+
+#[test]
+fn vacant_keys_are_not_redirected() {
+    let mut nodes = Nodes::<()>::new();
+    let removed = nodes.insert(Class("removed"));
+    let target = nodes.insert(Class("target"));
+    nodes.remove(removed);
+
+    assert_eq!(nodes.redirect(removed, target), None);
+    assert!(nodes.resolve(removed) == removed);
+    assert!(nodes.get(removed).is_none());
+}
+
+#[test]
+fn reserved_keys_are_redirected() {
+    let mut nodes = Nodes::<()>::new();
+    let reservation = nodes.reserve::<Class>();
+    let target = nodes.insert(Class("target"));
+
+    assert_eq!(nodes.redirect(reservation.key(), target), None);
+    assert!(nodes.resolve(reservation.key()) == target);
+    assert_eq!(nodes.get(reservation.key()), Some(&Class("target")));
+}
@@ -0,0 +1,45 @@
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, TableId},
+    Key, Nodes,
+};
+
+#[derive(FromLua)]
+struct Material {
+    density: f64,
+}
+
+#[derive(FromLua)]
+struct Beam {
+    #[typed_nodes(reference)]
+    material: Key<Material>,
+}
+
+fn parse_beam<'lua>(lua: &'lua mlua::Lua, context: &mut Context<'lua, AnyBounds>) -> Beam {
+    let value = lua.load(r#"{material = "wood"}"#).eval().unwrap();
+    Beam::from_lua(value, context).unwrap()
+}
+
+#[test]
+fn unresolved_name_is_pending_in_later_contexts() {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+
+    let mut context = Context::new(&lua, &mut nodes);
+    let first = parse_beam(&lua, &mut context);
+    assert!(context.finish().is_err());
+    drop(context);
+
+    let mut context = Context::new(&lua, &mut nodes);
+    let second = parse_beam(&lua, &mut context);
+    assert!(second.material == first.material);
+    assert!(context.finish().is_err());
+
+    let wood = context.insert_named("wood", Material { density: 0.6 });
+    assert!(context.finish().is_ok());
+    drop(context);
+
+    assert!(wood == first.material);
+    assert_eq!(nodes.get(first.material).unwrap().density, 0.6);
+    assert_eq!(nodes.get(second.material).unwrap().density, 0.6);
+}
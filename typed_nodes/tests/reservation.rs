@@ -0,0 +1,213 @@
+use typed_nodes::{DynKey, Key, Nodes};
+
+struct Room {
+    name: &'static str,
+    exit: Option<Key<Room>>,
+}
+
+impl Room {
+    fn new(name: &'static str) -> Self {
+        Room { name, exit: None }
+    }
+}
+
+fn reserved_slots(nodes: &Nodes<&'static str>) -> usize {
+    nodes.stats().iter().map(|stats| stats.reserved).sum()
+}
+
+#[test]
+fn fulfill_fills_the_slot() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reservation, old_key) = nodes.reserve_id::<Room>("hall");
+    let key = reservation.key();
+
+    assert!(old_key.is_none());
+    assert!(nodes.get(key).is_none());
+    assert!(nodes.get_key::<Room, _>("hall") == Some(key));
+
+    let filled = nodes.fulfill(reservation, Room::new("hall")).ok().unwrap();
+
+    assert!(filled == key);
+    assert_eq!(nodes.get(key).unwrap().name, "hall");
+    assert_eq!(nodes.release_abandoned(), 0);
+    assert_eq!(reserved_slots(&nodes), 0);
+}
+
+#[test]
+fn fulfill_with_circular_reference() {
+    let mut nodes = Nodes::<&str>::new();
+    let reservation = nodes.reserve::<Room>();
+    let hall = nodes.insert(Room {
+        name: "hall",
+        exit: Some(reservation.key()),
+    });
+    let cellar = nodes
+        .fulfill(
+            reservation,
+            Room {
+                name: "cellar",
+                exit: Some(hall),
+            },
+        )
+        .ok()
+        .unwrap();
+
+    let exit = nodes.get(hall).unwrap().exit.unwrap();
+    assert_eq!(nodes.get(exit).unwrap().name, "cellar");
+    assert!(nodes.get(cellar).unwrap().exit == Some(hall));
+}
+
+#[test]
+fn dropped_reservation_is_released() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reservation, _) = nodes.reserve_id::<Room>("attic");
+    let key = reservation.key();
+    nodes.tag(key, "upstairs");
+    drop(reservation);
+
+    assert_eq!(reserved_slots(&nodes), 1);
+    assert_eq!(nodes.release_abandoned(), 1);
+    assert_eq!(reserved_slots(&nodes), 0);
+    assert!(nodes.get_key::<Room, _>("attic").is_none());
+    assert_eq!(nodes.keys_with_tag("upstairs").count(), 0);
+
+    assert_eq!(nodes.release_abandoned(), 0);
+}
+
+#[test]
+fn dropped_reservations_are_released_by_later_reservations() {
+    let mut nodes = Nodes::<&str>::new();
+
+    for _ in 0..1000 {
+        let _ = nodes.reserve::<Room>();
+    }
+
+    assert!(reserved_slots(&nodes) < 1000);
+}
+
+#[test]
+fn live_reservations_are_kept() {
+    let mut nodes = Nodes::<&str>::new();
+    let reservations: Vec<_> = (0..100).map(|_| nodes.reserve::<Room>()).collect();
+
+    assert_eq!(nodes.release_abandoned(), 0);
+    assert_eq!(reserved_slots(&nodes), 100);
+
+    for reservation in reservations {
+        assert!(nodes.fulfill(reservation, Room::new("room")).is_ok());
+    }
+
+    assert_eq!(nodes.stats()[0].nodes, 100);
+}
+
+#[test]
+fn remove_reserved_slot() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reservation, _) = nodes.reserve_id::<Room>("hall");
+    let key = reservation.key();
+    nodes.tag(key, "ground floor");
+
+    assert!(nodes.remove(key).is_none());
+    assert!(nodes.get_key::<Room, _>("hall").is_none());
+    assert_eq!(nodes.keys_with_tag("ground floor").count(), 0);
+    assert_eq!(reserved_slots(&nodes), 0);
+
+    let node = nodes.fulfill(reservation, Room::new("hall")).err().unwrap();
+    assert_eq!(node.name, "hall");
+    assert!(nodes.get(key).is_none());
+}
+
+#[test]
+fn remove_dyn_reserved_slot() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reservation, _) = nodes.reserve_id::<Room>("hall");
+
+    assert!(!nodes.remove_dyn(DynKey::from(reservation.key())));
+    assert!(nodes.get_key::<Room, _>("hall").is_none());
+    assert!(nodes.fulfill(reservation, Room::new("hall")).is_err());
+}
+
+#[test]
+fn try_insert_reserved_after_remove() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reserved_key, _) = nodes.reserve_with_id::<Room>("hall");
+    let key = reserved_key.key();
+    nodes.remove(key);
+
+    assert!(nodes
+        .try_insert_reserved(reserved_key, Room::new("hall"))
+        .is_err());
+    assert!(nodes.get(key).is_none());
+}
+
+#[test]
+fn try_insert_reserved_without_group() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reserved_key, _) = nodes.reserve_with_id::<Room>("hall");
+    let mut other_nodes = Nodes::<&str>::new();
+
+    assert!(other_nodes
+        .try_insert_reserved(reserved_key, Room::new("hall"))
+        .is_err());
+}
+
+#[test]
+#[should_panic(expected = "reserved slot was removed")]
+fn insert_reserved_after_remove_panics() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reserved_key, _) = nodes.reserve_with_id::<Room>("hall");
+    nodes.remove(reserved_key.key());
+
+    nodes.insert_reserved(reserved_key, Room::new("hall"));
+}
+
+#[test]
+fn replace_fills_reservation() {
+    let mut nodes = Nodes::<&str>::new();
+    let reservation = nodes.reserve::<Room>();
+    let key = reservation.key();
+
+    assert!(nodes.replace(key, Room::new("hall")).is_none());
+    drop(reservation);
+
+    assert_eq!(nodes.release_abandoned(), 0);
+    assert_eq!(nodes.get(key).unwrap().name, "hall");
+}
+
+#[test]
+fn reserve_id_replaces_id() {
+    let mut nodes = Nodes::<&str>::new();
+    let (old_key, _) = nodes.insert_with_id("hall", Room::new("old hall"));
+    let (reservation, replaced) = nodes.reserve_id::<Room>("hall");
+
+    assert!(replaced == Some(old_key));
+    assert!(nodes.get_key::<Room, _>("hall") == Some(reservation.key()));
+
+    drop(reservation);
+    nodes.release_abandoned();
+
+    assert!(nodes.get_key::<Room, _>("hall").is_none());
+    assert_eq!(nodes.get(old_key).unwrap().name, "old hall");
+}
+
+#[test]
+fn id_reassigned_before_release() {
+    let mut nodes = Nodes::<&str>::new();
+    let (reservation, _) = nodes.reserve_id::<Room>("hall");
+    let (key, _) = nodes.insert_with_id("hall", Room::new("hall"));
+    drop(reservation);
+
+    assert_eq!(nodes.release_abandoned(), 1);
+    assert!(nodes.get_key::<Room, _>("hall") == Some(key));
+}
+
+#[test]
+fn take_and_remove() {
+    let mut nodes = Nodes::<&str>::new();
+    let key = nodes.insert(Room::new("hall"));
+    let node = nodes.take(key).unwrap();
+
+    assert!(nodes.remove(key).is_none());
+    assert_eq!(nodes.put_back(key, node).unwrap_err().name, "hall");
+    assert_eq!(reserved_slots(&nodes), 0);
+}
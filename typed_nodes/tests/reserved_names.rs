@@ -0,0 +1,74 @@
+// The fields with reserved names trigger warnings in `GenerateLua`.
+#![allow(deprecated)]
+
+use mlua::Lua;
+use typed_nodes::mlua::{Context, FromLua, GenerateLua, LuaModule};
+use typed_nodes::{Key, Nodes};
+
+#[derive(FromLua, GenerateLua)]
+struct Range {
+    r#for: String,
+    end: u32,
+    local: bool,
+}
+
+#[derive(FromLua, GenerateLua)]
+enum Block {
+    #[typed_nodes(lua_method = "end")]
+    End {
+        #[typed_nodes(lua_self)]
+        function: Key<Block>,
+    },
+    Start {},
+}
+
+fn load_module() -> Lua {
+    let lua = Lua::new();
+    let mut module = LuaModule::new();
+    Range::generate_lua(&mut module);
+    Block::generate_lua(&mut module);
+    lua.globals()
+        .set("my_lib", module.load_into_table(&lua).unwrap())
+        .unwrap();
+
+    lua
+}
+
+#[test]
+fn escaped_parameters_and_keys() {
+    let lua = load_module();
+
+    let value = lua
+        .load(r#"my_lib.Range.new("items", 5, true)"#)
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let range = Range::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    assert_eq!(range.r#for, "items");
+    assert_eq!(range.end, 5);
+    assert!(range.local);
+
+    let is_table_key: bool = lua
+        .load(r#"my_lib.Range.new("items", 5, true)["end"] == 5"#)
+        .eval()
+        .unwrap();
+    assert!(is_table_key);
+}
+
+#[test]
+fn reserved_method_name() {
+    let lua = load_module();
+
+    let value = lua
+        .load(r#"local start = my_lib.Block.start(); return start["end"](start)"#)
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let block = Block::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    let Block::End { function } = block else {
+        panic!("expected an end block");
+    };
+    assert!(matches!(nodes.get(function), Some(Block::Start {})));
+}
@@ -0,0 +1,112 @@
+use typed_nodes::{
+    graph::{RemapBounds, RemapKeys, VisitKeys},
+    pass::Rewrite,
+    Key, Nodes,
+};
+
+#[derive(VisitKeys, RemapKeys, Debug, PartialEq)]
+enum Expr {
+    Constant(i32),
+    Negate(Key<Expr>),
+    Add(Key<Expr>, Key<Expr>),
+}
+
+#[derive(VisitKeys, RemapKeys)]
+struct Output {
+    value: Key<Expr>,
+}
+
+#[test]
+fn keep_all() {
+    let mut nodes = Nodes::<(), RemapBounds>::new();
+    let one = nodes.insert(Expr::Constant(1));
+    let negated = nodes.insert(Expr::Negate(one));
+
+    let remap = nodes.rewrite::<Expr>(|_, _, _| Rewrite::Keep);
+
+    assert!(remap.is_empty());
+    assert_eq!(nodes.get(one), Some(&Expr::Constant(1)));
+    assert_eq!(nodes.get(negated), Some(&Expr::Negate(one)));
+}
+
+#[test]
+fn replace_in_dependency_order() {
+    let mut nodes = Nodes::<(), RemapBounds>::new();
+    let one = nodes.insert(Expr::Constant(1));
+    let negated = nodes.insert(Expr::Negate(one));
+    let twice = nodes.insert(Expr::Negate(negated));
+
+    nodes.rewrite::<Expr>(|_, expr, nodes| match *expr {
+        Expr::Negate(inner) => match nodes.get(inner) {
+            Some(&Expr::Constant(value)) => Rewrite::Replace(Expr::Constant(-value)),
+            _ => Rewrite::Keep,
+        },
+        _ => Rewrite::Keep,
+    });
+
+    // The inner negation is folded first, so the outer one can be folded too.
+    assert_eq!(nodes.get(negated), Some(&Expr::Constant(-1)));
+    assert_eq!(nodes.get(twice), Some(&Expr::Constant(1)));
+}
+
+#[test]
+fn redirect_updates_references_and_ids() {
+    let mut nodes = Nodes::<&str, RemapBounds>::new();
+    let (zero, _) = nodes.insert_with_id("zero", Expr::Constant(0));
+    let two = nodes.insert(Expr::Constant(2));
+    let sum = nodes.insert(Expr::Add(two, zero));
+    let output = nodes.insert(Output { value: sum });
+    nodes.insert_with_id("sum", Expr::Add(sum, zero));
+
+    let remap = nodes.rewrite::<Expr>(|_, expr, nodes| match *expr {
+        Expr::Add(left, right) if nodes.get(right) == Some(&Expr::Constant(0)) => {
+            Rewrite::Redirect(left)
+        }
+        _ => Rewrite::Keep,
+    });
+
+    assert!(remap.get(sum) == two);
+    assert!(nodes.get(sum).is_none());
+    assert!(nodes.get(output).unwrap().value == two);
+    assert!(nodes.get_key::<Expr, _>("sum") == Some(two));
+    assert!(nodes.get_key::<Expr, _>("zero") == Some(zero));
+}
+
+#[test]
+fn redirect_cycle_keeps_one_node() {
+    let mut nodes = Nodes::<(), RemapBounds>::new();
+    let first = nodes.insert(Expr::Constant(1));
+    let second = nodes.insert(Expr::Constant(2));
+
+    nodes.rewrite::<Expr>(|key, _, _| {
+        if key == first {
+            Rewrite::Redirect(second)
+        } else {
+            Rewrite::Redirect(first)
+        }
+    });
+
+    let remaining: Vec<_> = nodes.keys::<Expr>().collect();
+    assert_eq!(remaining.len(), 1);
+}
+
+#[test]
+fn remove_leaves_dangling_references() {
+    let mut nodes = Nodes::<(), RemapBounds>::new();
+    let one = nodes.insert(Expr::Constant(1));
+    let negated = nodes.insert(Expr::Negate(one));
+    let output = nodes.insert(Output { value: one });
+
+    let remap = nodes.rewrite::<Expr>(|_, expr, _| match expr {
+        Expr::Constant(_) => Rewrite::Remove,
+        _ => Rewrite::Keep,
+    });
+
+    assert!(remap.is_empty());
+    assert!(nodes.get(one).is_none());
+
+    // The references are not updated, so they don't point to any node.
+    assert_eq!(nodes.get(negated), Some(&Expr::Negate(one)));
+    let value = nodes.get(output).unwrap().value;
+    assert!(value == one && nodes.get(value).is_none());
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "snapshot")]
+
+use serde::{Deserialize, Serialize};
+use typed_nodes::{snapshot::SnapshotRegistry, DynKey, Key, Nodes};
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Item {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Inventory {
+    items: Vec<Key<Item>>,
+    favorite: Option<DynKey>,
+}
+
+fn registry() -> SnapshotRegistry<()> {
+    let mut registry = SnapshotRegistry::new();
+    registry
+        .register::<Item>("Item")
+        .register::<Inventory>("Inventory");
+    registry
+}
+
+#[test]
+fn keys_survive_snapshot() {
+    let mut nodes = Nodes::new();
+    let sword = nodes.insert(Item {
+        name: "sword".into(),
+    });
+    let shield = nodes.insert(Item {
+        name: "shield".into(),
+    });
+    let inventory = nodes.insert(Inventory {
+        items: vec![sword, shield],
+        favorite: Some(shield.into()),
+    });
+
+    let registry = registry();
+    let bytes = nodes.to_snapshot(&registry).unwrap();
+    let loaded = Nodes::from_snapshot(&bytes, &registry).unwrap();
+
+    let inventory = loaded.get(inventory).unwrap();
+    assert!(inventory.items == [sword, shield]);
+
+    let favorite = inventory.favorite.unwrap().into_static::<Item>().unwrap();
+    assert!(favorite == shield);
+    assert_eq!(loaded.get(favorite).unwrap().name, "shield");
+    assert_eq!(loaded.iter::<Item>().len(), 2);
+}
+
+#[test]
+fn dyn_key_needs_snapshot() {
+    let mut nodes = Nodes::<()>::new();
+    let key: DynKey = nodes
+        .insert(Item {
+            name: "sword".into(),
+        })
+        .into();
+
+    assert!(postcard::to_allocvec(&key).is_err());
+}
+
+#[test]
+fn unregistered_type() {
+    let mut nodes = Nodes::<()>::new();
+    nodes.insert(Item {
+        name: "sword".into(),
+    });
+
+    let error = nodes.to_snapshot(&SnapshotRegistry::new()).err().unwrap();
+    assert!(error.to_string().contains("Item"));
+}
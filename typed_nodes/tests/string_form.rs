@@ -0,0 +1,52 @@
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, TableId},
+    Nodes,
+};
+
+#[derive(FromLua, Debug, PartialEq)]
+#[typed_nodes(no_string_form)]
+enum Align {
+    Start,
+    End,
+    #[typed_nodes(untagged(string))]
+    Named(String),
+}
+
+#[derive(FromLua, Debug, PartialEq)]
+enum Size {
+    #[typed_nodes(string_form)]
+    Auto,
+    #[typed_nodes(string_form)]
+    Fill,
+    Fixed {
+        value: f64,
+    },
+}
+
+fn parse<T: for<'lua> FromLua<'lua, AnyBounds>>(source: &str) -> mlua::Result<T> {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+    let value = lua.load(source).eval()?;
+    T::from_lua(value, &mut Context::new(&lua, &mut nodes))
+}
+
+#[test]
+fn no_string_form() {
+    assert_eq!(
+        parse::<Align>("'start'").unwrap(),
+        Align::Named("start".to_owned())
+    );
+    assert_eq!(parse::<Align>("{type = 'start'}").unwrap(), Align::Start);
+}
+
+#[test]
+fn explicit_string_form() {
+    assert_eq!(parse::<Size>("'auto'").unwrap(), Size::Auto);
+    assert_eq!(parse::<Size>("'fill'").unwrap(), Size::Fill);
+    assert_eq!(
+        parse::<Size>("{type = 'fixed', value = 2}").unwrap(),
+        Size::Fixed { value: 2.0 }
+    );
+    assert!(parse::<Size>("'fixed'").is_err());
+}
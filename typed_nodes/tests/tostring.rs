@@ -0,0 +1,83 @@
+use mlua::Lua;
+use typed_nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule},
+    Key, Nodes,
+};
+
+#[derive(FromLua, GenerateLua)]
+#[typed_nodes(lua_define)]
+enum Shape {
+    Circle { radius: f64, label: String },
+    Group { shapes: Vec<Key<Shape>> },
+}
+
+#[derive(FromLua, GenerateLua)]
+struct Repeat<T, const N: usize> {
+    value: T,
+}
+
+fn load_module(lua: &Lua) {
+    let mut module = LuaModule::new();
+    Shape::generate_lua(&mut module);
+    Repeat::<Shape, 2>::generate_lua(&mut module);
+    lua.globals()
+        .set("shapes", module.load_into_table(lua).unwrap())
+        .unwrap();
+}
+
+fn to_string(lua: &Lua, source: &str) -> String {
+    lua.load(format!("tostring({source})")).eval().unwrap()
+}
+
+#[test]
+fn scalar_fields_and_tag() {
+    let lua = Lua::new();
+    load_module(&lua);
+
+    assert_eq!(
+        to_string(&lua, r#"shapes.Shape.circle(2, "sun"):define("big")"#),
+        r#"Shape {label = "sun", radius = 2, type = "circle"}"#
+    );
+    assert_eq!(
+        to_string(&lua, "shapes.Shape.group({})"),
+        r#"Shape {type = "group"}"#
+    );
+}
+
+#[test]
+fn generic_variant_name() {
+    let lua = Lua::new();
+    load_module(&lua);
+
+    assert_eq!(
+        to_string(&lua, "shapes.Repeat(shapes.Shape, 2).new(5)"),
+        "Repeat(Shape,2) {value = 5}"
+    );
+}
+
+#[test]
+fn values_with_metamethods_can_be_parsed() {
+    let lua = Lua::new();
+    load_module(&lua);
+
+    let value = lua
+        .load(
+            r#"
+            local sun = shapes.Shape.circle(2, "sun")
+            return shapes.Repeat(shapes.Shape, 2).new(shapes.Shape.group({sun}))
+            "#,
+        )
+        .eval()
+        .unwrap();
+    let mut nodes = Nodes::new();
+    let repeat = Repeat::<Shape, 2>::from_lua(value, &mut Context::new(&lua, &mut nodes)).unwrap();
+
+    let Shape::Group { shapes } = repeat.value else {
+        panic!("expected a group");
+    };
+    let Some(Shape::Circle { radius, label }) = nodes.get(shapes[0]) else {
+        panic!("expected a circle");
+    };
+    assert_eq!(*radius, 2.0);
+    assert_eq!(label, "sun");
+}
@@ -0,0 +1,12 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+enum Shape {
+    #[typed_nodes(alias = "box")]
+    Rect,
+    Point,
+    #[typed_nodes(alias = "box")]
+    Square,
+}
+
+fn main() {}
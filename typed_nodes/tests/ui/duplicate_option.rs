@@ -0,0 +1,10 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+#[typed_nodes(is_node, tag = kind)]
+#[typed_nodes(tag = variant)]
+enum Shape {
+    Circle { radius: f64 },
+}
+
+fn main() {}
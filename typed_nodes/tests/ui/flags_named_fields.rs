@@ -0,0 +1,9 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+#[typed_nodes(flags(read = 1, write = 2))]
+struct Permissions {
+    bits: u8,
+}
+
+fn main() {}
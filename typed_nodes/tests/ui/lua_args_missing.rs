@@ -0,0 +1,10 @@
+use typed_nodes::mlua::GenerateLua;
+
+#[derive(GenerateLua)]
+#[typed_nodes(lua_args(radius))]
+struct Circle {
+    center: (f32, f32),
+    radius: f32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use typed_nodes::mlua::FromLua;
+
+struct Opaque;
+
+#[derive(FromLua)]
+struct Wrapper {
+    name: String,
+    value: Opaque,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+enum Shape {
+    #[typed_nodes(default)]
+    Circle { radius: f64 },
+    #[typed_nodes(default)]
+    Square { side: f64 },
+}
+
+fn main() {}
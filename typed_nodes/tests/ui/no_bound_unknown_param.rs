@@ -0,0 +1,9 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+#[typed_nodes(no_bound(U))]
+struct Wrapper<T> {
+    value: T,
+}
+
+fn main() {}
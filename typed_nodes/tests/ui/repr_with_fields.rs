@@ -0,0 +1,10 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+#[typed_nodes(repr = integer)]
+enum BlendMode {
+    Normal,
+    Tinted(u32),
+}
+
+fn main() {}
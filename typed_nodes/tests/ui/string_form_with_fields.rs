@@ -0,0 +1,11 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+enum Size {
+    #[typed_nodes(string_form)]
+    Auto,
+    #[typed_nodes(string_form)]
+    Fixed { value: f64 },
+}
+
+fn main() {}
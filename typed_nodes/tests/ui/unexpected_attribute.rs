@@ -0,0 +1,10 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+struct Point {
+    #[typed_nodes(flatter)]
+    x: f64,
+    y: f64,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+union Number {
+    integer: i64,
+    float: f64,
+}
+
+fn main() {}
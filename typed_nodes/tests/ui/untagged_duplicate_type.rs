@@ -0,0 +1,11 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+enum Number {
+    #[typed_nodes(untagged(integer))]
+    Integer(i64),
+    #[typed_nodes(untagged(number, integer))]
+    Float(f64),
+}
+
+fn main() {}
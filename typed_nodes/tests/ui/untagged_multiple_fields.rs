@@ -0,0 +1,9 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+enum Pair {
+    #[typed_nodes(untagged(table, integer))]
+    Both(i64, i64),
+}
+
+fn main() {}
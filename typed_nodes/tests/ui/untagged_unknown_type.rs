@@ -0,0 +1,9 @@
+use typed_nodes::mlua::FromLua;
+
+#[derive(FromLua)]
+enum Number {
+    #[typed_nodes(untagged(float))]
+    Float(f64),
+}
+
+fn main() {}
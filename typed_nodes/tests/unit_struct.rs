@@ -0,0 +1,74 @@
+use mlua::Lua;
+use typed_nodes::{
+    mlua::{Context, FromLua, GenerateLua, LuaModule},
+    Nodes,
+};
+
+#[derive(FromLua, GenerateLua, Debug, PartialEq)]
+struct Marker;
+
+#[derive(FromLua, GenerateLua, Debug, PartialEq)]
+#[typed_nodes(unit_as(table))]
+struct TableMarker;
+
+#[derive(FromLua, Debug, PartialEq)]
+struct Options {
+    #[typed_nodes(optional)]
+    hidden: Option<Marker>,
+}
+
+fn parse<T>(lua: &Lua, source: &str) -> mlua::Result<T>
+where
+    T: for<'lua> FromLua<'lua, typed_nodes::bounds::AnyBounds>,
+{
+    let value = lua.load(source).eval()?;
+    let mut nodes = Nodes::new();
+    T::from_lua(value, &mut Context::new(lua, &mut nodes))
+}
+
+#[test]
+fn marker_values() {
+    let lua = Lua::new();
+
+    assert_eq!(parse::<Marker>(&lua, "nil").unwrap(), Marker);
+    assert_eq!(parse::<Marker>(&lua, "true").unwrap(), Marker);
+    assert_eq!(parse::<Marker>(&lua, "{}").unwrap(), Marker);
+
+    let error = parse::<Marker>(&lua, "false").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("unexpected boolean, expected one of: nil, table, true"));
+}
+
+#[test]
+fn marker_fields() {
+    let lua = Lua::new();
+
+    assert_eq!(
+        parse::<Options>(&lua, "{hidden = true}").unwrap(),
+        Options {
+            hidden: Some(Marker)
+        }
+    );
+}
+
+#[test]
+fn restricted_marker_values() {
+    let lua = Lua::new();
+
+    assert_eq!(parse::<TableMarker>(&lua, "{}").unwrap(), TableMarker);
+    assert!(parse::<TableMarker>(&lua, "true").is_err());
+}
+
+#[test]
+fn constructors() {
+    let mut module = LuaModule::new();
+    Marker::generate_lua(&mut module);
+    TableMarker::generate_lua(&mut module);
+
+    let methods: Vec<_> = module
+        .iter_methods()
+        .map(|entry| (entry.metatable, entry.name))
+        .collect();
+    assert_eq!(methods, [("TableMarker", "new")]);
+}
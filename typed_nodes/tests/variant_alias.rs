@@ -0,0 +1,71 @@
+use typed_nodes::{
+    bounds::AnyBounds,
+    mlua::{Context, FromLua, TableId},
+    Nodes,
+};
+
+#[derive(FromLua, Debug, PartialEq)]
+enum Shape {
+    #[typed_nodes(alias = "rectangle", deprecated_alias = "box")]
+    Rect { width: i32, height: i32 },
+    #[typed_nodes(deprecated_alias = "dot")]
+    Point,
+}
+
+#[derive(FromLua, Debug, PartialEq)]
+enum Align {
+    Start,
+    #[typed_nodes(deprecated_alias = "middle")]
+    Center,
+    End,
+}
+
+fn parse<T: for<'lua> FromLua<'lua, AnyBounds>>(source: &str) -> (T, Vec<String>) {
+    let lua = mlua::Lua::new();
+    let mut nodes = Nodes::<TableId>::new();
+    let mut context = Context::new(&lua, &mut nodes);
+    let value = lua.load(source).eval().unwrap();
+    let result = T::from_lua(value, &mut context).unwrap();
+
+    (result, context.take_warnings())
+}
+
+#[test]
+fn aliases() {
+    let (shape, warnings) = parse::<Shape>("{type = 'rectangle', width = 1, height = 2}");
+    assert_eq!(
+        shape,
+        Shape::Rect {
+            width: 1,
+            height: 2
+        }
+    );
+    assert!(warnings.is_empty());
+
+    let (shape, warnings) = parse::<Shape>("{type = 'point'}");
+    assert_eq!(shape, Shape::Point);
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn deprecated_aliases() {
+    let (shape, warnings) = parse::<Shape>("{type = 'box', width = 1, height = 2}");
+    assert_eq!(
+        shape,
+        Shape::Rect {
+            width: 1,
+            height: 2
+        }
+    );
+    assert_eq!(
+        warnings,
+        ["the variant name \"box\" is deprecated, use \"rect\" instead"]
+    );
+
+    let (align, warnings) = parse::<Align>("'middle'");
+    assert_eq!(align, Align::Center);
+    assert_eq!(
+        warnings,
+        ["the variant name \"middle\" is deprecated, use \"center\" instead"]
+    );
+}
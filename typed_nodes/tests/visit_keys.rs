@@ -0,0 +1,44 @@
+use typed_nodes::{graph::VisitKeys, DynKey, Key, Nodes};
+
+#[derive(VisitKeys)]
+struct Leaf(u32);
+
+#[derive(VisitKeys)]
+enum Expr {
+    Literal(Key<Leaf>),
+    Add { left: Key<Expr>, right: Key<Expr> },
+    Group(Vec<Key<Expr>>, Option<Key<Leaf>>),
+    Empty,
+}
+
+#[derive(VisitKeys)]
+enum Never {}
+
+fn keys(value: &impl VisitKeys) -> Vec<DynKey> {
+    let mut keys = Vec::new();
+    value.visit_keys(&mut |key| keys.push(key));
+    keys
+}
+
+#[test]
+fn derived_visit_order() {
+    let mut nodes = Nodes::<()>::new();
+    let one = nodes.insert(Leaf(1));
+    let literal = nodes.insert(Expr::Literal(one));
+    let empty = nodes.insert(Expr::Empty);
+
+    assert!(keys(&Expr::Literal(one)) == [one.into()]);
+    assert!(
+        keys(&Expr::Add {
+            left: literal,
+            right: empty
+        }) == [literal.into(), empty.into()]
+    );
+    assert!(
+        keys(&Expr::Group(vec![empty, literal], Some(one)))
+            == [empty.into(), literal.into(), one.into()]
+    );
+    assert!(keys(&Expr::Empty).is_empty());
+    assert!(keys(&Leaf(2)).is_empty());
+    assert!(keys(&None::<Never>).is_empty());
+}
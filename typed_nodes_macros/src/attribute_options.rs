@@ -1,7 +1,11 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use proc_macro2::Ident;
-use syn::{punctuated::Punctuated, Attribute, Error, Expr, Meta, MetaNameValue, Path, Token, Type};
+use proc_macro2::{Ident, Span};
+use quote::quote_spanned;
+use syn::{
+    parse_quote_spanned, punctuated::Punctuated, spanned::Spanned, Attribute, Error, Expr, ExprLit,
+    Lit, Meta, MetaNameValue, Path, Token, Type, WherePredicate,
+};
 
 use crate::lua_type::LuaType;
 
@@ -9,28 +13,37 @@ use crate::lua_type::LuaType;
 pub(crate) struct TypeOptions {
     pub(crate) is_node: bool,
     pub(crate) sync: bool,
+    pub(crate) deny_unknown_fields: bool,
+    pub(crate) intern: bool,
+    pub(crate) lua_define: bool,
+    pub(crate) lua_no_new: bool,
     pub(crate) lua_metatable: Option<Expr>,
     pub(crate) lua_base_type: Option<Type>,
+    pub(crate) crate_path: Option<Path>,
+    pub(crate) from_lua_bound: Option<Vec<WherePredicate>>,
+    pub(crate) generate_lua_bound: Option<Vec<WherePredicate>>,
+    pub(crate) no_bound: Vec<Ident>,
 }
 
 impl TypeOptions {
-    fn parse_attribute(&mut self, attribute: &Attribute) -> syn::Result<bool> {
-        let Meta::List(ref list) = attribute.meta else {
-            return Ok(false);
-        };
-
-        if !list.path.is_ident("typed_nodes") {
-            return Ok(false);
-        }
+    /// The type parameters that should get the default bounds.
+    pub(crate) fn bounded_type_params<'a>(
+        &'a self,
+        type_params: &'a [Ident],
+    ) -> impl Iterator<Item = &'a Ident> + 'a {
+        type_params
+            .iter()
+            .filter(|param| !self.no_bound.contains(param))
+    }
 
-        let option: Meta = list.parse_args()?;
+    fn parse_option(&mut self, option: &Meta) -> syn::Result<bool> {
         match option.path().get_ident().map(Ident::to_string).as_deref() {
             Some("is_node") => {
                 self.is_node = true;
                 Ok(true)
             }
             Some("sync") => {
-                let Meta::Path(_) = &option else {
+                let Meta::Path(_) = option else {
                     return Err(Error::new_spanned(
                         option,
                         "expected `sync` without arguments or value",
@@ -41,15 +54,56 @@ impl TypeOptions {
 
                 Ok(true)
             }
-            Some("lua_metatable") => {
-                if self.lua_metatable.is_some() {
+            Some("intern") => {
+                let Meta::Path(_) = option else {
                     return Err(Error::new_spanned(
                         option,
-                        "multiple `lua_metatable` attributes",
+                        "expected `intern` without arguments or value",
                     ));
-                }
+                };
+
+                self.intern = true;
+
+                Ok(true)
+            }
+            Some("lua_define") => {
+                let Meta::Path(_) = option else {
+                    return Err(Error::new_spanned(
+                        option,
+                        "expected `lua_define` without arguments or value",
+                    ));
+                };
+
+                self.lua_define = true;
+
+                Ok(true)
+            }
+            Some("lua_no_new") => {
+                let Meta::Path(_) = option else {
+                    return Err(Error::new_spanned(
+                        option,
+                        "expected `lua_no_new` without arguments or value",
+                    ));
+                };
 
-                let Meta::NameValue(value) = &option else {
+                self.lua_no_new = true;
+
+                Ok(true)
+            }
+            Some("deny_unknown_fields") => {
+                let Meta::Path(_) = option else {
+                    return Err(Error::new_spanned(
+                        option,
+                        "expected `deny_unknown_fields` without arguments or value",
+                    ));
+                };
+
+                self.deny_unknown_fields = true;
+
+                Ok(true)
+            }
+            Some("lua_metatable") => {
+                let Meta::NameValue(value) = option else {
                     return Err(Error::new_spanned(
                         option,
                         "expected `lua_metatable = \"MyMetatable\"`",
@@ -60,15 +114,83 @@ impl TypeOptions {
 
                 Ok(true)
             }
-            Some("lua_base_type") => {
-                if self.lua_base_type.is_some() {
+            Some("crate") => {
+                let Meta::NameValue(MetaNameValue {
+                    value:
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(path),
+                            ..
+                        }),
+                    ..
+                }) = option
+                else {
                     return Err(Error::new_spanned(
                         option,
-                        "multiple `lua_base_type` attributes",
+                        "expected `crate = \"path::to::typed_nodes\"`",
                     ));
+                };
+
+                self.crate_path = Some(path.parse()?);
+
+                Ok(true)
+            }
+            Some("bound") => {
+                match option {
+                    Meta::NameValue(MetaNameValue { value, .. }) => {
+                        let predicates = parse_where_predicates(value)?;
+                        self.from_lua_bound = Some(predicates.clone());
+                        self.generate_lua_bound = Some(predicates);
+                    }
+                    Meta::List(list) => {
+                        let values = list.parse_args_with(
+                            Punctuated::<MetaNameValue, Token![,]>::parse_terminated,
+                        )?;
+
+                        for value in values {
+                            let bound = match value.path.get_ident().map(Ident::to_string).as_deref() {
+                                Some("from_lua") => &mut self.from_lua_bound,
+                                Some("generate_lua") => &mut self.generate_lua_bound,
+                                _ => {
+                                    return Err(Error::new_spanned(
+                                        value.path,
+                                        "expected `from_lua` or `generate_lua`",
+                                    ))
+                                }
+                            };
+
+                            if bound.is_some() {
+                                return Err(Error::new_spanned(value.path, "multiple bounds for the same derive"));
+                            }
+
+                            *bound = Some(parse_where_predicates(&value.value)?);
+                        }
+                    }
+                    Meta::Path(_) => {
+                        return Err(Error::new_spanned(
+                            option,
+                            "expected `bound = \"T: MyTrait\"` or `bound(from_lua = \"...\", generate_lua = \"...\")`",
+                        ))
+                    }
                 }
 
-                let Meta::List(list) = &option else {
+                Ok(true)
+            }
+            Some("no_bound") => {
+                let Meta::List(list) = option else {
+                    return Err(Error::new_spanned(
+                        option,
+                        "expected a list of type parameters, such as `no_bound(T, U)`",
+                    ));
+                };
+
+                self.no_bound.extend(
+                    list.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?,
+                );
+
+                Ok(true)
+            }
+            Some("lua_base_type") => {
+                let Meta::List(list) = option else {
                     return Err(Error::new_spanned(
                         option,
                         "expected `lua_base_type(MyType)`",
@@ -84,31 +206,168 @@ impl TypeOptions {
     }
 }
 
+fn parse_lua_args(option: &Meta) -> syn::Result<Vec<Ident>> {
+    let Meta::List(list) = option else {
+        return Err(Error::new_spanned(
+            option,
+            "expected a list of field names, such as `lua_args(radius, center)`",
+        ));
+    };
+
+    Ok(list
+        .parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?
+        .into_iter()
+        .collect())
+}
+
+fn parse_where_predicates(value: &Expr) -> syn::Result<Vec<WherePredicate>> {
+    let Expr::Lit(ExprLit {
+        lit: Lit::Str(predicates),
+        ..
+    }) = value
+    else {
+        return Err(Error::new_spanned(
+            value,
+            "expected a string with where clause predicates, such as `\"T: MyTrait\"`",
+        ));
+    };
+
+    Ok(predicates
+        .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?
+        .into_iter()
+        .collect())
+}
+
+/// Calls `parse_option` for each option in the `#[typed_nodes(...)]`
+/// attributes, such as `is_node` and `tag = kind` in
+/// `#[typed_nodes(is_node, tag = kind)]`. Options that aren't in `repeatable`
+/// may only be set once.
+fn parse_options(
+    attrs: &[Attribute],
+    repeatable: &[&str],
+    mut parse_option: impl FnMut(Meta) -> syn::Result<()>,
+) -> syn::Result<()> {
+    let mut seen_options = BTreeSet::new();
+
+    for attribute in attrs {
+        let Meta::List(ref list) = attribute.meta else {
+            continue;
+        };
+
+        if !list.path.is_ident("typed_nodes") {
+            continue;
+        }
+
+        let options = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+
+        for option in options {
+            if let Some(name) = option.path().get_ident().map(Ident::to_string) {
+                if !repeatable.contains(&&*name) && !seen_options.insert(name.clone()) {
+                    return Err(Error::new_spanned(
+                        option.path(),
+                        format!("duplicate `{name}` option"),
+                    ));
+                }
+            }
+
+            parse_option(option)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Default)]
 pub(crate) struct StructOptions {
     pub(crate) type_options: TypeOptions,
+    pub(crate) lua_args: Option<Vec<Ident>>,
+    pub(crate) unit_as: Option<(BTreeSet<LuaType>, Span)>,
+    pub(crate) flags: Option<(Vec<(Ident, Expr)>, Span)>,
+}
+
+impl StructOptions {
+    /// The Lua types a unit struct can be parsed from. `boolean` only
+    /// includes `true`.
+    pub(crate) fn unit_types(&self) -> BTreeSet<LuaType> {
+        if let Some((unit_types, _)) = &self.unit_as {
+            unit_types.clone()
+        } else {
+            [LuaType::Nil, LuaType::Boolean, LuaType::Table].into()
+        }
+    }
 }
 
 impl StructOptions {
     pub(crate) fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut options = Self::default();
 
-        for attribute in attrs {
-            if options.type_options.parse_attribute(attribute)? {
-                continue;
+        parse_options(attrs, &["no_bound"], |option| {
+            if options.type_options.parse_option(&option)? {
+                return Ok(());
+            }
+
+            if option.path().is_ident("lua_args") {
+                options.lua_args = Some(parse_lua_args(&option)?);
+                return Ok(());
             }
 
-            if let Meta::List(ref list) = attribute.meta {
-                if !list.path.is_ident("typed_nodes") {
-                    continue;
+            if option.path().is_ident("unit_as") {
+                let Meta::List(list) = &option else {
+                    return Err(Error::new_spanned(
+                        option,
+                        "expected a list of lua type names, such as `unit_as(nil, table)`",
+                    ));
+                };
+
+                let mut unit_types = BTreeSet::new();
+                for ident in
+                    list.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?
+                {
+                    let span = ident.span();
+                    let lua_type = LuaType::try_from(ident)?;
+
+                    if !matches!(lua_type, LuaType::Nil | LuaType::Boolean | LuaType::Table) {
+                        return Err(Error::new(span, "expected one of: nil, boolean, table"));
+                    }
+
+                    if !unit_types.insert(lua_type) {
+                        return Err(Error::new(span, "duplicate unit Lua type"));
+                    }
                 }
 
-                let option: Meta = list.parse_args()?;
-                match option.path().get_ident().map(Ident::to_string).as_deref() {
-                    _ => return Err(Error::new_spanned(option, "unexpected struct attribute")),
+                options.unit_as = Some((unit_types, list.path.span()));
+                return Ok(());
+            }
+
+            if option.path().is_ident("flags") {
+                let Meta::List(list) = &option else {
+                    return Err(Error::new_spanned(
+                        option,
+                        "expected a list of flag names and values, such as `flags(read = 1, write = 2)`",
+                    ));
+                };
+
+                let mut flags: Vec<(Ident, Expr)> = Vec::new();
+                for flag in
+                    list.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)?
+                {
+                    let Some(name) = flag.path.get_ident() else {
+                        return Err(Error::new_spanned(flag.path, "expected a flag name"));
+                    };
+
+                    if flags.iter().any(|(existing, _)| existing == name) {
+                        return Err(Error::new_spanned(name, "duplicate flag"));
+                    }
+
+                    flags.push((name.clone(), flag.value));
                 }
+
+                options.flags = Some((flags, list.path.span()));
+                return Ok(());
             }
-        }
+
+            Err(Error::new_spanned(option, "unexpected struct attribute"))
+        })?;
 
         Ok(options)
     }
@@ -118,53 +377,61 @@ impl StructOptions {
 pub(crate) struct EnumOptions {
     pub(crate) type_options: TypeOptions,
     pub(crate) tag_name: Option<String>,
+    pub(crate) no_string_form: Option<Span>,
+    pub(crate) repr: Option<(LuaType, Span)>,
 }
 
 impl EnumOptions {
     pub(crate) fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut options = Self::default();
 
-        for attribute in attrs {
-            if options.type_options.parse_attribute(attribute)? {
-                continue;
+        parse_options(attrs, &["no_bound"], |option| {
+            if options.type_options.parse_option(&option)? {
+                return Ok(());
             }
 
-            if let Meta::List(ref list) = attribute.meta {
-                if !list.path.is_ident("typed_nodes") {
-                    continue;
+            match option.path().get_ident().map(Ident::to_string).as_deref() {
+                Some("tag") => {
+                    let Meta::NameValue(MetaNameValue {
+                        value: Expr::Path(path),
+                        ..
+                    }) = &option
+                    else {
+                        return Err(Error::new_spanned(option, "expected `tag = property_name`"));
+                    };
+
+                    let Some(ident) = path.path.get_ident() else {
+                        return Err(Error::new_spanned(option, "expected `tag = property_name`"));
+                    };
+
+                    options.tag_name = Some(ident.to_string());
                 }
-
-                let option: Meta = list.parse_args()?;
-                match option.path().get_ident().map(Ident::to_string).as_deref() {
-                    Some("tag") => {
-                        if options.tag_name.is_some() {
-                            return Err(Error::new_spanned(option, "multiple `tag` attributes"));
-                        }
-
-                        let Meta::NameValue(MetaNameValue {
-                            value: Expr::Path(path),
-                            ..
-                        }) = &option
-                        else {
-                            return Err(Error::new_spanned(
-                                option,
-                                "expected `tag = property_name`",
-                            ));
-                        };
-
-                        let Some(ident) = path.path.get_ident() else {
-                            return Err(Error::new_spanned(
-                                option,
-                                "expected `tag = property_name`",
-                            ));
-                        };
-
-                        options.tag_name = Some(ident.to_string());
+                Some("no_string_form") => options.no_string_form = Some(option.path().span()),
+                Some("repr") => {
+                    let Meta::NameValue(MetaNameValue {
+                        value: Expr::Path(path),
+                        ..
+                    }) = &option
+                    else {
+                        return Err(Error::new_spanned(option, "expected `repr = integer`"));
+                    };
+
+                    let Some(ident) = path.path.get_ident() else {
+                        return Err(Error::new_spanned(option, "expected `repr = integer`"));
+                    };
+
+                    let lua_type = LuaType::try_from(ident.clone())?;
+                    if lua_type != LuaType::Integer {
+                        return Err(Error::new_spanned(ident, "only `integer` is supported"));
                     }
-                    _ => return Err(Error::new_spanned(option, "unexpected enum attribute")),
+
+                    options.repr = Some((lua_type, option.path().span()));
                 }
+                _ => return Err(Error::new_spanned(option, "unexpected enum attribute")),
             }
-        }
+
+            Ok(())
+        })?;
 
         Ok(options)
     }
@@ -172,51 +439,91 @@ impl EnumOptions {
 
 #[derive(Default)]
 pub(crate) struct VariantOptions {
-    pub(crate) untagged_as: BTreeSet<LuaType>,
-    pub(crate) default: bool,
+    pub(crate) untagged_as: BTreeMap<LuaType, Span>,
+    pub(crate) default: Option<Span>,
+    pub(crate) default_any: bool,
     pub(crate) skip: bool,
     pub(crate) skip_method: bool,
     pub(crate) lua_base_type: Option<Type>,
     pub(crate) lua_method: Option<Expr>,
+    pub(crate) lua_args: Option<Vec<Ident>>,
+    pub(crate) aliases: Vec<VariantAlias>,
+    pub(crate) string_form: Option<Span>,
 }
 
 impl VariantOptions {
     pub(crate) fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut options = Self::default();
 
-        for attribute in attrs {
-            if let Meta::List(ref list) = attribute.meta {
-                if !list.path.is_ident("typed_nodes") {
-                    continue;
-                }
-
-                let option: Meta = list.parse_args()?;
+        parse_options(
+            attrs,
+            &["untagged", "alias", "deprecated_alias"],
+            |option| {
                 match option.path().get_ident().map(Ident::to_string).as_deref() {
-                    Some("untagged") => {
-                        let Meta::List(list) = option else {
+                    Some(name @ ("alias" | "deprecated_alias")) => {
+                        let Meta::NameValue(MetaNameValue {
+                            value:
+                                Expr::Lit(ExprLit {
+                                    lit: Lit::Str(alias),
+                                    ..
+                                }),
+                            ..
+                        }) = &option
+                        else {
                             return Err(Error::new_spanned(
                                 option,
-                                "expected a list of lua type names, such as `untagged(number, integer)`",
+                                format!("expected `{name} = \"old_name\"`"),
                             ));
                         };
 
-                        options.untagged_as.extend(
-                            list.parse_args_with(
-                                Punctuated::<LuaType, Token![,]>::parse_terminated,
-                            )?,
-                        );
+                        options.aliases.push(VariantAlias {
+                            name: alias.value(),
+                            span: alias.span(),
+                            is_deprecated: name == "deprecated_alias",
+                        });
                     }
-                    Some("skip") => options.skip = true,
-                    Some("default") => options.default = true,
-                    Some("skip_method") => options.skip_method = true,
-                    Some("lua_base_type") => {
-                        if options.lua_base_type.is_some() {
+                    Some("untagged") => {
+                        let Meta::List(list) = option else {
                             return Err(Error::new_spanned(
-                                option,
-                                "multiple `lua_base_type` attributes",
-                            ));
+                            option,
+                            "expected a list of lua type names, such as `untagged(number, integer)`",
+                        ));
+                        };
+
+                        let lua_types =
+                            list.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?;
+
+                        for ident in lua_types {
+                            let span = ident.span();
+                            if options
+                                .untagged_as
+                                .insert(LuaType::try_from(ident)?, span)
+                                .is_some()
+                            {
+                                return Err(Error::new(span, "duplicate untagged Lua type"));
+                            }
+                        }
+                    }
+                    Some("skip") => options.skip = true,
+                    Some("default") => {
+                        if let Meta::List(list) = &option {
+                            let value: Ident = list.parse_args()?;
+
+                            if value != "any" {
+                                return Err(Error::new_spanned(
+                                    value,
+                                    "expected `default` or `default(any)`",
+                                ));
+                            }
+
+                            options.default_any = true;
                         }
 
+                        options.default = Some(option.path().span());
+                    }
+                    Some("skip_method") => options.skip_method = true,
+                    Some("string_form") => options.string_form = Some(option.path().span()),
+                    Some("lua_base_type") => {
                         let Meta::List(list) = &option else {
                             return Err(Error::new_spanned(
                                 option,
@@ -227,13 +534,6 @@ impl VariantOptions {
                         options.lua_base_type = Some(list.parse_args()?);
                     }
                     Some("lua_method") => {
-                        if options.lua_method.is_some() {
-                            return Err(Error::new_spanned(
-                                option,
-                                "multiple `lua_method` attributes",
-                            ));
-                        }
-
                         let Meta::NameValue(value) = &option else {
                             return Err(Error::new_spanned(
                                 option,
@@ -243,71 +543,138 @@ impl VariantOptions {
 
                         options.lua_method = Some(value.value.clone());
                     }
+                    Some("lua_args") => options.lua_args = Some(parse_lua_args(&option)?),
                     _ => return Err(Error::new_spanned(option, "unexpected variant attribute")),
                 }
-            }
-        }
+
+                Ok(())
+            },
+        )?;
 
         Ok(options)
     }
 }
 
+pub(crate) struct VariantAlias {
+    pub(crate) name: String,
+    pub(crate) span: Span,
+    pub(crate) is_deprecated: bool,
+}
+
 #[derive(Clone, Default)]
 pub(crate) struct FieldOptions {
     pub(crate) flatten: bool,
-    pub(crate) parse_with: Option<Path>,
+    pub(crate) parse_with: Option<Expr>,
     pub(crate) is_optional: bool,
     pub(crate) lua_self: bool,
     pub(crate) lua_arguments: bool,
+    pub(crate) lua_variadic: bool,
 }
 
 impl FieldOptions {
+    /// If the field is an argument of the generated Lua constructor.
+    pub(crate) fn is_lua_argument(&self) -> bool {
+        !self.lua_self && !self.lua_arguments && !self.lua_variadic
+    }
+
     pub(crate) fn from_attributes(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut options = Self::default();
 
-        for attribute in attrs {
-            if let Meta::List(ref list) = attribute.meta {
-                if !list.path.is_ident("typed_nodes") {
-                    continue;
+        parse_options(attrs, &[], |option| {
+            match option.path().get_ident().map(Ident::to_string).as_deref() {
+                Some("flatten") => {
+                    options.flatten = true;
                 }
-
-                let option: Meta = list.parse_args()?;
-                match option.path().get_ident().map(Ident::to_string).as_deref() {
-                    Some("flatten") => {
-                        options.flatten = true;
+                Some("parse_with") => {
+                    if options.parse_with.is_some() {
+                        return Err(Error::new_spanned(
+                            option,
+                            "`parse_with` can't be combined with `reference`, `stream`, `bytes`, `expand` or `lua_variadic`",
+                        ));
                     }
-                    Some("parse_with") => {
-                        if options.parse_with.is_some() {
-                            return Err(Error::new_spanned(
-                                option,
-                                "multiple `parse_with` attributes",
-                            ));
-                        }
-
-                        let Meta::NameValue(MetaNameValue {
-                            value: Expr::Path(path),
-                            ..
-                        }) = option
-                        else {
-                            return Err(Error::new_spanned(
-                                option,
-                                "expected `parse_with = path::to::function`",
-                            ));
-                        };
 
-                        options.parse_with = Some(path.path);
-                    }
-                    Some("optional") => {
-                        options.is_optional = true;
+                    let Meta::NameValue(MetaNameValue {
+                        value: Expr::Path(path),
+                        ..
+                    }) = option
+                    else {
+                        return Err(Error::new_spanned(
+                            option,
+                            "expected `parse_with = path::to::function`",
+                        ));
+                    };
+
+                    options.parse_with = Some(Expr::Path(path));
+                }
+                Some(name @ ("reference" | "stream" | "bytes" | "expand" | "lua_variadic")) => {
+                    if options.parse_with.is_some() {
+                        return Err(Error::new_spanned(
+                            option,
+                            format!("`{name}` can't be combined with `parse_with`, `reference`, `stream`, `bytes`, `expand` or `lua_variadic`"),
+                        ));
                     }
-                    Some("lua_self") => options.lua_self = true,
-                    Some("lua_arguments") => options.lua_arguments = true,
-                    _ => {
-                        return Err(Error::new_spanned(option, "unexpected field attribute"));
+
+                    let span = option.span();
+                    options.parse_with = Some(match name {
+                        "reference" => {
+                            parse_quote_spanned!(span => typed_nodes::mlua::FromLuaReference::from_lua_reference)
+                        }
+                        "stream" => {
+                            parse_quote_spanned!(span => typed_nodes::mlua::from_lua_stream)
+                        }
+                        "expand" => {
+                            parse_quote_spanned!(span => typed_nodes::mlua::FromLuaExpanded::from_lua_expanded)
+                        }
+                        "lua_variadic" => {
+                            options.lua_variadic = true;
+                            parse_quote_spanned!(span => typed_nodes::mlua::from_lua_variadic)
+                        }
+                        _ => {
+                            parse_quote_spanned!(span => typed_nodes::mlua::FromLuaBytes::from_lua_bytes)
+                        }
+                    });
+                }
+                Some("dyn_registry") => {
+                    if options.parse_with.is_some() {
+                        return Err(Error::new_spanned(
+                            option,
+                            "`dyn_registry` can't be combined with `parse_with`, `reference`, `stream`, `bytes`, `expand` or `lua_variadic`",
+                        ));
                     }
+
+                    let Meta::NameValue(MetaNameValue {
+                        value: Expr::Path(registry),
+                        ..
+                    }) = option
+                    else {
+                        return Err(Error::new_spanned(
+                            option,
+                            "expected `dyn_registry = path::to::REGISTRY`",
+                        ));
+                    };
+
+                    // The registry is passed as the last argument. The closure is
+                    // kept as raw tokens, since parsing it as an expression would
+                    // require syn's `full` feature.
+                    let span = registry.span();
+                    options.parse_with = Some(Expr::Verbatim(
+                        quote_spanned!(span => (|value, context| {
+                            typed_nodes::mlua::FromDynRegistry::from_dyn_registry(value, context, &#registry)
+                        })),
+                    ));
+                }
+                Some("optional") => {
+                    options.is_optional = true;
+                }
+                Some("lua_self") => options.lua_self = true,
+                Some("lua_arguments") => options.lua_arguments = true,
+                _ => {
+                    return Err(Error::new_spanned(option, "unexpected field attribute"));
                 }
             }
-        }
+
+            Ok(())
+        })?;
 
         Ok(options)
     }
@@ -1,6 +1,6 @@
 use proc_macro2::{Ident, TokenStream};
-use quote::quote;
-use syn::Path;
+use quote::{quote, quote_spanned};
+use syn::{ext::IdentExt, spanned::Spanned, Path, Type};
 
 use crate::{
     iter_ext::IterExt as _,
@@ -35,22 +35,22 @@ fn make_named_fields_parsing_code(
     let mut parse_exprs = Vec::with_capacity(fields.len());
 
     for (is_last, (ident, field)) in fields.into_iter().with_is_last() {
-        let field_options = field.options;
-        let lua_name = ident.to_string();
+        let field_options = field.options.clone();
+        let lua_name = ident.unraw().to_string();
 
         let get_from_lua = if always_flatten || field_options.flatten {
             lua_type.wrap_value_expression(!is_last)
         } else {
-            quote!(value.get(#lua_name)?)
+            quote!({
+                context.mark_field_used(&value, #lua_name);
+                value.get(#lua_name)?
+            })
         };
 
         let expr = if let Some(parse_fn) = field_options.parse_with {
             quote!(#parse_fn(#get_from_lua, context))
         } else {
-            quote!(typed_nodes::mlua::FromLua::from_lua(
-                #get_from_lua,
-                context
-            ))
+            from_lua_call(&field, &get_from_lua)
         };
 
         let expr = if field_options.flatten {
@@ -80,55 +80,88 @@ fn make_named_fields_parsing_code(
     }
 }
 
+/// Makes code for updating named fields with the ones that are set in the
+/// table, for `FromLua::patch_from_lua`. Flattened fields are patched with the
+/// whole table.
+pub(crate) fn make_named_fields_patching_code(
+    fields: &[(Ident, Field)],
+    bounds_type: &Type,
+) -> TokenStream {
+    let patch_statements = fields.iter().map(|(ident, field)| {
+        let lua_name = ident.unraw().to_string();
+
+        let field_type = &field.ty;
+
+        // The call has the same span and trait bound as the `from_lua` call,
+        // so a missing implementation is only reported once.
+        let patch_fn = quote_spanned! {field.ty.span() =>
+            <#field_type as typed_nodes::mlua::FromLua<'_, #bounds_type>>::patch_from_lua
+        };
+
+        if field.options.flatten {
+            return quote! {
+                #patch_fn(&mut self.#ident, mlua::Value::Table(value.clone()), context)?;
+            };
+        }
+
+        let patch = if let Some(parse_fn) = &field.options.parse_with {
+            if field.options.is_optional {
+                quote!(#parse_fn(field_value, context).map(|new_value: Option<#field_type>| {
+                    if let Some(new_value) = new_value {
+                        self.#ident = new_value;
+                    }
+                }))
+            } else {
+                quote!(#parse_fn(field_value, context).map(|new_value| self.#ident = new_value))
+            }
+        } else {
+            quote!(#patch_fn(&mut self.#ident, field_value, context))
+        };
+
+        quote!({
+            context.mark_field_used(&value, #lua_name);
+            let field_value: mlua::Value = value.get(#lua_name)?;
+
+            if !field_value.is_nil() {
+                #patch.map_err(|mut error| {error.add_context_field_name(#lua_name); error})?;
+            }
+        })
+    });
+
+    quote! {{
+        #(#patch_statements)*
+        Ok(())
+    }}
+}
+
+/// Makes code for parsing fields from the sequence part of a table, in
+/// order, no matter if they are named or not.
+pub(crate) fn make_positional_fields_parsing_code(self_path: Path, fields: Fields) -> TokenStream {
+    match fields {
+        Fields::Named { fields } => {
+            let (field_names, fields): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+            let parse_exprs = make_positional_parse_exprs(fields, LuaType::Table, false);
+
+            quote! {
+                Ok(#self_path {
+                    #(#field_names: #parse_exprs,)*
+                })
+            }
+        }
+        Fields::Unnamed { fields } => {
+            make_unnamed_fields_parsing_code(self_path, fields, LuaType::Table, false)
+        }
+        Fields::Unit => quote!(Ok(#self_path)),
+    }
+}
+
 fn make_unnamed_fields_parsing_code(
     self_path: Path,
     fields: Vec<Field>,
     lua_type: LuaType,
     always_flatten: bool,
 ) -> TokenStream {
-    let parse_exprs =
-        fields
-            .into_iter()
-            .enumerate()
-            .with_is_last()
-            .map(|(is_last, (index, field))| {
-                let index = index + 1;
-
-                let field_options = field.options;
-
-                let get_from_lua = if always_flatten || field_options.flatten {
-                    lua_type.wrap_value_expression(!is_last)
-                } else {
-                    quote!(value.get(#index)?)
-                };
-
-                let expr = if let Some(parse_fn) = field_options.parse_with {
-                    quote! {
-                        #parse_fn(#get_from_lua, context)
-                    }
-                } else {
-                    quote! {
-                        typed_nodes::mlua::FromLua::from_lua(#get_from_lua, context)
-                    }
-                };
-
-                let expr = if field_options.flatten {
-                    quote!(#expr?)
-                } else {
-                    quote!(#expr.map_err(|mut error| {error.add_context_index(#index); error})?)
-                };
-
-                if field_options.is_optional {
-                    let field_type = field.ty;
-
-                    quote!({
-                        let maybe_value: Option<#field_type> = #expr;
-                        maybe_value.unwrap_or_else(Default::default)
-                    })
-                } else {
-                    expr
-                }
-            });
+    let parse_exprs = make_positional_parse_exprs(fields, lua_type, always_flatten);
 
     quote! {
         Ok(#self_path (
@@ -136,3 +169,75 @@ fn make_unnamed_fields_parsing_code(
         ))
     }
 }
+
+fn make_positional_parse_exprs(
+    fields: Vec<Field>,
+    lua_type: LuaType,
+    always_flatten: bool,
+) -> impl Iterator<Item = TokenStream> {
+    fields
+        .into_iter()
+        .enumerate()
+        .with_is_last()
+        .map(move |(is_last, (index, field))| {
+            let index = index + 1;
+
+            let field_options = field.options.clone();
+
+            let get_from_lua = if always_flatten || field_options.flatten {
+                lua_type.wrap_value_expression(!is_last)
+            } else {
+                quote!(value.get(#index)?)
+            };
+
+            let expr = if let Some(parse_fn) = field_options.parse_with {
+                quote! {
+                    #parse_fn(#get_from_lua, context)
+                }
+            } else {
+                from_lua_call(&field, &get_from_lua)
+            };
+
+            let expr = if field_options.flatten {
+                quote!(#expr?)
+            } else {
+                quote!(#expr.map_err(|mut error| {error.add_context_index(#index); error})?)
+            };
+
+            if field_options.is_optional {
+                let field_type = field.ty;
+
+                quote!({
+                    let maybe_value: Option<#field_type> = #expr;
+                    maybe_value.unwrap_or_else(Default::default)
+                })
+            } else {
+                expr
+            }
+        })
+}
+
+/// Wraps fields parsing code with checks for unknown fields in the table.
+/// `known_fields` are additional field names that are allowed, such as the
+/// enum tag.
+pub(crate) fn deny_unknown_fields(body: TokenStream, known_fields: &[&str]) -> TokenStream {
+    quote! {
+        context.track_used_fields(&value);
+        #(context.mark_field_used(&value, #known_fields);)*
+
+        let result: mlua::Result<_> = {
+            let value = value.clone();
+            (|| #body)()
+        };
+
+        context.check_unknown_fields(&value, result)
+    }
+}
+
+/// Makes a `FromLua::from_lua` call for a field, spanned to the field's type
+/// so a missing implementation is reported there.
+fn from_lua_call(field: &Field, get_from_lua: &TokenStream) -> TokenStream {
+    quote_spanned! {field.ty.span() =>
+        typed_nodes::mlua::FromLua::from_lua(#get_from_lua, context)
+    }
+}
@@ -3,12 +3,15 @@ use std::collections::{btree_map, BTreeMap};
 use convert_case::{Case, Casing};
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{parse_quote, Error, Ident, LitByteStr, Path, Type};
+use syn::{ext::IdentExt, parse_quote, Error, Ident, LitByteStr, Path, Type};
 
 use crate::{
-    field_parsing::make_fields_parsing_code,
+    field_parsing::{
+        deny_unknown_fields, make_fields_parsing_code, make_named_fields_patching_code,
+        make_positional_fields_parsing_code,
+    },
     lua_type::LuaType,
-    type_data::{EnumData, StructData},
+    type_data::{EnumData, Fields, StructData},
     DEFAULT_TAG_NAME,
 };
 
@@ -37,31 +40,153 @@ pub(crate) fn derive_for_struct(struct_data: StructData) -> TokenStream {
             where_clause.predicates.push(parse_quote!(#base: 'static));
         }
 
-        for param in type_params {
-            where_clause.predicates.push(
-                parse_quote!(#param: typed_nodes::mlua::FromLua<'lua, #bounds_type> + 'static),
-            );
+        if let Some(predicates) = &struct_options.type_options.from_lua_bound {
+            where_clause.predicates.extend(predicates.iter().cloned());
+        } else {
+            for param in struct_options
+                .type_options
+                .bounded_type_params(&type_params)
+            {
+                where_clause.predicates.push(
+                    parse_quote!(#param: typed_nodes::mlua::FromLua<'lua, #bounds_type> + 'static),
+                );
+            }
         }
     }
 
-    let function_body = make_fields_parsing_code(
+    let unit_check = if let Fields::Unit = fields {
+        let unit_types = struct_options.unit_types();
+        let arms = unit_types.iter().map(|lua_type| match lua_type {
+            LuaType::Nil => quote!(mlua::Value::Nil => return Ok(Self),),
+            LuaType::Boolean => quote!(mlua::Value::Boolean(true) => return Ok(Self),),
+            _ => quote!(mlua::Value::Table(_) => {}),
+        });
+        let expected = match &*unit_types
+            .iter()
+            .map(|lua_type| match lua_type {
+                LuaType::Boolean => "true".to_owned(),
+                lua_type => lua_type.to_string(),
+            })
+            .collect::<Vec<_>>()
+        {
+            [name] => name.clone(),
+            names => format!("one of: {}", names.join(", ")),
+        };
+
+        Some(quote! {
+            match &value {
+                #(#arms)*
+                value => return Err(mlua::Error::invalid_type(value, #expected)),
+            }
+        })
+    } else {
+        if let Some((_, span)) = struct_options.unit_as {
+            return Error::new(span, "`unit_as` is only supported for unit structs")
+                .into_compile_error();
+        }
+
+        None
+    };
+
+    let flags_type = match (&struct_options.flags, &fields) {
+        (Some(_), Fields::Unnamed { fields }) => fields.first().map(|field| field.ty.clone()),
+        _ => None,
+    };
+    let has_named_fields = !matches!(fields, Fields::Unnamed { .. });
+    let mut patch_body = match &fields {
+        Fields::Named { fields } => Some(make_named_fields_patching_code(fields, &bounds_type)),
+        _ => None,
+    };
+    let mut function_body = make_fields_parsing_code(
         Path::from(Ident::new("Self", Span::call_site())),
         fields,
         LuaType::Table,
         false,
     );
+
+    if struct_options.type_options.deny_unknown_fields {
+        if !has_named_fields {
+            return Error::new(
+                name.span(),
+                "`deny_unknown_fields` is only supported for structs with named fields",
+            )
+            .into_compile_error();
+        }
+
+        function_body = deny_unknown_fields(function_body, &[]);
+        patch_body = patch_body.map(|body| deny_unknown_fields(body, &[]));
+    }
+
     let where_clause = impl_generics.where_clause.take();
     let (_, generics, _) = generics.split_for_impl();
+    let insert_parsed = struct_options.type_options.intern.then(|| {
+        quote! {
+            fn insert_parsed(node: Self, reserved_key: typed_nodes::ReservedKey<Self>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> typed_nodes::Key<Self> {
+                context.intern(node, reserved_key)
+            }
+        }
+    });
+    let patch_from_lua = patch_body.map(|patch_body| {
+        quote! {
+            fn patch_from_lua(&mut self, value: mlua::Value<'lua>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> mlua::Result<()> {
+                use typed_nodes::mlua::Error as _;
 
-    quote! {
-        impl #impl_generics typed_nodes::mlua::FromLua<'lua, #bounds_type> for #name #generics #where_clause {
-            fn from_lua(value: mlua::Value<'lua>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> mlua::Result<Self> {
+                if let mlua::Value::Table(table) = &value {
+                    context.record_inline_table::<Self>(table)?;
+                }
+
+                typed_nodes::mlua::VisitTable::visit(value, context, |value, context|{
+                    #patch_body
+                })
+            }
+        }
+    });
+    let node_impl = struct_options.type_options.is_node.then(|| {
+        quote! {
+            impl #impl_generics typed_nodes::mlua::FromLuaNode<'lua, #bounds_type> for #name #generics #where_clause {}
+        }
+    });
+
+    // Flags are parsed from strings as well as tables, so they don't go
+    // through the fields parsing code.
+    let from_lua_body =
+        if let (Some((flags, _)), Some(flags_type)) = (&struct_options.flags, flags_type) {
+            let (flag_names, flag_values): (Vec<_>, Vec<_>) = flags
+                .iter()
+                .map(|(name, value)| (name.unraw().to_string(), value))
+                .unzip();
+
+            quote! {
+                let flags: &[(&str, #flags_type)] = &[#((#flag_names, #flag_values),)*];
+                typed_nodes::mlua::from_lua_flags(value, context, flags).map(Self)
+            }
+        } else {
+            quote! {
                 use typed_nodes::mlua::Error as _;
 
+                #unit_check
+
+                if let mlua::Value::Table(table) = &value {
+                    context.record_inline_table::<Self>(table)?;
+                }
+
                 typed_nodes::mlua::VisitTable::visit(value, context, |value, context|{
                     #function_body
                 })
             }
+        };
+
+    quote! {
+        #node_impl
+
+        impl #impl_generics typed_nodes::mlua::FromLua<'lua, #bounds_type> for #name #generics #where_clause {
+            fn from_lua(value: mlua::Value<'lua>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> mlua::Result<Self> {
+                #from_lua_body
+            }
+
+            #patch_from_lua
+
+            #insert_parsed
         }
     }
 }
@@ -92,19 +217,48 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
             );
         }
 
-        for param in type_params {
-            where_clause.predicates.push(
-                parse_quote!(#param: typed_nodes::mlua::FromLua<'lua, #bounds_type> + 'static),
-            );
+        if let Some(predicates) = &enum_options.type_options.from_lua_bound {
+            where_clause.predicates.extend(predicates.iter().cloned());
+        } else {
+            for param in enum_options.type_options.bounded_type_params(&type_params) {
+                where_clause.predicates.push(
+                    parse_quote!(#param: typed_nodes::mlua::FromLua<'lua, #bounds_type> + 'static),
+                );
+            }
         }
     }
 
     let mut variant_names_bytes = Vec::with_capacity(variants.len());
     let mut variant_names_str = Vec::with_capacity(variants.len());
     let mut variant_bodies = Vec::with_capacity(variants.len());
+    let mut string_names_bytes = Vec::with_capacity(variants.len());
+    let mut string_names_str = Vec::with_capacity(variants.len());
+    let mut string_bodies = Vec::with_capacity(variants.len());
     let mut untagged_bodies = BTreeMap::new();
     let mut default_body = None;
+    let mut default_any_body = None;
     let mut all_are_empty = true;
+    let tag_name = enum_options.tag_name.as_deref().unwrap_or(DEFAULT_TAG_NAME);
+    let mut alias_spans = BTreeMap::new();
+
+    // Only the marked variants have a string form if any variant is marked.
+    let has_explicit_string_form = variants
+        .iter()
+        .any(|variant| variant.options.string_form.is_some());
+
+    if let (Some(span), true) = (enum_options.no_string_form, has_explicit_string_form) {
+        return Error::new(
+            span,
+            "`no_string_form` can't be combined with `string_form` variants",
+        )
+        .into_compile_error();
+    }
+
+    let repr_variants: Vec<_> = variants
+        .iter()
+        .filter(|variant| !variant.options.skip)
+        .map(|variant| variant.name.clone())
+        .collect();
 
     for variant in variants {
         let variant_options = variant.options;
@@ -121,12 +275,40 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
         let mut self_path = Path::from(name.clone());
         self_path.segments.push(variant.name.into());
 
-        if variant_options.default {
+        if variant_options.default.is_some() || !variant_options.untagged_as.is_empty() {
+            if let Some(alias) = variant_options.aliases.first() {
+                return Error::new(alias.span, "only tagged variants can have aliases")
+                    .into_compile_error();
+            }
+
+            if let Some(span) = variant_options.string_form {
+                return Error::new(span, "only tagged variants can have a string form")
+                    .into_compile_error();
+            }
+        }
+
+        if let Some(span) = variant_options.string_form {
+            if !variant.fields.is_empty() {
+                return Error::new(span, "only variants without fields can have a string form")
+                    .into_compile_error();
+            }
+        }
+
+        if let Some(default_span) = variant_options.default {
             if default_body.is_some() {
-                return Error::new(variant_name_span, format!("more than one default variant"))
+                return Error::new(default_span, "more than one default variant")
                     .into_compile_error();
             }
 
+            if variant_options.default_any {
+                default_any_body = Some(make_fields_parsing_code(
+                    self_path.clone(),
+                    variant.fields.clone(),
+                    LuaType::Any,
+                    true,
+                ));
+            }
+
             default_body = Some(make_fields_parsing_code(
                 self_path.clone(),
                 variant.fields,
@@ -134,48 +316,113 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
                 true,
             ))
         } else if variant_options.untagged_as.is_empty() {
-            variant_names_bytes.push(LitByteStr::new(
-                snake_case_name.as_bytes(),
-                variant_name_span,
-            ));
+            let has_named_fields = !matches!(variant.fields, Fields::Unnamed { .. });
+            let body = make_fields_parsing_code(self_path, variant.fields, LuaType::Table, false);
+            let has_string_form =
+                !has_explicit_string_form || variant_options.string_form.is_some();
+
+            // Unnamed fields are stored in a sequence, so they can't be checked.
+            let table_body = if enum_options.type_options.deny_unknown_fields && has_named_fields {
+                deny_unknown_fields(body.clone(), &[tag_name])
+            } else {
+                body.clone()
+            };
+
+            for alias in variant_options.aliases {
+                if let Some(&span) = alias_spans.get(&alias.name) {
+                    let mut error = Error::new(alias.span, "duplicate variant alias");
+                    error.combine(Error::new(span, "previously used here"));
+                    return error.into_compile_error();
+                }
+                alias_spans.insert(alias.name.clone(), alias.span);
+
+                let warning = alias.is_deprecated.then(|| {
+                    let message = format!(
+                        "the variant name \"{}\" is deprecated, use \"{snake_case_name}\" instead",
+                        alias.name
+                    );
+                    quote!(context.warn(#message);)
+                });
+
+                let alias_bytes = LitByteStr::new(alias.name.as_bytes(), alias.span);
+
+                if has_string_form {
+                    string_names_bytes.push(alias_bytes.clone());
+                    string_bodies.push(quote!(#warning #body));
+                }
+
+                variant_names_bytes.push(alias_bytes);
+                variant_bodies.push(quote!(#warning #table_body));
+            }
+
+            let name_bytes = LitByteStr::new(snake_case_name.as_bytes(), variant_name_span);
+
+            if has_string_form {
+                string_names_bytes.push(name_bytes.clone());
+                string_names_str.push(snake_case_name.clone());
+                string_bodies.push(body);
+            }
+
+            variant_names_bytes.push(name_bytes);
             variant_names_str.push(snake_case_name);
-            variant_bodies.push(make_fields_parsing_code(
-                self_path,
-                variant.fields,
-                LuaType::Table,
-                false,
-            ));
-        } else if variant.fields.len() <= 1 {
-            for lua_type in variant_options.untagged_as {
+            variant_bodies.push(table_body);
+        } else if variant.fields.len() <= 1
+            || variant_options.untagged_as.keys().eq([&LuaType::Table])
+        {
+            // Multiple fields are parsed from the sequence part of the table.
+            let is_positional = variant.fields.len() > 1;
+
+            for (lua_type, lua_type_span) in variant_options.untagged_as {
                 if let btree_map::Entry::Vacant(entry) = untagged_bodies.entry(lua_type) {
-                    entry.insert(make_fields_parsing_code(
-                        self_path.clone(),
-                        variant.fields.clone(),
-                        lua_type,
-                        true,
-                    ));
+                    entry.insert(if is_positional {
+                        make_positional_fields_parsing_code(
+                            self_path.clone(),
+                            variant.fields.clone(),
+                        )
+                    } else {
+                        make_fields_parsing_code(
+                            self_path.clone(),
+                            variant.fields.clone(),
+                            lua_type,
+                            true,
+                        )
+                    });
                 } else {
                     return Error::new(
-                        variant_name_span,
+                        lua_type_span,
                         format!("more than one untagged {lua_type} variant"),
                     )
                     .into_compile_error();
                 }
             }
         } else {
+            let span = variant_options
+                .untagged_as
+                .iter()
+                .find(|&(&lua_type, _)| lua_type != LuaType::Table)
+                .map(|(_, &span)| span)
+                .unwrap_or(variant_name_span);
+
             return Error::new(
-                variant_name_span,
-                "only variants with no or one field can be untagged",
+                span,
+                "variants with more than one field can only be untagged as `table`",
             )
             .into_compile_error();
         }
     }
 
+    for name in &variant_names_str {
+        if let Some(&span) = alias_spans.get(name) {
+            return Error::new(span, "the alias is already used as a variant name")
+                .into_compile_error();
+        }
+    }
+
     let where_clause = impl_generics.where_clause.take();
     let (visitor_generics, generics, _) = generics.split_for_impl();
 
     let table_visitor = make_enum_table_visitor_fn(
-        enum_options.tag_name.as_deref().unwrap_or(DEFAULT_TAG_NAME),
+        tag_name,
         &variant_bodies,
         &variant_names_bytes,
         &variant_names_str,
@@ -183,19 +430,21 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
         default_body,
         &bounds_type,
     );
+    let has_string_form =
+        has_explicit_string_form || (all_are_empty && enum_options.no_string_form.is_none());
     let string_visitor = make_enum_string_visitor_fn(
-        &variant_bodies,
-        &variant_names_bytes,
-        &variant_names_str,
+        &string_bodies,
+        &string_names_bytes,
+        &string_names_str,
         untagged_bodies.remove(&LuaType::String),
-        all_are_empty,
+        has_string_form,
         &bounds_type,
     );
 
     let mut expected_types: Vec<_> = untagged_bodies
         .keys()
-        .chain(table_visitor.is_some().then(|| &LuaType::Table))
-        .chain(string_visitor.is_some().then(|| &LuaType::String))
+        .chain(table_visitor.is_some().then_some(&LuaType::Table))
+        .chain(string_visitor.is_some().then_some(&LuaType::String))
         .map(|lua_type| lua_type.to_string())
         .collect();
 
@@ -208,33 +457,100 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
         }
     };
 
+    // Values that no visitor would accept go to a `default(any)` variant.
+    let parse_unhandled = default_any_body.map(|body| {
+        let handled_patterns: Vec<_> = untagged_bodies
+            .keys()
+            .chain(table_visitor.is_some().then_some(&LuaType::Table))
+            .chain(string_visitor.is_some().then_some(&LuaType::String))
+            .map(LuaType::value_pattern)
+            .collect();
+
+        if handled_patterns.is_empty() {
+            quote! {
+                if true {
+                    return #body;
+                }
+            }
+        } else {
+            quote! {
+                if !matches!(value, #(#handled_patterns)|*) {
+                    return #body;
+                }
+            }
+        }
+    });
+
     let untagged_visitors = untagged_bodies
         .into_iter()
         .map(|(lua_type, body)| lua_type.make_delegating_visitor_fn(&bounds_type, &body));
+    let insert_parsed = enum_options.type_options.intern.then(|| {
+        quote! {
+            fn insert_parsed(node: Self, reserved_key: typed_nodes::ReservedKey<Self>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> typed_nodes::Key<Self> {
+                context.intern(node, reserved_key)
+            }
+        }
+    });
+    let node_impl = enum_options.type_options.is_node.then(|| {
+        quote! {
+            impl #impl_generics typed_nodes::mlua::FromLuaNode<'lua, #bounds_type> for #name #generics #where_clause {}
+        }
+    });
 
-    quote! {
-        impl #impl_generics typed_nodes::mlua::FromLua<'lua, #bounds_type> for #name #generics #where_clause {
-            fn from_lua(value: mlua::Value<'lua>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> mlua::Result<Self> {
-                use typed_nodes::mlua::Error as _;
+    // Integer codes are matched against the discriminants.
+    let from_lua_body = if enum_options.repr.is_some() {
+        quote! {
+            use typed_nodes::mlua::Error as _;
+
+            typed_nodes::mlua::VisitInteger::visit(value, context, |code, _| {
+                #(
+                    if code == Self::#repr_variants as mlua::Integer {
+                        return Ok(Self::#repr_variants);
+                    }
+                )*
 
-                struct __Visitor #visitor_generics (std::marker::PhantomData<fn() -> #name #generics>);
+                Err(mlua::Error::invalid_code(code, &[#(Self::#repr_variants as mlua::Integer),*]))
+            })
+        }
+    } else {
+        quote! {
+            use typed_nodes::mlua::Error as _;
 
-                impl #impl_generics typed_nodes::mlua::VisitLua<'lua, #bounds_type> for __Visitor #generics #where_clause {
-                    type Output = #name #generics;
+            if let mlua::Value::Table(table) = &value {
+                context.record_inline_table::<Self>(table)?;
+            }
 
-                    fn expected(&self) -> String {
-                        #expected.into()
-                    }
+            #parse_unhandled
 
-                    #table_visitor
+            struct __Visitor #visitor_generics (std::marker::PhantomData<fn() -> #name #generics>);
 
-                    #string_visitor
+            impl #impl_generics typed_nodes::mlua::VisitLua<'lua, #bounds_type> for __Visitor #generics #where_clause {
+                type Output = #name #generics;
 
-                    #(#untagged_visitors)*
+                fn expected(&self) -> String {
+                    #expected.into()
                 }
 
-                typed_nodes::mlua::VisitLua::visit_lua(&mut __Visitor(std::marker::PhantomData), value, context)
+                #table_visitor
+
+                #string_visitor
+
+                #(#untagged_visitors)*
+            }
+
+            typed_nodes::mlua::VisitLua::visit_lua(&mut __Visitor(std::marker::PhantomData), value, context)
+        }
+    };
+
+    quote! {
+        #node_impl
+
+        impl #impl_generics typed_nodes::mlua::FromLua<'lua, #bounds_type> for #name #generics #where_clause {
+            fn from_lua(value: mlua::Value<'lua>, context: &mut typed_nodes::mlua::Context<'lua, #bounds_type>) -> mlua::Result<Self> {
+                #from_lua_body
             }
+
+            #insert_parsed
         }
     }
 }
@@ -249,16 +565,12 @@ fn make_enum_table_visitor_fn(
     bounds_type: &Type,
 ) -> Option<TokenStream> {
     if !variant_bodies.is_empty() {
-        let untagged_arm = if let Some(body) = untagged_body {
-            Some(quote!(None => #body,))
-        } else {
-            None
-        };
+        let untagged_arm = untagged_body.map(|body| quote!(None => #body,));
 
         let default_body = if let Some(body) = default_body {
             body
         } else {
-            quote!(Err(typed_nodes::mlua::Error::invalid_variant(
+            quote!(Err(context.invalid_variant(
                 variant.as_ref().map(mlua::String::to_string_lossy).as_deref().unwrap_or("<nil>"),
                 &[#(#variant_names_str),*]
             )))
@@ -274,10 +586,8 @@ fn make_enum_table_visitor_fn(
                 }
             }
         })
-    } else if let Some(body) = untagged_body {
-        Some(LuaType::Table.make_delegating_visitor_fn(bounds_type, &body))
     } else {
-        None
+        untagged_body.map(|body| LuaType::Table.make_delegating_visitor_fn(bounds_type, &body))
     }
 }
 
@@ -286,14 +596,14 @@ fn make_enum_string_visitor_fn(
     variant_names_bytes: &[LitByteStr],
     variant_names_str: &[String],
     untagged_body: Option<TokenStream>,
-    all_are_empty: bool,
+    has_string_form: bool,
     bounds_type: &Type,
 ) -> Option<TokenStream> {
-    if all_are_empty {
+    if has_string_form {
         let default_string_body = if let Some(body) = untagged_body {
             body
         } else {
-            quote!(Err(typed_nodes::mlua::Error::invalid_variant(&*value.to_string_lossy(), &[#(#variant_names_str),*])))
+            quote!(Err(context.invalid_variant(&*value.to_string_lossy(), &[#(#variant_names_str),*])))
         };
 
         Some(quote! {
@@ -304,9 +614,7 @@ fn make_enum_string_visitor_fn(
                 }
             }
         })
-    } else if let Some(body) = untagged_body {
-        Some(LuaType::String.make_delegating_visitor_fn(bounds_type, &body))
     } else {
-        None
+        untagged_body.map(|body| LuaType::String.make_delegating_visitor_fn(bounds_type, &body))
     }
 }
@@ -1,10 +1,15 @@
+use std::collections::BTreeSet;
+
 use convert_case::{Case, Casing};
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{quote, quote_spanned};
-use syn::{parse_quote, parse_quote_spanned, spanned::Spanned, Type};
+use syn::{
+    ext::IdentExt, parse_quote, parse_quote_spanned, spanned::Spanned, GenericParam, Generics, Type,
+};
 
 use crate::{
-    attribute_options::TypeOptions,
+    attribute_options::{EnumOptions, TypeOptions},
+    lua_type::LuaType,
     type_data::{EnumData, Fields, StructData, Variant},
     DEFAULT_TAG_NAME,
 };
@@ -18,7 +23,7 @@ pub(crate) fn derive_for_struct(struct_data: StructData) -> TokenStream {
         type_params,
     } = struct_data;
 
-    let type_signature = type_signature_expr(&options.type_options, &name, &type_params);
+    let type_signature = type_signature_expr(&options.type_options, &name, &generics, &type_params);
     let base_type_delegate =
         base_type_delegate_expr(options.type_options.lua_base_type.as_ref(), &mut generics);
 
@@ -29,16 +34,64 @@ pub(crate) fn derive_for_struct(struct_data: StructData) -> TokenStream {
             where_clause.predicates.push(parse_quote!(#base: 'static));
         }
 
-        for param in type_params {
-            where_clause
-                .predicates
-                .push(parse_quote!(#param: typed_nodes::mlua::GenerateLua + 'static));
+        if let Some(predicates) = &options.type_options.generate_lua_bound {
+            where_clause.predicates.extend(predicates.iter().cloned());
+        } else {
+            for param in options.type_options.bounded_type_params(&type_params) {
+                where_clause
+                    .predicates
+                    .push(parse_quote!(#param: typed_nodes::mlua::GenerateLua + 'static));
+            }
         }
     }
 
     let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
 
-    let new_method = method_expr(fields, None);
+    // Unit structs that can be written as literal values don't need
+    // constructors.
+    let literal_forms: Vec<_> = if let Fields::Unit = fields {
+        options
+            .unit_types()
+            .into_iter()
+            .filter(LuaType::is_literal)
+            .map(|lua_type| {
+                let lua_type = lua_type.to_string();
+                quote!(module.add_literal_form(&Self::TYPE_SIGNATURE, #lua_type);)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    // Flags are written as strings, with the names as constants.
+    let flag_constants: Vec<_> = options
+        .flags
+        .iter()
+        .flat_map(|(flags, _)| flags)
+        .map(|(name, _)| {
+            let value = name.unraw().to_string();
+            let constant = value.to_case(Case::UpperSnake);
+            quote!(module.add_constant(&Self::TYPE_SIGNATURE, #constant, typed_nodes::mlua::LuaExpression::String { value: #value });)
+        })
+        .collect();
+    let literal_forms = if options.flags.is_some() {
+        vec![
+            quote!(module.add_literal_form(&Self::TYPE_SIGNATURE, "string");),
+            quote!(module.add_literal_form(&Self::TYPE_SIGNATURE, "string[]");),
+        ]
+    } else {
+        literal_forms
+    };
+    let skip_new = options.type_options.lua_no_new || !literal_forms.is_empty();
+
+    let new_method = (!skip_new).then(|| {
+        let new_method = method_expr(fields, options.lua_args.as_deref(), None, false);
+
+        quote! {
+            let new_method = #new_method;
+            module.add_method(&Self::TYPE_SIGNATURE, "new", new_method);
+        }
+    });
+    let define_method = define_method_expr(&options.type_options);
 
     quote! {
         impl #impl_generics typed_nodes::mlua::GenerateLua for #name #type_generics #where_clause {
@@ -53,8 +106,13 @@ pub(crate) fn derive_for_struct(struct_data: StructData) -> TokenStream {
 
                 #base_type_delegate;
 
-                let new_method = #new_method;
-                module.add_method(&Self::TYPE_SIGNATURE, "new", new_method);
+                #(#literal_forms)*
+
+                #(#flag_constants)*
+
+                #new_method
+
+                #define_method
             }
         }
     }
@@ -73,7 +131,8 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
         type_params,
     } = enum_data;
 
-    let type_signature = type_signature_expr(&options.type_options, &name, &type_params);
+    let type_signature = type_signature_expr(&options.type_options, &name, &generics, &type_params);
+    let define_method = define_method_expr(&options.type_options);
     let base_type_delegates: Vec<_> =
         base_type_delegate_expr(options.type_options.lua_base_type.as_ref(), &mut generics)
             .into_iter()
@@ -90,9 +149,58 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
             )
             .collect();
 
+    let enum_info = enum_info_expr(&options, &variants);
+
+    // Integer codes are written as they are, with the variants as constants.
+    let repr_constants: Vec<_> = if options.repr.is_some() {
+        variants
+            .iter()
+            .filter(|variant| !variant.options.skip)
+            .map(|variant| {
+                let variant_name = &variant.name;
+                let constant = variant_name.to_string().to_case(Case::UpperSnake);
+                quote! {
+                    module.add_constant(
+                        &Self::TYPE_SIGNATURE,
+                        #constant,
+                        typed_nodes::mlua::LuaExpression::Integer { value: Self::#variant_name as i64 },
+                    );
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let literal_forms: Vec<_> = if let Some((lua_type, _)) = &options.repr {
+        let lua_type = lua_type.to_string();
+        vec![quote!(module.add_literal_form(&Self::TYPE_SIGNATURE, #lua_type);)]
+    } else {
+        variants
+            .iter()
+            .filter(|&variant| include_variant(variant))
+            .flat_map(|variant| variant.options.untagged_as.keys())
+            .filter(|lua_type| lua_type.is_literal())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|lua_type| {
+                let lua_type = lua_type.to_string();
+                quote!(module.add_literal_form(&Self::TYPE_SIGNATURE, #lua_type);)
+            })
+            .collect()
+    };
+
+    // Variants that are methods of other types are kept without constructors,
+    // and literal values are only accepted as they are.
     let variant_code = variants
         .into_iter()
         .filter(include_variant)
+        .filter(|_| options.repr.is_none())
+        .filter(|variant| !options.type_options.lua_no_new || variant.options.lua_base_type.is_some())
+        .filter(|variant| {
+            let untagged_as = &variant.options.untagged_as;
+            untagged_as.is_empty() || !untagged_as.keys().all(LuaType::is_literal)
+        })
         .map(|variant| {
             let method_name = if let Some(method_name) = variant.options.lua_method {
                 method_name
@@ -101,7 +209,7 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
                 parse_quote_spanned! {variant.name.span() => #name_str}
             };
 
-            let set_tag = if !variant.options.default && variant.options.untagged_as.is_empty() {
+            let set_tag = if variant.options.default.is_none() && variant.options.untagged_as.is_empty() {
                 let tag_name = options.tag_name.as_deref().unwrap_or(DEFAULT_TAG_NAME);
                 let tag = variant.name.to_string().to_case(Case::Snake);
                 Some(quote!((#tag_name, Box::new(typed_nodes::mlua::LuaExpression::String{value: #tag}))))
@@ -115,7 +223,15 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
                 quote!(&Self::TYPE_SIGNATURE)
             };
 
-            let method = method_expr(variant.fields, set_tag);
+            // Untagged variants with multiple fields are parsed from sequences.
+            let is_positional = variant.fields.len() > 1
+                && variant.options.untagged_as.contains_key(&LuaType::Table);
+            let method = method_expr(
+                variant.fields,
+                variant.options.lua_args.as_deref(),
+                set_tag,
+                is_positional,
+            );
 
             quote! {
                 let method = #method;
@@ -130,10 +246,14 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
             where_clause.predicates.push(parse_quote!(#base: 'static));
         }
 
-        for param in type_params {
-            where_clause
-                .predicates
-                .push(parse_quote!(#param: typed_nodes::mlua::GenerateLua + 'static));
+        if let Some(predicates) = &options.type_options.generate_lua_bound {
+            where_clause.predicates.extend(predicates.iter().cloned());
+        } else {
+            for param in options.type_options.bounded_type_params(&type_params) {
+                where_clause
+                    .predicates
+                    .push(parse_quote!(#param: typed_nodes::mlua::GenerateLua + 'static));
+            }
         }
     }
 
@@ -142,6 +262,7 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
     quote! {
         impl #impl_generics typed_nodes::mlua::GenerateLua for #name #type_generics #where_clause {
             const TYPE_SIGNATURE: typed_nodes::mlua::TypeSignature = #type_signature;
+            const ENUM_INFO: Option<&'static typed_nodes::mlua::EnumInfo> = Some(#enum_info);
 
             fn generate_lua(module: &mut typed_nodes::mlua::LuaModule) {
                 if !module.visit_type::<Self>() {
@@ -150,13 +271,24 @@ pub(crate) fn derive_for_enum(enum_data: EnumData) -> TokenStream {
 
                 #(#base_type_delegates;)*
 
+                #(#literal_forms)*
+
+                #(#repr_constants)*
+
                 #(#variant_code)*
+
+                #define_method
             }
         }
     }
 }
 
-fn method_expr(fields: Fields, set_tag: Option<TokenStream>) -> TokenStream {
+fn method_expr(
+    fields: Fields,
+    lua_args: Option<&[Ident]>,
+    set_tag: Option<TokenStream>,
+    is_positional: bool,
+) -> TokenStream {
     match fields {
         crate::type_data::Fields::Named { fields } => {
             let method_constructor = if fields.iter().any(|(_, field)| field.options.lua_self) {
@@ -164,11 +296,23 @@ fn method_expr(fields: Fields, set_tag: Option<TokenStream>) -> TokenStream {
             } else {
                 Ident::new("new_static", Span::call_site())
             };
-            let argument_names = fields
+            // The order has already been checked to contain the same fields.
+            let arguments: Vec<_> = if let Some(lua_args) = lua_args {
+                lua_args.iter().collect()
+            } else {
+                fields
+                    .iter()
+                    .filter(|(_, field)| field.options.is_lua_argument())
+                    .map(|(name, _)| name)
+                    .collect()
+            };
+            let argument_names = arguments.iter().map(|name| name.unraw().to_string());
+            let reserved_name_warnings = arguments
+                .iter()
+                .filter_map(|name| reserved_name_warning(name));
+            let variable_arguments = if fields
                 .iter()
-                .filter(|(_, field)| !field.options.lua_self && !field.options.lua_arguments)
-                .map(|(name, _)| name.to_string());
-            let variable_arguments = if fields.iter().any(|(_, field)| field.options.lua_arguments)
+                .any(|(_, field)| field.options.lua_arguments || field.options.lua_variadic)
             {
                 Some(quote!(method.set_variable_arguments()))
             } else {
@@ -178,30 +322,46 @@ fn method_expr(fields: Fields, set_tag: Option<TokenStream>) -> TokenStream {
             let lua_fields = fields
                 .iter()
                 .map(|(name, field)| {
-                    let name = name.to_string();
+                    let name = name.unraw().to_string();
 
                     let value = if field.options.lua_self {
                         quote!(typed_nodes::mlua::LuaExpression::Identifier { name: "self" })
                     } else if field.options.lua_arguments {
                         quote!(typed_nodes::mlua::LuaExpression::MakeArgumentsTable)
+                    } else if field.options.lua_variadic {
+                        quote!(typed_nodes::mlua::LuaExpression::PackArguments)
                     } else {
                         quote!(typed_nodes::mlua::LuaExpression::Identifier{name: #name})
                     };
 
-                    quote!((#name, Box::new(#value)))
+                    if is_positional {
+                        value
+                    } else {
+                        quote!((#name, Box::new(#value)))
+                    }
                 })
                 .chain(set_tag.clone());
 
+            let make_table = if is_positional {
+                quote!(typed_nodes::mlua::LuaExpression::MakeSequence {
+                    values: vec![#(#lua_fields),*]
+                })
+            } else {
+                quote!(typed_nodes::mlua::LuaExpression::MakeTable {
+                    fields: vec![#(#lua_fields),*]
+                })
+            };
+
             quote! {{
+                #(#reserved_name_warnings)*
+
                 let mut method = typed_nodes::mlua::Method::#method_constructor(
                     vec![#(#argument_names),*]
                 );
                 #variable_arguments;
                 method.add_statement(typed_nodes::mlua::LuaStatement::Assign {
                     variable: "__self",
-                    expression: typed_nodes::mlua::LuaExpression::MakeTable {
-                        fields: vec![#(#lua_fields),*]
-                    }
+                    expression: #make_table
                 });
                 method.add_statement(typed_nodes::mlua::LuaStatement::Return{
                     expression: typed_nodes::mlua::LuaExpression::SetMetatable {
@@ -250,7 +410,117 @@ fn method_expr(fields: Fields, set_tag: Option<TokenStream>) -> TokenStream {
     }
 }
 
-fn type_signature_expr(options: &TypeOptions, name: &Ident, type_params: &[Ident]) -> TokenStream {
+/// Words that can't be used as names in Lua code.
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Reserved words are escaped in the generated code, which changes the
+/// parameter names in the constructor. There's no way to emit a proper
+/// warning from a derive macro, so it uses a deprecated constant instead.
+fn reserved_name_warning(name: &Ident) -> Option<TokenStream> {
+    let lua_name = name.unraw().to_string();
+
+    if !LUA_KEYWORDS.contains(&&*lua_name) {
+        return None;
+    }
+
+    let note = format!(
+        "`{lua_name}` is a reserved word in Lua and is renamed to `__{lua_name}` in the generated constructor's parameters"
+    );
+
+    Some(quote_spanned! {name.span() =>
+        {
+            #[deprecated(note = #note)]
+            #[allow(non_upper_case_globals)]
+            const reserved_lua_name: () = ();
+            reserved_lua_name
+        };
+    })
+}
+
+/// Describes the accepted forms of the enum, the same way as `FromLua`
+/// parses them.
+fn enum_info_expr(options: &EnumOptions, variants: &[Variant]) -> TokenStream {
+    let tag_name = options.tag_name.as_deref().unwrap_or(DEFAULT_TAG_NAME);
+    let variants: Vec<_> = variants
+        .iter()
+        .filter(|variant| !variant.options.skip)
+        .collect();
+
+    // Only the marked variants have a string form if any variant is marked.
+    let has_explicit_string_form = variants
+        .iter()
+        .any(|variant| variant.options.string_form.is_some());
+    let all_are_empty = variants.iter().all(|variant| variant.fields.is_empty());
+
+    let variant_infos = variants.iter().map(|variant| {
+        let variant_options = &variant.options;
+        let name = variant.name.to_string();
+        let is_default = variant_options.default.is_some();
+        let is_tagged =
+            !is_default && variant_options.untagged_as.is_empty() && options.repr.is_none();
+
+        let tag = if is_tagged {
+            let tag = name.to_case(Case::Snake);
+            quote!(Some(#tag))
+        } else {
+            quote!(None)
+        };
+        let aliases = variant_options.aliases.iter().map(|alias| &alias.name);
+        let deprecated_aliases = variant_options
+            .aliases
+            .iter()
+            .filter(|alias| alias.is_deprecated)
+            .map(|alias| &alias.name);
+        let untagged_as = variant_options
+            .untagged_as
+            .keys()
+            .chain(options.repr.as_ref().map(|(lua_type, _)| lua_type))
+            .map(|lua_type| lua_type.to_string());
+        let has_string_form = is_tagged
+            && if has_explicit_string_form {
+                variant_options.string_form.is_some()
+            } else {
+                all_are_empty && options.no_string_form.is_none()
+            };
+
+        quote! {
+            typed_nodes::mlua::VariantInfo {
+                name: #name,
+                tag: #tag,
+                aliases: &[#(#aliases),*],
+                deprecated_aliases: &[#(#deprecated_aliases),*],
+                untagged_as: &[#(#untagged_as),*],
+                is_default: #is_default,
+                has_string_form: #has_string_form,
+            }
+        }
+    });
+
+    quote! {
+        &typed_nodes::mlua::EnumInfo {
+            tag_name: #tag_name,
+            variants: &[#(#variant_infos),*],
+        }
+    }
+}
+
+fn define_method_expr(options: &TypeOptions) -> Option<TokenStream> {
+    options.lua_define.then(|| {
+        quote! {
+            module.add_method(&Self::TYPE_SIGNATURE, "define", typed_nodes::mlua::Method::define_name());
+        }
+    })
+}
+
+fn type_signature_expr(
+    options: &TypeOptions,
+    name: &Ident,
+    generics: &Generics,
+    type_params: &[Ident],
+) -> TokenStream {
     // lua_metatable overrides lua_base_type. The type name is the default.
     let name = if let Some(metatable) = options.lua_metatable.clone() {
         metatable
@@ -261,13 +531,41 @@ fn type_signature_expr(options: &TypeOptions, name: &Ident, type_params: &[Ident
         parse_quote_spanned! {name.span() => #name_str}
     };
 
-    let type_params = type_params
-        .iter()
-        .map(|param| quote_spanned! {param.span() => #param::TYPE_SIGNATURE});
+    // Unbounded parameters can't be asked for their signatures, so they are
+    // identified by their type names instead.
+    let bounded_type_params: Vec<_> = options.bounded_type_params(type_params).collect();
+
+    let generic_arguments = generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(param) if bounded_type_params.contains(&&param.ident) => {
+            let param = &param.ident;
+            Some(quote_spanned! {param.span() =>
+                typed_nodes::mlua::GenericArgument::Type(&#param::TYPE_SIGNATURE)
+            })
+        }
+        GenericParam::Type(param) => {
+            let param = &param.ident;
+            Some(quote_spanned! {param.span() =>
+                typed_nodes::mlua::GenericArgument::TypeName(::core::any::type_name::<#param>)
+            })
+        }
+        GenericParam::Const(param) => {
+            let ident = &param.ident;
+            let value = match &param.ty {
+                Type::Path(path) if path.path.is_ident("bool") => quote!(Bool(#ident)),
+                Type::Path(path) if path.path.is_ident("char") => quote!(Char(#ident)),
+                _ => quote!(Integer(#ident as i128)),
+            };
+
+            Some(quote_spanned! {ident.span() =>
+                typed_nodes::mlua::GenericArgument::Const(typed_nodes::mlua::ConstArgument::#value)
+            })
+        }
+        _ => None,
+    });
 
     quote!(typed_nodes::mlua::TypeSignature {
         name: #name,
-        generics: &[#(&#type_params),*],
+        generics: &[#(#generic_arguments),*],
     })
 }
 
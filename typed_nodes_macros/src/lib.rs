@@ -1,6 +1,8 @@
-use proc_macro2::Span;
-use syn::{parse_macro_input, DeriveInput, Error};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Error, Path};
 use type_data::{EnumData, StructData};
+use visit_keys::KeysTrait;
 
 mod attribute_options;
 mod field_parsing;
@@ -9,6 +11,7 @@ mod generate_lua;
 mod iter_ext;
 mod lua_type;
 mod type_data;
+mod visit_keys;
 
 const DEFAULT_TAG_NAME: &str = "type";
 
@@ -24,7 +27,8 @@ pub fn from_lua(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
                     Err(error) => return error.into_compile_error().into(),
                 };
 
-            from_lua::derive_for_struct(struct_data).into()
+            let crate_path = struct_data.options.type_options.crate_path.clone();
+            with_crate_path(crate_path, from_lua::derive_for_struct(struct_data)).into()
         }
         syn::Data::Enum(enum_data) => {
             let enum_data = match EnumData::new(input.attrs, input.ident, input.generics, enum_data)
@@ -33,11 +37,14 @@ pub fn from_lua(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 Err(error) => return error.into_compile_error().into(),
             };
 
-            from_lua::derive_for_enum(enum_data).into()
+            let crate_path = enum_data.options.type_options.crate_path.clone();
+            with_crate_path(crate_path, from_lua::derive_for_enum(enum_data)).into()
+        }
+        syn::Data::Union(union_data) => {
+            Error::new_spanned(union_data.union_token, "unions are not supported")
+                .into_compile_error()
+                .into()
         }
-        syn::Data::Union(_) => Error::new(Span::call_site(), "unions are not supported")
-            .into_compile_error()
-            .into(),
     }
 }
 
@@ -53,7 +60,8 @@ pub fn generate_lua(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream
                     Err(error) => return error.into_compile_error().into(),
                 };
 
-            generate_lua::derive_for_struct(struct_data).into()
+            let crate_path = struct_data.options.type_options.crate_path.clone();
+            with_crate_path(crate_path, generate_lua::derive_for_struct(struct_data)).into()
         }
         syn::Data::Enum(enum_data) => {
             let enum_data = match EnumData::new(input.attrs, input.ident, input.generics, enum_data)
@@ -62,10 +70,74 @@ pub fn generate_lua(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream
                 Err(error) => return error.into_compile_error().into(),
             };
 
-            generate_lua::derive_for_enum(enum_data).into()
+            let crate_path = enum_data.options.type_options.crate_path.clone();
+            with_crate_path(crate_path, generate_lua::derive_for_enum(enum_data)).into()
+        }
+        syn::Data::Union(union_data) => {
+            Error::new_spanned(union_data.union_token, "unions are not supported")
+                .into_compile_error()
+                .into()
+        }
+    }
+}
+
+#[proc_macro_derive(VisitKeys, attributes(typed_nodes))]
+pub fn visit_keys(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    derive_keys_trait(parse_macro_input!(tokens as DeriveInput), KeysTrait::Visit).into()
+}
+
+#[proc_macro_derive(RemapKeys, attributes(typed_nodes))]
+pub fn remap_keys(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    derive_keys_trait(parse_macro_input!(tokens as DeriveInput), KeysTrait::Remap).into()
+}
+
+fn derive_keys_trait(input: DeriveInput, keys_trait: KeysTrait) -> TokenStream {
+    match input.data {
+        syn::Data::Struct(struct_data) => {
+            let struct_data =
+                match StructData::new(input.attrs, input.ident, input.generics, struct_data) {
+                    Ok(data) => data,
+                    Err(error) => return error.into_compile_error(),
+                };
+
+            let crate_path = struct_data.options.type_options.crate_path.clone();
+            with_crate_path(
+                crate_path,
+                visit_keys::derive_for_struct(struct_data, keys_trait),
+            )
+        }
+        syn::Data::Enum(enum_data) => {
+            let enum_data = match EnumData::new(input.attrs, input.ident, input.generics, enum_data)
+            {
+                Ok(data) => data,
+                Err(error) => return error.into_compile_error(),
+            };
+
+            let crate_path = enum_data.options.type_options.crate_path.clone();
+            with_crate_path(
+                crate_path,
+                visit_keys::derive_for_enum(enum_data, keys_trait),
+            )
+        }
+        syn::Data::Union(union_data) => {
+            Error::new_spanned(union_data.union_token, "unions are not supported")
+                .into_compile_error()
         }
-        syn::Data::Union(_) => Error::new(Span::call_site(), "unions are not supported")
-            .into_compile_error()
-            .into(),
+    }
+}
+
+/// Makes `typed_nodes` refer to `crate_path` in the generated code, if a
+/// custom path was set with `#[typed_nodes(crate = "...")]`.
+fn with_crate_path(crate_path: Option<Path>, tokens: TokenStream) -> TokenStream {
+    let Some(crate_path) = crate_path else {
+        return tokens;
+    };
+
+    quote! {
+        const _: () = {
+            use #crate_path as typed_nodes;
+
+            #tokens
+        };
     }
 }
@@ -10,11 +10,13 @@ macro_rules! make_lua_type {
         $visibility:vis enum LuaType {
             $($variant:ident => $token:ident),*
             $(,)?
+            $(; $($unnamed:ident),* $(,)?)?
         }
     ) => {
         $(#[$meta])*
         $visibility enum LuaType {
             $($variant,)*
+            $($($unnamed,)*)?
         }
 
         impl TryFrom<Ident> for LuaType {
@@ -23,9 +25,9 @@ macro_rules! make_lua_type {
             fn try_from(ident: Ident) -> syn::Result<Self> {
                 match &*ident.to_string() {
                     $(stringify!($token) => Ok(Self::$variant),)*
-                    ident => {
+                    _ => {
                         let types = [$(stringify!($token)),*].join(", ");
-                        return Err(Error::new_spanned(
+                        Err(Error::new_spanned(
                             ident,
                             format!("unexpected Lua value type, expected one of: {types}")
                         ))
@@ -44,7 +46,8 @@ macro_rules! make_lua_type {
         impl fmt::Display for LuaType {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 match *self {
-                    $(Self::$variant => f.write_str(stringify!($token))),*
+                    $(Self::$variant => f.write_str(stringify!($token)),)*
+                    $($(Self::$unnamed => f.write_str(stringify!($unnamed)),)*)?
                 }
             }
         }
@@ -59,11 +62,18 @@ make_lua_type! {
         Number => number,
         Integer => integer,
         String => string,
-        Boolean => boolean,
+        Boolean => boolean;
+        // Can't be used in attributes.
+        Any,
     }
 }
 
 impl LuaType {
+    /// Literal values can't have metatables, so they have no constructors.
+    pub(crate) fn is_literal(&self) -> bool {
+        matches!(self, LuaType::Nil | LuaType::Boolean)
+    }
+
     pub(crate) fn wrap_value_expression(&self, clone_value: bool) -> TokenStream {
         let value = if clone_value {
             quote!(value.clone())
@@ -78,6 +88,7 @@ impl LuaType {
             LuaType::Integer => quote!(mlua::Value::Integer(#value)),
             LuaType::String => quote!(mlua::Value::String(#value)),
             LuaType::Boolean => quote!(mlua::Value::Boolean(#value)),
+            LuaType::Any => value,
         }
     }
 
@@ -129,6 +140,20 @@ impl LuaType {
                     }
                 }
             }
+            LuaType::Any => unreachable!("`any` is not a visited Lua type"),
+        }
+    }
+
+    /// A pattern that matches `mlua::Value`s of this type.
+    pub(crate) fn value_pattern(&self) -> TokenStream {
+        match self {
+            LuaType::Nil => quote!(mlua::Value::Nil),
+            LuaType::Table => quote!(mlua::Value::Table(_)),
+            LuaType::Number => quote!(mlua::Value::Number(_)),
+            LuaType::Integer => quote!(mlua::Value::Integer(_)),
+            LuaType::String => quote!(mlua::Value::String(_)),
+            LuaType::Boolean => quote!(mlua::Value::Boolean(_)),
+            LuaType::Any => quote!(_),
         }
     }
 }
@@ -1,7 +1,9 @@
 use proc_macro2::Ident;
-use syn::{Attribute, Generics, Type, TypePath};
+use syn::{parse_quote, Attribute, Generics, Type, TypePath};
 
-use crate::attribute_options::{EnumOptions, FieldOptions, StructOptions, VariantOptions};
+use crate::attribute_options::{
+    EnumOptions, FieldOptions, StructOptions, TypeOptions, VariantOptions,
+};
 
 pub(crate) struct StructData {
     pub options: StructOptions,
@@ -15,18 +17,23 @@ impl StructData {
     pub fn new(
         attributes: Vec<Attribute>,
         name: Ident,
-        generics: Generics,
+        mut generics: Generics,
         struct_data: syn::DataStruct,
     ) -> syn::Result<Self> {
+        require_static_lifetimes(&mut generics);
         let options = StructOptions::from_attributes(&attributes)?;
+        check_no_bound(&options.type_options, &generics)?;
         let type_params =
             get_type_parameters(&generics, options.type_options.lua_base_type.as_ref())?;
+        let fields = Fields::new(struct_data.fields)?;
+        check_lua_args(options.lua_args.as_deref(), &fields, &name)?;
+        check_flags(&options, &fields)?;
 
         Ok(Self {
             options,
             name,
             generics,
-            fields: Fields::new(struct_data.fields)?,
+            fields,
             type_params,
         })
     }
@@ -44,16 +51,19 @@ impl EnumData {
     pub fn new(
         attributes: Vec<Attribute>,
         name: Ident,
-        generics: Generics,
+        mut generics: Generics,
         enum_data: syn::DataEnum,
     ) -> syn::Result<Self> {
+        require_static_lifetimes(&mut generics);
         let options = EnumOptions::from_attributes(&attributes)?;
+        check_no_bound(&options.type_options, &generics)?;
 
         let variants = enum_data
             .variants
             .into_iter()
-            .map(|variant| Variant::new(variant))
-            .collect::<syn::Result<_>>()?;
+            .map(Variant::new)
+            .collect::<syn::Result<Vec<_>>>()?;
+        check_repr(&options, &variants)?;
 
         let type_params =
             get_type_parameters(&generics, options.type_options.lua_base_type.as_ref())?;
@@ -76,10 +86,14 @@ pub(crate) struct Variant {
 
 impl Variant {
     fn new(variant: syn::Variant) -> syn::Result<Self> {
+        let options = VariantOptions::from_attributes(&variant.attrs)?;
+        let fields = Fields::new(variant.fields)?;
+        check_lua_args(options.lua_args.as_deref(), &fields, &variant.ident)?;
+
         Ok(Self {
-            options: VariantOptions::from_attributes(&variant.attrs)?,
+            options,
             name: variant.ident,
-            fields: Fields::new(variant.fields)?,
+            fields,
         })
     }
 }
@@ -183,3 +197,122 @@ fn get_type_parameters(generics: &Generics, base_type: Option<&Type>) -> syn::Re
 
     Ok(type_parameters)
 }
+
+fn check_no_bound(options: &TypeOptions, generics: &Generics) -> syn::Result<()> {
+    for param in &options.no_bound {
+        if !generics
+            .type_params()
+            .any(|type_param| &type_param.ident == param)
+        {
+            return Err(syn::Error::new_spanned(param, "expected a type parameter"));
+        }
+    }
+
+    Ok(())
+}
+
+/// The fields in `lua_args` have to be the same as the constructor's
+/// arguments, but can be in a different order.
+fn check_repr(options: &EnumOptions, variants: &[Variant]) -> syn::Result<()> {
+    if options.repr.is_none() {
+        return Ok(());
+    }
+
+    for variant in variants {
+        if !variant.fields.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &variant.name,
+                "`repr` is only supported for enums without fields",
+            ));
+        }
+
+        let variant_options = &variant.options;
+        if variant_options.default.is_some()
+            || !variant_options.untagged_as.is_empty()
+            || !variant_options.aliases.is_empty()
+            || variant_options.string_form.is_some()
+        {
+            return Err(syn::Error::new_spanned(
+                &variant.name,
+                "variants of `repr` enums can't be untagged, default, aliased or have a string form",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_flags(options: &StructOptions, fields: &Fields) -> syn::Result<()> {
+    let Some((_, span)) = options.flags else {
+        return Ok(());
+    };
+
+    match fields {
+        Fields::Unnamed { fields } if fields.len() == 1 => Ok(()),
+        _ => Err(syn::Error::new(
+            span,
+            "`flags` is only supported for structs with a single unnamed field",
+        )),
+    }
+}
+
+fn check_lua_args(lua_args: Option<&[Ident]>, fields: &Fields, name: &Ident) -> syn::Result<()> {
+    let Some(lua_args) = lua_args else {
+        return Ok(());
+    };
+
+    let Fields::Named { fields } = fields else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "`lua_args` is only supported for named fields",
+        ));
+    };
+
+    let arguments: Vec<_> = fields
+        .iter()
+        .filter(|(_, field)| field.options.is_lua_argument())
+        .map(|(name, _)| name)
+        .collect();
+
+    for (index, argument) in lua_args.iter().enumerate() {
+        if !arguments.contains(&argument) {
+            return Err(syn::Error::new_spanned(
+                argument,
+                "expected the name of a field that is a constructor argument",
+            ));
+        }
+
+        if lua_args[..index].contains(argument) {
+            return Err(syn::Error::new_spanned(argument, "duplicate argument"));
+        }
+    }
+
+    if let Some(missing) = arguments.iter().find(|&&name| !lua_args.contains(name)) {
+        return Err(syn::Error::new_spanned(
+            name,
+            format!("`{missing}` is missing from `lua_args`"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Node types have to be `'static`, so any lifetime parameters are required to
+/// be `'static` in the generated implementations.
+fn require_static_lifetimes(generics: &mut Generics) {
+    let lifetimes: Vec<_> = generics
+        .lifetimes()
+        .map(|param| param.lifetime.clone())
+        .collect();
+
+    if lifetimes.is_empty() {
+        return;
+    }
+
+    let where_clause = generics.make_where_clause();
+    for lifetime in lifetimes {
+        where_clause
+            .predicates
+            .push(parse_quote!(#lifetime: 'static));
+    }
+}
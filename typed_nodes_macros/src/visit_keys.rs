@@ -0,0 +1,154 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Generics, Ident};
+
+use crate::{
+    attribute_options::TypeOptions,
+    type_data::{EnumData, Fields, StructData},
+};
+
+/// The traits for walking the keys in a node.
+#[derive(Clone, Copy)]
+pub(crate) enum KeysTrait {
+    Visit,
+    Remap,
+}
+
+impl KeysTrait {
+    fn trait_path(self) -> TokenStream {
+        match self {
+            KeysTrait::Visit => quote!(typed_nodes::graph::VisitKeys),
+            KeysTrait::Remap => quote!(typed_nodes::graph::RemapKeys),
+        }
+    }
+
+    fn method_signature(self) -> TokenStream {
+        match self {
+            KeysTrait::Visit => {
+                quote!(fn visit_keys(&self, visitor: &mut dyn FnMut(typed_nodes::DynKey)))
+            }
+            KeysTrait::Remap => {
+                quote!(fn remap_keys(&mut self, remap: &typed_nodes::graph::KeyRemap))
+            }
+        }
+    }
+
+    fn method_call(self, binding: &Ident) -> TokenStream {
+        let trait_path = self.trait_path();
+
+        match self {
+            KeysTrait::Visit => quote!(#trait_path::visit_keys(#binding, visitor);),
+            KeysTrait::Remap => quote!(#trait_path::remap_keys(#binding, remap);),
+        }
+    }
+}
+
+pub(crate) fn derive_for_struct(struct_data: StructData, keys_trait: KeysTrait) -> TokenStream {
+    let StructData {
+        options,
+        name,
+        generics,
+        fields,
+        type_params,
+    } = struct_data;
+
+    let generics = add_bounds(&options.type_options, generics, &type_params, keys_trait);
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let (pattern, body) = visit_fields(&fields, keys_trait);
+    let trait_path = keys_trait.trait_path();
+    let method_signature = keys_trait.method_signature();
+
+    quote! {
+        impl #impl_generics #trait_path for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            #method_signature {
+                let Self #pattern = self;
+                #body
+            }
+        }
+    }
+}
+
+pub(crate) fn derive_for_enum(enum_data: EnumData, keys_trait: KeysTrait) -> TokenStream {
+    let EnumData {
+        options,
+        name,
+        generics,
+        variants,
+        type_params,
+    } = enum_data;
+
+    let generics = add_bounds(&options.type_options, generics, &type_params, keys_trait);
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let trait_path = keys_trait.trait_path();
+    let method_signature = keys_trait.method_signature();
+
+    let arms: Vec<_> = variants
+        .iter()
+        .map(|variant| {
+            let variant_name = &variant.name;
+            let (pattern, body) = visit_fields(&variant.fields, keys_trait);
+
+            quote!(Self::#variant_name #pattern => {#body})
+        })
+        .collect();
+
+    // An empty enum can't be matched by reference.
+    let match_expr = if arms.is_empty() {
+        quote!(*self)
+    } else {
+        quote!(self)
+    };
+
+    quote! {
+        impl #impl_generics #trait_path for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            #method_signature {
+                match #match_expr {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
+
+fn add_bounds(
+    options: &TypeOptions,
+    mut generics: Generics,
+    type_params: &[Ident],
+    keys_trait: KeysTrait,
+) -> Generics {
+    let trait_path = keys_trait.trait_path();
+    let where_clause = generics.make_where_clause();
+
+    for param in options.bounded_type_params(type_params) {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: #trait_path));
+    }
+
+    generics
+}
+
+/// Makes a pattern that binds each field, and code that visits them in order.
+fn visit_fields(fields: &Fields, keys_trait: KeysTrait) -> (TokenStream, TokenStream) {
+    let bindings: Vec<_> = match fields {
+        Fields::Named { fields } => fields.iter().map(|(name, _)| name.clone()).collect(),
+        Fields::Unnamed { fields } => (0..fields.len())
+            .map(|index| format_ident!("field_{index}"))
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let pattern = match fields {
+        Fields::Named { .. } => quote!({ #(#bindings),* }),
+        Fields::Unnamed { .. } => quote!(( #(#bindings),* )),
+        Fields::Unit => quote!(),
+    };
+
+    let calls = bindings
+        .iter()
+        .map(|binding| keys_trait.method_call(binding));
+
+    (pattern, quote!(#(#calls)*))
+}